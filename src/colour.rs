@@ -0,0 +1,350 @@
+use std::str::FromStr;
+
+use crate::shadow::Shadow;
+use crate::shapes::ShapeComponent;
+use crate::vect;
+use crate::vector::Vec3;
+
+mod tests;
+
+/// Everything known about a face when choosing its style.
+pub struct FaceInfo<'a> {
+    /// The face itself, giving its normal and geometry.
+    pub component: &'a ShapeComponent,
+    /// The cell the face's shape was placed at.
+    pub cell: Vec3<usize>,
+    /// The equality group the face's shape belongs to, if any.
+    pub group: Option<&'a str>,
+    /// The colour the face would be filled with by default, after lighting and every other effect.
+    pub colour: Vec3<f64>,
+    /// When gradient fills are on, the id of the gradient from `colour` down to a darker shade,
+    /// which is only written to the output if some face's style refers to it.
+    pub gradient: Option<String>,
+    /// How much of what's behind the face it hides, from 0 to 1.
+    pub opacity: f64,
+    /// The outline to draw around the face, when every face is outlined on its own.
+    pub stroke: Option<Stroke>,
+}
+
+/// Chooses the `style` attribute of every face in the output.
+pub type Shader = dyn Fn(&FaceInfo) -> String;
+
+/// The shader used unless another is given, simply filling each face with its computed colour.
+pub fn default_shader(face: &FaceInfo) -> String {
+    let css = match &face.gradient {
+        Some(id) => face.component.generate_gradient_css(id),
+        None => face.component.generate_css(face.colour),
+    };
+    let css = if face.opacity < 1.0 {
+        format!("{};fill-opacity:{}", css, face.opacity)
+    }
+    else {
+        css
+    };
+    match &face.stroke {
+        Some(stroke) => format!("{};{}", css, stroke.css()),
+        None => css,
+    }
+}
+
+/// Deterministic per-cell noise on tile colours, so large areas of one tile don't look quite so flat.
+#[derive(Debug, Clone, Copy)]
+pub struct ColourVariation {
+    /// The furthest any one channel can move from the base colour, where channels range over [0, 1].
+    pub amplitude: f64,
+    pub seed: u64,
+}
+
+impl ColourVariation {
+    /// Perturbs `colour` by an amount decided entirely by `cell` and the seed,
+    /// so the same cell comes out the same colour on every render.
+    pub fn apply(&self, colour: Vec3<f64>, cell: Vec3<usize>) -> Vec3<f64> {
+        let mut state = cell_hash(cell, self.seed);
+        let mut offset = || {
+            state = split_mix(state);
+            // the top 53 bits fit exactly into a double, mapped to [-1, 1)
+            (state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+        };
+        let offset = vect![offset(), offset(), offset()];
+        clamp(colour + offset * self.amplitude)
+    }
+}
+
+/// Every other tile tinted, like the squares of a chessboard, so rows of the same tile are easier to count.
+/// The squares are worked out from the cell in the world, so a shape drawn for several cells is tinted by the one it's anchored at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checker {
+    /// What the colour of each tinted tile is multiplied by.
+    pub tint: Vec3<f64>,
+    /// How much of the tint shows, from 0 for none to 1 for all of it. Going below 0 lightens the tiles instead.
+    pub strength: f64,
+    /// Which coordinates are added up to find the squares, such as only `x` and `z` for a board on the ground.
+    pub axes: Vec3<bool>,
+}
+
+impl Checker {
+    /// `colour` tinted if `cell` adds up to an odd number along the axes counted, and as it was otherwise.
+    pub fn apply(&self, colour: Vec3<f64>, cell: Vec3<usize>) -> Vec3<f64> {
+        let sum: usize = [(self.axes.x, cell.x), (self.axes.y, cell.y), (self.axes.z, cell.z)].into_iter()
+            .filter(|(counted, _)| *counted)
+            .map(|(_, coordinate)| coordinate)
+            .sum();
+        if sum & 1 == 0 {
+            return colour;
+        }
+        clamp(Vec3::lerp(colour, colour * self.tint, self.strength))
+    }
+}
+
+/// A light shining evenly across the whole scene from one direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    /// The direction the light shines from, which should be normalised.
+    pub direction: Vec3<f64>,
+    /// How bright the light is, where a face pointing straight at a light of intensity 1 gets its full colour.
+    pub intensity: f64,
+    /// The colour of the light, multiplied into the colour of everything it falls on.
+    pub colour: Vec3<f64>,
+}
+
+impl Light {
+    /// A white light of intensity 1 from `direction`.
+    pub fn new(direction: Vec3<f64>) -> Light {
+        Light { direction, intensity: 1.0, colour: vect![1.0, 1.0, 1.0] }
+    }
+}
+
+impl Default for Light {
+    /// The light scenes are drawn with unless they say otherwise.
+    fn default() -> Light {
+        Light::new(crate::LIGHT_VECTOR.normalise())
+    }
+}
+
+/// How faces are lit: an ambient term every face gets, plus a diffuse term for how directly each faces each light.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lighting {
+    /// Every light in the scene. With none at all, the default light is used instead.
+    pub lights: Vec<Light>,
+    /// How much of the object colour shows on every face, even those facing away from the lights.
+    pub ambient: f64,
+    /// How much of the object colour a face pointing straight at a light gets on top of `ambient`.
+    pub diffuse: f64,
+    /// A colour for the ambient light, like a sky tint, added on in place of the object colour when given.
+    pub ambient_colour: Option<Vec3<f64>>,
+    /// Whether light is added up in linear space, taking every colour out of sRGB first and putting the result back.
+    /// Without it, faces lit at an angle come out darker than they should.
+    pub gamma_correct: bool,
+}
+
+impl Lighting {
+    /// Plain diffuse light from `lights`, leaving faces turned away from all of them black.
+    pub fn new(lights: Vec<Light>) -> Lighting {
+        Lighting { lights, ambient: 0.0, diffuse: 1.0, ambient_colour: None, gamma_correct: false }
+    }
+    /// The lights actually shining on the scene, which is just the default light when none were given.
+    pub fn lights(&self) -> impl Iterator<Item = Light> + '_ {
+        let fallback = self.lights.is_empty().then(Light::default);
+        self.lights.iter().copied().chain(fallback)
+    }
+}
+
+impl Default for Lighting {
+    fn default() -> Lighting {
+        Lighting::new(vec![])
+    }
+}
+
+/// Fades colours into `colour` the further back they're drawn.
+#[derive(Debug, Clone, Copy)]
+pub struct Fog {
+    pub colour: Vec3<f64>,
+    /// The distance back from the front of the grid fog starts to appear at.
+    pub start: f64,
+    /// The distance past which everything is entirely fog.
+    pub end: f64,
+}
+
+impl Fog {
+    /// How much of the fog colour shows through at `distance` cells back, from 0 to 1.
+    pub fn density(&self, distance: f64) -> f64 {
+        if self.end <= self.start {
+            return if distance < self.start { 0.0 } else { 1.0 };
+        }
+        ((distance - self.start) / (self.end - self.start)).clamp(0.0, 1.0)
+    }
+    pub fn apply(&self, colour: Vec3<f64>, distance: f64) -> Vec3<f64> {
+        let density = self.density(distance);
+        colour * (1.0 - density) + self.colour * density
+    }
+}
+
+/// Shades faces from their lit colour at the top to a darker one at the bottom.
+#[derive(Debug, Clone, Copy)]
+pub struct Gradient {
+    /// How much darker the bottom of each face is than the top, from 0 to 1.
+    pub darkening: f64,
+}
+
+impl Gradient {
+    /// The colour at the bottom of a face whose top is `colour`.
+    pub fn bottom(&self, colour: Vec3<f64>) -> Vec3<f64> {
+        clamp(colour * (1.0 - self.darkening))
+    }
+    /// The id of the gradient starting from `colour`, which is the same for every face the same colour.
+    pub fn id(&self, colour: Vec3<f64>) -> String {
+        format!("gradient-{}", &hex(colour)[1..])
+    }
+}
+
+/// Lines drawn along the edges of shapes.
+#[derive(Debug, Clone, Copy)]
+pub struct Stroke {
+    pub colour: Vec3<f64>,
+    pub width: f64,
+    pub mode: StrokeMode,
+}
+
+impl Stroke {
+    /// The style properties drawing the line, without any fill.
+    pub fn css(&self) -> String {
+        format!("stroke:{};stroke-width:{}", hex(self.colour), self.width)
+    }
+}
+
+/// Which edges get a `Stroke`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum StrokeMode {
+    /// Every primitive is outlined, including the edges between faces.
+    #[default]
+    Primitives,
+    /// Only the outline around each shape as a whole, as a path of its own drawn after the shape's faces.
+    Silhouette,
+}
+
+impl FromStr for StrokeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "primitives" => Ok(StrokeMode::Primitives),
+            "silhouette" => Ok(StrokeMode::Silhouette),
+            _ => Err(format!("'{}' is not a stroke mode, expected one of \"primitives\" or \"silhouette\"", s)),
+        }
+    }
+}
+
+/// How faces are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RenderMode {
+    /// Filled in with their colours, as whatever shader is in use decides.
+    #[default]
+    Solid,
+    /// Only their outlines, as lines of `colour` `width` wide with nothing filled in, for cutting out or laying over other pictures.
+    /// Shadows aren't drawn, as they'd only be filled in.
+    Wireframe { colour: Vec3<f64>, width: f64 },
+}
+
+/// Every effect applied to faces after they've been lit, and anything else drawn along with them.
+#[derive(Debug, Clone, Default)]
+pub struct Effects {
+    pub fog: Option<Fog>,
+    pub gradient: Option<Gradient>,
+    pub stroke: Option<Stroke>,
+    pub shadow: Option<Shadow>,
+}
+
+/// Writes `colour` the way CSS does, like `#4080bf`, rounding each channel to the nearest of 256 levels.
+pub fn hex(colour: Vec3<f64>) -> String {
+    let channel = |c: f64| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(colour.x), channel(colour.y), channel(colour.z))
+}
+
+/// Takes each channel of an sRGB `colour` to how much light it actually stands for.
+pub fn to_linear(colour: Vec3<f64>) -> Vec3<f64> {
+    let channel = |c: f64| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+    vect![channel(colour.x), channel(colour.y), channel(colour.z)]
+}
+
+/// The inverse of `to_linear`, taking an amount of light back to the sRGB colour that shows it.
+pub fn from_linear(colour: Vec3<f64>) -> Vec3<f64> {
+    let channel = |c: f64| if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    vect![channel(colour.x), channel(colour.y), channel(colour.z)]
+}
+
+/// Clamps every channel of `colour` into [0, 1].
+pub fn clamp(colour: Vec3<f64>) -> Vec3<f64> {
+    vect![colour.x.clamp(0.0, 1.0), colour.y.clamp(0.0, 1.0), colour.z.clamp(0.0, 1.0)]
+}
+
+/// Hashes a cell coordinate and seed together.
+/// Unlike the standard library hashers, this is guaranteed to be the same across platforms and versions.
+pub(crate) fn cell_hash(cell: Vec3<usize>, seed: u64) -> u64 {
+    [cell.x, cell.y, cell.z].into_iter().fold(split_mix(seed), |hash, c| split_mix(hash ^ c as u64))
+}
+
+// http://xoshiro.di.unimi.it/splitmix64.c
+fn split_mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// The 16 basic colours named in CSS.
+const NAMED_COLOURS: [(&str, [u8; 3]); 16] = [
+    ("black", [0x00, 0x00, 0x00]),
+    ("silver", [0xc0, 0xc0, 0xc0]),
+    ("gray", [0x80, 0x80, 0x80]),
+    ("white", [0xff, 0xff, 0xff]),
+    ("maroon", [0x80, 0x00, 0x00]),
+    ("red", [0xff, 0x00, 0x00]),
+    ("purple", [0x80, 0x00, 0x80]),
+    ("fuchsia", [0xff, 0x00, 0xff]),
+    ("green", [0x00, 0x80, 0x00]),
+    ("lime", [0x00, 0xff, 0x00]),
+    ("olive", [0x80, 0x80, 0x00]),
+    ("yellow", [0xff, 0xff, 0x00]),
+    ("navy", [0x00, 0x00, 0x80]),
+    ("blue", [0x00, 0x00, 0xff]),
+    ("teal", [0x00, 0x80, 0x80]),
+    ("aqua", [0x00, 0xff, 0xff]),
+];
+
+/// Reads a CSS colour value, either as `#rrggbb`, `#rgb`, `rgb(r, g, b)`, `rgba(r, g, b, a)` or one of the basic colour names.
+/// `rgb` channels can be numbers up to 255 or percentages, and any alpha is ignored.
+pub fn parse_css_colour(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim();
+    let lower = value.to_ascii_lowercase();
+    if let Some(args) = lower.strip_prefix("rgba(").or_else(|| lower.strip_prefix("rgb(")) {
+        let channels = args.strip_suffix(')')?
+            .split([',', ' ', '/'])
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>();
+        if !(3..=4).contains(&channels.len()) {
+            return None;
+        }
+        let channel = |c: &str| -> Option<u8> {
+            let c = match c.strip_suffix('%') {
+                Some(percent) => percent.parse::<f64>().ok()? * 2.55,
+                None => c.parse::<f64>().ok()?,
+            };
+            Some(c.round().clamp(0.0, 255.0) as u8)
+        };
+        return Some([channel(channels[0])?, channel(channels[1])?, channel(channels[2])?]);
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        return match hex.len() {
+            6 => Some([0, 2, 4].map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())),
+            // each digit of the shorthand is repeated, so #8f8 is #88ff88
+            3 => Some([0, 1, 2].map(|i| u8::from_str_radix(&hex[i..i + 1], 16).unwrap() * 0x11)),
+            _ => None,
+        };
+    }
+    NAMED_COLOURS.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, colour)| *colour)
+}
@@ -0,0 +1,147 @@
+#![cfg(test)]
+
+use crate::colour::{from_linear, to_linear, Checker, ColourVariation, Fog, Light, Lighting};
+use crate::shapes::{ShapeComponent, ShapePrimitive};
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+
+/// The style a face of the given colour comes out with.
+fn fill(colour: Vec3<f64>) -> String {
+    let component = ShapeComponent::new(
+        vect![0.0, 1.0, 0.0],
+        vec![ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![0.0, 1.0]] }],
+    ).unwrap();
+    component.generate_css(colour)
+}
+
+#[test]
+fn test_variation_pinned() {
+    let variation = ColourVariation { amplitude: 0.1, seed: 42 };
+    let base = vect![0.25, 0.5, 0.75];
+    assert_eq!(fill(base), "fill:#4080bf");
+    assert_eq!(fill(variation.apply(base, vect![0, 0, 0])), "fill:#4f86c2");
+    assert_eq!(fill(variation.apply(base, vect![1, 0, 0])), "fill:#3e66b5");
+    assert_eq!(fill(variation.apply(base, vect![3, 2, 1])), "fill:#5084b9");
+}
+#[test]
+fn test_variation_seeded() {
+    let base = vect![0.25, 0.5, 0.75];
+    let a = ColourVariation { amplitude: 0.1, seed: 1 };
+    let b = ColourVariation { amplitude: 0.1, seed: 2 };
+    assert_eq!(a.apply(base, vect![2, 0, 2]), a.apply(base, vect![2, 0, 2]));
+    assert_ne!(a.apply(base, vect![2, 0, 2]), b.apply(base, vect![2, 0, 2]));
+}
+#[test]
+fn test_variation_clamped() {
+    let variation = ColourVariation { amplitude: 5.0, seed: 7 };
+    for x in 0..10 {
+        let colour = variation.apply(vect![0.0, 0.5, 1.0], vect![x, 0, 0]);
+        for channel in [colour.x, colour.y, colour.z] {
+            assert!((0.0..=1.0).contains(&channel));
+        }
+    }
+}
+#[test]
+fn test_checker() {
+    let checker = Checker { tint: vect![1.0, 0.5, 0.0], strength: 1.0, axes: vect![true, false, true] };
+    let base = vect![0.4, 0.8, 0.6];
+    assert_eq!(checker.apply(base, vect![0, 0, 0]), base);
+    assert_eq!(checker.apply(base, vect![1, 0, 1]), base);
+    // the height isn't counted, so a column is tinted all the way up
+    assert_eq!(checker.apply(base, vect![1, 0, 0]), vect![0.4, 0.4, 0.0]);
+    assert_eq!(checker.apply(base, vect![1, 3, 0]), vect![0.4, 0.4, 0.0]);
+
+    let half = Checker { strength: 0.5, ..checker };
+    assert_eq!(fill(half.apply(base, vect![0, 0, 1])), fill(vect![0.4, 0.6, 0.3]));
+    let lighter = Checker { tint: vect![0.5, 0.5, 0.5], strength: -1.0, axes: vect![true, true, true] };
+    assert_eq!(fill(lighter.apply(vect![0.4, 0.8, 0.8], vect![0, 1, 0])), fill(vect![0.6, 1.0, 1.0]));
+}
+#[test]
+fn test_fog_monotonic() {
+    let fog = Fog { colour: vect![200.0, 200.0, 220.0] / 255.0, start: 10.0, end: 40.0 };
+    let base = vect![0.6, 0.2, 0.9];
+    assert_eq!(fill(fog.apply(base, 0.0)), fill(base));
+    assert_eq!(fill(fog.apply(base, 10.0)), fill(base));
+    assert_eq!(fill(fog.apply(base, 40.0)), fill(fog.colour));
+    assert_eq!(fill(fog.apply(base, 100.0)), fill(fog.colour));
+
+    let mut last_distance = f64::INFINITY;
+    for depth in 0..50 {
+        let distance = (fog.apply(base, depth as f64) - fog.colour).magnitude();
+        assert!(distance <= last_distance);
+        last_distance = distance;
+    }
+}
+#[test]
+fn test_ambient_lighting() {
+    let component = ShapeComponent::new(
+        vect![0.0, 1.0, 0.0],
+        vec![ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![0.0, 1.0]] }],
+    ).unwrap();
+    let colour = vect![0.5, 1.0, 0.25];
+    // lit from the side, the face only gets the ambient light
+    let side = Lighting { ambient: 0.3, ..Lighting::new(vec![Light::new(vect![1.0, 0.0, 0.0])]) };
+    assert_eq!(component.shade(&side, colour), colour * 0.3);
+    assert_eq!(component.shade(&Lighting::new(vec![Light::new(vect![1.0, 0.0, 0.0])]), colour), vect![0.0, 0.0, 0.0]);
+
+    // lit head on with both terms, the channels stop at full rather than wrapping
+    let bright = Lighting { ambient: 0.5, diffuse: 1.0, ..Lighting::new(vec![Light::new(vect![0.0, 1.0, 0.0])]) };
+    assert_eq!(fill(component.shade(&bright, colour)), "fill:#bfff60");
+
+    // a sky tint is added to the diffuse light instead of scaling the object colour
+    let tinted = Lighting { ambient: 0.5, ambient_colour: Some(vect![0.0, 0.0, 1.0]), ..Lighting::new(vec![Light::new(vect![1.0, 0.0, 0.0])]) };
+    assert_eq!(component.shade(&tinted, colour), vect![0.0, 0.0, 0.5]);
+}
+#[test]
+fn test_css_channels_clamped() {
+    assert_eq!(fill(vect![1.0, 2.0, -1.0]), "fill:#ffff00");
+}
+#[test]
+fn test_multiple_lights() {
+    let component = ShapeComponent::new(
+        vect![0.0, 1.0, 0.0],
+        vec![ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![0.0, 1.0]] }],
+    ).unwrap();
+    let colour = vect![0.5, 1.0, 0.25];
+    let key = Light::new(vect![0.0, 1.0, 0.0]);
+    let fill_light = Light { intensity: 0.25, colour: vect![1.0, 0.0, 1.0], ..Light::new(vect![0.6, 0.8, 0.0]) };
+    let lighting = Lighting::new(vec![Light { intensity: 0.5, ..key }, fill_light]);
+    // the key light gives half the colour, and the fill a fifth of its red and blue
+    assert_eq!(component.shade(&lighting, colour), vect![0.35, 0.5, 0.175]);
+
+    // lights add up, but never past full
+    let bright = Lighting::new(vec![key, key]);
+    assert_eq!(component.shade(&bright, colour), vect![1.0, 1.0, 0.5]);
+
+    // with no lights given, faces are lit just as by the default light
+    let single = Lighting::new(vec![Light::default()]);
+    assert_eq!(component.shade(&Lighting::default(), colour), component.shade(&single, colour));
+}
+#[test]
+fn test_brightness_pinned() {
+    let component = ShapeComponent::new(
+        vect![0.0, 1.0, 0.0],
+        vec![ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![0.0, 1.0]] }],
+    ).unwrap();
+    let white = vect![1.0, 1.0, 1.0];
+    let lit = |brightness: f64, gamma_correct: bool| {
+        let light = Light { intensity: brightness, ..Light::new(vect![0.0, 1.0, 0.0]) };
+        let lighting = Lighting { gamma_correct, ..Lighting::new(vec![light]) };
+        component.generate_css(component.shade(&lighting, white))
+    };
+    assert_eq!(lit(0.0, false), "fill:#000000");
+    assert_eq!(lit(0.5, false), "fill:#808080");
+    assert_eq!(lit(1.0, false), "fill:#ffffff");
+    // half the light looks a lot brighter than half way to white
+    assert_eq!(lit(0.0, true), "fill:#000000");
+    assert_eq!(lit(0.5, true), "fill:#bcbcbc");
+    assert_eq!(lit(1.0, true), "fill:#ffffff");
+}
+#[test]
+fn test_linear_round_trip() {
+    for c in [0.0, 0.02, 0.2, 0.5, 0.8, 1.0] {
+        let colour = vect![c, c, c];
+        assert!((to_linear(from_linear(colour)) - colour).magnitude() < 1e-12);
+        assert!((from_linear(to_linear(colour)) - colour).magnitude() < 1e-12);
+    }
+}
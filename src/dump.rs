@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::RunError;
+use crate::shapes::{Shape, ShapeId, ShapeRegistry};
+use crate::vector::{Vec2, Vec3};
+
+mod tests;
+
+/// What a scene looks like just before it's drawn, for finding out why a render looks wrong.
+/// Written out as JSON by `dump_scene`, which needs the `serde` feature.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SceneDump {
+    /// Every shape read from the components, by label.
+    pub shapes: BTreeMap<ShapeId, Shape>,
+    /// Every shape placed in the grid, including those culled for being hidden, in order of their cells.
+    pub instances: Vec<Instance>,
+}
+
+/// A shape placed in the grid.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Instance {
+    /// The cell the shape is drawn from, in the world.
+    pub cell: Vec3<usize>,
+    pub shape: ShapeId,
+    /// The middle of the box around the shape, once it's been moved into place in the image.
+    pub position: Vec2<f64>,
+    /// The cells holding the shapes which hid this one, with none if it's drawn.
+    /// Shapes hidden on every side by opaque neighbours are hidden by all three of the ones in front of them.
+    pub culled_by: Vec<Vec3<usize>>,
+}
+
+impl SceneDump {
+    /// A dump of `shapes`, with nothing placed yet.
+    pub fn new(shapes: &ShapeRegistry) -> SceneDump {
+        SceneDump {
+            shapes: shapes.iter().map(|(id, shape)| (id.clone(), (**shape).clone())).collect(),
+            instances: vec![],
+        }
+    }
+    /// Writes the dump to `path` as JSON.
+    #[cfg(feature = "serde")]
+    pub fn write(&self, path: &Path) -> Result<(), RunError> {
+        let failed = |reason: String| RunError::Dump { path: path.to_path_buf(), reason };
+        let file = std::fs::File::create(path).map_err(|why| failed(why.to_string()))?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self).map_err(|why| failed(why.to_string()))
+    }
+    /// Always fails, as writing the dump needs the `serde` feature.
+    #[cfg(not(feature = "serde"))]
+    pub fn write(&self, path: &Path) -> Result<(), RunError> {
+        Err(RunError::Dump { path: path.to_path_buf(), reason: String::from("isometric has to be built with the serde feature to write it") })
+    }
+}
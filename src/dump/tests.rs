@@ -0,0 +1,95 @@
+#![cfg(test)]
+
+use quick_xml::reader::Reader;
+use quick_xml::writer::Writer;
+
+use crate::error::RunError;
+use crate::grid::Grid;
+use crate::parser;
+use crate::scene::{render_grid, RenderOptions};
+use crate::shapes::ShapeId;
+use crate::stats::RenderStats;
+use crate::vect;
+use crate::vector::Vec3;
+
+/// Renders `grid` with `options`, dumping the scene to a file named after `name`.
+fn render_dumped(name: &str, grid: &Grid, options: RenderOptions) -> (Result<RenderStats, RunError>, String, std::path::PathBuf) {
+    let mut reader = Reader::from_str(include_str!("../../components.svg"));
+    reader.trim_text(true);
+    let shapes = parser::parse_shapes(&mut reader).unwrap();
+    let path = std::env::temp_dir().join(format!("isometric-{}-{}.json", name, std::process::id()));
+    let options = RenderOptions { dump_scene: Some(path.clone()), ..options };
+    let mut output = vec![];
+    let result = render_grid(grid, &shapes, &options, Writer::new(&mut output));
+    (result, String::from_utf8(output).unwrap(), path)
+}
+
+/// Renders a cube with another straight in front of it, hiding it completely, dumping the scene to a file named after `name`.
+fn render_hidden(name: &str) -> (Result<RenderStats, RunError>, String, std::path::PathBuf) {
+    let mut grid = Grid::new(vect![2, 2, 2]);
+    grid.set(vect![0, 0, 0], ShapeId::from(255u8));
+    grid.set(vect![1, 1, 1], ShapeId::from(255u8));
+    render_dumped(name, &grid, RenderOptions::default())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_dump_scene() {
+    let (result, svg, path) = render_hidden("dump");
+    result.unwrap();
+    let dump: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert!(dump["shapes"]["11111111"]["components"][0]["normal"].is_object());
+    let instances = dump["instances"].as_array().unwrap();
+    assert_eq!(instances.len(), 2);
+    assert_eq!(instances[0]["cell"], serde_json::json!({ "x": 0, "y": 0, "z": 0 }));
+    assert_eq!(instances[0]["culled_by"], serde_json::json!([{ "x": 1, "y": 1, "z": 1 }]));
+    assert_eq!(instances[1]["culled_by"], serde_json::json!([]));
+    // dumping doesn't change what's drawn
+    assert_eq!(svg.matches("<g id=").count(), 1);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_dump_buried() {
+    use itertools::iproduct;
+
+    let instances = |name: &str, options: RenderOptions| {
+        let mut grid = Grid::new(vect![2, 2, 2]);
+        for (x, y, z) in iproduct!(0..2, 0..2, 0..2) {
+            grid.set(vect![x, y, z], ShapeId::from(255u8));
+        }
+        let (result, _, path) = render_dumped(name, &grid, options);
+        result.unwrap();
+        let dump: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        dump["instances"].as_array().unwrap().clone()
+    };
+    // the cube at the back is hidden by all three in front of it at once
+    let buried = instances("buried", RenderOptions::default());
+    assert_eq!(buried[0]["cell"], serde_json::json!({ "x": 0, "y": 0, "z": 0 }));
+    assert_eq!(buried[0]["culled_by"], serde_json::json!([{ "x": 1, "y": 0, "z": 0 }, { "x": 0, "y": 1, "z": 0 }, { "x": 0, "y": 0, "z": 1 }]));
+
+    // a connected shape isn't known to fill its cells, so the cube behind it is only hidden once it's been drawn,
+    // by the cell the connected shape is drawn from
+    let mut options = RenderOptions::default();
+    options.connections.insert(String::from("pair"), vec![vect![1, 0, 0], vect![1, 0, 1]]);
+    let connected = instances("buried-connected", options);
+    assert_eq!(connected[0]["cell"], serde_json::json!({ "x": 0, "y": 0, "z": 0 }));
+    assert_eq!(connected[0]["culled_by"], serde_json::json!([{ "x": 1, "y": 0, "z": 0 }]));
+    for instance in &connected {
+        for cell in instance["culled_by"].as_array().unwrap() {
+            assert!(connected.iter().any(|other| &other["cell"] == cell), "{} has no shape of its own", cell);
+        }
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+#[test]
+fn test_dump_scene_needs_serde() {
+    let (result, svg, path) = render_hidden("no-dump");
+    assert_matches!(result, Err(RunError::Dump { .. }));
+    assert!(svg.is_empty());
+    assert!(!path.exists());
+}
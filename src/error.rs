@@ -0,0 +1,200 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+
+use config::ConfigError;
+use itertools::Itertools;
+
+use crate::parser;
+use crate::shapes::ShapeId;
+use crate::validation::ConnectionError;
+use crate::vector::Vec3;
+
+/// Everything which can stop a scene from being rendered.
+#[derive(Debug)]
+pub enum RunError {
+    /// The components file couldn't be read.
+    Parse(parser::Error),
+    /// A setting the scene can't be drawn without isn't in the config.
+    MissingKey { key: String },
+    /// A setting is present, but doesn't hold a usable value.
+    InvalidKey { key: String, reason: String },
+    /// One entry of `tiles` couldn't be placed, counting from 0.
+    InvalidTile { index: usize, reason: String },
+    /// One entry of `fills` couldn't be placed, counting from 0.
+    InvalidFill { index: usize, reason: String },
+    /// A shape couldn't be put at `cell`, because it's outside the grid or there's no such shape.
+    InvalidPlacement { cell: Vec3<usize>, reason: String },
+    /// The shape at `cell` covers several cells, and some of them are taken or outside the grid.
+    InvalidFootprint { cell: Vec3<usize>, reason: String },
+    /// One member of an `equalities` group isn't a coordinate in the grid, counting from 0.
+    InvalidMember { group: String, index: usize, reason: String },
+    /// No shape in the components file has the label a setting asked for.
+    MissingShape { key: String, id: ShapeId },
+    /// There's no cube at `reference_shape` to work out the axes from. `found` holds every label which does have a shape.
+    MissingReferenceShape { id: ShapeId, found: Vec<ShapeId> },
+    /// The shape at `reference_shape` doesn't have a face for each axis.
+    IncompleteReferenceShape { id: ShapeId, missing: Vec<&'static str> },
+    /// The shape at `reference_shape` has more than one face for `axis`, and they aren't the same size.
+    MismatchedReferenceShape { id: ShapeId, axis: &'static str },
+    /// The `equalities` groups don't agree with each other or the grid.
+    InvalidEqualities(ConnectionError),
+    /// Two members of the same equality group were given different `tile_colours`.
+    ConflictingColours { group: String, first: Vec3<usize>, second: Vec3<usize> },
+    /// The output couldn't be written.
+    Write(quick_xml::Error),
+    /// The scene couldn't be dumped to `path`, as `dump_scene` asked.
+    Dump { path: PathBuf, reason: String },
+    /// One of several output files couldn't be created at `path`.
+    Output { path: PathBuf, reason: String },
+    /// One entry of `frames` couldn't be applied, counting from 0.
+    InvalidFrame { index: usize, reason: String },
+    /// More than one thing is wrong with the config, all of which are listed.
+    Several(Vec<RunError>),
+}
+
+impl RunError {
+    /// Wraps an error from reading `key` out of the config.
+    pub(crate) fn from_config(key: &str, error: ConfigError) -> RunError {
+        match error {
+            ConfigError::NotFound(_) => RunError::MissingKey { key: key.to_string() },
+            why => RunError::InvalidKey { key: key.to_string(), reason: why.to_string() },
+        }
+    }
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Parse(why) => write!(f, "couldn't read components: {}", why),
+            RunError::MissingKey { key } => write!(f, "missing required setting {}", key),
+            RunError::InvalidKey { key, reason } => write!(f, "invalid value for {}: {}", key, reason),
+            RunError::InvalidTile { index, reason } => write!(f, "invalid tile at tiles[{}]: {}", index, reason),
+            RunError::InvalidFill { index, reason } => write!(f, "invalid fill at fills[{}]: {}", index, reason),
+            RunError::InvalidPlacement { cell, reason } => write!(f, "couldn't place a shape at {}: {}", cell, reason),
+            RunError::InvalidFootprint { cell, reason } => write!(f, "invalid footprint for the shape at {}: {}", cell, reason),
+            RunError::InvalidMember { group, index, reason } => write!(f, "invalid member at equalities.{}[{}]: {}", group, index, reason),
+            RunError::MissingShape { key, id } => write!(
+                f, "no shape labelled {} was found, which {} needs", id, key
+            ),
+            RunError::MissingReferenceShape { id, found } => {
+                write!(
+                    f, "no shape labelled {} was found for reference_shape. \
+                    A full cube has to be drawn there, as the direction of each axis is measured from its faces. ",
+                    id,
+                )?;
+                if found.is_empty() {
+                    write!(f, "No labelled shapes were found at all")
+                }
+                else {
+                    write!(f, "Shapes were found labelled {}", found.iter().join(", "))
+                }
+            }
+            RunError::IncompleteReferenceShape { id, missing } => write!(
+                f, "the reference cube labelled {} has no face for {}", id, missing.join(", ")
+            ),
+            RunError::MismatchedReferenceShape { id, axis } => write!(
+                f, "the reference cube labelled {} has faces for {} which aren't the same size", id, axis
+            ),
+            RunError::InvalidEqualities(why) => write!(f, "invalid equalities: {}", why),
+            RunError::ConflictingColours { group, first, second } => write!(
+                f, "equalities.{} members {} and {} are given different tile_colours", group, first, second
+            ),
+            RunError::Write(why) => write!(f, "couldn't write output: {}", why),
+            RunError::Dump { path, reason } => write!(f, "couldn't dump the scene to {}: {}", path.display(), reason),
+            RunError::Output { path, reason } => write!(f, "couldn't write to {}: {}", path.display(), reason),
+            RunError::InvalidFrame { index, reason } => write!(f, "invalid frame at frames[{}]: {}", index, reason),
+            RunError::Several(problems) => {
+                write!(f, "{} problems were found:", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n    {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for RunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RunError::Parse(why) => Some(why),
+            RunError::InvalidEqualities(why) => Some(why),
+            RunError::Write(why) => Some(why),
+            _ => None,
+        }
+    }
+}
+
+impl From<quick_xml::Error> for RunError {
+    fn from(error: quick_xml::Error) -> Self {
+        RunError::Write(error)
+    }
+}
+
+impl From<parser::Error> for RunError {
+    fn from(error: parser::Error) -> Self {
+        RunError::Parse(error)
+    }
+}
+
+/// Any error from the library's entry points, whichever part of reading, checking or writing a scene it came from.
+/// Each variant is shown and traced exactly as the error it wraps.
+#[derive(Debug)]
+pub enum IsometricError {
+    /// The scene couldn't be set up from the config, or couldn't be rendered.
+    Run(RunError),
+    /// The components file couldn't be read.
+    Parse(parser::Error),
+    /// The SVG couldn't be written.
+    Xml(quick_xml::Error),
+    /// A file couldn't be read or written.
+    Io(io::Error),
+}
+
+impl Display for IsometricError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IsometricError::Run(why) => why.fmt(f),
+            IsometricError::Parse(why) => why.fmt(f),
+            IsometricError::Xml(why) => why.fmt(f),
+            IsometricError::Io(why) => why.fmt(f),
+        }
+    }
+}
+
+impl Error for IsometricError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IsometricError::Run(why) => why.source(),
+            IsometricError::Parse(why) => why.source(),
+            IsometricError::Xml(why) => why.source(),
+            IsometricError::Io(why) => why.source(),
+        }
+    }
+}
+
+impl From<RunError> for IsometricError {
+    fn from(error: RunError) -> Self {
+        IsometricError::Run(error)
+    }
+}
+
+impl From<parser::Error> for IsometricError {
+    fn from(error: parser::Error) -> Self {
+        IsometricError::Parse(error)
+    }
+}
+
+impl From<quick_xml::Error> for IsometricError {
+    fn from(error: quick_xml::Error) -> Self {
+        IsometricError::Xml(error)
+    }
+}
+
+impl From<io::Error> for IsometricError {
+    fn from(error: io::Error) -> Self {
+        IsometricError::Io(error)
+    }
+}
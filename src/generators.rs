@@ -0,0 +1,81 @@
+use itertools::iproduct;
+
+use crate::colour::cell_hash;
+use crate::grid::Grid;
+use crate::shapes::ShapeId;
+use crate::vect;
+use crate::vector::Vec3;
+
+mod tests;
+
+/// Fills a grid with cells worked out from a few numbers, for backdrops and testing, before any tiles go on top.
+///
+/// Everything a generator makes comes from integer hashing and plain arithmetic on doubles,
+/// so the same settings give exactly the same grid on every platform.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    /// Rolling terrain, as columns rising from the ground to a height given by smoothed value noise.
+    Noise {
+        seed: u64,
+        /// The tallest a column can be, which is at least 1.
+        max_height: usize,
+        /// How many cells apart the random heights the terrain is smoothed between are.
+        scale: f64,
+        shape: ShapeId,
+    },
+    /// The bottom layer of the grid alternating between `shape` and `other`, with `shape` at the origin.
+    /// Without `other`, every other cell is left empty.
+    Checkerboard { shape: ShapeId, other: Option<ShapeId> },
+    /// Every cell of the bottom `height` layers.
+    Slab { height: usize, shape: ShapeId },
+}
+
+impl Generator {
+    /// Puts the generated cells into `grid`, leaving the rest as they were. Nothing goes outside the grid.
+    pub fn generate(&self, grid: &mut Grid) {
+        let size = grid.size();
+        match self {
+            Generator::Noise { seed, max_height, scale, shape } => {
+                for (x, z) in iproduct!(0..size.x, 0..size.z) {
+                    let noise = value_noise(x as f64 / scale, z as f64 / scale, *seed);
+                    let height = usize::min(1 + (noise * *max_height as f64) as usize, *max_height);
+                    for y in 0..usize::min(height, size.y) {
+                        grid.set(vect![x, y, z], shape.clone());
+                    }
+                }
+            }
+            Generator::Checkerboard { shape, other } => {
+                if size.y == 0 {
+                    return;
+                }
+                for (x, z) in iproduct!(0..size.x, 0..size.z) {
+                    let id = if (x + z) % 2 == 0 { Some(shape) } else { other.as_ref() };
+                    if let Some(id) = id {
+                        grid.set(vect![x, 0, z], id.clone());
+                    }
+                }
+            }
+            Generator::Slab { height, shape } => {
+                for (x, y, z) in iproduct!(0..size.x, 0..usize::min(*height, size.y), 0..size.z) {
+                    grid.set(vect![x, y, z], shape.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Smooth noise in [0, 1) over the ground, interpolating between random values at each whole `(x, z)`.
+fn value_noise(x: f64, z: f64, seed: u64) -> f64 {
+    let (x0, z0) = (x.floor(), z.floor());
+    // smoothstep, so the slope is continuous across each lattice line
+    let ease = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (ease(x - x0), ease(z - z0));
+    let (x0, z0) = (x0 as usize, z0 as usize);
+    let corner = |x: usize, z: usize| {
+        // the top 53 bits fit exactly into a double, mapped to [0, 1)
+        (cell_hash(vect![x, 0, z], seed) >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let near = corner(x0, z0) + (corner(x0 + 1, z0) - corner(x0, z0)) * tx;
+    let far = corner(x0, z0 + 1) + (corner(x0 + 1, z0 + 1) - corner(x0, z0 + 1)) * tx;
+    near + (far - near) * tz
+}
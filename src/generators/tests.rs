@@ -0,0 +1,83 @@
+#![cfg(test)]
+
+use itertools::iproduct;
+
+use crate::generators::{value_noise, Generator};
+use crate::grid::Grid;
+use crate::shapes::ShapeId;
+use crate::vect;
+use crate::vector::Vec3;
+
+/// How many cells of each column of `grid` are filled.
+fn heights(grid: &Grid) -> Vec<usize> {
+    let size = grid.size();
+    iproduct!(0..size.x, 0..size.z)
+        .map(|(x, z)| (0..size.y).filter(|y| *grid.get(vect![x, *y, z]) != ShapeId::EMPTY).count())
+        .collect()
+}
+
+fn noise(seed: u64) -> Grid {
+    let mut grid = Grid::new(vect![6, 8, 6]);
+    Generator::Noise { seed, max_height: 6, scale: 3.0, shape: ShapeId::from(255u8) }.generate(&mut grid);
+    grid
+}
+
+#[test]
+fn test_noise() {
+    let heights_42 = heights(&noise(42));
+    assert_eq!(heights(&noise(42)), heights_42);
+    assert_ne!(heights(&noise(43)), heights_42);
+    assert!(heights_42.iter().all(|h| (1..=6).contains(h)));
+    // the terrain isn't flat, but neighbouring columns along each row are close in height
+    assert!(heights_42.iter().min() != heights_42.iter().max());
+    for row in heights_42.chunks(6) {
+        assert!(row.windows(2).all(|pair| pair[0].abs_diff(pair[1]) <= 2), "{:?}", row);
+    }
+}
+
+#[test]
+fn test_noise_is_reproducible() {
+    // pinned down exactly, so anything which would move the terrain for a seed people already use shows up here
+    assert_eq!(heights(&noise(42)), vec![
+        5, 5, 4, 3, 3, 3,
+        4, 4, 3, 3, 3, 3,
+        3, 3, 3, 3, 4, 5,
+        2, 2, 3, 3, 4, 6,
+        2, 3, 4, 4, 5, 6,
+        3, 3, 5, 5, 6, 6,
+    ]);
+    assert!((0.0..1.0).contains(&value_noise(2.5, 3.5, 42)));
+}
+
+#[test]
+fn test_noise_columns_stop_at_the_grid() {
+    let mut grid = Grid::new(vect![4, 2, 4]);
+    Generator::Noise { seed: 7, max_height: 10, scale: 2.0, shape: ShapeId::from(255u8) }.generate(&mut grid);
+    assert!(heights(&grid).iter().all(|h| (1..=2).contains(h)));
+}
+
+#[test]
+fn test_checkerboard() {
+    let (a, b) = (ShapeId::from(255u8), ShapeId::from(1u8));
+    let mut grid = Grid::new(vect![3, 2, 3]);
+    Generator::Checkerboard { shape: a.clone(), other: Some(b.clone()) }.generate(&mut grid);
+    assert_eq!(*grid.get(vect![0, 0, 0]), a);
+    assert_eq!(*grid.get(vect![1, 0, 0]), b);
+    assert_eq!(*grid.get(vect![1, 0, 1]), a);
+    assert_eq!(grid.iter_occupied().count(), 9);
+
+    let mut gaps = Grid::new(vect![3, 2, 3]);
+    Generator::Checkerboard { shape: a, other: None }.generate(&mut gaps);
+    assert_eq!(gaps.iter_occupied().count(), 5);
+    assert_eq!(*gaps.get(vect![0, 0, 1]), ShapeId::EMPTY);
+}
+
+#[test]
+fn test_slab() {
+    let mut grid = Grid::new(vect![2, 3, 2]);
+    Generator::Slab { height: 2, shape: ShapeId::from(255u8) }.generate(&mut grid);
+    assert_eq!(heights(&grid), vec![2; 4]);
+    // a slab taller than the grid just fills it
+    Generator::Slab { height: 5, shape: ShapeId::from(255u8) }.generate(&mut grid);
+    assert_eq!(grid.iter_occupied().count(), 12);
+}
@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::{Index, IndexMut};
+
+use itertools::{iproduct, Itertools};
+
+use crate::shapes::ShapeId;
+use crate::vect;
+use crate::vector::Vec3;
+
+mod tests;
+
+/// Grids with at most this many cells store every one of them, which is quicker to look up.
+/// Anything bigger only stores the occupied cells, so memory scales with the tile count.
+const DENSE_LIMIT: usize = 1_000_000;
+
+/// The cells of a scene, and which shape each one holds.
+///
+/// Indexing with a coordinate outside the grid panics, saying where it was and how big the grid is.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    size: Vec3<usize>,
+    cells: Cells,
+}
+
+#[derive(Debug, Clone)]
+enum Cells {
+    /// Every cell, laid out `z` first, then `y`, then `x`.
+    Dense(Vec<ShapeId>),
+    /// Only the occupied cells.
+    Sparse(HashMap<Vec3<usize>, ShapeId>),
+}
+
+impl Grid {
+    /// An empty grid, with cells from `(0, 0, 0)` up to but not including `size`.
+    pub fn new(size: Vec3<usize>) -> Grid {
+        if size.x.saturating_mul(size.y).saturating_mul(size.z) <= DENSE_LIMIT {
+            Grid::dense(size)
+        }
+        else {
+            Grid::sparse(size)
+        }
+    }
+    /// An empty grid storing every cell, however many there are.
+    pub fn dense(size: Vec3<usize>) -> Grid {
+        Grid { size, cells: Cells::Dense(vec![ShapeId::EMPTY; size.x * size.y * size.z]) }
+    }
+    /// An empty grid storing only the cells which are occupied.
+    pub fn sparse(size: Vec3<usize>) -> Grid {
+        Grid { size, cells: Cells::Sparse(HashMap::new()) }
+    }
+    pub fn size(&self) -> Vec3<usize> {
+        self.size
+    }
+    /// Whether only the occupied cells are stored, rather than every one.
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.cells, Cells::Sparse(_))
+    }
+    /// Whether `cell` is inside the grid, occupied or not.
+    pub fn contains(&self, cell: Vec3<usize>) -> bool {
+        cell.x < self.size.x && cell.y < self.size.y && cell.z < self.size.z
+    }
+    /// The shape at `cell`, which is `ShapeId::EMPTY` for unoccupied cells and anywhere outside the grid.
+    pub fn get(&self, cell: Vec3<usize>) -> &ShapeId {
+        if !self.contains(cell) {
+            return &ShapeId::EMPTY;
+        }
+        match &self.cells {
+            Cells::Dense(cells) => &cells[self.offset(cell)],
+            Cells::Sparse(cells) => cells.get(&cell).unwrap_or(&ShapeId::EMPTY),
+        }
+    }
+    /// Puts `id` at `cell`, where setting `ShapeId::EMPTY` clears it.
+    ///
+    /// # Panics
+    /// If `cell` is outside the grid.
+    pub fn set(&mut self, cell: Vec3<usize>, id: ShapeId) {
+        self.check_bounds(cell);
+        match &mut self.cells {
+            Cells::Sparse(cells) if id == ShapeId::EMPTY => {
+                cells.remove(&cell);
+            }
+            _ => self[cell] = id,
+        }
+    }
+    /// Every occupied cell, in no particular order.
+    pub fn iter_occupied(&self) -> Box<dyn Iterator<Item = (Vec3<usize>, &ShapeId)> + '_> {
+        match &self.cells {
+            Cells::Dense(cells) => {
+                let size = self.size;
+                Box::new(cells.iter().enumerate()
+                    .filter(|(_, id)| **id != ShapeId::EMPTY)
+                    .map(move |(i, id)| (vect![i / (size.y * size.z), i / size.z % size.y, i % size.z], id)))
+            }
+            Cells::Sparse(cells) => Box::new(cells.iter()
+                .filter(|(_, id)| **id != ShapeId::EMPTY)
+                .map(|(cell, id)| (*cell, id))),
+        }
+    }
+    /// Every occupied cell, back to front in the same order as `depth_order`.
+    /// Drawing shapes in this order has nearer ones painted over those behind them.
+    pub fn painter_order(&self) -> Vec<(Vec3<usize>, &ShapeId)> {
+        match &self.cells {
+            Cells::Dense(cells) => depth_order(self.size)
+                .map(|cell| (cell, &cells[self.offset(cell)]))
+                .filter(|(_, id)| **id != ShapeId::EMPTY)
+                .collect(),
+            // a sparse grid can have far more cells than tiles, so only the tiles are put in order
+            Cells::Sparse(_) => self.iter_occupied()
+                .sorted_by_key(|(c, _)| (c.x + c.y + c.z, c.x, c.y))
+                .collect(),
+        }
+    }
+    /// Every occupied cell back to front like `painter_order`, where some shapes cover a whole box of cells.
+    ///
+    /// Each box is given by its corner nearest the origin, which is the only one of its cells that should be occupied,
+    /// and the number of cells it covers along each axis. Boxes come after everything behind any part of them,
+    /// and before everything in front, so some cells may be drawn earlier than they usually would be.
+    pub fn painter_order_with(&self, boxes: &[(Vec3<usize>, Vec3<usize>)]) -> Vec<(Vec3<usize>, &ShapeId)> {
+        let order = self.painter_order();
+        if boxes.is_empty() {
+            return order;
+        }
+        let key = |c: Vec3<usize>| (c.x + c.y + c.z, c.x, c.y);
+        let extent = |cell: Vec3<usize>| boxes.iter()
+            .find(|(corner, _)| *corner == cell)
+            .map_or((cell, vect![1, 1, 1]), |b| *b);
+
+        let mut drawn = Vec::with_capacity(order.len());
+        let mut placed = HashSet::new();
+        let mut expanded = HashSet::new();
+        for (next, _) in &order {
+            // everything which has to be drawn before this is drawn first, depth first
+            let mut stack = vec![(*next, false)];
+            while let Some((item, ready)) = stack.pop() {
+                if placed.contains(&item) {
+                    continue;
+                }
+                if ready {
+                    placed.insert(item);
+                    drawn.push((item, self.get(item)));
+                    continue;
+                }
+                // anything seen twice before it's drawn would have to be drawn both in front of and behind something
+                if !expanded.insert(item) {
+                    continue;
+                }
+                stack.push((item, true));
+                let this = extent(item);
+                let mut before = boxes.iter()
+                    .filter(|other| other.0 != item && draws_over(this, **other))
+                    .map(|other| other.0)
+                    .collect_vec();
+                // cells coming after this one in the usual order have only been drawn if something pulled them forward,
+                // so anything behind them might not have been drawn yet
+                if key(item) > key(*next) || this.1 != vect![1, 1, 1] {
+                    before.extend(self.cells_behind(this).filter(|cell| !boxes.iter().any(|(corner, _)| corner == cell)));
+                }
+                stack.extend(before.into_iter().filter(|cell| !placed.contains(cell)).map(|cell| (cell, false)));
+            }
+        }
+        drawn
+    }
+    /// The occupied cells which could be hidden behind the box with its corner nearest the origin and size given by `extent`.
+    fn cells_behind(&self, (corner, size): (Vec3<usize>, Vec3<usize>)) -> impl Iterator<Item = Vec3<usize>> + '_ {
+        // cells only ever hide each other along the diagonal the viewer looks down, so those are all that need checking
+        let reach = (corner.x + size.x).min(corner.y + size.y).min(corner.z + size.z) + 2;
+        (0..=reach)
+            .flat_map(move |t| iproduct!(0..size.x + 3, 0..size.y + 3, 0..size.z + 3).filter_map(move |(x, y, z)| {
+                Some(vect![(corner.x + x).checked_sub(t + 2)?, (corner.y + y).checked_sub(t + 2)?, (corner.z + z).checked_sub(t + 2)?])
+            }))
+            .unique()
+            .filter(move |cell| *self.get(*cell) != ShapeId::EMPTY && *cell != corner && draws_over((corner, size), (*cell, vect![1, 1, 1])))
+    }
+    fn check_bounds(&self, cell: Vec3<usize>) {
+        assert!(self.contains(cell), "{} is outside a grid of size {}", cell, self.size);
+    }
+    fn offset(&self, cell: Vec3<usize>) -> usize {
+        (cell.x * self.size.y + cell.y) * self.size.z + cell.z
+    }
+}
+
+/// Whether the box `front` has to be drawn after `back`, each given by their corner nearest the origin and their size.
+/// That's when they overlap once drawn, and `back` is further from the viewer along one of the axes.
+fn draws_over(front: (Vec3<usize>, Vec3<usize>), back: (Vec3<usize>, Vec3<usize>)) -> bool {
+    let behind = |(a, a_size): (Vec3<usize>, Vec3<usize>), (b, _): (Vec3<usize>, Vec3<usize>)| {
+        a.x + a_size.x <= b.x || a.y + a_size.y <= b.y || a.z + a_size.z <= b.z
+    };
+    behind(back, front) && !behind(front, back) && overlap_when_drawn(front, back)
+}
+
+/// Whether two boxes of cells cover any of the same area once drawn, rather than just touching.
+fn overlap_when_drawn(a: (Vec3<usize>, Vec3<usize>), b: (Vec3<usize>, Vec3<usize>)) -> bool {
+    // looking down the diagonal, a box is drawn as a hexagon with sides along which x - y, y - z or z - x stay the same,
+    // so it's the space between the furthest apart lines of each kind through its corners
+    let ranges = |(min, size): (Vec3<usize>, Vec3<usize>)| {
+        let (lo, hi) = ((min.x as i64, min.y as i64, min.z as i64), ((min.x + size.x) as i64, (min.y + size.y) as i64, (min.z + size.z) as i64));
+        [(lo.0 - hi.1, hi.0 - lo.1), (lo.1 - hi.2, hi.1 - lo.2), (lo.2 - hi.0, hi.2 - lo.0)]
+    };
+    let (a, b) = (ranges(a), ranges(b));
+    let [u, v, w] = [0, 1, 2].map(|i| (a[i].0.max(b[i].0), a[i].1.min(b[i].1)));
+    if u.0 >= u.1 || v.0 >= v.1 || w.0 >= w.1 {
+        return false;
+    }
+    // the three differences always add up to zero, so the third has to be reachable from the other two
+    i64::max(u.0 + v.0, -w.1) < i64::min(u.1 + v.1, -w.0)
+}
+
+/// Every cell of a grid `size` big, each once, back to front: by `x + y + z`, then `x`, then `y`.
+///
+/// Only cells inside the grid are ever visited, so going through it takes no longer than there are cells.
+pub fn depth_order(size: Vec3<usize>) -> impl Iterator<Item = Vec3<usize>> {
+    let depths = if size.x == 0 || size.y == 0 || size.z == 0 { 0 } else { size.x + size.y + size.z - 2 };
+    (0..depths).flat_map(move |depth| {
+        // `y + z` can only make up so much of the depth, which leaves `x` to make up the rest
+        let xs = depth.saturating_sub(size.y + size.z - 2)..=usize::min(size.x - 1, depth);
+        xs.flat_map(move |x| {
+            let rest = depth - x;
+            (rest.saturating_sub(size.z - 1)..=usize::min(size.y - 1, rest)).map(move |y| vect![x, y, rest - y])
+        })
+    })
+}
+
+/// The cells sharing a face with `cell`, leaving out any which would be below zero.
+/// The others might be past the far edge of the grid.
+pub fn neighbours(cell: Vec3<usize>) -> impl Iterator<Item = Vec3<usize>> {
+    [
+        (cell.x > 0).then(|| vect![cell.x - 1, cell.y, cell.z]),
+        (cell.y > 0).then(|| vect![cell.x, cell.y - 1, cell.z]),
+        (cell.z > 0).then(|| vect![cell.x, cell.y, cell.z - 1]),
+        Some(vect![cell.x + 1, cell.y, cell.z]),
+        Some(vect![cell.x, cell.y + 1, cell.z]),
+        Some(vect![cell.x, cell.y, cell.z + 1]),
+    ].into_iter().flatten()
+}
+
+impl Index<Vec3<usize>> for Grid {
+    type Output = ShapeId;
+
+    fn index(&self, cell: Vec3<usize>) -> &ShapeId {
+        self.check_bounds(cell);
+        self.get(cell)
+    }
+}
+
+impl IndexMut<Vec3<usize>> for Grid {
+    fn index_mut(&mut self, cell: Vec3<usize>) -> &mut ShapeId {
+        self.check_bounds(cell);
+        let offset = self.offset(cell);
+        match &mut self.cells {
+            Cells::Dense(cells) => &mut cells[offset],
+            Cells::Sparse(cells) => cells.entry(cell).or_default(),
+        }
+    }
+}
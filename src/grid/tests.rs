@@ -0,0 +1,143 @@
+#![cfg(test)]
+
+use itertools::{iproduct, Itertools};
+
+use crate::grid::{depth_order, draws_over, overlap_when_drawn, Grid};
+use crate::shapes::ShapeId;
+use crate::vect;
+use crate::vector::Vec3;
+
+#[test]
+fn test_get_set() {
+    for mut grid in [Grid::dense(vect![2, 3, 4]), Grid::sparse(vect![2, 3, 4])] {
+        assert_eq!(grid.get(vect![1, 2, 3]), &ShapeId::EMPTY);
+        grid.set(vect![1, 2, 3], 255u8.into());
+        assert_eq!(grid.get(vect![1, 2, 3]), &ShapeId::Index(255));
+        assert_eq!(grid[vect![1, 2, 3]], ShapeId::Index(255));
+        grid[vect![0, 2, 1]] = 3u8.into();
+        assert_eq!(grid.get(vect![0, 2, 1]), &ShapeId::Index(3));
+        // outside the grid is always empty
+        assert_eq!(grid.get(vect![2, 0, 0]), &ShapeId::EMPTY);
+        grid.set(vect![1, 2, 3], ShapeId::EMPTY);
+        assert_eq!(grid.iter_occupied().collect::<Vec<_>>(), vec![(vect![0, 2, 1], &ShapeId::Index(3))]);
+    }
+}
+
+#[test]
+#[should_panic(expected = "(0, 2, 0) is outside a grid of size (2, 2, 2)")]
+fn test_set_outside() {
+    Grid::new(vect![2, 2, 2]).set(vect![0, 2, 0], 255u8.into());
+}
+
+#[test]
+#[should_panic(expected = "(0, 0, 5) is outside a grid of size (2, 2, 2)")]
+fn test_index_outside() {
+    let _ = &Grid::new(vect![2, 2, 2])[vect![0, 0, 5]];
+}
+
+#[test]
+fn test_painter_order() {
+    for mut grid in [Grid::dense(vect![3, 3, 3]), Grid::sparse(vect![3, 3, 3])] {
+        for cell in [vect![2, 0, 0], vect![0, 0, 1], vect![1, 1, 0], vect![0, 0, 0], vect![0, 2, 0]] {
+            grid.set(cell, 255u8.into());
+        }
+        let order = grid.painter_order().into_iter().map(|(cell, _)| cell).collect::<Vec<_>>();
+        assert_eq!(order, vec![vect![0, 0, 0], vect![0, 0, 1], vect![0, 2, 0], vect![1, 1, 0], vect![2, 0, 0]]);
+    }
+}
+
+#[test]
+fn test_depth_order() {
+    let order = depth_order(vect![2, 3, 4]).collect::<Vec<_>>();
+    assert_eq!(order.len(), 24);
+    assert_eq!(order.iter().unique().count(), 24);
+    assert!(order.iter().all(|cell| cell.x < 2 && cell.y < 3 && cell.z < 4));
+    assert!(order.iter().tuple_windows().all(|(a, b)| a.x + a.y + a.z <= b.x + b.y + b.z));
+    // the same order painter_order puts the cells of a full grid in, whichever axis is longest
+    for size in [vect![2, 3, 4], vect![4, 3, 2], vect![1, 5, 1], vect![3, 1, 3]] {
+        let sorted = iproduct!(0..size.x, 0..size.y, 0..size.z)
+            .map(|(x, y, z)| vect![x, y, z])
+            .sorted_by_key(|c| (c.x + c.y + c.z, c.x, c.y))
+            .collect::<Vec<_>>();
+        assert_eq!(depth_order(size).collect::<Vec<_>>(), sorted);
+    }
+    assert_eq!(depth_order(vect![3, 0, 3]).count(), 0);
+}
+
+/// Where each of `cells` comes in the order drawn from `grid` with `boxes`.
+fn positions(grid: &Grid, boxes: &[(Vec3<usize>, Vec3<usize>)], cells: &[Vec3<usize>]) -> Vec<usize> {
+    let order = grid.painter_order_with(boxes).into_iter().map(|(cell, _)| cell).collect::<Vec<_>>();
+    assert_eq!(order.len(), grid.iter_occupied().count());
+    cells.iter().map(|cell| order.iter().position(|c| c == cell).unwrap()).collect()
+}
+
+#[test]
+fn test_painter_order_with_boxes() {
+    let full = |cells: &[Vec3<usize>]| {
+        let mut grid = Grid::new(vect![4, 4, 4]);
+        for cell in cells {
+            grid.set(*cell, 255u8.into());
+        }
+        grid
+    };
+    // without any boxes, it's the usual order
+    let grid = full(&[vect![0, 0, 0], vect![1, 0, 0], vect![0, 1, 0]]);
+    assert_eq!(grid.painter_order_with(&[]), grid.painter_order());
+
+    // a bed along x, with a cube sitting on its far end, which usually comes up before the bed's near end
+    let bed = (vect![0, 0, 0], vect![2, 1, 1]);
+    let grid = full(&[vect![0, 0, 0], vect![0, 1, 0], vect![2, 0, 0]]);
+    let [bed_at, on_top, beside] = positions(&grid, &[bed], &[vect![0, 0, 0], vect![0, 1, 0], vect![2, 0, 0]])[..] else { panic!() };
+    assert!(bed_at < on_top);
+    assert!(bed_at < beside);
+
+    // a raised bed, with a cube under its near end which would usually come up after the bed's far end
+    let raised = (vect![0, 1, 0], vect![2, 1, 1]);
+    let grid = full(&[vect![0, 1, 0], vect![1, 0, 0]]);
+    let [bed_at, under] = positions(&grid, &[raised], &[vect![0, 1, 0], vect![1, 0, 0]])[..] else { panic!() };
+    assert!(under < bed_at);
+
+    // a tall shape comes after the cubes behind it, and before the ones in front, all the way up
+    let tall = (vect![1, 0, 1], vect![1, 2, 1]);
+    let behind = [vect![0, 0, 1], vect![1, 1, 0], vect![0, 1, 1], vect![1, 0, 0]];
+    let in_front = [vect![2, 0, 1], vect![1, 1, 2], vect![1, 0, 2], vect![2, 1, 1], vect![1, 2, 1]];
+    let grid = full(&[&[tall.0][..], &behind, &in_front].concat());
+    let tall_at = positions(&grid, &[tall], &[tall.0])[0];
+    assert!(positions(&grid, &[tall], &behind).into_iter().all(|p| p < tall_at));
+    assert!(positions(&grid, &[tall], &in_front).into_iter().all(|p| p > tall_at));
+}
+
+#[test]
+fn test_cells_behind() {
+    // checking along the diagonal should find everything a search of the whole grid does
+    let mut grid = Grid::new(vect![5, 5, 5]);
+    for (x, y, z) in itertools::iproduct!(0..5, 0..5, 0..5) {
+        grid.set(vect![x, y, z], 255u8.into());
+    }
+    for extent in [(vect![2, 2, 2], vect![1, 1, 1]), (vect![1, 3, 2], vect![2, 1, 1]), (vect![3, 0, 3], vect![1, 2, 1]), (vect![4, 4, 4], vect![1, 1, 1])] {
+        let found = grid.cells_behind(extent).sorted_by_key(|c| (c.x, c.y, c.z)).collect::<Vec<_>>();
+        let everything = grid.iter_occupied()
+            .map(|(cell, _)| cell)
+            .filter(|cell| *cell != extent.0 && draws_over(extent, (*cell, vect![1, 1, 1])))
+            .sorted_by_key(|c| (c.x, c.y, c.z))
+            .collect::<Vec<_>>();
+        assert_eq!(found, everything);
+        assert!(!found.is_empty());
+    }
+}
+
+#[test]
+fn test_overlap_when_drawn() {
+    let cell = |x, y, z| (vect![x, y, z], vect![1, 1, 1]);
+    // along the diagonal, and next to each other
+    assert!(overlap_when_drawn(cell(0, 0, 0), cell(1, 1, 1)));
+    assert!(overlap_when_drawn(cell(0, 0, 0), cell(1, 0, 0)));
+    assert!(overlap_when_drawn(cell(0, 0, 0), cell(1, 1, 0)));
+    // cells at the same depth only ever touch
+    assert!(!overlap_when_drawn(cell(1, 0, 0), cell(0, 1, 0)));
+    assert!(!overlap_when_drawn(cell(2, 0, 0), cell(0, 0, 0)));
+    // a long box reaches further
+    assert!(overlap_when_drawn((vect![0, 0, 0], vect![3, 1, 1]), cell(2, 0, 0)));
+    assert!(draws_over(cell(1, 1, 1), cell(0, 0, 0)));
+    assert!(!draws_over(cell(0, 0, 0), cell(1, 1, 1)));
+}
@@ -1,63 +1,708 @@
-use std::collections::VecDeque;
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::str::FromStr;
 
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::{CaptureMatches, Regex};
-use quick_xml::events::{Event, BytesStart, BytesEnd};
+use quick_xml::events::{Event, BytesDecl, BytesStart, BytesEnd, BytesText};
 
-use crate::path::{Command, CommandType};
-use crate::shapes::{Shape, ShapePrimitive};
+use crate::colour::{self, Effects, FaceInfo, Fog, Gradient, Lighting, Shader, Stroke, StrokeMode};
+use crate::num::Float;
+use crate::path::{AntiSeam, Command, CommandType, PathMerging, PathOptions};
+use crate::shapes::{ComponentStroke, PlacedShape, Polygonal, Shape, ShapeComponent, ShapePrimitive};
 use crate::vect;
 use crate::vector::{Vec2, Vec3};
 
 lazy_static! {
-    static ref PATH_REGEX: Regex = Regex::new(r"(?i)(?P<cmd>[MVHLZ])\s*(?P<nums>(([+-]?\d+\.?\d*(E\d+)?)(\s|,)?)*)").unwrap();
+    static ref NUMBER_REGEX: Regex = Regex::new(r"^[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?").unwrap();
+    static ref PATH_REGEX: Regex = Regex::new(r"(?i)(?P<cmd>[MVHLCQTAZ])(?P<nums>[^MVHLCQTAZ]*)").unwrap();
 }
 
-pub fn object_svg_iter(shapes: &Vec<Shape>, width: f64, height: f64, light_vector: Vec3<f64>, object_colour: Vec3<f64>) -> impl Iterator<Item=Event> {
+/// A length in one of the units SVG understands, like `120mm`. Plain numbers have no unit, so are pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Length {
+    pub value: f64,
+    pub unit: String,
+}
+
+impl Length {
+    const UNITS: [&'static str; 9] = ["", "px", "mm", "cm", "in", "pt", "pc", "em", "ex"];
+
+    /// This length `factor` times over, in the same unit.
+    pub fn scale(&self, factor: f64) -> Length {
+        Length { value: self.value * factor, unit: self.unit.clone() }
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let number = NUMBER_REGEX.find(s).ok_or_else(|| format!("'{}' doesn't start with a number", s))?;
+        let value: f64 = number.as_str().parse().map_err(|_| format!("'{}' doesn't start with a number", s))?;
+        let unit = s[number.end()..].trim();
+        if !Length::UNITS.contains(&unit) {
+            return Err(format!("'{}' is not a unit of length, expected one of {}", unit, Length::UNITS[1..].join(", ")));
+        }
+        if value <= 0.0 {
+            return Err(format!("the image has to have some size, but was {}", s));
+        }
+        Ok(Length { value, unit: unit.to_string() })
+    }
+}
+
+impl Display for Length {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.value, self.unit)
+    }
+}
+
+/// The area an image is drawn over, and how big it's shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    /// The size of the image in the units shapes are drawn in, which is what the `viewBox` covers.
+    pub size: Vec2<f64>,
+    /// How wide and high the image is shown. By default, one unit is one pixel.
+    pub display: Option<(Length, Length)>,
+}
+
+impl Canvas {
+    /// A canvas of `width` by `height`, shown at one pixel per unit.
+    pub fn new(width: f64, height: f64) -> Canvas {
+        Canvas { size: vect![width, height], display: None }
+    }
+}
+
+/// The `<?xml?>` declaration every document starts with, ahead of the `<svg>` tag.
+pub fn xml_declaration_event<'a>() -> Event<'a> {
+    Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None))
+}
+
+/// The opening `<svg>` tag for a document covering `canvas`.
+pub fn svg_start_event<'a>(canvas: &Canvas) -> Event<'a> {
 
     let mut start_bytes = BytesStart::new("svg");
-    let width = width.to_string();
-    let height = height.to_string();
+    let (width, height) = match &canvas.display {
+        Some((width, height)) => (width.to_string(), height.to_string()),
+        None => (canvas.size.x.to_string(), canvas.size.y.to_string()),
+    };
+    let view_box = format!("0 0 {} {}", canvas.size.x, canvas.size.y);
 
     start_bytes.push_attribute(("width", width.as_str()));
     start_bytes.push_attribute(("height", height.as_str()));
+    start_bytes.push_attribute(("viewBox", view_box.as_str()));
     start_bytes.push_attribute(("version", "1.1"));
     start_bytes.push_attribute(("xmlns", "http://www.w3.org/2000/svg"));
 
-    let start_svg = Event::Start(start_bytes);
-    let end_svg = Event::End(BytesEnd::new("svg"));
+    Event::Start(start_bytes)
+}
+
+/// A `<path>` element with the given outline and style, followed by any other `attributes`.
+pub fn path_event<'a>(d: &str, style: &str, attributes: &[(String, String)]) -> Event<'a> {
+    let mut tag_bytes = BytesStart::new("path");
+    tag_bytes.push_attribute(("d", d));
+    tag_bytes.push_attribute(("style", style));
+    for (name, value) in attributes {
+        tag_bytes.push_attribute((name.as_str(), value.as_str()));
+    }
+    Event::Empty(tag_bytes)
+}
+
+/// Joins runs of neighbouring paths which share a style and attributes, keeping their order.
+/// A path which overlaps any already in the run starts a new one instead, as the two could cancel out where they cross.
+fn merge_styles(paths: impl IntoIterator<Item = OutlinedPath>) -> impl Iterator<Item = StyledPath> {
+    let mut paths = paths.into_iter().peekable();
+    std::iter::from_fn(move || {
+        let ((style, attributes, mut d), mut outline) = paths.next()?;
+        while let Some(((_, _, next_d), next_outline)) = paths.next_if(|((next_style, next_attributes, _), next_outline)| {
+            *next_style == style && *next_attributes == attributes
+                && !outline.iter().any(|p| next_outline.iter().any(|q| p.overlaps(q)))
+        }) {
+            d += &next_d;
+            outline.extend(next_outline);
+        }
+        Some((style, attributes, d))
+    })
+}
+
+/// Gives every object an `id` to find it by in the output.
+/// Objects from an equality group are named after it, and anything else after its cell, like `tile-3-0-2`.
+/// Names are made into valid XML ids, and any which end up the same, or the same as one already `taken`, are told apart by a numeric suffix.
+fn object_ids(shapes: &[PlacedShape], taken: HashSet<String>) -> Vec<String> {
+    let mut used = taken;
+    shapes.iter()
+        .map(|shape| {
+            let name = match &shape.group {
+                Some(group) => xml_id(group),
+                None => format!("tile-{}-{}-{}", shape.cell.x, shape.cell.y, shape.cell.z),
+            };
+            let mut id = name.clone();
+            let mut suffix = 2;
+            while !used.insert(id.clone()) {
+                id = format!("{}-{}", name, suffix);
+                suffix += 1;
+            }
+            id
+        })
+        .collect()
+}
+
+/// Replaces anything which can't go in an XML id with `_`, including at the start where digits aren't allowed.
+fn xml_id(name: &str) -> String {
+    let mut id: String = name.chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    if !id.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        id.insert(0, '_');
+    }
+    id
+}
+
+/// The `(style, attributes, d)` of a path, with `attributes` being those carried over from the face it's drawn for.
+type StyledPath = (String, Vec<(String, String)>, String);
+/// A path along with the primitives it fills, which it mustn't be merged over.
+type OutlinedPath = (StyledPath, Vec<ShapePrimitive>);
+/// Each path an object is drawn with.
+type StyledPaths = Vec<OutlinedPath>;
+
+/// What goes into a document besides the shapes themselves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DocumentOptions {
+    /// Whether each object is tagged with where it came from, as `data-x`, `data-y`, `data-z` and `data-group` attributes.
+    /// A shape drawn for a whole group or a box of cells gives the one cell it's anchored at.
+    pub cell_attributes: bool,
+    /// Whether shapes drawn exactly the same more than once are written once to `<defs>`, and placed with a `<use>` each time.
+    /// Shapes which have lost any part to what's in front of them only match others which lost the same part, so are usually written out in full.
+    /// Nothing is shared when every path is being merged together, as there are no objects to place.
+    pub reuse_shapes: bool,
+    /// Which coordinate objects are gathered into layers by, for editing in Inkscape.
+    pub layers: Layers,
+    /// Whether a `fill` attribute carried over from the components file is kept, in place of the fill each face is shaded with.
+    /// Otherwise the shading wins, and the attribute is dropped.
+    pub preserve_fill: bool,
+    /// Whether a stroke a face was drawn with in the components file is lit the same way as the face's fill.
+    /// Otherwise it's written exactly as it was given.
+    pub shade_strokes: bool,
+    /// How the scene builds itself up when it's opened, if it does at all.
+    /// There are no objects to fade in when every path is being merged together, so nothing's animated then.
+    pub animation: Option<Animation>,
+}
+
+impl Default for DocumentOptions {
+    fn default() -> Self {
+        DocumentOptions { cell_attributes: true, reuse_shapes: false, layers: Layers::Off, preserve_fill: false, shade_strokes: false, animation: None }
+    }
+}
+
+/// Objects fading in one step at a time, so the scene looks to be put together piece by piece.
+///
+/// Every object is given the class of its step, like `step-3`, which a `<style>` at the top of the document fades in from nothing.
+/// The objects are only see-through before their fade starts, so a viewer which doesn't animate still shows the whole scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animation {
+    /// How long each step takes to fade in, in seconds, which is also how long after the one before it starts.
+    pub step_duration: f64,
+    pub order: BuildOrder,
+}
+
+/// Which objects are put in place first when the scene builds itself up.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum BuildOrder {
+    /// From the back to the front, a step for each depth counted in [`PlacedShape::depth`].
+    #[default]
+    Depth,
+    /// From the bottom up, a step for each `y` in the world.
+    Layer,
+}
+
+impl BuildOrder {
+    /// What a shape is put in place along with everything else sharing it.
+    fn level(self, shape: &PlacedShape) -> usize {
+        match self {
+            BuildOrder::Depth => shape.depth,
+            BuildOrder::Layer => shape.cell.y,
+        }
+    }
+}
+
+impl FromStr for BuildOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depth" => Ok(BuildOrder::Depth),
+            "layer" => Ok(BuildOrder::Layer),
+            _ => Err(format!("'{}' is not an order to build in, expected \"depth\" or \"layer\"", s)),
+        }
+    }
+}
+
+impl Display for BuildOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BuildOrder::Depth => "depth",
+            BuildOrder::Layer => "layer",
+        })
+    }
+}
+
+/// The step each of `shapes` fades in at, counting only the levels there are shapes at, so there are no pauses.
+/// Gives back how many steps there are along with them.
+fn build_steps(shapes: &[PlacedShape], order: BuildOrder) -> (Vec<usize>, usize) {
+    let levels = shapes.iter().map(|shape| order.level(shape)).sorted().dedup().collect_vec();
+    let steps = shapes.iter().map(|shape| levels.binary_search(&order.level(shape)).unwrap()).collect();
+    (steps, levels.len())
+}
+
+/// A `<style>` fading in every object with the class of each of `steps` steps, one after another.
+/// The objects stay hidden until their step starts, and then are left as they'd have been without it.
+fn animation_style<'a>(animation: Animation, steps: usize) -> Vec<Event<'a>> {
+    // times are written in whole milliseconds, so adding up the steps doesn't leave rounding errors in the output
+    let milliseconds = |seconds: f64| (seconds * 1000.0).round();
+    let mut css = String::from("@keyframes build-up{from{opacity:0}to{opacity:1}}");
+    for step in 0..steps {
+        css += &format!(
+            ".step-{}{{animation:build-up {}ms linear {}ms backwards}}",
+            step, milliseconds(animation.step_duration), milliseconds(animation.step_duration * step as f64),
+        );
+    }
+    vec![
+        Event::Start(BytesStart::new("style")),
+        Event::Text(BytesText::new(&css).into_owned()),
+        Event::End(BytesEnd::new("style")),
+    ]
+}
+
+/// Which coordinate shapes are gathered into Inkscape layers by, if any.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Layers {
+    /// Every object goes straight in the document.
+    #[default]
+    Off,
+    /// One layer for each coordinate along an axis of the world.
+    X,
+    Y,
+    Z,
+    /// One layer for each depth, from the back to the front, as counted in [`PlacedShape::depth`].
+    Depth,
+}
+
+impl Layers {
+    /// How to find the layer a shape goes in, unless there are no layers.
+    fn level_fn(self) -> Option<fn(&PlacedShape) -> usize> {
+        match self {
+            Layers::Off => None,
+            Layers::X => Some(|shape| shape.cell.x),
+            Layers::Y => Some(|shape| shape.cell.y),
+            Layers::Z => Some(|shape| shape.cell.z),
+            Layers::Depth => Some(|shape| shape.depth),
+        }
+    }
+}
+
+impl FromStr for Layers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Layers::Off),
+            "x" => Ok(Layers::X),
+            "y" => Ok(Layers::Y),
+            "z" => Ok(Layers::Z),
+            "depth" => Ok(Layers::Depth),
+            _ => Err(format!("'{}' is not a way of layering, expected one of \"off\", \"x\", \"y\", \"z\" or \"depth\"", s)),
+        }
+    }
+}
+
+impl Display for Layers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Layers::Off => "off",
+            Layers::X => "x",
+            Layers::Y => "y",
+            Layers::Z => "z",
+            Layers::Depth => "depth",
+        })
+    }
+}
+
+/// Splits the indices of `shapes` into layers by `level`, each given with the level its members are at.
+///
+/// Layers are drawn one after another, so gathering every shape at a level together would paint some shapes
+/// over others they used to be behind. As long as none of those shapes overlap, rather than only touching, there's one layer for each level, from the lowest up.
+/// Otherwise, the shapes are kept in order, and a new layer is started each time the level changes.
+pub(crate) fn layers(shapes: &[PlacedShape], level: impl Fn(&PlacedShape) -> usize) -> Vec<(usize, Vec<usize>)> {
+    let primitives = shapes.iter()
+        .map(|shape| shape.shape.component_iter().flat_map(|c| c.primitives()).collect_vec())
+        .collect_vec();
+    let overlap = |a: usize, b: usize| primitives[a].iter().any(|p| primitives[b].iter().any(|q| p.overlaps(q)));
+    let reordered = (0..shapes.len())
+        .any(|a| (a + 1..shapes.len()).any(|b| level(&shapes[b]) < level(&shapes[a]) && overlap(a, b)));
+
+    let order = if reordered {
+        (0..shapes.len()).collect_vec()
+    }
+    else {
+        (0..shapes.len()).sorted_by_key(|i| level(&shapes[*i])).collect_vec()
+    };
+    order.into_iter()
+        .group_by(|i| level(&shapes[*i]))
+        .into_iter()
+        .map(|(level, members)| (level, members.collect()))
+        .collect()
+}
+
+/// How each face is coloured and written.
+struct Styler<'a> {
+    lighting: &'a Lighting,
+    fog: Option<Fog>,
+    gradient: Option<Gradient>,
+    stroke: Option<Stroke>,
+    path_options: PathOptions,
+    shader: &'a Shader,
+    preserve_fill: bool,
+    shade_strokes: bool,
+}
+
+impl<'a> Styler<'a> {
+    /// The `(style, attributes, d)` of each path drawn for `shape`, and the primitives it fills, with its outline taken from `geometry` so it can be drawn somewhere else.
+    /// `offer` is given the id and colour of the gradient each face could be filled with, whether or not the shader uses it.
+    fn style(&self, shape: &PlacedShape, geometry: &Shape, mut offer: impl FnMut(&str, Vec3<f64>)) -> StyledPaths {
+        geometry.component_iter().map(|c| {
+            let c = &*self.shade_stroke(c);
+            let mut fill = c.shade(self.lighting, shape.colour);
+            if let Some(fog) = &self.fog {
+                fill = fog.apply(fill, shape.distance as f64);
+            }
+            let gradient = self.gradient.map(|gradient| {
+                let id = gradient.id(fill);
+                offer(&id, fill);
+                id
+            });
+            let style = (self.shader)(&FaceInfo {
+                component: c,
+                cell: shape.cell,
+                group: shape.group.as_deref(),
+                colour: fill,
+                gradient,
+                opacity: shape.opacity,
+                stroke: self.stroke.filter(|stroke| stroke.mode == StrokeMode::Primitives),
+            });
+            let style = match self.path_options.anti_seam {
+                AntiSeam::Stroke { width } => seam_stroke(style, width),
+                _ => style,
+            };
+            let (style, attributes) = self.carry_attributes(style, c);
+            ((style, attributes, c.generate_d(&self.path_options)), c.primitives().to_vec())
+        })
+        // an outline fills nothing, so there's nothing for it to cancel out
+        .chain(self.stroke
+            .filter(|stroke| stroke.mode == StrokeMode::Silhouette)
+            .map(|stroke| ((format!("fill:none;{}", stroke.css()), vec![], geometry.generate_outline_d()), vec![]))
+            .filter(|((_, _, d), _)| !d.is_empty()))
+        .collect()
+    }
+    /// `component`, with its stroke lit like a fill if strokes are being shaded and its colour is one that can be.
+    fn shade_stroke<'c>(&self, component: &'c ShapeComponent) -> Cow<'c, ShapeComponent> {
+        let colour = component.stroke()
+            .and_then(|stroke| stroke.colour.as_deref())
+            .and_then(colour::parse_css_colour);
+        match colour {
+            Some([r, g, b]) if self.shade_strokes => {
+                let shaded = component.shade(self.lighting, vect![r as f64, g as f64, b as f64] / 255.0);
+                let stroke = component.stroke().map(|stroke| ComponentStroke { colour: Some(colour::hex(shaded)), ..stroke.clone() });
+                Cow::Owned(component.clone().with_stroke(stroke))
+            }
+            _ => Cow::Borrowed(component),
+        }
+    }
+    /// `style` along with the attributes `component` carries. A `fill` among them would fight the shading,
+    /// so it's dropped, unless fills are being preserved, when it's the fill in `style` which goes instead.
+    fn carry_attributes(&self, style: String, component: &ShapeComponent) -> (String, Vec<(String, String)>) {
+        if self.preserve_fill && component.attributes().iter().any(|(name, _)| name == "fill") {
+            let style = style.split(';')
+                .filter(|declaration| declaration.split(':').next().map(str::trim) != Some("fill"))
+                .join(";");
+            (style, component.attributes().to_vec())
+        }
+        else {
+            (style, component.attributes().iter().filter(|(name, _)| name != "fill").cloned().collect())
+        }
+    }
+    /// A `<path>` for each of `paths`, merged within the shape if that's been asked for.
+    fn shape_paths(&self, paths: StyledPaths) -> Vec<Event<'a>> {
+        let paths: Vec<StyledPath> = if self.path_options.merging == PathMerging::Shapes {
+            merge_styles(paths).collect()
+        }
+        else {
+            paths.into_iter().map(|(path, _)| path).collect()
+        };
+        paths.into_iter().map(|(style, attributes, d)| path_event(&d, &style, &attributes)).collect()
+    }
+}
+
+/// Everything needed to write out any one object, shared between every layer they're written in.
+struct Objects<'a> {
+    shapes: &'a [PlacedShape],
+    styler: Styler<'a>,
+    ids: Vec<String>,
+    /// The step of the build-up each shape fades in at, if it's animated.
+    steps: Vec<usize>,
+    /// Where each shape drawn from a shared symbol is placed, and which symbol it is.
+    placements: Vec<Option<(Vec2<f64>, usize)>>,
+    symbol_ids: Vec<String>,
+    options: DocumentOptions,
+}
 
-    let paths: Vec<_> = shapes.iter().map(|shape|
-        [
-            vec![Event::Start(BytesStart::new("g"))],
-            shape.component_iter().map(|c|
-                c.generate_path(light_vector, object_colour)
-            ).collect::<Vec<_>>(),
-            vec![Event::End(BytesEnd::new("g"))],
-        ].into_iter().flatten()
-    ).flatten().collect();
+impl<'a> Objects<'a> {
+    /// The `<g>` holding the shape at `index`, or the `<use>` placing it.
+    fn events(&self, index: usize) -> Vec<Event<'a>> {
+        let shape = &self.shapes[index];
+        let placement = self.placements[index];
+        let mut start = BytesStart::new(if placement.is_some() { "use" } else { "g" });
+        start.push_attribute(("id", self.ids[index].as_str()));
+        if let Some((corner, symbol)) = placement {
+            start.push_attribute(("href", format!("#{}", self.symbol_ids[symbol]).as_str()));
+            start.push_attribute(("x", corner.x.to_string().as_str()));
+            start.push_attribute(("y", corner.y.to_string().as_str()));
+        }
+        if let Some(step) = self.steps.get(index) {
+            start.push_attribute(("class", format!("step-{}", step).as_str()));
+        }
+        if self.options.cell_attributes {
+            for (name, coordinate) in [("data-x", shape.cell.x), ("data-y", shape.cell.y), ("data-z", shape.cell.z)] {
+                start.push_attribute((name, coordinate.to_string().as_str()));
+            }
+            if let Some(group) = &shape.group {
+                start.push_attribute(("data-group", group.as_str()));
+            }
+        }
+        match placement {
+            Some(_) => vec![Event::Empty(start)],
+            None => [
+                vec![Event::Start(start)],
+                self.styler.shape_paths(self.styler.style(shape, &shape.shape, |_, _| ())),
+                vec![Event::End(BytesEnd::new("g"))],
+            ].concat(),
+        }
+    }
+}
 
-    [
-        vec![start_svg],
-        paths,
-        vec![end_svg],
-    ].into_iter().flatten()
+/// The events for every shape in `members`, styled one at a time as they're asked for.
+fn layer_events<'a>(objects: Rc<Objects<'a>>, members: Vec<usize>) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+    match objects.styler.path_options.merging {
+        PathMerging::Global => Box::new(merge_styles(members.into_iter()
+            .flat_map(move |i| objects.styler.style(&objects.shapes[i], &objects.shapes[i].shape, |_, _| ())))
+            .map(|(style, attributes, d)| path_event(&d, &style, &attributes))),
+        _ => Box::new(members.into_iter().flat_map(move |i| objects.events(i))),
+    }
 }
 
-pub struct ToDStringIter<'a> {
-    command_iter: ToSvgCommandIter<'a>,
+/// Writes `shapes` out as a whole document, each as an object of its own unless every path is being merged together.
+///
+/// Objects are styled and written one at a time as the events are asked for, so a scene never has to be held in memory all at once.
+/// The only exceptions are gradients and shared shapes, which have to be found in every shape before `<defs>` can be written.
+pub fn object_svg_iter<'a>(shapes: &'a [PlacedShape], canvas: &Canvas, lighting: &'a Lighting, effects: Effects, path_options: PathOptions, shader: &'a Shader, options: DocumentOptions) -> impl Iterator<Item=Event<'a>> + 'a {
+    lazy_static! {
+        static ref URL_REGEX: Regex = Regex::new(r"url\(#([^)]*)\)").unwrap();
+    }
+
+    let Effects { fog, gradient, stroke, shadow } = effects;
+    let styler = Styler { lighting, fog, gradient, stroke, path_options, shader, preserve_fill: options.preserve_fill, shade_strokes: options.shade_strokes };
+
+    // every gradient a face could use, by id, in the order they first come up.
+    // Only gradients actually used by a style are written, as a custom shader might not use them at all
+    let gradients = match gradient {
+        Some(_) => {
+            let mut offered: Vec<(String, Vec3<f64>)> = vec![];
+            let mut used = HashSet::new();
+            for shape in shapes {
+                let paths = styler.style(shape, &shape.shape, |id, fill| {
+                    if !offered.iter().any(|(g, _)| g == id) {
+                        offered.push((id.to_string(), fill));
+                    }
+                });
+                for ((style, _, _), _) in paths {
+                    used.extend(URL_REGEX.captures_iter(&style).map(|caps| caps[1].to_string()));
+                }
+            }
+            offered.into_iter().filter(|(id, _)| used.contains(id)).collect_vec()
+        }
+        None => vec![],
+    };
+
+    // each shape moved so the corner of the box around it is at the origin, which is the same for every copy of the same shape.
+    // Only shapes drawn more than once are worth sharing
+    let (symbols, placements) = if options.reuse_shapes && path_options.merging != PathMerging::Global {
+        let local: Vec<Option<(Vec2<f64>, StyledPaths)>> = shapes.iter()
+            .map(|shape| {
+                // a shape with nothing left to draw has no box to place it by
+                shape.shape.points_iter().next()?;
+                let corner = shape.shape.bounds().min;
+                let mut geometry = shape.shape.clone();
+                geometry.shift(vect![0.0, 0.0] - corner);
+                Some((corner, styler.style(shape, &geometry, |_, _| ())))
+            })
+            .collect();
+        // copies are told apart by how they're drawn, as the outlines of two copies can differ by a rounding error
+        let drawn = |paths: &StyledPaths| paths.iter().map(|(path, _)| path.clone()).collect_vec();
+        let symbols: Vec<StyledPaths> = local.iter().flatten().map(|(_, paths)| paths).duplicates_by(|paths| drawn(paths)).cloned().collect();
+        let placements = local.iter()
+            .map(|local| local.as_ref().and_then(|(corner, paths)| Some((*corner, symbols.iter().position(|symbol| drawn(symbol) == drawn(paths))?))))
+            .collect_vec();
+        (symbols, placements)
+    }
+    else {
+        (vec![], vec![None; shapes.len()])
+    };
+    let symbol_ids = (0..symbols.len()).map(|i| format!("shape-{}", i)).collect_vec();
+
+    let mut defs = match gradient {
+        Some(gradient) => gradient_defs(&gradients, |colour| gradient.bottom(colour)),
+        None => vec![],
+    };
+    for (paths, id) in symbols.into_iter().zip(&symbol_ids) {
+        let mut start = BytesStart::new("g");
+        start.push_attribute(("id", id.as_str()));
+        defs.push(Event::Start(start));
+        defs.extend(styler.shape_paths(paths));
+        defs.push(Event::End(BytesEnd::new("g")));
+    }
+    if !defs.is_empty() {
+        defs.insert(0, Event::Start(BytesStart::new("defs")));
+        defs.push(Event::End(BytesEnd::new("defs")));
+    }
+    let taken: HashSet<String> = gradients.into_iter().map(|(id, _)| id).chain(symbol_ids.iter().cloned()).collect();
+
+    // every shadow is one path under everything else, so where they overlap is no darker
+    let shadow = shadow.iter()
+        .filter(|shadow| !shadow.primitives.is_empty())
+        .map(|shadow| {
+            let d: String = shadow.primitives.iter().map(|p| p.generate_d(&path_options)).collect();
+            path_event(&d, &format!("fill:{};fill-opacity:{}", colour::hex(shadow.colour), shadow.opacity), &[])
+        })
+        .collect_vec();
+
+    let ids = object_ids(shapes, taken);
+    // the opening tag of each layer, if there are any, along with the shapes in it
+    let groups: Vec<(Option<BytesStart>, Vec<usize>)> = match options.layers.level_fn() {
+        None => vec![(None, (0..shapes.len()).collect())],
+        Some(level) => {
+            let mut used = HashSet::new();
+            layers(shapes, level).into_iter().map(|(level, members)| {
+                let label = format!("{}={}", options.layers, level);
+                let name = format!("layer-{}-{}", options.layers, level);
+                let mut id = name.clone();
+                let mut suffix = 2;
+                while !used.insert(id.clone()) || ids.contains(&id) {
+                    id = format!("{}-{}", name, suffix);
+                    suffix += 1;
+                }
+                let mut start = BytesStart::new("g");
+                start.push_attribute(("id", id.as_str()));
+                start.push_attribute(("inkscape:groupmode", "layer"));
+                start.push_attribute(("inkscape:label", label.as_str()));
+                (Some(start), members)
+            }).collect()
+        }
+    };
+
+    let mut start_svg = svg_start_event(canvas);
+    if let Event::Start(start) = &mut start_svg {
+        if options.layers != Layers::Off {
+            start.push_attribute(("xmlns:inkscape", "http://www.inkscape.org/namespaces/inkscape"));
+        }
+    }
+
+    let (steps, style) = match options.animation {
+        Some(animation) if path_options.merging != PathMerging::Global => {
+            let (steps, count) = build_steps(shapes, animation.order);
+            (steps, animation_style(animation, count))
+        }
+        _ => (vec![], vec![]),
+    };
+
+    let objects = Rc::new(Objects { shapes, styler, ids, steps, placements, symbol_ids, options });
+    let body = groups.into_iter().flat_map(move |(start, members)| {
+        let end = start.as_ref().map(|_| Event::End(BytesEnd::new("g")));
+        start.map(Event::Start).into_iter()
+            .chain(layer_events(objects.clone(), members))
+            .chain(end)
+    });
+
+    [xml_declaration_event(), start_svg].into_iter()
+        .chain(style)
+        .chain(defs)
+        .chain(shadow)
+        .chain(body)
+        .chain(std::iter::once(Event::End(BytesEnd::new("svg"))))
+}
+
+/// Adds a line `width` wide around the face in the same paint as its fill, which covers the seams between it and its neighbours.
+/// Faces which already have a stroke, or can be seen through so would show the line, are left alone.
+fn seam_stroke(style: String, width: f64) -> String {
+    lazy_static! {
+        static ref FILL_REGEX: Regex = Regex::new(r"(?:^|;)\s*fill\s*:\s*([^;]+)").unwrap();
+    }
+    if style.contains("stroke") || style.contains("opacity") {
+        return style;
+    }
+    match FILL_REGEX.captures(&style).map(|caps| caps[1].trim().to_string()) {
+        Some(fill) if fill != "none" => format!("{};stroke:{};stroke-width:{}", style, fill, width),
+        _ => style,
+    }
+}
+
+/// A vertical `<linearGradient>` for each `(id, colour)` in `gradients`, from `colour` at the top to `bottom(colour)`, to go in `<defs>`.
+/// The gradients are drawn over the bounding box of whatever uses them, so work for any face without knowing where it is.
+fn gradient_defs<'a>(gradients: &[(String, Vec3<f64>)], bottom: impl Fn(Vec3<f64>) -> Vec3<f64>) -> Vec<Event<'a>> {
+    let mut events = vec![];
+    for (id, colour) in gradients {
+        let mut start = BytesStart::new("linearGradient");
+        start.push_attribute(("id", id.as_str()));
+        start.push_attribute(("x1", "0"));
+        start.push_attribute(("y1", "0"));
+        start.push_attribute(("x2", "0"));
+        start.push_attribute(("y2", "1"));
+        events.push(Event::Start(start));
+        for (offset, stop_colour) in [("0", *colour), ("1", bottom(*colour))] {
+            let mut stop = BytesStart::new("stop");
+            stop.push_attribute(("offset", offset));
+            stop.push_attribute(("stop-color", colour::hex(stop_colour).as_str()));
+            events.push(Event::Empty(stop));
+        }
+        events.push(Event::End(BytesEnd::new("linearGradient")));
+    }
+    events
+}
+
+pub struct ToDStringIter<'a, F: Float = f64> {
+    command_iter: Box<dyn Iterator<Item = Command<F>> + 'a>,
     char_queue: VecDeque<char>,
 }
 
-impl<'a> ToDStringIter<'a> {
-    pub fn from_vec(points: &'_ Vec<Vec2<f64>>) -> ToDStringIter {
+impl<'a, F: Float> ToDStringIter<'a, F> {
+    pub fn from_vec(points: &'_ [Vec2<F>]) -> ToDStringIter<'_, F> {
+        ToDStringIter::from_commands(ToSvgCommandIter::from_vec(points))
+    }
+    /// Like `from_vec`, but with every command after the first point relative to the point before it.
+    pub fn relative_from_vec(points: &'_ [Vec2<F>]) -> ToDStringIter<'_, F> {
+        ToDStringIter::from_commands(relative_commands(ToSvgCommandIter::from_vec(points)))
+    }
+    /// Writes out any commands, one after the other.
+    pub fn from_commands(commands: impl Iterator<Item = Command<F>> + 'a) -> ToDStringIter<'a, F> {
         ToDStringIter {
-            command_iter: ToSvgCommandIter::from_vec(points),
+            command_iter: Box::new(commands),
             char_queue: VecDeque::new(),
         }
     }
 }
-impl<'a> Iterator for ToDStringIter<'a> {
+impl<'a, F: Float> Iterator for ToDStringIter<'a, F> {
     type Item = char;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -77,29 +722,43 @@ impl<'a> Iterator for ToDStringIter<'a> {
     }
 }
 
-pub struct ToSvgCommandIter<'a> {
-    points_iter: Box<dyn Iterator<Item = Vec2<f64>> + 'a>,
+pub struct ToSvgCommandIter<'a, F: Float = f64> {
+    points_iter: Box<dyn Iterator<Item = Vec2<F>> + 'a>,
     first: bool,
-    last_point: Vec2<f64>,
-    current_point: Vec2<f64>,
+    last_point: Vec2<F>,
+    current_point: Vec2<F>,
     closed: bool,
     finished: bool,
 }
 
-impl<'a> ToSvgCommandIter<'a> {
-    pub fn from_vec(points: &'_ Vec<Vec2<f64>>) -> ToSvgCommandIter {
+impl<'a, F: Float> ToSvgCommandIter<'a, F> {
+    /// Commands drawing the ring through `points`, left to `z` to close.
+    /// A ring which comes back to where it started would have its last side drawn twice, so any points repeating the first at the end are left out.
+    pub fn from_vec(points: &'_ [Vec2<F>]) -> ToSvgCommandIter<'_, F> {
+        let mut end = points.len();
+        while end > 1 && points[end - 1] == points[0] {
+            end -= 1;
+        }
+        ToSvgCommandIter::from_points(points[..end].iter().cloned())
+    }
+    /// Commands drawing the ring through `points`, with its last side drawn back to the start before the `z`.
+    pub fn with_explicit_close(points: &'_ [Vec2<F>]) -> ToSvgCommandIter<'_, F> {
+        let back = points.first().filter(|first| points.len() > 1 && points.last() != Some(first)).cloned();
+        ToSvgCommandIter::from_points(points.iter().cloned().chain(back))
+    }
+    fn from_points(points: impl Iterator<Item = Vec2<F>> + 'a) -> ToSvgCommandIter<'a, F> {
         ToSvgCommandIter {
-            points_iter: Box::new(points.iter().cloned()),
+            points_iter: Box::new(points),
             first: true,
-            last_point: vect![0.0, 0.0],
-            current_point: vect![0.0, 0.0],
+            last_point: vect![F::zero(), F::zero()],
+            current_point: vect![F::zero(), F::zero()],
             closed: false,
             finished: false,
         }
     }
 }
-impl<'a> Iterator for ToSvgCommandIter<'a> {
-    type Item = Command;
+impl<'a, F: Float> Iterator for ToSvgCommandIter<'a, F> {
+    type Item = Command<F>;
 
     fn next(&mut self) -> Option<Self::Item> {
         // bodged myself into a corner with this one huh
@@ -198,13 +857,67 @@ impl<'a> Iterator for ToSvgCommandIter<'a> {
     }
 }
 
+/// Turns the absolute commands from a `ToSvgCommandIter` into relative ones, keeping only the first point absolute.
+/// Any points after the first in the opening `M` become a relative `l`, as a relative `m` after another subpath would move from where that one ended.
+pub fn relative_commands<F: Float>(commands: impl Iterator<Item = Command<F>>) -> impl Iterator<Item = Command<F>> {
+    let mut current = vect![F::zero(), F::zero()];
+    let mut start = vect![F::zero(), F::zero()];
+    commands.flat_map(move |command| match command.cmd_type {
+        CommandType::MoveToAbs => {
+            let (first, rest) = command.params.split_at(2);
+            start = vect![first[0], first[1]];
+            current = start;
+            let mut commands = vec![Command { cmd_type: CommandType::MoveToAbs, params: first.to_vec() }];
+            if !rest.is_empty() {
+                commands.push(relative_to(&mut current, CommandType::LineToRel, rest));
+            }
+            commands
+        }
+        CommandType::LineToAbs => vec![relative_to(&mut current, CommandType::LineToRel, &command.params)],
+        CommandType::HorizAbs => vec![relative_to(&mut current, CommandType::HorizRel, &command.params)],
+        CommandType::VertAbs => vec![relative_to(&mut current, CommandType::VertRel, &command.params)],
+        CommandType::ClosePath => {
+            current = start;
+            vec![command]
+        }
+        _ => vec![command],
+    })
+}
+
+/// A relative command of `cmd_type` through the absolute `params`, each measured from `current`, which is moved along to the last of them.
+fn relative_to<F: Float>(current: &mut Vec2<F>, cmd_type: CommandType, params: &[F]) -> Command<F> {
+    let mut relative = vec![];
+    match cmd_type {
+        CommandType::HorizRel => for x in params {
+            relative.push(*x - current.x);
+            current.x = *x;
+        },
+        CommandType::VertRel => for y in params {
+            relative.push(*y - current.y);
+            current.y = *y;
+        },
+        _ => for point in params.chunks(2) {
+            let point = vect![point[0], point[1]];
+            let delta = point - *current;
+            relative.extend([delta.x, delta.y]);
+            *current = point;
+        },
+    }
+    Command { cmd_type, params: relative }
+}
+
 pub struct FromSvgCommandIter<'r, 't> {
     capture_matches: CaptureMatches<'r, 't>,
+    skipped: Vec<String>,
 }
 
 impl<'r, 't> FromSvgCommandIter<'r, 't> {
     pub fn from_str(s: &'t str) -> FromSvgCommandIter<'r, 't> {
-        FromSvgCommandIter { capture_matches: PATH_REGEX.captures_iter(s) }
+        FromSvgCommandIter { capture_matches: PATH_REGEX.captures_iter(s), skipped: vec![] }
+    }
+    /// Everything skipped so far for not being a number, in the order it came up.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
     }
 }
 impl<'r, 't> Iterator for FromSvgCommandIter<'r, 't> {
@@ -214,15 +927,10 @@ impl<'r, 't> Iterator for FromSvgCommandIter<'r, 't> {
         let next = self.capture_matches.next();
         if let Some(captures) = next {
             let command = CommandType::from_opcode(&captures["cmd"]);
-            let numbers = captures["nums"].split_terminator(&[',', ' '][..]);
-            let numbers = numbers.map(|num| {
-                if let Ok(gen_num) = num.parse::<f64>() {
-                    gen_num
-                } else {
-                    panic!("'{}' could not be converted to a float", num);
-                }
-            });
-            Some(Command { cmd_type: command, params: numbers.collect() })
+            let arc = matches!(command, CommandType::ArcAbs | CommandType::ArcRel);
+            let (params, skipped) = split_params(&captures["nums"], arc);
+            self.skipped.extend(skipped);
+            Some(Command { params, cmd_type: command })
         }
         else {
             None
@@ -230,6 +938,110 @@ impl<'r, 't> Iterator for FromSvgCommandIter<'r, 't> {
     }
 }
 
+/// How many straight lines each curve is split into when it isn't given explicitly.
+pub const DEFAULT_CURVE_SEGMENTS: usize = 8;
+
+/// The point a distance `t` along the cubic Bézier curve from `p0` to `p3`.
+fn cubic_point(p0: Vec2<f64>, p1: Vec2<f64>, p2: Vec2<f64>, p3: Vec2<f64>, t: f64) -> Vec2<f64> {
+    let u = 1.0 - t;
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+/// The point a distance `t` along the quadratic Bézier curve from `p0` to `p2`.
+fn quadratic_point(p0: Vec2<f64>, p1: Vec2<f64>, p2: Vec2<f64>, t: f64) -> Vec2<f64> {
+    let u = 1.0 - t;
+    p0 * (u * u) + p1 * (2.0 * u * t) + p2 * (t * t)
+}
+
+/// Points along the elliptical arc from `p0` to `p1`, not including `p0`.
+/// This follows the SVG implementation notes, picking whichever of the four possible arcs the flags describe.
+fn arc_points(p0: Vec2<f64>, radii: Vec2<f64>, rotation: f64, large_arc: bool, sweep: bool, p1: Vec2<f64>, segments: usize) -> Vec<Vec2<f64>> {
+    if p0 == p1 {
+        return vec![];
+    }
+    let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+    if rx == 0.0 || ry == 0.0 {
+        return vec![p1];
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+
+    // the start point, relative to the midpoint of the chord and rotated to line up with the ellipse's axes
+    let half = (p0 - p1) / 2.0;
+    let x1 = cos * half.x + sin * half.y;
+    let y1 = -sin * half.x + cos * half.y;
+
+    // radii too small to reach between the points are scaled up until they just do
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc != sweep { 1.0 } else { -1.0 };
+    let coefficient = sign * f64::max(numerator / denominator, 0.0).sqrt();
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = coefficient * -ry * x1 / rx;
+    let mid = (p0 + p1) / 2.0;
+    let centre = vect![cos * cx1 - sin * cy1 + mid.x, sin * cx1 + cos * cy1 + mid.y];
+
+    let start = vect![(x1 - cx1) / rx, (y1 - cy1) / ry];
+    let end = vect![(-x1 - cx1) / rx, (-y1 - cy1) / ry];
+    let theta = vect![1.0, 0.0].angle_between(start);
+    let mut delta = start.angle_between(end);
+    if !sweep && delta > 0.0 {
+        delta -= std::f64::consts::TAU;
+    }
+    else if sweep && delta < 0.0 {
+        delta += std::f64::consts::TAU;
+    }
+
+    let mut points = (1..segments).map(|i| {
+        let (sin_t, cos_t) = (theta + delta * i as f64 / segments as f64).sin_cos();
+        vect![
+            centre.x + rx * cos * cos_t - ry * sin * sin_t,
+            centre.y + rx * sin * cos_t + ry * cos * sin_t
+        ]
+    }).collect_vec();
+    // land exactly on the end point, rather than wherever the maths puts it
+    points.push(p1);
+    points
+}
+
+/// Reads the numbers following a command.
+/// Numbers only need separating where they'd otherwise run together, so `1.5.5-2e-1` is `1.5`, `.5` and `-2e-1`.
+/// The two flags of an arc are a single digit each, and don't need separating at all.
+/// Anything else that isn't a number is skipped, and given back along with the numbers for the caller to report.
+pub(crate) fn split_params(nums: &str, arc: bool) -> (Vec<f64>, Vec<String>) {
+    let mut params = vec![];
+    let mut skipped = vec![];
+    let mut rest = nums;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() {
+            break;
+        }
+        if arc && matches!(params.len() % 7, 3 | 4) && rest.starts_with(['0', '1']) {
+            params.push(if rest.starts_with('1') { 1.0 } else { 0.0 });
+            rest = &rest[1..];
+            continue;
+        }
+        match NUMBER_REGEX.find(rest) {
+            Some(number) => {
+                params.push(number.as_str().parse::<f64>().unwrap());
+                rest = &rest[number.end()..];
+            }
+            None => {
+                let end = rest.find(|c: char| c.is_whitespace() || c == ',').unwrap_or(rest.len());
+                skipped.push(rest[..end].to_string());
+                rest = &rest[end..];
+            }
+        }
+    }
+    (params, skipped)
+}
+
 pub struct SvgPointIter<'r, 't> {
     command_iter: FromSvgCommandIter<'r, 't>,
     current_point: Vec2<f64>,
@@ -238,19 +1050,55 @@ pub struct SvgPointIter<'r, 't> {
     pointer: usize,
     implicit_lineto: bool,
     ret: bool,
+    curve_segments: usize,
+    // points along a curve that haven't been given out yet
+    pending: VecDeque<Vec2<f64>>,
+    // the control point of the previous segment, if it was a quadratic curve, for `T` to reflect
+    quad_control: Option<Vec2<f64>>,
 }
 
 impl<'r, 't> SvgPointIter<'r, 't> {
     pub fn from_str(s: &'t str) -> SvgPointIter<'r, 't> {
+        SvgPointIter::with_curve_segments(s, DEFAULT_CURVE_SEGMENTS)
+    }
+    /// Like `from_str`, but splitting every curve into `curve_segments` straight lines.
+    pub fn with_curve_segments(s: &'t str, curve_segments: usize) -> SvgPointIter<'r, 't> {
         let mut command_iter = FromSvgCommandIter::from_str(s);
         SvgPointIter {
-            current_command: command_iter.next(),
+            current_command: command_iter.next().map(|command| command.to_absolute(vect![0.0, 0.0]).0),
             command_iter,
             current_point: Vec2 { x: 0.0, y: 0.0 },
             start_point: Vec2 { x: 0.0, y: 0.0 },
             pointer: 0,
             implicit_lineto: false,
             ret: false,
+            curve_segments: usize::max(curve_segments, 1),
+            pending: VecDeque::new(),
+            quad_control: None,
+        }
+    }
+    fn flatten_cubic(&mut self, p1: Vec2<f64>, p2: Vec2<f64>, p3: Vec2<f64>) {
+        let p0 = self.current_point;
+        for i in 1..=self.curve_segments {
+            let t = i as f64 / self.curve_segments as f64;
+            self.pending.push_back(cubic_point(p0, p1, p2, p3, t));
+        }
+        self.current_point = p3;
+    }
+    fn flatten_quadratic(&mut self, p1: Vec2<f64>, p2: Vec2<f64>) {
+        let p0 = self.current_point;
+        for i in 1..=self.curve_segments {
+            let t = i as f64 / self.curve_segments as f64;
+            self.pending.push_back(quadratic_point(p0, p1, p2, t));
+        }
+        self.current_point = p2;
+        self.quad_control = Some(p1);
+    }
+    /// The control point of a smooth quadratic curve, mirroring the previous one through the current point.
+    fn reflected_control(&self, previous: Option<Vec2<f64>>) -> Vec2<f64> {
+        match previous {
+            Some(control) => self.current_point * 2.0 - control,
+            None => self.current_point,
         }
     }
 }
@@ -258,8 +1106,14 @@ impl<'r, 't> Iterator for SvgPointIter<'r, 't> {
     type Item = (Vec2<f64>, bool);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(command) = &self.current_command {
+        if let Some(point) = self.pending.pop_front() {
+            return Some((point, false));
+        }
+        if let Some(command) = self.current_command.take() {
             self.ret = false;
+            let quad_control = self.quad_control.take();
+            // set when the command doesn't go anywhere, so has no point to give
+            let mut skip = false;
             match command.cmd_type {
                 CommandType::MoveToAbs => {
                     let x = command.params[self.pointer];
@@ -272,17 +1126,6 @@ impl<'r, 't> Iterator for SvgPointIter<'r, 't> {
                         self.implicit_lineto = true;
                     }
                 }
-                CommandType::MoveToRel => {
-                    let x = command.params[self.pointer];
-                    self.pointer += 1;
-                    let y = command.params[self.pointer];
-                    self.pointer += 1;
-                    self.current_point += (x, y);
-                    if !self.implicit_lineto {
-                        self.start_point = self.current_point;
-                        self.implicit_lineto = true;
-                    }
-                }
                 CommandType::LineToAbs => {
                     let x = command.params[self.pointer];
                     self.pointer += 1;
@@ -290,44 +1133,64 @@ impl<'r, 't> Iterator for SvgPointIter<'r, 't> {
                     self.pointer += 1;
                     self.current_point = vect![x, y];
                 }
-                CommandType::LineToRel => {
-                    let x = command.params[self.pointer];
-                    self.pointer += 1;
-                    let y = command.params[self.pointer];
-                    self.pointer += 1;
-                    self.current_point += (x, y);
-                }
                 CommandType::VertAbs => {
                     let y = command.params[self.pointer];
                     self.pointer += 1;
                     self.current_point.y = y;
                 }
-                CommandType::VertRel => {
-                    let y = command.params[self.pointer];
-                    self.pointer += 1;
-                    self.current_point.y += y;
-                }
                 CommandType::HorizAbs => {
                     let x = command.params[self.pointer];
                     self.pointer += 1;
                     self.current_point.x = x;
                 }
-                CommandType::HorizRel => {
-                    let x = command.params[self.pointer];
-                    self.pointer += 1;
-                    self.current_point.x += x;
+                CommandType::CurveToAbs => {
+                    let params = &command.params[self.pointer..self.pointer + 6];
+                    self.pointer += 6;
+                    self.flatten_cubic(vect![params[0], params[1]], vect![params[2], params[3]], vect![params[4], params[5]]);
+                }
+                CommandType::QuadToAbs => {
+                    let params = &command.params[self.pointer..self.pointer + 4];
+                    self.pointer += 4;
+                    self.flatten_quadratic(vect![params[0], params[1]], vect![params[2], params[3]]);
+                }
+                CommandType::SmoothQuadToAbs => {
+                    let params = &command.params[self.pointer..self.pointer + 2];
+                    self.pointer += 2;
+                    let control = self.reflected_control(quad_control);
+                    self.flatten_quadratic(control, vect![params[0], params[1]]);
+                }
+                CommandType::ArcAbs => {
+                    let params = &command.params[self.pointer..self.pointer + 7];
+                    self.pointer += 7;
+                    let end = vect![params[5], params[6]];
+                    let points = arc_points(
+                        self.current_point, vect![params[0], params[1]], params[2],
+                        params[3] != 0.0, params[4] != 0.0, end, self.curve_segments,
+                    );
+                    skip = points.is_empty();
+                    self.pending.extend(points);
+                    self.current_point = end;
                 }
                 CommandType::ClosePath => {
                     self.current_point = self.start_point;
                     self.ret = true;
                 }
+                _ => unreachable!("commands are made absolute as they're read"),
             };
             if self.pointer == command.params.len() {
-                self.current_command = self.command_iter.next();
+                // the pen is wherever this command left it, so the next one can be made absolute from there
+                self.current_command = self.command_iter.next().map(|next| next.to_absolute(self.current_point).0);
                 self.pointer = 0;
                 self.implicit_lineto = false;
             }
-            Some((self.current_point, self.ret))
+            else {
+                self.current_command = Some(command);
+            }
+            if skip {
+                return self.next();
+            }
+            let point = self.pending.pop_front().unwrap_or(self.current_point);
+            Some((point, self.ret))
         } else {
             None
         }
@@ -343,6 +1206,11 @@ impl<'r, 't> PrimitiveIter<'r, 't> {
         let point_iter = SvgPointIter::from_str(s);
         PrimitiveIter { point_iter }
     }
+    /// Like `from_str`, but splitting every curve into `curve_segments` straight lines.
+    pub fn with_curve_segments(s: &'t str, curve_segments: usize) -> PrimitiveIter<'r, 't> {
+        let point_iter = SvgPointIter::with_curve_segments(s, curve_segments);
+        PrimitiveIter { point_iter }
+    }
 }
 impl<'r, 't> Iterator for PrimitiveIter<'r, 't> {
     type Item = ShapePrimitive;
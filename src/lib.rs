@@ -1,72 +1,1033 @@
 use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::io::{BufRead, Write};
-use std::ops::Deref;
-use std::rc::Rc;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
 
-use config::Config;
-use itertools::Itertools;
+use config::{Config, ValueKind};
+use serde::de::DeserializeOwned;
+use itertools::{iproduct, Itertools};
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::reader::Reader;
 use quick_xml::writer::Writer;
 
-use crate::iter::object_svg_iter;
-use crate::shapes::{Shape, Polygonal, OptObscurable, ShapePrimitive, ShapeComponent};
+use crate::iter::{svg_start_event, xml_declaration_event, Animation, BuildOrder, Canvas, DocumentOptions, Layers, Length};
+use crate::path::{AntiSeam, PathMerging, PathOptions};
+use crate::scene::{Frame, FrameSequence, Materials, Occlusion, RenderOptions};
+use crate::settings::{CheckerSettings, LightSettings, Settings};
+use crate::shadow::Shadow;
+use crate::colour::{Checker, ColourVariation, Effects, Fog, Gradient, Light, Lighting, RenderMode, Shader, Stroke, StrokeMode};
+use crate::dump::Instance;
+use crate::error::RunError;
+use crate::generators::Generator;
+use crate::grid::{neighbours, Grid};
+use crate::shapes::{Bounds, Shape, Polygonal, OptReducible, ShapeId, ShapePrimitive, ShapeComponent, ComponentStroke, ShapeRegistry, ShapeInstance};
+use crate::stats::{Phase, Progress, RenderStats};
+use crate::validation::Connection;
 use crate::vector::{Vec2, Vec3};
+use crate::view::View;
 
 #[cfg(test)]
 #[macro_use]
 extern crate assert_matches;
 
+mod tests;
+
+pub use error::IsometricError;
+
+pub mod colour;
+pub mod dump;
+pub mod error;
+pub mod generators;
+pub mod grid;
 pub mod iter;
+pub mod matrix;
 pub mod num;
 pub mod parser;
 pub mod path;
+pub mod scene;
+pub mod settings;
+pub mod shadow;
 pub mod shapes;
+pub mod stats;
+pub mod transform;
+pub mod validation;
 pub mod vector;
+pub mod view;
 
-pub fn run<I: BufRead, O: Write>(mut reader: Reader<I>, mut writer: Writer<O>, settings: Config) {
-    
-    let shapes = parser::parse_shapes(&mut reader);
-    let cube = shapes[255].clone().unwrap();
-    let (x_vec, y_vec, z_vec) = dimensions_from_cube(cube.borrow_mut().deref());
+const LIGHT_VECTOR: Vec3<f64> = vect![0.3, 0.7, 0.5];
+const SCENE_COLOUR: Vec3<f64> = vect![0.6, 0.2, 0.9];
+
+pub fn run<I: BufRead, O: Write>(reader: Reader<I>, writer: Writer<O>, settings: Config) -> Result<RenderStats, IsometricError> {
+    Renderer::new().run(reader, writer, settings)
+}
+
+/// Like `run`, but with shapes which have already been read, such as from several files combined with `parser::merge_shapes`.
+pub fn run_shapes<O: Write>(shapes: ShapeRegistry, writer: Writer<O>, settings: Config) -> Result<RenderStats, IsometricError> {
+    Renderer::new().run_shapes(shapes, writer, settings)
+}
+
+/// Reads the `components` setting, which is either a single path or a list of them.
+/// Returns an empty list if it isn't given.
+pub fn component_files(settings: &Config) -> Result<Vec<String>, RunError> {
+    Ok(Settings::read(settings)?.components)
+}
+
+/// How many frames the config describes: one for the scene itself, and another for each entry of `frames`.
+pub fn frame_count(settings: &Config) -> Result<usize, RunError> {
+    Ok(Settings::read(settings)?.frames.len() + 1)
+}
+
+/// Renders scenes described by a components file and config, with hooks for customising the output.
+pub struct Renderer {
+    shader: Box<Shader>,
+    progress: RefCell<Box<Progress>>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer { shader: Box::new(colour::default_shader), progress: RefCell::new(Box::new(|_, _, _| ())) }
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Renderer {
+        Renderer::default()
+    }
+    /// Replaces how faces are coloured. The returned string is used as each path's `style` attribute.
+    pub fn set_shader(&mut self, shader: Box<Shader>) {
+        self.shader = shader;
+    }
+    /// Tells `progress` how far through each phase of every render it is, such as for showing a progress bar.
+    pub fn set_progress(&mut self, progress: Box<Progress>) {
+        self.progress = RefCell::new(progress);
+    }
+    pub fn run<I: BufRead, O: Write>(&self, mut reader: Reader<I>, writer: Writer<O>, settings: Config) -> Result<RenderStats, IsometricError> {
+        let shapes = parser::parse_shapes(&mut reader)?;
+        self.run_shapes(shapes, writer, settings)
+    }
+    /// Renders `shapes` into `writer`, as `settings` describes.
+    /// With `pretty_print` set, each element is put on its own line, indented by how deeply it's nested.
+    /// Gives back how much work the render took.
+    pub fn run_shapes<O: Write>(&self, mut shapes: ShapeRegistry, writer: Writer<O>, settings: Config) -> Result<RenderStats, IsometricError> {
+
+        let settings = Settings::read(&settings)?;
+        let writer = pretty_printed(writer, &settings);
+
+        let (grid, options) = read_scene(&mut shapes, &settings)?;
+        Ok(scene::render_grid_reporting(&grid, &shapes, &options, writer, &self.shader, &mut **self.progress.borrow_mut())?)
+    }
+    /// Renders each object the scene `settings` describes is made of into its own image, as `scene::split_objects` finds them.
+    /// `writer_for` is given each object's name, and gives back where to write it.
+    /// Gives back the name of each object rendered, in order, with how much work it took.
+    pub fn run_objects<O: Write>(&self, mut shapes: ShapeRegistry, settings: Config, loose_tiles: bool, mut writer_for: impl FnMut(&str) -> Result<Writer<O>, RunError>) -> Result<Vec<(String, RenderStats)>, IsometricError> {
+
+        let settings = Settings::read(&settings)?;
+        let (grid, options) = read_scene(&mut shapes, &settings)?;
+        let mut rendered = vec![];
+        for object in scene::split_objects(&grid, &options, loose_tiles)? {
+            let writer = pretty_printed(writer_for(&object.name)?, &settings);
+            let stats = scene::render_grid_reporting(&object.grid, &shapes, &object.options, writer, &self.shader, &mut **self.progress.borrow_mut())?;
+            rendered.push((object.name, stats));
+        }
+        Ok(rendered)
+    }
+    /// Renders the scene `settings` describes once for each frame, starting with the scene before any of its `frames` change it.
+    /// `writer_for` is given the number of each frame, counting from 0, and gives back where to write it.
+    /// Gives back how much work each frame took.
+    pub fn run_frames<O: Write>(&self, mut shapes: ShapeRegistry, settings: Config, mut writer_for: impl FnMut(usize) -> Result<Writer<O>, RunError>) -> Result<Vec<RenderStats>, IsometricError> {
+
+        let settings = Settings::read(&settings)?;
+        let (grid, options) = read_scene(&mut shapes, &settings)?;
+        // every frame is checked before any are written, so a mistake in the last doesn't leave the first few behind
+        let frames = read_frames(&settings, &shapes, &grid)?;
+        let mut sequence = FrameSequence::new(&shapes, grid, options)?;
+        let mut rendered = vec![];
+        for (index, frame) in std::iter::once(None).chain(frames.iter().map(Some)).enumerate() {
+            if let Some(frame) = frame {
+                sequence.advance(frame)?;
+            }
+            let writer = pretty_printed(writer_for(index)?, &settings);
+            rendered.push(sequence.render_reporting(writer, &self.shader, &mut **self.progress.borrow_mut())?);
+        }
+        Ok(rendered)
+    }
+}
+
+/// `writer`, putting each element on its own line indented by how deeply it's nested if `pretty_print` is set.
+fn pretty_printed<O: Write>(writer: Writer<O>, settings: &Settings) -> Writer<O> {
+    if settings.pretty_print {
+        Writer::new_with_indent(writer.into_inner(), b' ', 2)
+    }
+    else {
+        writer
+    }
+}
+
+/// Reads the grid from `settings`, along with everything about how to draw it with `shapes`.
+/// Every entry of the `palette` is added to `shapes`, so the grid can hold it like any other shape.
+fn read_scene(shapes: &mut ShapeRegistry, settings: &Settings) -> Result<(Grid, RenderOptions), RunError> {
+    let reference_shape = match settings.reference_shape.clone() {
+        Some(value) => shape_id(value).map_err(|reason| RunError::InvalidKey { key: String::from("reference_shape"), reason })?,
+        None => ShapeId::from(255u8),
+    };
+    // nothing can be placed without the axes, so there's no use reporting anything else first
+    reference_cube(shapes, &reference_shape)?;
+
+    let palette = read_palette(settings, shapes)?;
+    let (grid, connections) = read_grid(settings, shapes)?;
+    let mut footprints = read_footprints(settings, shapes)?;
+
+    let mut opaque = settings.opaque_shapes.iter()
+        .map(|value| shape_id(value.clone()).map_err(|reason| RunError::InvalidKey { key: String::from("opaque_shapes"), reason }))
+        .collect::<Result<HashSet<_>, _>>()?;
+    let mut opacity = read_opacity(settings)?;
+    let mut shape_colours = HashMap::new();
+    // a palette entry is drawn as the shape it stands for, other than anything it sets itself,
+    // though `opacity` and `footprints` can still name it directly
+    for entry in palette {
+        if opaque.contains(&entry.shape) {
+            opaque.insert(entry.id.clone());
+        }
+        if let Some(size) = footprints.get(&entry.shape).copied() {
+            footprints.entry(entry.id.clone()).or_insert(size);
+        }
+        if let Some(value) = entry.opacity {
+            opacity.entry(entry.id.clone()).or_insert(value);
+        }
+        if let Some(colour) = entry.colour {
+            shape_colours.insert(entry.id, colour);
+        }
+    }
+    let materials = Materials { opaque, opacity };
+
+    let epsilon = settings.epsilon;
+    if epsilon < 0.0 || epsilon.is_nan() {
+        return Err(RunError::InvalidKey { key: String::from("epsilon"), reason: format!("the tolerance can't be negative, but was {}", epsilon) });
+    }
+    let render_mode = read_render_mode(settings)?;
+    // a solid render paints over whatever's hidden anyway, but a wireframe shows everything it's given
+    let keep = [("keep_hidden_faces", settings.keep_hidden_faces), ("wireframe_hidden_faces", settings.wireframe_hidden_faces)].into_iter()
+        .find(|(_, set)| *set)
+        .map(|(key, _)| key);
+    let occlusion = match (keep, settings.clip_hidden_faces) {
+        (None, false) => Occlusion::Delete { epsilon },
+        (Some(_), false) => Occlusion::Keep,
+        (None, true) => Occlusion::Clip { epsilon },
+        (Some(keep), true) => return Err(RunError::InvalidKey {
+            key: String::from("clip_hidden_faces"),
+            reason: format!("hidden faces can't be clipped when {} is also set", keep),
+        }),
+    };
+
+    let margin = settings.margin;
+    if margin < 0.0 || margin.is_nan() {
+        return Err(RunError::InvalidKey { key: String::from("margin"), reason: format!("the margin can't be negative, but was {}", margin) });
+    }
+
+    let coefficient = |key: &str, value: f64| -> Result<f64, RunError> {
+        if value < 0.0 {
+            return Err(RunError::InvalidKey { key: key.to_string(), reason: format!("light can't be taken away, but was {}", value) });
+        }
+        Ok(value)
+    };
+    let lighting = Lighting {
+        ambient: coefficient("ambient", settings.ambient)?,
+        diffuse: coefficient("diffuse", settings.diffuse)?,
+        ambient_colour: settings.ambient_colour.clone().map(|channels| read_colour("ambient_colour", channels)).transpose()?,
+        gamma_correct: settings.gamma_correct,
+        ..Lighting::new(read_lights("", settings.light_vector, &settings.lights)?)
+    };
+
+    let variation = settings.colour_variation.as_ref().map(|variation| ColourVariation {
+        amplitude: variation.amplitude,
+        seed: variation.seed,
+    });
+
+    let checker = settings.checker.as_ref().map(read_checker).transpose()?;
+
+    let fog = match &settings.fog {
+        Some(fog) => Some(Fog {
+            colour: read_colour("fog.colour", required("fog.colour", fog.colour.clone())?)?,
+            start: required("fog.start", fog.start)?,
+            end: required("fog.end", fog.end)?,
+        }),
+        None => None,
+    };
+
+    let gradient = match &settings.gradient {
+        Some(gradient) => {
+            let darkening = gradient.darkening;
+            if !(0.0..=1.0).contains(&darkening) {
+                return Err(RunError::InvalidKey { key: String::from("gradient.darkening"), reason: format!("must be between 0 and 1, but was {}", darkening) });
+            }
+            Some(Gradient { darkening })
+        }
+        None => None,
+    };
+
+    let stroke = if settings.stroke_colour.is_some() || settings.stroke_width.is_some() || settings.stroke_mode.is_some() {
+        let width = settings.stroke_width.unwrap_or(1.0);
+        if width <= 0.0 {
+            return Err(RunError::InvalidKey { key: String::from("stroke_width"), reason: format!("lines have to have some width, but was {}", width) });
+        }
+        Some(Stroke {
+            colour: colour_or("stroke_colour", settings.stroke_colour.clone(), vect![0.0, 0.0, 0.0])?,
+            width,
+            mode: parse_or("stroke_mode", settings.stroke_mode.as_deref(), StrokeMode::default())?,
+        })
+    }
+    else {
+        None
+    };
+
+    let shadow = match &settings.shadow {
+        Some(shadow) => {
+            let opacity = shadow.opacity;
+            if !(0.0..=1.0).contains(&opacity) {
+                return Err(RunError::InvalidKey { key: String::from("shadow.opacity"), reason: format!("must be between 0 and 1, but was {}", opacity) });
+            }
+            Some(Shadow {
+                primitives: vec![],
+                colour: colour_or("shadow.colour", shadow.colour.clone(), vect![0.0, 0.0, 0.0])?,
+                opacity,
+            })
+        }
+        None => None,
+    };
+
+    let path_options = PathOptions {
+        canonical_start: settings.canonical_paths,
+        merging: parse_or("merge_paths", settings.merge_paths.as_deref(), PathMerging::default())?,
+        anti_seam: read_anti_seam(settings)?,
+        relative: settings.relative_paths,
+        explicit_close: settings.explicit_close,
+    };
+
+    let document = DocumentOptions {
+        cell_attributes: settings.cell_attributes,
+        reuse_shapes: settings.reuse_shapes,
+        layers: parse_or("layers", settings.layers.as_deref(), Layers::default())?,
+        preserve_fill: settings.preserve_fill,
+        shade_strokes: settings.shade_strokes,
+        animation: read_animation(settings)?,
+    };
+    if render_mode != RenderMode::Solid && path_options.anti_seam != AntiSeam::Off {
+        return Err(RunError::InvalidKey {
+            key: String::from("anti_seam"),
+            reason: String::from("a wireframe has no seams between faces to cover"),
+        });
+    }
+    if document.animation.is_some() && path_options.merging == PathMerging::Global {
+        return Err(RunError::InvalidKey {
+            key: String::from("animate"),
+            reason: String::from("there are no objects to fade in when merge_paths is \"global\", as every path is merged together"),
+        });
+    }
+    if document.reuse_shapes && path_options.merging == PathMerging::Global {
+        return Err(RunError::InvalidKey {
+            key: String::from("reuse_shapes"),
+            reason: String::from("shapes can't be reused when merge_paths is \"global\", as every path is merged together"),
+        });
+    }
+
+    let options = RenderOptions {
+        reference_shape,
+        connections,
+        merge_equalities: settings.merge_equalities,
+        auto_merge: settings.auto_merge,
+        view: parse_or("view", settings.view.as_deref(), View::default())?,
+        footprints,
+        materials,
+        occlusion,
+        margin,
+        crop: settings.crop,
+        output_size: (read_length("output_width", settings.output_width.as_deref())?, read_length("output_height", settings.output_height.as_deref())?),
+        merge_faces: settings.merge_faces,
+        lighting,
+        colour: colour_or("scene_colour", settings.scene_colour.clone(), SCENE_COLOUR)?,
+        shape_colours,
+        tile_colours: read_tile_colours(settings)?,
+        variation,
+        checker,
+        integer_coordinates: settings.integer_coordinates,
+        effects: Effects { fog, gradient, stroke, shadow },
+        render_mode,
+        path_options,
+        document,
+        dump_scene: settings.dump_scene.as_ref().map(PathBuf::from),
+    };
+    Ok((grid, options))
+}
+
+/// Writes every shape in `shapes` to its own cell of a sprite sheet, labelled with the indices it can be found at.
+/// Shapes given several labels in the components file are only drawn once.
+pub fn render_catalogue<O: Write>(shapes: &ShapeRegistry, mut writer: Writer<O>) -> Result<(), RunError> {
+
+    const PADDING: f64 = 10.0;
+    const LABEL_HEIGHT: f64 = 12.0;
+
+
+    let mut entries: Vec<(Arc<Shape>, Vec<&ShapeId>)> = vec![];
+    for (id, shape) in shapes {
+        // a group without any paths has nothing to draw, nor a size to lay out
+        if shape.points_iter().next().is_none() { continue; }
+        match entries.iter_mut().find(|(s, _)| Arc::ptr_eq(s, shape)) {
+            Some((_, ids)) => ids.push(id),
+            None => entries.push((shape.clone(), vec![id])),
+        }
+    }
+
+    let columns = usize::max((entries.len() as f64).sqrt().ceil() as usize, 1);
+    let rows = entries.len().div_ceil(columns);
+    let cell_size = entries.iter()
+        .fold(vect![0.0, 0.0], |size: Vec2<f64>, (shape, _)| {
+            vect![f64::max(size.x, shape.width()), f64::max(size.y, shape.height())]
+        }) + (2.0 * PADDING, 2.0 * PADDING + LABEL_HEIGHT);
+
+    let lighting = Lighting::default();
+
+    writer.write_event(xml_declaration_event())?;
+    writer.write_event(svg_start_event(&Canvas::new(cell_size.x * columns as f64, cell_size.y * rows as f64)))?;
+    for (i, (shape, ids)) in entries.into_iter().enumerate() {
+        let cell_origin = vect![(i % columns) as f64, (i / columns) as f64] * cell_size;
+        let mut shape = Shape::clone(&shape);
+        shape.move_to(cell_origin + (cell_size.x / 2.0, (cell_size.y - LABEL_HEIGHT) / 2.0));
+
+        writer.write_event(Event::Start(BytesStart::new("g")))?;
+        for component in shape.component_iter() {
+            let style = component.generate_css(component.shade(&lighting, SCENE_COLOUR));
+            writer.write_event(component.generate_path(&style, &PathOptions::default()))?;
+        }
+        writer.write_event(Event::End(BytesEnd::new("g")))?;
+
+        let label = ids.iter().join(";");
+        let x = (cell_origin.x + cell_size.x / 2.0).to_string();
+        let y = (cell_origin.y + cell_size.y - PADDING).to_string();
+        let mut text = BytesStart::new("text");
+        text.push_attribute(("x", x.as_str()));
+        text.push_attribute(("y", y.as_str()));
+        text.push_attribute(("text-anchor", "middle"));
+        text.push_attribute(("font-size", "10"));
+        writer.write_event(Event::Start(text))?;
+        writer.write_event(Event::Text(BytesText::new(&label)))?;
+        writer.write_event(Event::End(BytesEnd::new("text")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("svg")))?;
+    Ok(())
+}
+
+/// Takes a setting which has to be given, naming `key` if it wasn't.
+fn required<T>(key: &str, value: Option<T>) -> Result<T, RunError> {
+    value.ok_or_else(|| RunError::MissingKey { key: key.to_string() })
+}
+
+/// Reads every entry of the table at `key` as a `T`, like `equalities`.
+/// Entries are read in order, so the same bad entry is reported every time.
+fn table_entries<T: DeserializeOwned>(key: &str, table: &HashMap<String, config::Value>) -> Result<HashMap<String, T>, RunError> {
+    table.iter()
+        .sorted_by(|a, b| a.0.cmp(b.0))
+        .map(|(name, value)| {
+            let entry = value.clone().try_deserialize::<T>()
+                .map_err(|why| RunError::InvalidKey { key: format!("{}.{}", key, name), reason: why.to_string() })?;
+            Ok((name.clone(), entry))
+        })
+        .collect()
+}
+
+/// Reads the colour at `key`, given as three channels.
+/// Whole numbers are taken to run from 0 to 255, but as soon as any channel has a decimal point they run from 0 to 1.
+fn read_colour(key: &str, channels: Vec<config::Value>) -> Result<Vec3<f64>, RunError> {
+    let invalid = |reason: String| RunError::InvalidKey { key: key.to_string(), reason };
+    if channels.len() != 3 {
+        return Err(invalid(format!("a colour needs 3 channels, but {} were given", channels.len())));
+    }
+    let scale = if channels.iter().any(|c| matches!(c.kind, ValueKind::Float(_))) { 1.0 } else { 255.0 };
+    let channels = channels.into_iter()
+        .map(|c| c.into_float().map_err(|why| invalid(why.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let colour = vect![channels[0], channels[1], channels[2]] / scale;
+    if [colour.x, colour.y, colour.z].iter().any(|c| !(0.0..=1.0).contains(c)) {
+        return Err(invalid(format!("channels must be between 0 and {}", scale)));
+    }
+    Ok(colour)
+}
+
+/// Reads an optional colour at `key`, in the same form as `read_colour`.
+fn colour_or(key: &str, channels: Option<Vec<config::Value>>, default: Vec3<f64>) -> Result<Vec3<f64>, RunError> {
+    channels.map_or(Ok(default), |channels| read_colour(key, channels))
+}
+
+/// Reads the `checker` table, where `axes` names each coordinate counted once, in any order.
+fn read_checker(checker: &CheckerSettings) -> Result<Checker, RunError> {
+    let invalid = |key: &str, reason: String| RunError::InvalidKey { key: format!("checker.{}", key), reason };
+    if !(-1.0..=1.0).contains(&checker.strength) {
+        return Err(invalid("strength", format!("must be between -1 and 1, but was {}", checker.strength)));
+    }
+    let mut axes = vect![false, false, false];
+    for axis in checker.axes.chars() {
+        let counted = match axis {
+            'x' => &mut axes.x,
+            'y' => &mut axes.y,
+            'z' => &mut axes.z,
+            _ => return Err(invalid("axes", format!("'{}' is not an axis, expected some of x, y and z", axis))),
+        };
+        if *counted {
+            return Err(invalid("axes", format!("{} is given more than once", axis)));
+        }
+        *counted = true;
+    }
+    if axes == vect![false, false, false] {
+        return Err(invalid("axes", String::from("at least one axis has to be counted")));
+    }
+    Ok(Checker {
+        tint: colour_or("checker.tint", checker.tint.clone(), vect![0.0, 0.0, 0.0])?,
+        strength: checker.strength,
+        axes,
+    })
+}
+
+/// Reads every entry of `frames` into the changes it makes to the frame before, checking each tile fits in `grid` and has a shape.
+/// Cells are emptied before the frame's tiles go down, so a tile can take the place of one that's removed.
+fn read_frames(settings: &Settings, shapes: &ShapeRegistry, grid: &Grid) -> Result<Vec<Frame>, RunError> {
+    settings.frames.iter().enumerate()
+        .map(|(index, frame)| {
+            let invalid = |reason: String| RunError::InvalidFrame { index, reason };
+            let mut tiles = frame.remove.iter().map(|cell| (*cell, ShapeId::EMPTY)).collect_vec();
+            for tile in &frame.tiles {
+                let (cell, id) = read_tile(tile.clone()).map_err(invalid)?;
+                if !shapes.contains_key(&id) {
+                    return Err(invalid(format!("{} uses shape {}, but no shape has that label", cell, id)));
+                }
+                tiles.push((cell, id));
+            }
+            if let Some((cell, _)) = tiles.iter().find(|(cell, _)| !grid.contains(*cell)) {
+                return Err(invalid(format!("{} is outside grid_size {}", cell, grid.size())));
+            }
+            let prefix = format!("frames[{}].", index);
+            let lights = if frame.light_vector.is_some() || !frame.lights.is_empty() {
+                Some(read_lights(&prefix, frame.light_vector, &frame.lights)?)
+            }
+            else {
+                None
+            };
+            let colour = frame.scene_colour.clone().map(|channels| read_colour(&format!("{}scene_colour", prefix), channels)).transpose()?;
+            Ok(Frame { tiles, lights, colour })
+        })
+        .collect()
+}
+
+/// Reads every entry of `lights`, each a `direction` in the world with an optional `intensity` and `colour`.
+/// Without any, there's a single white light from `light_vector`, or the default one without that either.
+/// Every key an error names starts with `prefix`, for lights given somewhere other than the top of the config.
+fn read_lights(prefix: &str, light_vector: Option<Vec3<f64>>, lights: &[LightSettings]) -> Result<Vec<Light>, RunError> {
+    let nonzero = |key: &str, direction: Vec3<f64>| -> Result<Vec3<f64>, RunError> {
+        if direction.square_magnitude() == 0.0 {
+            return Err(RunError::InvalidKey { key: key.to_string(), reason: String::from("the light has to come from some direction, but was zero") });
+        }
+        Ok(direction)
+    };
+
+    if lights.is_empty() {
+        let direction = light_vector.unwrap_or(LIGHT_VECTOR);
+        return Ok(vec![Light::new(nonzero(&format!("{}light_vector", prefix), direction)?)]);
+    }
+    if light_vector.is_some() {
+        return Err(RunError::InvalidKey {
+            key: format!("{}light_vector", prefix),
+            reason: String::from("the light can't be given by light_vector as well as a list of lights"),
+        });
+    }
+    let mut read = vec![];
+    for (i, light) in lights.iter().enumerate() {
+        let key = |field: &str| format!("{}lights[{}].{}", prefix, i, field);
+        if light.intensity < 0.0 {
+            return Err(RunError::InvalidKey { key: key("intensity"), reason: format!("light can't be taken away, but was {}", light.intensity) });
+        }
+        read.push(Light {
+            direction: nonzero(&key("direction"), required(&key("direction"), light.direction)?)?,
+            intensity: light.intensity,
+            colour: colour_or(&key("colour"), light.colour.clone(), vect![1.0, 1.0, 1.0])?,
+        });
+    }
+    Ok(read)
+}
+
+/// Reads a length the image is shown at, such as `output_width`, if it's given.
+fn read_length(key: &str, length: Option<&str>) -> Result<Option<Length>, RunError> {
+    length.map(|length| length.parse::<Length>().map_err(|reason| RunError::InvalidKey { key: key.to_string(), reason })).transpose()
+}
+
+/// Reads how the seams between faces are covered, from `anti_seam` and how wide the cover is from `anti_seam_width`.
+/// Outset faces move out by half the width, so neighbours overlap by the whole of it.
+fn read_anti_seam(settings: &Settings) -> Result<AntiSeam, RunError> {
+    let width = settings.anti_seam_width;
+    if width <= 0.0 {
+        return Err(RunError::InvalidKey { key: String::from("anti_seam_width"), reason: format!("the cover has to have some width, but was {}", width) });
+    }
+    match settings.anti_seam.as_str() {
+        "off" => Ok(AntiSeam::Off),
+        "stroke" => Ok(AntiSeam::Stroke { width }),
+        "outset" => Ok(AntiSeam::Outset { distance: width / 2.0 }),
+        mode => Err(RunError::InvalidKey {
+            key: String::from("anti_seam"),
+            reason: format!("'{}' is not a way of covering seams, expected one of \"off\", \"stroke\" or \"outset\"", mode),
+        }),
+    }
+}
+
+/// Reads how the scene builds itself up from `step_duration` and `order`, if `animate` is set.
+fn read_animation(settings: &Settings) -> Result<Option<Animation>, RunError> {
+    if !settings.animate {
+        return Ok(None);
+    }
+    let step_duration = settings.step_duration;
+    if step_duration <= 0.0 || step_duration.is_nan() {
+        return Err(RunError::InvalidKey { key: String::from("step_duration"), reason: format!("each step has to take some time, but was {}", step_duration) });
+    }
+    let order = parse_or("order", settings.order.as_deref(), BuildOrder::default())?;
+    Ok(Some(Animation { step_duration, order }))
+}
+
+/// Reads whether faces are filled in or only outlined, along with the lines a wireframe is drawn with.
+fn read_render_mode(settings: &Settings) -> Result<RenderMode, RunError> {
+    match settings.render_mode.as_deref() {
+        None | Some("solid") => {
+            // the wireframe options do nothing for a solid render, so are more likely a mistake than not
+            let unused = [
+                ("wireframe_colour", settings.wireframe_colour.is_some()),
+                ("wireframe_width", settings.wireframe_width.is_some()),
+                ("wireframe_hidden_faces", settings.wireframe_hidden_faces),
+            ];
+            match unused.into_iter().find(|(_, set)| *set) {
+                Some((key, _)) => Err(RunError::InvalidKey { key: key.to_string(), reason: String::from("only used when render_mode is \"wireframe\"") }),
+                None => Ok(RenderMode::Solid),
+            }
+        }
+        Some("wireframe") => {
+            let width = settings.wireframe_width.unwrap_or(1.0);
+            if width <= 0.0 {
+                return Err(RunError::InvalidKey { key: String::from("wireframe_width"), reason: format!("lines have to have some width, but was {}", width) });
+            }
+            let colour = colour_or("wireframe_colour", settings.wireframe_colour.clone(), vect![0.0, 0.0, 0.0])?;
+            Ok(RenderMode::Wireframe { colour, width })
+        }
+        Some(mode) => Err(RunError::InvalidKey {
+            key: String::from("render_mode"),
+            reason: format!("'{}' is not a render mode, expected one of \"solid\" or \"wireframe\"", mode),
+        }),
+    }
+}
+
+/// Reads the `opacity` of any shapes which can be seen through, keyed by their shape id.
+/// Keys are read in order, so the same bad key is reported every time.
+fn read_opacity(settings: &Settings) -> Result<HashMap<ShapeId, f64>, RunError> {
+    let mut opacity = HashMap::new();
+    for (key, value) in settings.opacity.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+        let invalid = |reason: String| RunError::InvalidKey { key: format!("opacity.{}", key), reason };
+        let id = key.parse::<ShapeId>().map_err(invalid)?;
+        let value = value.clone().into_float().map_err(|why| invalid(why.to_string()))?;
+        if !(0.0..=1.0).contains(&value) {
+            return Err(invalid(format!("must be between 0 and 1, but was {}", value)));
+        }
+        opacity.insert(id, value);
+    }
+    Ok(opacity)
+}
+
+/// A name from the `palette`, standing for a shape drawn in a colour and opacity of its own.
+struct PaletteEntry {
+    /// The id the name is placed in the grid with.
+    id: ShapeId,
+    /// The shape it's drawn as.
+    shape: ShapeId,
+    colour: Option<Vec3<f64>>,
+    opacity: Option<f64>,
+}
+
+/// Reads every entry of the `palette`, adding each name to `shapes` as another label for the shape it's drawn as.
+/// Entries are read in order, so the same bad entry is reported every time.
+fn read_palette(settings: &Settings, shapes: &mut ShapeRegistry) -> Result<Vec<PaletteEntry>, RunError> {
+    let mut palette = vec![];
+    for (name, entry) in settings.palette.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+        let key = |field: &str| format!("palette.{}{}", name, field);
+        let invalid = |field: &str, reason: String| RunError::InvalidKey { key: key(field), reason };
+        let id = name.parse::<ShapeId>().map_err(|reason| invalid("", reason))?;
+        if shapes.contains_key(&id) {
+            return Err(invalid("", format!("a shape in the components file is already labelled {}", id)));
+        }
+        let shape = match entry.shape.clone() {
+            Some(value) => shape_id(value).map_err(|reason| invalid(".shape", reason))?,
+            None => ShapeId::from(255u8),
+        };
+        let Some(drawn) = shapes.get(&shape).cloned() else {
+            return Err(RunError::MissingShape { key: key(".shape"), id: shape });
+        };
+        if let Some(opacity) = entry.opacity {
+            if !(0.0..=1.0).contains(&opacity) {
+                return Err(invalid(".opacity", format!("must be between 0 and 1, but was {}", opacity)));
+            }
+        }
+        let colour = entry.colour.clone().map(|channels| read_colour(&key(".colour"), channels)).transpose()?;
+        shapes.insert(id.clone(), drawn);
+        palette.push(PaletteEntry { id, shape, colour, opacity: entry.opacity });
+    }
+    Ok(palette)
+}
+
+/// Reads the `tile_colours` overrides, each a `tile` coordinate with the `colour` it should be drawn in.
+fn read_tile_colours(settings: &Settings) -> Result<HashMap<Vec3<usize>, Vec3<f64>>, RunError> {
+    let mut tile_colours = HashMap::new();
+    for (i, entry) in settings.tile_colours.iter().enumerate() {
+        let key = |field: &str| format!("tile_colours[{}].{}", i, field);
+        let tile = required(&key("tile"), entry.tile)?;
+        let colour = read_colour(&key("colour"), required(&key("colour"), entry.colour.clone())?)?;
+        tile_colours.insert(tile, colour);
+    }
+    Ok(tile_colours)
+}
+
+/// Gives every member of an equality group the colour given to any of them in `tile_colours`.
+fn spread_tile_colours(mut tile_colours: HashMap<Vec3<usize>, Vec3<f64>>, connections: &[Connection]) -> Result<HashMap<Vec3<usize>, Vec3<f64>>, RunError> {
+    for (name, members) in connections {
+        let defined = members.iter().filter_map(|m| Some((*m, *tile_colours.get(m)?))).collect_vec();
+        let Some(&(first, colour)) = defined.first() else { continue; };
+        if let Some(&(second, _)) = defined.iter().find(|(_, c)| *c != colour) {
+            return Err(RunError::ConflictingColours { group: name.clone(), first, second });
+        }
+        for member in members {
+            tile_colours.insert(*member, colour);
+        }
+    }
+    Ok(tile_colours)
+}
+
+/// Parses the optional setting at `key` into a `T`, using `default` when it isn't given.
+fn parse_or<T: FromStr<Err = String>>(key: &str, value: Option<&str>, default: T) -> Result<T, RunError> {
+    match value {
+        Some(value) => value.parse().map_err(|reason| RunError::InvalidKey { key: key.to_string(), reason }),
+        None => Ok(default),
+    }
+}
 
-    let grid_size: Vec3<_> = settings.get::<(_, _, _)>("grid_size").unwrap().into();
-    let mut grid = vec![vec![vec![0u8; grid_size.z]; grid_size.y]; grid_size.x];
+/// The members of each `equalities` group, by the group's name.
+type Connections = HashMap<String, Vec<Vec3<usize>>>;
 
-    let tiles = settings.get::<Vec<(usize, usize, usize)>>("tiles").unwrap();
+/// Places every fill and tile into a grid, and reads the `equalities` groups to go with it.
+/// Rather than stopping at the first bad entry, every problem with the entries is reported together.
+fn read_grid(settings: &Settings, shapes: &ShapeRegistry) -> Result<(Grid, Connections), RunError> {
+
+    let mut problems = vec![];
+
+    let fills = settings.fills.iter()
+        .cloned()
+        .enumerate()
+        .filter_map(|(index, fill)| match read_fill(fill) {
+            Ok(fill) => Some((index, fill)),
+            Err(reason) => {
+                problems.push(RunError::InvalidFill { index, reason });
+                None
+            }
+        })
+        .collect_vec();
+
+    // a scene can be made entirely of fills, but otherwise needs its tiles listing
+    let tiles = match &settings.tiles {
+        Some(tiles) => tiles.clone(),
+        None if !settings.fills.is_empty() || settings.generate.is_some() => vec![],
+        None => return Err(RunError::MissingKey { key: String::from("tiles") }),
+    };
+    let tiles = tiles.into_iter()
+        .enumerate()
+        .filter_map(|(index, tile)| match read_tile(tile) {
+            Ok(tile) => Some((index, tile)),
+            Err(reason) => {
+                problems.push(RunError::InvalidTile { index, reason });
+                None
+            }
+        })
+        .collect_vec();
+
+    let grid_size = if settings.auto_size {
+        let minimum = settings.grid_size.unwrap_or(vect![0, 0, 0]);
+        let corners = fills.iter().map(|(_, (_, max, _))| *max).chain(tiles.iter().map(|(_, (tile, _))| *tile)).collect_vec();
+        fit_grid_size(&corners, minimum)
+    }
+    else {
+        required("grid_size", settings.grid_size)?
+    };
+    let mut grid = Grid::new(grid_size);
 
-    for tile in tiles {
-        grid[tile.0][tile.1][tile.2] = 255;
+    // anything generated goes down before the fills and tiles, which can then be put anywhere in it
+    if let Some(generator) = read_generator(settings, shapes, grid_size)? {
+        generator.generate(&mut grid);
     }
 
-    let connections = settings
-        .get::<HashMap<String, Vec<(usize, usize, usize)>>>("equalities")
-        .unwrap();
-    let connections: HashMap<String, Vec<Vec3<usize>>> = connections.iter()
-        .map(|pair| {
-            let (key, arr) = pair;
-            let arr = arr.iter().map(|e| Vec3::from(*e)).collect_vec();
-            (key.clone(), arr)
+    // fills go down first, so individual tiles can be picked out of them
+    for (index, (min, max, id)) in fills {
+        if !grid.contains(max) {
+            problems.push(RunError::InvalidFill { index, reason: format!("{} is outside grid_size {}", max, grid_size) });
+        }
+        else if !shapes.contains_key(&id) {
+            problems.push(RunError::InvalidFill { index, reason: format!("{} to {} uses shape {}, but no shape has that label", min, max, id) });
+        }
+        else {
+            for (x, y, z) in iproduct!(min.x..=max.x, min.y..=max.y, min.z..=max.z) {
+                grid.set(vect![x, y, z], id.clone());
+            }
+        }
+    }
+
+    for (index, (tile, id)) in tiles {
+        if !grid.contains(tile) {
+            problems.push(RunError::InvalidTile { index, reason: format!("{} is outside grid_size {}", tile, grid_size) });
+        }
+        else if !shapes.contains_key(&id) {
+            problems.push(RunError::InvalidTile { index, reason: format!("{} uses shape {}, but no shape has that label", tile, id) });
+        }
+        else {
+            grid.set(tile, id);
+        }
+    }
+
+    let connections = table_entries::<Vec<Vec3<usize>>>("equalities", &settings.equalities)?.into_iter()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .map(|(group, members)| {
+            let members = members.into_iter()
+                .enumerate()
+                .filter_map(|(index, member)| {
+                    if !grid.contains(member) {
+                        let reason = format!("{} is outside grid_size {}", member, grid_size);
+                        problems.push(RunError::InvalidMember { group: group.clone(), index, reason });
+                        return None;
+                    }
+                    Some(member)
+                })
+                .collect_vec();
+            (group, members)
         })
         .collect();
 
-    let (shapes, image_width, image_height) = get_objects(grid, shapes, x_vec, y_vec, z_vec, &connections.into_values().collect_vec());
+    match problems.len() {
+        0 => Ok((grid, connections)),
+        1 => Err(problems.pop().unwrap()),
+        _ => Err(RunError::Several(problems)),
+    }
+}
 
-    // let shapes = combine_shapes(shapes);
+/// Reads the `generate` table, if it's there, into the generator it describes.
+/// Noise reaches the top of the grid unless it's given a `max_height`.
+fn read_generator(settings: &Settings, shapes: &ShapeRegistry, grid_size: Vec3<usize>) -> Result<Option<Generator>, RunError> {
+    let Some(generate) = &settings.generate else { return Ok(None); };
+    let invalid = |key: &str, reason: String| RunError::InvalidKey { key: format!("generate.{}", key), reason };
+    let shape = |key: &str, value: Option<&config::Value>| -> Result<Option<ShapeId>, RunError> {
+        let Some(value) = value else { return Ok(None); };
+        let id = shape_id(value.clone()).map_err(|reason| invalid(key, reason))?;
+        if !shapes.contains_key(&id) {
+            return Err(RunError::MissingShape { key: format!("generate.{}", key), id });
+        }
+        Ok(Some(id))
+    };
+    let main_shape = shape("shape", generate.shape.as_ref())?.unwrap_or_else(|| ShapeId::from(255u8));
 
-    let light_vector = vect![0.3, 0.7, 0.5].normalise();
-    let scene_colour = vect![0.6, 0.2, 0.9];
+    let generator = match required("generate.kind", generate.kind.as_deref())? {
+        "noise" => {
+            let max_height = generate.max_height.unwrap_or(grid_size.y);
+            if max_height == 0 {
+                return Err(invalid("max_height", String::from("columns have to be at least one cell high, but was 0")));
+            }
+            if generate.scale <= 0.0 || generate.scale.is_nan() {
+                return Err(invalid("scale", format!("the noise has to have some scale, but was {}", generate.scale)));
+            }
+            Generator::Noise { seed: generate.seed, max_height, scale: generate.scale, shape: main_shape }
+        }
+        "checkerboard" => Generator::Checkerboard { shape: main_shape, other: shape("other", generate.other.as_ref())? },
+        "slab" => Generator::Slab { height: generate.height, shape: main_shape },
+        kind => return Err(invalid("kind", format!("'{}' is not a generator, expected one of \"noise\", \"checkerboard\" or \"slab\"", kind))),
+    };
+    Ok(Some(generator))
+}
+
+/// A shape covering a box of cells, rather than just the one it's placed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Footprint {
+    /// The corner of the box nearest the origin in the world, where the shape's tile is.
+    anchor: Vec3<usize>,
+    /// The corner of the box nearest the origin once rotated into the view.
+    view_corner: Vec3<usize>,
+    /// How many cells the box covers along each axis of the view, which are the axes the shape is drawn along.
+    size: Vec3<usize>,
+}
+
+/// Where the grid is drawn in the image.
+struct Layout {
+    /// How far the centre of a cell moves in the image for each step along `x`, `y` and `z`.
+    axes: CubeAxes,
+    /// The width and height of the reference cube, which every shape is drawn within.
+    cube_size: Vec2<f64>,
+    /// The centre of the reference cube where it was drawn in the components file.
+    cube_centre: Vec2<f64>,
+    /// The empty space left around the board on every side.
+    margin: f64,
+}
+
+impl Layout {
+    /// Where the centre of the cell at the origin is drawn, for a grid `size` big.
+    fn origin(&self, size: Vec3<usize>) -> Vec2<f64> {
+        let CubeAxes { y: y_vec, z: z_vec, .. } = self.axes;
+        vect![size.z as f64 * -z_vec.x, size.y as f64 * -y_vec.y] + (self.margin, self.margin)
+    }
+    /// The size of the image needed to fit a grid `size` big, margin included.
+    fn image_size(&self, size: Vec3<usize>) -> Vec2<f64> {
+        let CubeAxes { x: x_vec, y: y_vec, z: z_vec } = self.axes;
+        vect![
+            size.x as f64 * x_vec.x + size.z as f64 * -z_vec.x,
+            size.x as f64 * x_vec.y + size.y as f64 * -y_vec.y + size.z as f64 * z_vec.y
+        ] + (2.0 * self.margin, 2.0 * self.margin)
+    }
+}
+
+/// Cells in the view which are drawn as one shape between them.
+struct Groups<'a> {
+    /// The members of each of the `equalities`.
+    connections: &'a [Vec<Vec3<usize>>],
+    /// Shapes covering more than one cell.
+    footprints: &'a [Footprint],
+}
+
+/// Everything told about the shapes as they're placed, other than the shapes themselves.
+struct Report<'a> {
+    /// Every shape placed, along with whichever cell's shape hid those which were culled, if they're being listed.
+    instances: Option<&'a mut Vec<Instance>>,
+    stats: &'a mut RenderStats,
+    progress: &'a mut dyn FnMut(Phase, usize, usize),
+}
+
+/// Reads the size of the shapes in `footprints`, along the axes they're drawn with.
+fn read_footprints(settings: &Settings, shapes: &ShapeRegistry) -> Result<HashMap<ShapeId, Vec3<usize>>, RunError> {
+    let sizes = table_entries::<Vec3<usize>>("footprints", &settings.footprints)?;
+    let mut by_id = HashMap::new();
+    for (label, size) in sizes.into_iter().sorted_by(|a, b| a.0.cmp(&b.0)) {
+        let id = label.parse::<ShapeId>().map_err(|reason| RunError::InvalidKey { key: String::from("footprints"), reason })?;
+        if !shapes.contains_key(&id) {
+            return Err(RunError::MissingShape { key: String::from("footprints"), id });
+        }
+        if size.x == 0 || size.y == 0 || size.z == 0 {
+            return Err(RunError::InvalidKey { key: String::from("footprints"), reason: format!("{} has to cover at least one cell, but was {}", id, size) });
+        }
+        by_id.insert(id, size);
+    }
+    Ok(by_id)
+}
 
-    for event in object_svg_iter(&shapes, image_width, image_height, light_vector, scene_colour) {
-        writer.write_event(event).expect("TODO: panic message");
+/// Works out which cells each shape placed in `grid` with one of the sizes in `by_id` covers.
+/// The cells a shape covers, other than its own, have to be empty and inside the grid.
+fn place_footprints(by_id: &HashMap<ShapeId, Vec3<usize>>, grid: &Grid, view: View) -> Result<Vec<Footprint>, RunError> {
+    let grid_size = grid.size();
+    let mut problems = vec![];
+    let mut covered: HashMap<Vec3<usize>, Vec3<usize>> = HashMap::new();
+    let mut footprints = vec![];
+    for (anchor, id) in grid.painter_order() {
+        let Some(size) = by_id.get(id).copied() else { continue; };
+        if size == vect![1, 1, 1] {
+            continue;
+        }
+        // the size is along the axes the shape was drawn with, which swap round in some views
+        let world_size = view.view_size(size);
+        let far = anchor + world_size - (1, 1, 1);
+        if !grid.contains(far) {
+            problems.push(RunError::InvalidFootprint { cell: anchor, reason: format!("{} reaches {}, outside grid_size {}", id, far, grid_size) });
+            continue;
+        }
+        let overlap = iproduct!(anchor.x..=far.x, anchor.y..=far.y, anchor.z..=far.z)
+            .map(Vec3::from)
+            .filter(|cell| *cell != anchor)
+            .find_map(|cell| match covered.get(&cell) {
+                Some(other) => Some(format!("{} overlaps the shape at {}, which also covers {}", id, other, cell)),
+                None if *grid.get(cell) != ShapeId::EMPTY => Some(format!("{} covers {}, which already has shape {} in it", id, cell, grid.get(cell))),
+                None => None,
+            });
+        if let Some(reason) = overlap {
+            problems.push(RunError::InvalidFootprint { cell: anchor, reason });
+            continue;
+        }
+        for cell in iproduct!(anchor.x..=far.x, anchor.y..=far.y, anchor.z..=far.z) {
+            covered.insert(cell.into(), anchor);
+        }
+        let (near, far) = (view.to_view(anchor, grid_size), view.to_view(far, grid_size));
+        let view_corner = vect![near.x.min(far.x), near.y.min(far.y), near.z.min(far.z)];
+        footprints.push(Footprint { anchor, view_corner, size });
+    }
+
+    match problems.len() {
+        0 => Ok(footprints),
+        1 => Err(problems.pop().unwrap()),
+        _ => Err(RunError::Several(problems)),
+    }
+}
+
+/// Reads a shape id from the config, either a number or a label written the same way as in the components file.
+fn shape_id(value: config::Value) -> Result<ShapeId, String> {
+    match value.kind {
+        ValueKind::String(label) => label.parse(),
+        _ => value.try_deserialize::<u32>().map(ShapeId::Index).map_err(|why| why.to_string()),
     }
 }
 
-fn combine_shapes(shapes: Vec<Shape>) -> Vec<Shape> {
+/// Reads one entry of `tiles`, which is a coordinate optionally followed by the id of the shape to put there.
+/// Tiles without an id are full cubes.
+fn read_tile(tile: config::Value) -> Result<(Vec3<usize>, ShapeId), String> {
+    // the id sits in the same list as the coordinate, so has to be split off before the rest can be read as one
+    let mut values = tile.into_array().map_err(|why| why.to_string())?;
+    let id = match values.len() {
+        4 => Some(values.pop().unwrap()),
+        count if count > 4 => return Err(format!("a tile is 3 coordinates and an optional shape, but {} values were given", count)),
+        _ => None,
+    };
+    let coordinate = config::Value::new(None, values).try_deserialize::<Vec3<usize>>().map_err(|why| why.to_string())?;
+    match id {
+        Some(id) => {
+            let shown = id.to_string();
+            let id = shape_id(id).map_err(|why| format!("{} is given shape {}, which isn't a shape id: {}", coordinate, shown, why))?;
+            Ok((coordinate, id))
+        }
+        None => Ok((coordinate, ShapeId::from(255u8))),
+    }
+}
 
-    let components_iter = shapes.into_iter().map(|s| s.into_component_iter()).flatten();
+/// Reads one entry of `fills`, which is two opposite corners of a box optionally followed by the id of the shape to fill it with.
+/// The corners are returned as the lowest and highest coordinates of the box.
+fn read_fill(fill: config::Value) -> Result<(Vec3<usize>, Vec3<usize>, ShapeId), String> {
+    let mut values = fill.into_array().map_err(|why| why.to_string())?;
+    let id = match values.len() {
+        3 => shape_id(values.pop().unwrap())?,
+        2 => ShapeId::from(255u8),
+        count => return Err(format!("a fill is 2 corners and an optional shape, but {} values were given", count)),
+    };
+    let mut corners = values.into_iter().map(|corner| corner.try_deserialize::<Vec3<usize>>().map_err(|why| why.to_string()));
+    let (a, b) = (corners.next().unwrap()?, corners.next().unwrap()?);
+    let min = vect![usize::min(a.x, b.x), usize::min(a.y, b.y), usize::min(a.z, b.z)];
+    let max = vect![usize::max(a.x, b.x), usize::max(a.y, b.y), usize::max(a.z, b.z)];
+    Ok((min, max, id))
+}
+
+/// Finds the smallest grid size holding every one of `tiles`, and at least as big as `minimum`.
+fn fit_grid_size(tiles: &[Vec3<usize>], minimum: Vec3<usize>) -> Vec3<usize> {
+    tiles.iter().fold(minimum, |size, tile| vect![
+        usize::max(size.x, tile.x + 1),
+        usize::max(size.y, tile.y + 1),
+        usize::max(size.z, tile.z + 1)
+    ])
+}
+
+/// Fuses faces pointing the same way which share an edge and come from neighbouring cells, for `merge_faces`.
+/// The fused face is drawn where the later of the two was, so faces are only fused when nothing drawn in between overlaps the earlier one,
+/// leaving the image looking exactly the same. Faces of objects at any of the `unfused` cells are left as they are.
+fn combine_shapes(objects: Vec<(Shape, Vec3<usize>)>, unfused: &HashSet<Vec3<usize>>) -> Vec<(Shape, Vec3<usize>)> {
 
     /*
     Primarily taken from https://stackoverflow.com/questions/39638363/how-can-i-use-a-hashmap-with-f64-as-key-in-rust
@@ -119,183 +1080,419 @@ fn combine_shapes(shapes: Vec<Shape>) -> Vec<Shape> {
         }
     }
 
-    let mut primitives_hashmap: HashMap<ScaryVector, VecDeque<ShapePrimitive>> = HashMap::new();
-    for component in components_iter {
-        for primitive in component.primitives {
-            match primitives_hashmap.get_mut(&component.normal.into()) {
-                Some(vector) => {
-                    vector.push_back(primitive);
+    struct Face {
+        primitive: ShapePrimitive,
+        normal: Vec3<f64>,
+        /// every cell the face has been fused from, for finding its neighbours
+        cells: Vec<Vec3<usize>>,
+        object: usize,
+        /// the holes of the component the face came from, which are given to its first face.
+        /// Faces from a component with holes are left as they are, as the holes would have to move with them
+        holes: Option<Vec<ShapePrimitive>>,
+        /// whether the face can be fused with its neighbours at all
+        fusable: bool,
+        /// carried over from its component, so it's only fused with faces carrying the same
+        attributes: Vec<(String, String)>,
+        stroke: Option<ComponentStroke>,
+    }
+
+    // every face in the order it's drawn, with fused faces taken out
+    let mut faces: Vec<Option<Face>> = objects.iter().enumerate()
+        .flat_map(|(object, (shape, cell))| shape.component_iter().flat_map(move |component| {
+            component.primitives().iter().enumerate().map(move |(n, primitive)| Some(Face {
+                primitive: primitive.clone(),
+                normal: component.normal(),
+                cells: vec![*cell],
+                object,
+                holes: (!component.holes().is_empty()).then(|| if n == 0 { component.holes().to_vec() } else { vec![] }),
+                fusable: component.holes().is_empty() && !unfused.contains(cell),
+                attributes: component.attributes().to_vec(),
+                stroke: component.stroke().cloned(),
+            }))
+        }))
+        .collect();
+
+    // only ever looked up, never iterated, so the order faces are fused in doesn't depend on how they're hashed
+    let mut by_cell: HashMap<(Vec3<usize>, ScaryVector), Vec<usize>> = HashMap::new();
+    for (index, face) in faces.iter().enumerate() {
+        let Some(face) = face else { continue; };
+        if face.fusable {
+            by_cell.entry((face.cells[0], face.normal.into())).or_default().push(index);
+        }
+    }
+
+    for index in 0..faces.len() {
+        while let Some(face) = faces[index].as_ref().filter(|face| face.fusable) {
+            let candidates = face.cells.iter()
+                .flat_map(|cell| neighbours(*cell))
+                .filter_map(|n| by_cell.get(&(n, face.normal.into())))
+                .flatten()
+                .cloned()
+                .filter(|earlier| *earlier < index)
+                // the closest faces have the least drawn in between them
+                .sorted_by(|a, b| b.cmp(a))
+                .dedup();
+
+            let mut fused = None;
+            for earlier_index in candidates {
+                let Some(earlier) = faces[earlier_index].as_ref().filter(|earlier| earlier.attributes == face.attributes && earlier.stroke == face.stroke) else { continue; };
+                let Some(combined) = earlier.primitive.combine_common_edges(&face.primitive) else { continue; };
+                // anything else means the faces overlapped, or weren't really joined along the edge
+                let expected_area = earlier.primitive.signed_area().abs() + face.primitive.signed_area().abs();
+                if (combined.signed_area().abs() - expected_area).abs() > shapes::AREA_EPSILON {
+                    continue;
                 }
-                None => {
-                    primitives_hashmap.insert(component.normal.into(), {
-                        let mut a = VecDeque::with_capacity(1);
-                        a.push_back(primitive);
-                        a
-                    });
+                let painted_over = faces[earlier_index + 1..index].iter()
+                    .flatten()
+                    .any(|between| between.primitive.overlaps(&earlier.primitive));
+                if !painted_over {
+                    fused = Some((earlier_index, combined));
+                    break;
                 }
             }
+
+            let Some((earlier_index, combined)) = fused else { break; };
+            let earlier = faces[earlier_index].take().unwrap();
+            for cell in &earlier.cells {
+                by_cell.entry((*cell, earlier.normal.into())).or_default().push(index);
+            }
+            let face = faces[index].as_mut().unwrap();
+            face.primitive = combined;
+            face.primitive.simplify(shapes::TURN_EPSILON);
+            face.cells.extend(earlier.cells);
         }
     }
 
-    for (_, queue) in &mut primitives_hashmap {
-        fuse_faces(queue);
+    let mut components: Vec<Vec<ShapeComponent>> = vec![vec![]; objects.len()];
+    for face in faces.into_iter().flatten() {
+        let object = &mut components[face.object];
+        let holes = face.holes.unwrap_or_default();
+        match object.iter_mut().find(|component| component.normal() == face.normal && component.attributes() == face.attributes && component.stroke() == face.stroke.as_ref()) {
+            Some(component) => {
+                component.primitives_mut().push(face.primitive);
+                component.holes_mut().extend(holes);
+            }
+            // the normal came from a component, so it's never zero
+            None => object.push(ShapeComponent::new(face.normal, vec![face.primitive]).unwrap()
+                .with_holes(holes)
+                .with_attributes(face.attributes)
+                .with_stroke(face.stroke)),
+        }
     }
-
-    primitives_hashmap.into_iter()
-        .map(|(vec, primitives)|
-            Shape::new(vec![ShapeComponent {
-                primitives: primitives.into(),
-                normal: vec.into()
-            }])
-        ).collect()
+    objects.into_iter()
+        .zip(components)
+        .filter(|(_, components)| !components.is_empty())
+        .map(|((_, cell), components)| (Shape::new(components), cell))
+        .collect()
 }
 
+/// Fuses faces in `shapes` wherever they share an edge, until no two of them do.
 fn fuse_faces(shapes: &mut VecDeque<ShapePrimitive>) {
-    loop {
-        let original_len = shapes.len();
-        if original_len <= 1 { return; }
-        let mut was_fused = false;
+    // each face takes a turn at fusing with the rest, until every one of them has failed in a row
+    let mut failures = 0;
+    while shapes.len() > 1 && failures < shapes.len() {
         let Some(current) = shapes.pop_front() else { return; };
-        for shape in shapes.iter_mut() {
-            match current.combine_common_edges(shape) {
-                Some(fused) => {
-                    *shape = fused;
-                    was_fused = true;
-                    break;
-                }
-                None => (),
+        let fused = shapes.iter_mut()
+            .find_map(|shape| current.combine_common_edges(shape).map(|fused| (shape, fused)));
+        match fused {
+            Some((shape, fused)) => {
+                *shape = fused;
+                failures = 0;
+            }
+            None => {
+                shapes.push_back(current);
+                failures += 1;
             }
         }
-        if !was_fused {
-            shapes.push_back(current);
+    }
+    for shape in shapes {
+        shape.simplify(shapes::TURN_EPSILON);
+    }
+}
+
+/// Combines the objects placed in each of `regions` into one, drawn where the last of them would have been.
+/// Faces of the combined object pointing the same way are fused wherever they share an edge.
+fn merge_regions(objects: Vec<(Shape, Vec3<usize>)>, regions: &[Vec<Vec3<usize>>]) -> Vec<(Shape, Vec3<usize>)> {
+
+    let region_of = |cell: &Vec3<usize>| regions.iter().position(|members| members.contains(cell));
+    let mut remaining = vec![0; regions.len()];
+    for (_, cell) in &objects {
+        if let Some(r) = region_of(cell) {
+            remaining[r] += 1;
         }
-        let final_len = shapes.len();
-        if original_len == final_len {
-            return;
+    }
+
+    let mut pending: Vec<Vec<ShapeComponent>> = vec![vec![]; regions.len()];
+    let mut merged = vec![];
+    for (shape, cell) in objects {
+        let Some(r) = region_of(&cell) else {
+            merged.push((shape, cell));
+            continue;
+        };
+        pending[r].extend(shape.into_component_iter());
+        remaining[r] -= 1;
+        if remaining[r] > 0 { continue; }
+
+        // faces only join up with others pointing the same way and carrying the same attributes and stroke
+        let mut by_normal: Vec<ShapeComponent> = vec![];
+        for mut component in std::mem::take(&mut pending[r]) {
+            match by_normal.iter_mut().find(|c| c.normal() == component.normal() && c.styled_like(&component)) {
+                Some(c) => {
+                    c.primitives_mut().append(component.primitives_mut());
+                    c.holes_mut().append(component.holes_mut());
+                }
+                None => by_normal.push(component),
+            }
         }
+        // fusing primitives only ever grows them, so the holes are still inside whatever they were cut out of
+        let components = by_normal.into_iter()
+            .map(|mut component| {
+                let mut primitives = std::mem::take(component.primitives_mut()).into();
+                fuse_faces(&mut primitives);
+                *component.primitives_mut() = primitives.into();
+                component
+            })
+            .collect_vec();
+        merged.push((Shape::new(components), cell));
     }
+    merged
 }
 
-fn get_objects(grid: Vec<Vec<Vec<u8>>>, shapes: [Option<Rc<RefCell<Shape>>>; 256], x_vec: Vec2<f64>, y_vec: Vec2<f64>, z_vec: Vec2<f64>, connections: &[Vec<Vec3<usize>>]) -> (Vec<Shape>, f64, f64) {
-
-    // TODO: should probably put this elsewhere huh
-    let cube = shapes[255].clone().unwrap();
-    let cube = cube.borrow();
-    let shape_size = vect![cube.width(), cube.height()];
-    let centre_reference = cube.centre();
+/// Places a copy of each cell's shape in the scene, back to front, dealing with whatever ends up hidden behind later shapes as `occlusion` says.
+/// Cells surrounded on their visible sides by shapes in `opaque`, which have to fill their whole cell, still hide what's behind them,
+/// but are never drawn or checked against the shapes in front of them, as they'd be hidden anyway.
+/// Everything done is counted in `report`, which also lists every shape placed if it's been given somewhere to.
+///
+/// Moving the shapes into place doesn't depend on anything else in the scene, so it's all done first, across every core with the `parallel` feature.
+/// Only culling has to go cell by cell, as what each shape hides depends on what's already been hidden.
+fn get_objects(grid: Grid, shapes: ShapeRegistry, layout: &Layout, groups: &Groups, materials: &Materials, occlusion: Occlusion, report: Report) -> Vec<(Shape, Vec3<usize>)> {
+    let Report { mut instances, stats, progress } = report;
+    let CubeAxes { x: x_vec, y: y_vec, z: z_vec } = layout.axes;
 
-    let grid_size = vect![grid.len(), grid[0].len(), grid[0][0].len()];
+    let shape_size = layout.cube_size;
+    let centre_reference = layout.cube_centre;
 
-    // the size of our projected board
-    let board_width = grid_size.x as f64 * x_vec.x + grid_size.z as f64 * -z_vec.x;
-    let board_height = grid_size.x as f64 * x_vec.y + grid_size.y as f64 * -y_vec.y + grid_size.z as f64 * z_vec.y;
+    let origin = layout.origin(grid.size());
 
-    let origin = vect![
-        grid_size.z as f64 * -z_vec.x,
-        grid_size.y as f64 * -y_vec.y
-    ];
+    // shapes in a connection can be drawn away from their own cell, so can't be relied on to cover anything
+    let connected: HashSet<Vec3<usize>> = groups.connections.iter().flatten().cloned().collect();
+    let covers = |c: Vec3<usize>| grid.contains(c) && materials.covers(grid.get(c)) && !connected.contains(&c);
 
-    let mut to_draw: Vec<(Option<Rc<RefCell<Shape>>>, Vec3<usize>)> = vec![];
+    // `shape` moved into place at `cell`, along with the box around it
+    let place = |cell: Vec3<usize>, shape: &Arc<Shape>| -> (ShapeInstance, Bounds) {
+        let centre = origin + x_vec * cell.x as f64 + y_vec * cell.y as f64 + z_vec * cell.z as f64;
 
-    for depth in 0..grid_size.x + grid_size.y + grid_size.z {
-        for x in 0..usize::min(grid_size.x, depth + 1) {
-            for y in 0..usize::min(grid_size.y, depth + 1 - x) {
-                let z = depth - x - y;
-                if z >= grid_size.z { continue; } // might do the maths to avoid this at some point
-                let centre = origin + x_vec * x as f64 + y_vec * y as f64 + z_vec * z as f64;
+        // a shape covering several cells is drawn from the cell nearest the origin,
+        // so its centre is as far from that cell's as the middle of the box is
+        let spread = match groups.footprints.iter().find(|f| f.view_corner == cell) {
+            Some(f) => (x_vec * (f.size.x - 1) as f64 + y_vec * (f.size.y - 1) as f64 + z_vec * (f.size.z - 1) as f64) / 2.0,
+            None => vect![0.0, 0.0],
+        };
 
-                if let Some(shape) = &shapes[grid[x][y][z] as usize] {
-                    let mut existing_connection = None;
-                    let mut new_shape = true;
+        // the centre of the shape might not be the same as the centre of the encapsulating cube
+        let offset = (shape.centre() - spread - centre_reference + shape_size / 2.0) % shape_size - shape_size / 2.0;
 
-                    for connection in connections {
-                        if connection.contains(&vect![x, y, z]) {
-                            existing_connection = Some(connection);
-                        }
-                    }
+        let shape = ShapeInstance::moved_to(shape.clone(), centre + spread + offset);
+        let bounds = bounding_box(&shape);
+        (shape, bounds)
+    };
 
-                    let shape_cell = {
-                        if let Some(connection) = existing_connection {
-                            'a: {
-                                for (existing_shape, pos) in &to_draw {
-                                    if connection.contains(&pos) {
-                                        match existing_shape {
-                                            Some(s) => {
-                                                new_shape = false;
-                                                break 'a s.clone();
-                                            },
-                                            None => (),
-                                        }
-                                    }
-                                }
-                                Rc::new((**shape).clone())
-                            }
-                        }
-                        else {
-                            Rc::new((**shape).clone())
-                        }
-                    };
+    let boxes = groups.footprints.iter().map(|f| (f.view_corner, f.size)).collect_vec();
+    let order = grid.painter_order_with(&boxes);
+    let cell_count = order.len();
+    // placing is spread across threads, so is only told about as a whole
+    let start = Instant::now();
+    progress(Phase::Placing, 0, cell_count);
+    // a connection's shape is only placed again once every copy of it has been hidden, which isn't known until culling gets there
+    let placed = map_cells(&order, |(cell, id)| match shapes.get(*id) {
+        Some(shape) if !connected.contains(cell) => Some(place(*cell, shape)),
+        _ => None,
+    });
+    stats.shapes_placed += placed.iter().flatten().count();
+    progress(Phase::Placing, cell_count, cell_count);
+    stats.timings.push((Phase::Placing, start.elapsed()));
+    let start = Instant::now();
+    progress(Phase::Culling, 0, cell_count);
 
-                    // This condition is here for "connected" shapes.
-                    // I would check why this is necessary and fix it proper; but line-by-line debugging shows me
-                    // the original copy of the shape is put in the right place, so this is good enough.
-                    if new_shape {
-                        let mut shape = shape_cell.borrow_mut();
+    // each shape is kept with the box around it once it's been moved into place. Shapes only ever lose parts after that,
+    // so the box can grow stale, but never too small to catch a shape in front of it
+    let mut to_draw: Vec<(Option<ShapeInstance>, Vec3<usize>, Bounds)> = vec![];
 
-                        // the centre of the shape might not be the same as the centre of the encapsulating cube
-                        let offset = (shape.centre() - centre_reference + shape_size / 2.0) % shape_size - shape_size / 2.0;
+    for (done, ((cell, id), placed)) in order.into_iter().zip(placed).enumerate() {
+        stats.cells_visited += 1;
+        progress(Phase::Culling, done, cell_count);
+        let Some(template) = shapes.get(id) else { continue; };
+        // the viewer looks from the far corner of the grid, so any side facing them is toward a higher coordinate
+        let buried = occlusion.epsilon().is_some() && covers(cell + (1, 0, 0)) && covers(cell + (0, 1, 0)) && covers(cell + (0, 0, 1)) && !connected.contains(&cell);
 
-                        shape.move_to(centre + offset);
-                        drop(shape);
+        let mut anchor = cell;
+        let (shape, bounds) = match placed {
+            Some(placed) => placed,
+            None => {
+                // a connected shape is only drawn once, from the last of its cells, so whatever's left of it moves to the back of the list
+                let connection = groups.connections.iter().rev().find(|connection| connection.contains(&cell));
+                let existing = connection.and_then(|connection| {
+                    to_draw.iter_mut().find(|(shape, pos, _)| shape.is_some() && connection.contains(pos))
+                });
+                match existing {
+                    Some((shape, pos, _)) => {
+                        anchor = *pos;
+                        let shape = shape.take().unwrap();
+                        let bounds = bounding_box(&shape);
+                        (shape, bounds)
                     }
+                    None => {
+                        stats.shapes_placed += 1;
+                        place(cell, template)
+                    }
+                }
+            }
+        };
 
-                    for (opt_old_shape_cell, _old_pos) in &mut to_draw {
-                        let mut delete_this = false;
-                        match opt_old_shape_cell {
-                            Some(old_shape_cell) => {
-                                let old_shape = &mut *old_shape_cell.borrow_mut();
-                                let mut opt = Some(old_shape);
-                                if old_shape_cell.as_ptr() == shape_cell.as_ptr() {
-                                    // would be borrowing mutably in two places if this wasn't here!
-                                    delete_this = true;
-                                }
-                                else {
-                                    opt = opt.del_if_obscured_by(&*shape_cell.borrow());
-                                    // opt = delete_the_stragglers(opt, &*shape_cell.borrow());
-                                    delete_this = opt.is_none();
-                                }
-                            }
-                            None => (),
+        for (old_shape, old_pos, old_bounds) in &mut to_draw {
+            // nothing can be hidden by a shape it doesn't even overlap, or one it shows through
+            let Some(epsilon) = occlusion.epsilon() else { continue; };
+            if old_shape.is_none() || !old_bounds.overlaps(&bounds) || materials.translucent(id) {
+                continue;
+            }
+            let Some(old) = old_shape.take() else { continue; };
+            let parts = old.part_count();
+            *old_shape = old.del_if_obscured_by(&shape, epsilon);
+            match old_shape {
+                Some(left) => stats.primitives_clipped += parts - left.part_count(),
+                None => stats.shapes_culled += 1,
+            }
+            if old_shape.is_none() {
+                if let Some(instances) = instances.as_deref_mut() {
+                    instances.push(Instance { cell: *old_pos, shape: grid.get(*old_pos).clone(), position: old_bounds.centre(), culled_by: vec![anchor] });
+                }
+            }
+        }
+
+        if !buried {
+            to_draw.push((Some(shape), anchor, bounds));
+        }
+        else {
+            stats.shapes_culled += 1;
+            if let Some(instances) = instances.as_deref_mut() {
+                instances.push(Instance { cell, shape: id.clone(), position: bounds.centre(), culled_by: vec![cell + (1, 0, 0), cell + (0, 1, 0), cell + (0, 0, 1)] });
+            }
+        }
+    }
+
+    // everything from here on works on shapes of their own
+    let mut drawn = to_draw.into_iter()
+        .filter_map(|(shape, anchor, bounds)| Some((Some(shape?.into_shape()), anchor, bounds)))
+        .collect_vec();
+
+    if let Occlusion::Clip { epsilon } = occlusion {
+        // connected shapes move to the back of the list each time another of their cells comes up,
+        // so what ends up painted over what is only known once everything has been placed
+        for i in 0..drawn.len() {
+            let (earlier, later) = drawn.split_at_mut(i + 1);
+            let (shape, anchor, bounds) = &mut earlier[i];
+            for (later_shape, later_anchor, later_bounds) in later.iter() {
+                if let (Some(later_shape), true) = (later_shape, shape.is_some()) {
+                    if bounds.overlaps(later_bounds) && !materials.translucent(grid.get(*later_anchor)) {
+                        let parts = shape.as_ref().map_or(0, Shape::part_count);
+                        *shape = shape.take().del_whats_obscured_by(later_shape, epsilon);
+                        match shape {
+                            Some(left) => stats.primitives_clipped += parts.saturating_sub(left.part_count()),
+                            None => stats.shapes_culled += 1,
                         }
-                        if delete_this {
-                            *opt_old_shape_cell = None;
+                        if let (None, Some(instances)) = (&shape, instances.as_deref_mut()) {
+                            instances.push(Instance { cell: *anchor, shape: grid.get(*anchor).clone(), position: bounds.centre(), culled_by: vec![*later_anchor] });
                         }
                     }
-
-                    to_draw.push((Some(shape_cell), vect![x, y, z]));
                 }
             }
         }
     }
 
-    (
-        to_draw.into_iter()
-            .map(|e| e.0.clone())
-            .filter(|e| e.is_some())
-            .map(|e| (*e.unwrap().borrow()).clone())
-            .collect(),
-        board_width,
-        board_height,
-    )
+    progress(Phase::Culling, cell_count, cell_count);
+    stats.timings.push((Phase::Culling, start.elapsed()));
+
+    if let Some(instances) = instances {
+        for (_, anchor, bounds) in drawn.iter().filter(|(shape, _, _)| shape.is_some()) {
+            instances.push(Instance { cell: *anchor, shape: grid.get(*anchor).clone(), position: bounds.centre(), culled_by: vec![] });
+        }
+    }
+
+    drawn.into_iter()
+        .filter_map(|(shape, anchor, _)| Some((shape?, anchor)))
+        .collect()
+}
+
+/// `f` applied to every one of `cells`, in order, spread across every core with the `parallel` feature.
+#[cfg(feature = "parallel")]
+fn map_cells<T: Sync, U: Send>(cells: &[T], f: impl Fn(&T) -> U + Sync + Send) -> Vec<U> {
+    use rayon::prelude::*;
+    cells.par_iter().map(f).collect()
+}
+
+/// `f` applied to every one of `cells`, in order.
+#[cfg(not(feature = "parallel"))]
+fn map_cells<T, U>(cells: &[T], f: impl Fn(&T) -> U) -> Vec<U> {
+    cells.iter().map(f).collect()
+}
+
+/// The direction in the image of one step along each axis of the world.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CubeAxes {
+    x: Vec2<f64>,
+    y: Vec2<f64>,
+    z: Vec2<f64>,
+}
+
+/// The reference cube, along with the direction of each axis measured from its faces.
+type ReferenceCube = (Arc<Shape>, CubeAxes);
+
+/// The shape labelled `id`, which has to be a full cube, along with the direction of each axis measured from its faces.
+fn reference_cube(shapes: &ShapeRegistry, id: &ShapeId) -> Result<ReferenceCube, RunError> {
+    let cube = match shapes.get(id) {
+        Some(cube) => cube.clone(),
+        None => {
+            let found = shapes.keys().cloned().collect();
+            return Err(RunError::MissingReferenceShape { id: id.clone(), found });
+        }
+    };
+    let axes = dimensions_from_cube(&cube).map_err(|why| match why {
+        CubeError::Missing(missing) => RunError::IncompleteReferenceShape { id: id.clone(), missing },
+        CubeError::Mismatched(axis) => RunError::MismatchedReferenceShape { id: id.clone(), axis },
+    })?;
+    Ok((cube, axes))
+}
+
+/// The box around `shape`, which for a shape with no points at all is inside out, so it doesn't overlap anything.
+fn bounding_box(shape: &impl Polygonal) -> Bounds {
+    match shape.points_iter().next() {
+        Some(_) => shape.bounds(),
+        None => Bounds { min: vect![f64::INFINITY, f64::INFINITY], max: vect![f64::NEG_INFINITY, f64::NEG_INFINITY] },
+    }
+}
+
+/// Why the axes couldn't be worked out from the reference cube.
+enum CubeError {
+    /// A description of every face the cube is missing.
+    Missing(Vec<&'static str>),
+    /// The axis with several faces which don't match in size.
+    Mismatched(&'static str),
 }
 
-fn dimensions_from_cube(cube: &Shape) -> (Vec2<f64>, Vec2<f64>, Vec2<f64>) {
+/// Faces of the reference cube pointing along the same axis have to be within this fraction of each other's size.
+const FACE_TOLERANCE: f64 = 0.01;
+
+/// Works out the screen direction of each axis from the faces of a cube.
+/// Opposite faces of a cube are the same size, so either face can be used for each axis,
+/// and any more than one for an axis are checked against each other.
+fn dimensions_from_cube(cube: &Shape) -> Result<CubeAxes, CubeError> {
     
     // this information could be derived in a different way, but I'm not sure how to format supplying it...
     let mut x_vec = vect![0.0, 0.0];
     let mut y_vec = vect![0.0, 0.0];
     let mut z_vec = vect![0.0, 0.0];
-    let (mut h_r, mut h_g, mut h_b) = (0.0, 0.0, 0.0);
+    // the width and height of every face found along each axis
+    let (mut faces_r, mut faces_g, mut faces_b) = (vec![], vec![], vec![]);
 
     for component in cube.component_iter() {
         /*
@@ -326,29 +1523,48 @@ fn dimensions_from_cube(cube: &Shape) -> (Vec2<f64>, Vec2<f64>, Vec2<f64>) {
         Pretty much avoids the problem imo.
         */
         #[allow(illegal_floating_point_literal_pattern)]
-        match component.normal {
-            vectp![-0.001..=0.001, -0.001..=0.001, 0.999..=1.001] => {
-                // blue plane, positive z, left side
-                z_vec.x = -component.width();
-                h_b = -component.height();
+        match component.normal() {
+            vectp![-0.001..=0.001, -0.001..=0.001, 0.999..=1.001 | -1.001..=-0.999] => {
+                // blue plane, z, left side
+                faces_b.push((component.width(), component.height()));
             }
-            vectp![-0.001..=0.001, 0.999..=1.001, -0.001..=0.001] => {
-                // green plane, positive y, top side
-                h_g = -component.height();
+            vectp![-0.001..=0.001, 0.999..=1.001 | -1.001..=-0.999, -0.001..=0.001] => {
+                // green plane, y, top side
+                faces_g.push((component.width(), component.height()));
             }
-            vectp![0.999..=1.001, -0.001..=0.001, -0.001..=0.001] => {
-                // red plane, positive x, right side
-                x_vec.x = component.width();
-                h_r = -component.height();
+            vectp![0.999..=1.001 | -1.001..=-0.999, -0.001..=0.001, -0.001..=0.001] => {
+                // red plane, x, right side
+                faces_r.push((component.width(), component.height()));
             }
             _ => (),
         }
     }
 
+    let axes = [
+        (&faces_r, "+x (the right side, filled #8080ff) or -x (filled #808000)"),
+        (&faces_g, "+y (the top side, filled #80ff80) or -y (filled #800080)"),
+        (&faces_b, "+z (the left side, filled #ff8080) or -z (filled #008080)"),
+    ];
+    let missing = axes.iter().filter(|(faces, _)| faces.is_empty()).map(|(_, face)| *face).collect_vec();
+    if !missing.is_empty() {
+        return Err(CubeError::Missing(missing));
+    }
+    let matches = |a: f64, b: f64| (a - b).abs() <= FACE_TOLERANCE * f64::max(a, b);
+    for (faces, axis) in axes {
+        let (width, height) = faces[0];
+        if faces.iter().any(|(w, h)| !matches(*w, width) || !matches(*h, height)) {
+            return Err(CubeError::Mismatched(axis));
+        }
+    }
+
+    x_vec.x = faces_r[0].0;
+    z_vec.x = -faces_b[0].0;
+    let (h_r, h_g, h_b) = (-faces_r[0].1, -faces_g[0].1, -faces_b[0].1);
+
     // no unary plus :(
     x_vec.y = (-h_r - h_g + h_b) / 2.0;
     y_vec.y = ( h_r - h_g + h_b) / 2.0;
     z_vec.y = ( h_r - h_g - h_b) / 2.0;
 
-    (x_vec, y_vec, z_vec)
+    Ok(CubeAxes { x: x_vec, y: y_vec, z: z_vec })
 }
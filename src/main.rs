@@ -1,33 +1,374 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
 
 use quick_xml::reader::Reader;
 use quick_xml::writer::Writer;
-use config::Config;
+use config::{Config, ConfigError, FileFormat};
+use isometric::shapes::ShapeRegistry;
+use isometric::stats::{Phase, RenderStats};
+use isometric::error::RunError;
+use isometric::Renderer;
+
+/// How often `--watch` checks whether anything's changed.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Every extension the config can be read from, with the format it's read as.
+/// These are also the names `--config-format` takes.
+const CONFIG_FORMATS: [(&str, FileFormat); 7] = [
+    ("toml", FileFormat::Toml),
+    ("json", FileFormat::Json),
+    ("json5", FileFormat::Json5),
+    ("yaml", FileFormat::Yaml),
+    ("yml", FileFormat::Yaml),
+    ("ini", FileFormat::Ini),
+    ("ron", FileFormat::Ron),
+];
+
+const HELP: &str = "\
+Usage: isometric [OPTIONS]
+
+Options:
+    --components <PATH>  an SVG file the shapes are drawn in, which can be given several times
+                         [default: the config's components, or ./components.svg]
+    --config <PATH>      the config file describing the scene, with or without its extension,
+                         or - to read it from stdin as TOML or JSON [default: config]
+    --config-format <FORMAT>
+                         read the config as toml, json, json5, yaml, yml, ini or ron, whatever its extension
+                         [default: going by the config's extension]
+    --output <PATH>      where to write the rendered SVG, or - for stdout [default: ./output.svg]
+                         a config with frames writes each to a numbered file alongside, like ./output-0.svg
+    --output-dir <DIR>   write each equalities group and auto_merge region to its own SVG in DIR instead,
+                         named after the group and cropped down to it
+    --loose-tiles        with --output-dir, also write every tile in neither to its own SVG, named tile-x-y-z
+    --preview-shapes     draw every shape in the components file instead of a scene
+    --verbose            show how far through rendering it is, and how much work it took once it's done
+    --watch              render again whenever the config or components change, until stopped with Ctrl-C
+    -h, --help           print this message
+";
+
+struct Args {
+    components: Vec<String>,
+    config: String,
+    config_format: Option<FileFormat>,
+    output: String,
+    output_dir: Option<String>,
+    loose_tiles: bool,
+    preview_shapes: bool,
+    verbose: bool,
+    watch: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args {
+            components: vec![],
+            config: String::from("config"),
+            config_format: None,
+            output: String::from("./output.svg"),
+            output_dir: None,
+            loose_tiles: false,
+            preview_shapes: false,
+            verbose: false,
+            watch: false,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value after it", arg));
+            match arg.as_str() {
+                "--components" => parsed.components.push(value()?),
+                "--config" => parsed.config = value()?,
+                "--config-format" => {
+                    let name = value()?;
+                    let format = config_format(&name).ok_or_else(|| {
+                        let names = CONFIG_FORMATS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                        format!("unknown config format '{}', expected one of {}", name, names.join(", "))
+                    })?;
+                    parsed.config_format = Some(format);
+                }
+                "--output" => parsed.output = value()?,
+                "--output-dir" => parsed.output_dir = Some(value()?),
+                "--loose-tiles" => parsed.loose_tiles = true,
+                "--preview-shapes" => parsed.preview_shapes = true,
+                "--verbose" => parsed.verbose = true,
+                "--watch" => parsed.watch = true,
+                "-h" | "--help" => {
+                    print!("{}", HELP);
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+        if parsed.loose_tiles && parsed.output_dir.is_none() {
+            return Err(String::from("--loose-tiles only applies with --output-dir"));
+        }
+        Ok(parsed)
+    }
+
+    /// The format the config is read as: whatever `--config-format` says, or else whatever its extension says.
+    /// With neither, it's left to be worked out from whichever file is found.
+    fn format(&self) -> Option<FileFormat> {
+        self.config_format.or_else(|| {
+            Path::new(&self.config).extension()
+                .and_then(|extension| extension.to_str())
+                .and_then(config_format)
+        })
+    }
+}
 
 fn main() {
 
-    let path = Path::new("./components.svg");
-    let path_display = path.display();
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|why| {
+        fail(format!("{}\n\n{}", why, HELP))
+    });
+
+    if args.watch {
+        if args.config == "-" {
+            fail(String::from("--watch can't read the config from stdin, as there's no file to watch"));
+        }
+        watch(&args);
+    }
+    if let Err(why) = render(&args) {
+        fail(why);
+    }
+}
+
+/// Renders whatever `args` asks for, giving the message to show if anything goes wrong.
+fn render(args: &Args) -> Result<(), String> {
+
+    if args.preview_shapes {
+        let shapes = read_components(&components_or_default(args.components.clone()))?;
+        return isometric::render_catalogue(&shapes, create_writer(&args.output)?).map_err(|why| format!("Error: {}", why));
+    }
+
+    let format = args.format();
+    let settings = read_config(&args.config, format).map_err(|why| {
+        let name = if args.config == "-" { "the config from stdin" } else { &args.config };
+        match format {
+            Some(format) => format!("Couldn't read {} as {} for reason {}", name, format_name(format), why),
+            None => format!("Couldn't read {} for reason {}", name, why),
+        }
+    })?;
 
-    let mut components_reader = match Reader::from_file(path) {
-        Ok(v) => v,
-        Err(why) => panic!("Couldn't read {} for reason {}", path_display, why),
+    // files given on the command line take the place of the config's
+    let components = if args.components.is_empty() {
+        isometric::component_files(&settings).map_err(|why| format!("Error: {}", why))?
+    }
+    else {
+        args.components.clone()
     };
-    components_reader.trim_text(true);
+    let shapes = read_components(&components_or_default(components))?;
 
-    let settings = Config::builder()
-        .add_source(config::File::with_name("config"))
-        .build().unwrap();
+    let mut renderer = Renderer::new();
+    if args.verbose {
+        renderer.set_progress(Box::new(progress_line()));
+    }
+    let frames = isometric::frame_count(&settings).map_err(|why| format!("Error: {}", why))?;
+    if let Some(dir) = &args.output_dir {
+        if frames > 1 {
+            return Err(String::from("--output-dir can't split up a config with frames"));
+        }
+        std::fs::create_dir_all(dir).map_err(|why| format!("Couldn't create {} for reason {}", dir, why))?;
+        let rendered = renderer.run_objects(shapes, settings, args.loose_tiles, |name| {
+            create_output(Path::new(dir).join(format!("{}.svg", file_name(name))))
+        }).map_err(|why| format!("Error: {}", why))?;
+        for (name, stats) in rendered {
+            report(&stats, args.verbose, Some(&name));
+        }
+        return Ok(());
+    }
+    if frames > 1 {
+        if args.output == "-" {
+            return Err(String::from("a config with frames writes each to its own file, so can't write them to stdout"));
+        }
+        let rendered = renderer.run_frames(shapes, settings, |index| create_output(frame_path(&args.output, index, frames)))
+            .map_err(|why| format!("Error: {}", why))?;
+        for (index, stats) in rendered.iter().enumerate() {
+            report(stats, args.verbose, Some(&format!("frame {}", index)));
+        }
+        return Ok(());
+    }
+    let stats = renderer.run_shapes(shapes, create_writer(&args.output)?, settings).map_err(|why| format!("Error: {}", why))?;
+    report(&stats, args.verbose, None);
+    Ok(())
+}
 
-    let path = Path::new("./output.svg");
-    let path_display = path.display();
+/// Shows any warnings from a render, followed by its stats when it's verbose, under `heading` if there's more than one render.
+fn report(stats: &RenderStats, verbose: bool, heading: Option<&str>) {
+    for warning in &stats.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    match heading {
+        _ if !verbose => (),
+        Some(heading) => eprintln!("{}:\n{}", heading, stats),
+        None => eprintln!("{}", stats),
+    }
+}
 
-    let out_file = match File::create(path) {
-        Ok(v) => v,
-        Err(why) => panic!("Couldn't write to {} for reason {}", path_display, why),
+/// Where frame `index` of `count` is written, numbered after the file `output` names, like `output-03.svg`.
+/// The numbers are padded to all be as long as each other, so the files sort in order.
+fn frame_path(output: &str, index: usize, count: usize) -> PathBuf {
+    let path = Path::new(output);
+    let width = (count - 1).to_string().len();
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{:0width$}.{}", stem, index, extension.to_string_lossy(), width = width),
+        None => format!("{}-{:0width$}", stem, index, width = width),
     };
-    let writer = Writer::new(out_file);
+    path.with_file_name(name)
+}
+
+/// What an object named `name` is saved as, with anything that isn't safe in a file name replaced by `_`.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Shows how far through each phase of a render it is on a line of stderr, written over as it goes.
+/// The line is only written again when the percentage changes, so big scenes don't spend their time writing it.
+fn progress_line() -> impl FnMut(Phase, usize, usize) {
+    let mut shown = None;
+    move |phase, done, total| {
+        let percent = (done * 100).checked_div(total).unwrap_or(100);
+        if shown == Some((phase, percent)) {
+            return;
+        }
+        shown = Some((phase, percent));
+        eprint!("\r{} {}/{} ({}%)", phase, done, total, percent);
+        if done == total {
+            eprintln!();
+        }
+    }
+}
+
+/// Renders again each time the config or any of the components files change, until stopped with Ctrl-C.
+/// Nothing which goes wrong stops the watching, so a half-finished edit only shows an error until it's fixed.
+fn watch(args: &Args) -> ! {
+    let mut seen = None;
+    loop {
+        // the times are taken before rendering, so any change made while it runs is picked up next time round
+        let modified = watched_files(args).iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect::<Vec<_>>();
+        if seen.as_ref() != Some(&modified) {
+            let start = Instant::now();
+            match render(args) {
+                Ok(()) => eprintln!("Rendered {} in {} ms", args.output_dir.as_ref().unwrap_or(&args.output), start.elapsed().as_millis()),
+                Err(why) => eprintln!("{}", why),
+            }
+            seen = Some(modified);
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// The config file and every components file, as they'd be found if the scene were rendered now.
+fn watched_files(args: &Args) -> Vec<PathBuf> {
+    // the config can be named without its extension, in which case any file it could be read from is watched
+    let mut files = std::iter::once(PathBuf::from(&args.config))
+        .chain(CONFIG_FORMATS.iter().map(|(extension, _)| Path::new(&args.config).with_extension(extension)))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    files.sort();
+    files.dedup();
+    let components = if args.components.is_empty() && !args.preview_shapes {
+        read_config(&args.config, args.format()).ok()
+            .and_then(|settings| isometric::component_files(&settings).ok())
+            .unwrap_or_default()
+    }
+    else {
+        args.components.clone()
+    };
+    files.extend(components_or_default(components).into_iter().map(PathBuf::from));
+    files
+}
+
+/// Reads the config from `path`, or from stdin if it's `-`, as `format` if it's given.
+/// Without a format, a file is read as whatever its extension says,
+/// and config from stdin is taken to be JSON if it starts with an object, and TOML otherwise.
+fn read_config(path: &str, format: Option<FileFormat>) -> Result<Config, ConfigError> {
+    if path != "-" {
+        let source = match format {
+            Some(format) => config::File::new(path, format),
+            None => config::File::with_name(path),
+        };
+        return Config::builder().add_source(source).build();
+    }
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).map_err(|why| ConfigError::Foreign(Box::new(why)))?;
+    let format = format.unwrap_or_else(|| {
+        if text.trim_start().starts_with('{') { FileFormat::Json } else { FileFormat::Toml }
+    });
+    Config::builder().add_source(config::File::from_str(&text, format)).build()
+}
+
+/// The format a config with `extension` is read as, if it's one of `CONFIG_FORMATS`.
+fn config_format(extension: &str) -> Option<FileFormat> {
+    CONFIG_FORMATS.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(extension))
+        .map(|(_, format)| *format)
+}
+
+/// How `format` is named in error messages.
+fn format_name(format: FileFormat) -> &'static str {
+    match format {
+        FileFormat::Toml => "TOML",
+        FileFormat::Json => "JSON",
+        FileFormat::Json5 => "JSON5",
+        FileFormat::Yaml => "YAML",
+        FileFormat::Ini => "INI",
+        FileFormat::Ron => "RON",
+    }
+}
+
+fn components_or_default(components: Vec<String>) -> Vec<String> {
+    if components.is_empty() {
+        vec![String::from("./components.svg")]
+    }
+    else {
+        components
+    }
+}
+
+/// Reads the shapes from every file in `paths`, with later files taking precedence.
+fn read_components(paths: &[String]) -> Result<ShapeRegistry, String> {
+    let mut sets = vec![];
+    for path in paths {
+        let path = Path::new(path);
+        let mut reader = Reader::from_file(path).map_err(|why| format!("Couldn't read {} for reason {}", path.display(), why))?;
+        reader.trim_text(true);
+        match isometric::parser::parse_shapes(&mut reader) {
+            Ok(shapes) => sets.push((path.display(), shapes)),
+            Err(why) => return Err(format!("Error: couldn't read {}: {}", path.display(), why)),
+        }
+    }
+    let (shapes, overrides) = isometric::parser::merge_shapes(sets);
+    for replaced in overrides {
+        eprintln!("Warning: {}", replaced);
+    }
+    Ok(shapes)
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// A writer for a new file at `path`, for renders which write several files at once.
+fn create_output(path: PathBuf) -> Result<Writer<File>, RunError> {
+    let file = File::create(&path).map_err(|why| RunError::Output { path, reason: why.to_string() })?;
+    Ok(Writer::new(file))
+}
+
+fn create_writer(path: &str) -> Result<Writer<Box<dyn Write>>, String> {
+
+    if path == "-" {
+        return Ok(Writer::new(Box::new(std::io::stdout().lock())));
+    }
+
+    let path = Path::new(path);
 
-    isometric::run(components_reader, writer, settings);
+    let out_file = File::create(path).map_err(|why| format!("Couldn't write to {} for reason {}", path.display(), why))?;
+    Ok(Writer::new(Box::new(out_file)))
 }
@@ -0,0 +1,151 @@
+use std::ops;
+
+use crate::num;
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+
+mod tests;
+
+/// A 2×2 matrix, stored a row at a time, which multiplies column vectors on its right.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat2<T: Copy> {
+    pub rows: [[T; 2]; 2],
+}
+impl<T> Mat2<T> where T: Copy + num::Zero + num::One {
+    pub fn identity() -> Self {
+        Mat2::scale(T::one(), T::one())
+    }
+    pub fn scale(x: T, y: T) -> Self {
+        Mat2 { rows: [[x, T::zero()], [T::zero(), y]] }
+    }
+}
+impl<T> Mat2<T> where T: Copy + ops::Neg<Output=T> + num::Sin<Output=T> + num::Cos<Output=T> {
+    /// A rotation by `angle` radians, anticlockwise when y points up.
+    pub fn rotation(angle: T) -> Self {
+        let (sin, cos) = (angle.sin(), angle.cos());
+        Mat2 { rows: [[cos, -sin], [sin, cos]] }
+    }
+}
+impl<T> Mat2<T> where T: Copy + ops::Sub<Output=T> + ops::Mul<Output=T> {
+    pub fn determinant(self) -> T {
+        let [[a, b], [c, d]] = self.rows;
+        a * d - b * c
+    }
+}
+impl<T> Mat2<T> where T: Copy + ops::Sub<Output=T> + ops::Mul<Output=T> + ops::Div<Output=T> + ops::Neg<Output=T> + num::Zero + PartialEq {
+    /// The matrix undoing this one, if there is one, which there isn't when it squashes the plane onto a line or a point.
+    pub fn inverse(self) -> Option<Self> {
+        let det = self.determinant();
+        if det == T::zero() {
+            return None;
+        }
+        let [[a, b], [c, d]] = self.rows;
+        Some(Mat2 { rows: [[d / det, -b / det], [-c / det, a / det]] })
+    }
+}
+impl<T> ops::Mul<Vec2<T>> for Mat2<T> where T: Copy + ops::Add<Output=T> + ops::Mul<Output=T> {
+    type Output = Vec2<T>;
+
+    fn mul(self, rhs: Vec2<T>) -> Self::Output {
+        let [row_x, row_y] = self.rows;
+        vect![row_x[0] * rhs.x + row_x[1] * rhs.y, row_y[0] * rhs.x + row_y[1] * rhs.y]
+    }
+}
+/// `first * second` applies `second`, then `first`.
+impl<T> ops::Mul for Mat2<T> where T: Copy + ops::Add<Output=T> + ops::Mul<Output=T> {
+    type Output = Mat2<T>;
+
+    fn mul(self, rhs: Mat2<T>) -> Self::Output {
+        let column = |j: usize| self * vect![rhs.rows[0][j], rhs.rows[1][j]];
+        let (first, second) = (column(0), column(1));
+        Mat2 { rows: [[first.x, second.x], [first.y, second.y]] }
+    }
+}
+
+/// A 3×3 matrix, stored a row at a time, which multiplies column vectors on its right.
+/// As well as transforms of 3D space, these hold 2D affine transforms, acting on points `(x, y, 1)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat3<T: Copy> {
+    pub rows: [[T; 3]; 3],
+}
+impl<T> Mat3<T> where T: Copy + num::Zero + num::One {
+    pub fn identity() -> Self {
+        Mat3::scale(T::one(), T::one())
+    }
+    /// A 2D scale about the origin.
+    pub fn scale(x: T, y: T) -> Self {
+        let (zero, one) = (T::zero(), T::one());
+        Mat3 { rows: [[x, zero, zero], [zero, y, zero], [zero, zero, one]] }
+    }
+    /// A 2D translation.
+    pub fn translation(x: T, y: T) -> Self {
+        let (zero, one) = (T::zero(), T::one());
+        Mat3 { rows: [[one, zero, x], [zero, one, y], [zero, zero, one]] }
+    }
+}
+impl<T> Mat3<T> where T: Copy + ops::Neg<Output=T> + num::Sin<Output=T> + num::Cos<Output=T> + num::Zero + num::One {
+    /// A 2D rotation by `angle` radians about the origin, the same as `Mat2::rotation`.
+    pub fn rotation(angle: T) -> Self {
+        Mat3::from(Mat2::rotation(angle))
+    }
+}
+impl<T> From<Mat2<T>> for Mat3<T> where T: Copy + num::Zero + num::One {
+    fn from(m: Mat2<T>) -> Self {
+        let [[a, b], [c, d]] = m.rows;
+        let (zero, one) = (T::zero(), T::one());
+        Mat3 { rows: [[a, b, zero], [c, d, zero], [zero, zero, one]] }
+    }
+}
+impl<T> Mat3<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> {
+    pub fn determinant(self) -> T {
+        let [row_0, row_1, row_2] = self.rows.map(|[x, y, z]| vect![x, y, z]);
+        row_0.dot(row_1.cross(row_2))
+    }
+}
+impl<T> Mat3<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> + ops::Div<Output=T> + num::Zero + PartialEq {
+    /// The matrix undoing this one, if there is one, which there isn't when it squashes space flat.
+    pub fn inverse(self) -> Option<Self> {
+        let det = self.determinant();
+        if det == T::zero() {
+            return None;
+        }
+        // the columns of the inverse are the cross products of pairs of rows, scaled down by the determinant
+        let [row_0, row_1, row_2] = self.rows.map(|[x, y, z]| vect![x, y, z]);
+        let columns = [row_1.cross(row_2), row_2.cross(row_0), row_0.cross(row_1)].map(|c| c / det);
+        Some(Mat3 { rows: [
+            [columns[0].x, columns[1].x, columns[2].x],
+            [columns[0].y, columns[1].y, columns[2].y],
+            [columns[0].z, columns[1].z, columns[2].z],
+        ] })
+    }
+}
+impl<T> Mat3<T> where T: Copy + ops::Add<Output=T> + ops::Mul<Output=T> {
+    /// Where the 2D point `p` ends up, treating it as `(x, y, 1)`.
+    /// The bottom row is taken to be `(0, 0, 1)`, as it is for any affine transform.
+    pub fn transform_point(self, p: Vec2<T>) -> Vec2<T> {
+        let [row_x, row_y, _] = self.rows;
+        vect![row_x[0] * p.x + row_x[1] * p.y + row_x[2], row_y[0] * p.x + row_y[1] * p.y + row_y[2]]
+    }
+}
+impl<T> ops::Mul<Vec3<T>> for Mat3<T> where T: Copy + ops::Add<Output=T> + ops::Mul<Output=T> {
+    type Output = Vec3<T>;
+
+    fn mul(self, rhs: Vec3<T>) -> Self::Output {
+        let [row_x, row_y, row_z] = self.rows.map(|[x, y, z]| vect![x, y, z]);
+        vect![row_x.dot(rhs), row_y.dot(rhs), row_z.dot(rhs)]
+    }
+}
+/// `first * second` applies `second`, then `first`.
+impl<T> ops::Mul for Mat3<T> where T: Copy + ops::Add<Output=T> + ops::Mul<Output=T> {
+    type Output = Mat3<T>;
+
+    fn mul(self, rhs: Mat3<T>) -> Self::Output {
+        let column = |j: usize| self * vect![rhs.rows[0][j], rhs.rows[1][j], rhs.rows[2][j]];
+        let [first, second, third] = [column(0), column(1), column(2)];
+        Mat3 { rows: [
+            [first.x, second.x, third.x],
+            [first.y, second.y, third.y],
+            [first.z, second.z, third.z],
+        ] }
+    }
+}
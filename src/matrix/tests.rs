@@ -0,0 +1,88 @@
+#![cfg(test)]
+
+use std::f64::consts::FRAC_PI_2;
+
+use crate::matrix::{Mat2, Mat3};
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+
+fn assert_close_2(a: Mat2<f64>, b: Mat2<f64>) {
+    let close = a.rows.iter().flatten().zip(b.rows.iter().flatten()).all(|(x, y)| (x - y).abs() < 1e-9);
+    assert!(close, "{:?} is not {:?}", a, b);
+}
+
+fn assert_close_3(a: Mat3<f64>, b: Mat3<f64>) {
+    let close = a.rows.iter().flatten().zip(b.rows.iter().flatten()).all(|(x, y)| (x - y).abs() < 1e-9);
+    assert!(close, "{:?} is not {:?}", a, b);
+}
+
+#[test]
+fn test_mat2() {
+    let m = Mat2 { rows: [[1.0, 2.0], [3.0, 4.0]] };
+    assert_eq!(m * vect![1.0, 1.0], vect![3.0, 7.0]);
+    assert_eq!(m * Mat2::identity(), m);
+    assert_eq!(Mat2::scale(2.0, 3.0) * m, Mat2 { rows: [[2.0, 4.0], [9.0, 12.0]] });
+    assert_eq!(m * m, Mat2 { rows: [[7.0, 10.0], [15.0, 22.0]] });
+    assert_eq!(m.determinant(), -2.0);
+
+    // a quarter turn takes x to y
+    let turned = Mat2::rotation(FRAC_PI_2) * vect![1.0, 0.0];
+    assert!((turned - vect![0.0, 1.0]).magnitude() < 1e-12);
+    assert_eq!(vect![3.0, -2.0].rot(0.5), Mat2::rotation(0.5) * vect![3.0, -2.0]);
+}
+
+#[test]
+fn test_mat2_inverse() {
+    let m = Mat2 { rows: [[1.0, 2.0], [3.0, 4.0]] };
+    let inverse = m.inverse().unwrap();
+    assert_eq!(inverse, Mat2 { rows: [[-2.0, 1.0], [1.5, -0.5]] });
+    assert_close_2(m * inverse, Mat2::identity());
+    assert_close_2(inverse * m, Mat2::identity());
+    let rotation = Mat2::rotation(0.3);
+    assert_close_2(rotation.inverse().unwrap(), Mat2::rotation(-0.3));
+
+    // rows in proportion squash everything onto a line
+    assert_eq!(Mat2 { rows: [[1.0, 2.0], [2.0, 4.0]] }.inverse(), None);
+    assert_eq!(Mat2::scale(0.0, 1.0).inverse(), None);
+}
+
+#[test]
+fn test_mat3() {
+    let p: Vec2<f64> = vect![1.0, 2.0];
+    assert_eq!(Mat3::translation(10.0, 20.0).transform_point(p), vect![11.0, 22.0]);
+    assert_eq!(Mat3::scale(2.0, -1.0).transform_point(p), vect![2.0, -2.0]);
+    // the right hand side is applied first
+    let m = Mat3::translation(10.0, 0.0) * Mat3::scale(2.0, 2.0);
+    assert_eq!(m.transform_point(p), vect![12.0, 4.0]);
+    assert_eq!(m * vect![1.0, 2.0, 1.0], vect![12.0, 4.0, 1.0]);
+    // directions, with a 0 at the end, aren't moved
+    assert_eq!(m * vect![1.0, 2.0, 0.0], vect![2.0, 4.0, 0.0]);
+    let turned = Mat3::rotation(FRAC_PI_2).transform_point(p);
+    assert!((turned - vect![-2.0, 1.0]).magnitude() < 1e-12);
+
+    let m = Mat3 { rows: [[2.0, 0.0, 1.0], [1.0, 3.0, 0.0], [0.0, 1.0, 1.0]] };
+    // 2 (3 - 0) - 0 + 1 (1 - 0)
+    assert_eq!(m.determinant(), 7.0);
+    assert_eq!(Mat3::translation(5.0, 5.0).determinant(), 1.0);
+    assert_eq!(Mat3::scale(2.0, 3.0).determinant(), 6.0);
+}
+
+#[test]
+fn test_mat3_inverse() {
+    let m = Mat3 { rows: [[2.0, 0.0, 1.0], [1.0, 3.0, 0.0], [0.0, 1.0, 1.0]] };
+    let inverse = m.inverse().unwrap();
+    assert_close_3(m * inverse, Mat3::identity());
+    assert_close_3(inverse * m, Mat3::identity());
+    let v: Vec3<f64> = vect![1.0, -2.0, 3.0];
+    assert!((inverse * (m * v) - v).magnitude() < 1e-12);
+
+    let affine = Mat3::translation(3.0, -4.0) * Mat3::rotation(0.7) * Mat3::scale(2.0, 0.5);
+    let undone = affine.inverse().unwrap();
+    assert_close_3(undone, Mat3::scale(0.5, 2.0) * Mat3::rotation(-0.7) * Mat3::translation(-3.0, 4.0));
+    let p = vect![1.0, 2.0];
+    assert!((undone.transform_point(affine.transform_point(p)) - p).magnitude() < 1e-12);
+
+    // the third row is the sum of the other two, so everything is squashed onto a plane
+    assert_eq!(Mat3 { rows: [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [5.0, 7.0, 9.0]] }.inverse(), None);
+    assert_eq!(Mat3::scale(1.0, 0.0).inverse(), None);
+}
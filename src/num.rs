@@ -1,3 +1,6 @@
+use std::cmp::Ordering;
+use std::{fmt, iter, ops};
+
 macro_rules! default_trait {
     ($trait:ident, $type:ident, $method:ident) => {
         impl $trait for $type {
@@ -9,6 +12,17 @@ macro_rules! default_trait {
     };
 }
 
+macro_rules! default_binary_trait {
+    ($trait:ident, $type:ident, $method:ident) => {
+        impl $trait for $type {
+            type Output = $type;
+            fn $method(self, other: Self) -> Self::Output {
+                $type::$method(self, other)
+            }
+        }
+    };
+}
+
 pub trait Sqrt {
     type Output;
     fn sqrt(self) -> Self::Output;
@@ -29,3 +43,106 @@ pub trait Cos {
 }
 default_trait!(Cos, f32, cos);
 default_trait!(Cos, f64, cos);
+
+pub trait Atan2 {
+    type Output;
+    fn atan2(self, other: Self) -> Self::Output;
+}
+default_binary_trait!(Atan2, f32, atan2);
+default_binary_trait!(Atan2, f64, atan2);
+
+pub trait Min {
+    type Output;
+    fn min(self, other: Self) -> Self::Output;
+}
+default_binary_trait!(Min, f32, min);
+default_binary_trait!(Min, f64, min);
+
+pub trait Max {
+    type Output;
+    fn max(self, other: Self) -> Self::Output;
+}
+default_binary_trait!(Max, f32, max);
+default_binary_trait!(Max, f64, max);
+
+pub trait Zero {
+    fn zero() -> Self;
+}
+impl Zero for f32 {
+    fn zero() -> Self {
+        0.0
+    }
+}
+impl Zero for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+}
+
+pub trait One {
+    fn one() -> Self;
+}
+impl One for f32 {
+    fn one() -> Self {
+        1.0
+    }
+}
+impl One for f64 {
+    fn one() -> Self {
+        1.0
+    }
+}
+
+/// The floating point types shapes can be built from, which are `f32` and `f64`.
+/// Everything the geometry needs beyond the traits above is gathered here.
+pub trait Float:
+    'static + Copy + PartialOrd + fmt::Debug + fmt::Display + Default + iter::Sum
+    + ops::Add<Output=Self> + ops::Sub<Output=Self> + ops::Mul<Output=Self> + ops::Div<Output=Self> + ops::Neg<Output=Self>
+    + ops::AddAssign + ops::SubAssign
+    + Sqrt<Output=Self> + Sin<Output=Self> + Cos<Output=Self> + Atan2<Output=Self> + Min<Output=Self> + Max<Output=Self> + Zero + One
+{
+    /// The difference between 1 and the next number up.
+    const EPSILON: Self;
+    /// The nearest value to `v`, which is `v` itself for `f64`.
+    fn from_f64(v: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn abs(self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+    fn signum(self) -> Self;
+    fn asin(self) -> Self;
+    fn is_nan(self) -> bool;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+macro_rules! float_impl {
+    ($type:ident) => {
+        impl Float for $type {
+            const EPSILON: Self = $type::EPSILON;
+            fn from_f64(v: f64) -> Self {
+                v as $type
+            }
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            fn abs(self) -> Self {
+                $type::abs(self)
+            }
+            fn clamp(self, min: Self, max: Self) -> Self {
+                $type::clamp(self, min, max)
+            }
+            fn signum(self) -> Self {
+                $type::signum(self)
+            }
+            fn asin(self) -> Self {
+                $type::asin(self)
+            }
+            fn is_nan(self) -> bool {
+                $type::is_nan(self)
+            }
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                $type::total_cmp(self, other)
+            }
+        }
+    };
+}
+float_impl!(f32);
+float_impl!(f64);
@@ -1,123 +1,502 @@
 use std::borrow::Cow;
-use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::io::BufRead;
-use std::rc::Rc;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use quick_xml;
 use quick_xml::events::{BytesStart, Event};
+use quick_xml::events::attributes::AttrError;
 use regex::Regex;
 
-use crate::iter::PrimitiveIter;
-use crate::shapes::{Shape, ShapeComponent};
-use crate::vector::Vec3;
+use crate::colour::parse_css_colour;
+use crate::error::IsometricError;
+use crate::iter::{split_params, FromSvgCommandIter, PrimitiveIter};
+use crate::path::CommandType;
+use crate::shapes::{Polygonal, Shape, ShapeComponent, ComponentStroke, ShapeError, ShapeId, ShapePrimitive, ShapeRegistry};
+use crate::transform::Transform;
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
 
 lazy_static!{
-    static ref COLOUR_REGEX: Regex = Regex::new(r"fill:#(?P<r>[\d|a-f]{2})(?P<g>[\d|a-f]{2})(?P<b>[\d|a-f]{2})").unwrap();
+    static ref FILL_REGEX: Regex = Regex::new(r"(?:^|;)\s*fill\s*:(?P<fill>[^;]*)").unwrap();
+    static ref STROKE_REGEX: Regex = Regex::new(r"(?:^|;)\s*(?P<property>stroke(?:-width|-linejoin)?)\s*:(?P<value>[^;]*)").unwrap();
 }
 
+/// The attributes of a face carried over onto every path drawn for it, along with any `data-` attributes.
+/// Anything to do with the outline is left behind, as are ids, which would be repeated for every copy, and whatever an editor adds for itself.
+const CARRIED_ATTRIBUTES: [&str; 17] = [
+    "class", "fill", "fill-opacity", "opacity", "stroke", "stroke-width", "stroke-dasharray", "stroke-dashoffset",
+    "stroke-linecap", "stroke-linejoin", "stroke-miterlimit", "stroke-opacity", "filter", "mask", "visibility",
+    "vector-effect", "shape-rendering",
+];
+
 mod tests;
 
-pub fn parse_shapes<T: BufRead>(reader: &mut quick_xml::reader::Reader<T>) -> [Option<Rc<RefCell<Shape>>>; 256] {
+/// Reasons the components file couldn't be read.
+/// Each carries the byte offset in the file just after the element at fault.
+#[derive(Debug)]
+pub enum Error {
+    /// A group's label isn't a list of shape ids, separated by `;`.
+    BadLabel { value: String, position: usize },
+    /// A face has neither a `style` nor a `fill` to encode its normal in.
+    /// `path_d` describes the face's outline, to help find it.
+    MissingStyle { path_d: String, position: usize },
+    /// A face has nothing describing its outline, like a path without a `d`.
+    MissingOutline { position: usize },
+    /// An attribute describing a face's outline can't be read.
+    BadAttribute { name: String, value: String, position: usize },
+    /// A command in a path's outline isn't given a whole number of repeats' worth of numbers.
+    BadCommand { command: CommandType, given: usize, path_d: String, position: usize },
+    /// Something in a list of numbers describing a face's outline isn't one.
+    NotANumber { token: String, name: String, position: usize },
+    /// A path's style doesn't contain a fill colour that can be read.
+    BadColour { style: String, position: usize },
+    /// A path's outline or normal can't make a usable shape.
+    BadShape { error: ShapeError, position: usize },
+    /// The file isn't valid XML.
+    XmlError { error: quick_xml::Error, position: usize },
+}
+
+impl Error {
+    /// Where in the file the error was found.
+    pub fn position(&self) -> usize {
+        match self {
+            Error::BadLabel { position, .. } |
+            Error::MissingStyle { position, .. } |
+            Error::MissingOutline { position } |
+            Error::BadAttribute { position, .. } |
+            Error::BadCommand { position, .. } |
+            Error::NotANumber { position, .. } |
+            Error::BadColour { position, .. } |
+            Error::BadShape { position, .. } |
+            Error::XmlError { position, .. } => *position,
+        }
+    }
+    // the element parsers don't know where they are, so this is filled in afterwards
+    fn at(mut self, at: usize) -> Self {
+        match &mut self {
+            Error::BadLabel { position, .. } |
+            Error::MissingStyle { position, .. } |
+            Error::MissingOutline { position } |
+            Error::BadAttribute { position, .. } |
+            Error::BadCommand { position, .. } |
+            Error::NotANumber { position, .. } |
+            Error::BadColour { position, .. } |
+            Error::BadShape { position, .. } |
+            Error::XmlError { position, .. } => *position = at,
+        }
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::BadLabel { value, position } => write!(
+                f, "group before position {} is labelled '{}', which isn't a ;-separated list of binary numbers, decimal numbers or names", position, value
+            ),
+            Error::MissingStyle { path_d, position } => write!(f, "'{}' before position {} has no style or fill", path_d, position),
+            Error::MissingOutline { position } => write!(f, "element before position {} has no outline", position),
+            Error::BadAttribute { name, value, position } => write!(
+                f, "couldn't read {}=\"{}\" in the element before position {}", name, value, position
+            ),
+            Error::BadCommand { command, given, path_d, position } => {
+                let takes = match command.param_count() {
+                    0 => String::from("no numbers"),
+                    1 => String::from("numbers one at a time"),
+                    n => format!("{} numbers at a time", n),
+                };
+                write!(f, "'{}' in '{}' before position {} takes {}, but was given {}", command, path_d, position, takes, given)
+            }
+            Error::NotANumber { token, name, position } => write!(
+                f, "'{}' in the {} of the element before position {} isn't a number", token, name, position
+            ),
+            Error::BadColour { style, position } => write!(f, "couldn't find a fill colour in style '{}' before position {}", style, position),
+            Error::BadShape { error, position } => write!(f, "invalid outline before position {}: {}", position, error),
+            Error::XmlError { error, position } => write!(f, "invalid XML at position {}: {}", position, error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::XmlError { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ShapeError> for Error {
+    fn from(error: ShapeError) -> Self {
+        Error::BadShape { error, position: 0 }
+    }
+}
+
+impl From<AttrError> for Error {
+    fn from(error: AttrError) -> Self {
+        Error::XmlError { error: error.into(), position: 0 }
+    }
+}
+
+/// Reads every shape in a components file, by each id it's labelled with.
+/// Anything wrong with the file comes back as `IsometricError::Parse`.
+pub fn parse_shapes<T: BufRead>(reader: &mut quick_xml::reader::Reader<T>) -> Result<ShapeRegistry, IsometricError> {
 
     let mut buffer = Vec::new();
 
-    const INIT: Option<Rc<RefCell<Shape>>> = None;
-    let mut shapes = [INIT; 256];
+    let mut shapes = ShapeRegistry::new();
 
-    let mut groups = vec![];
-    let mut components = vec![];
+    // every group we're currently inside, with the outermost level of the document at the bottom
+    let mut stack = vec![GroupFrame::default()];
 
     loop {
         match reader.read_event_into(&mut buffer) {
-            Err(e) => panic!("Error at position {}: {}", reader.buffer_position(), e),
+            Err(error) => return Err(Error::XmlError { error, position: reader.buffer_position() }.into()),
 
             Ok(Event::Eof) => break,
 
+            // the document's own units are turned into pixels before anything else, so shapes come out the size they're drawn
+            Ok(Event::Start(e)) if e.name().as_ref() == b"svg" && stack.len() == 1 => {
+                let position = reader.buffer_position();
+                stack[0].transform = parse_viewport(&e).map_err(|e| e.at(position))?;
+            }
+
             Ok(Event::Start(e)) if e.name().as_ref() == b"g" => {
-                groups.append(parse_group(e).as_mut())
+                let position = reader.buffer_position();
+                let transform = stack.last().unwrap().transform * parse_transform(&e).map_err(|e| e.at(position))?;
+                let labels = parse_group(e).map_err(|e| e.at(position))?;
+                stack.push(GroupFrame { labels, transform, ..GroupFrame::default() });
             }
 
-            Ok(Event::Empty(e)) if e.name().as_ref() == b"path" => {
-                let component = parse_component(e);
-                components.push(component);
+            Ok(Event::Empty(e)) if matches!(e.name().as_ref(), b"path" | b"rect" | b"polygon" | b"polyline") => {
+                let position = reader.buffer_position();
+                let mut component = parse_component(e).map_err(|e| e.at(position))?;
+                let frame = stack.last_mut().unwrap();
+                component.points_iter_mut().for_each(|p| *p = frame.transform.apply(*p));
+                frame.components.push(component);
             }
 
-            Ok(Event::End(e)) if e.name().as_ref() == b"g" => {
-                let shape = Shape::new(components);
-                let shape = Rc::new(RefCell::new(shape));
-                for group in groups {
-                    shapes[group as usize] = Some(Rc::clone(&shape));
+            Ok(Event::End(e)) if e.name().as_ref() == b"g" && stack.len() > 1 => {
+                let frame = stack.pop().unwrap();
+                let parent = stack.last_mut().unwrap();
+                match frame.labels {
+                    Some(labels) if !frame.contains_shapes => {
+                        let mut shape = Shape::new(frame.components);
+                        // paths can be drawn either way round, but nothing after this has to care which
+                        shape.normalise_winding();
+                        let shape = Arc::new(shape);
+                        for label in labels {
+                            shapes.insert(label, Arc::clone(&shape));
+                        }
+                        parent.contains_shapes = true;
+                    }
+                    // anything else just holds other elements, so they're treated as if it wasn't there
+                    _ => {
+                        parent.components.extend(frame.components);
+                        parent.contains_shapes |= frame.contains_shapes;
+                    }
                 }
-                groups = vec![];
-                components = vec![];
             }
             _ => (),
         }
     }
 
-    shapes
+    Ok(shapes)
 }
 
-fn parse_group(e: BytesStart) -> Vec<u8> {
+/// A shape from one components file replacing the one with the same id from an earlier file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeOverride {
+    pub id: ShapeId,
+    /// The file the shape which was kept came from.
+    pub file: String,
+    /// The file the shape it replaced came from.
+    pub previous: String,
+}
 
-    let mut group_name: Option<Cow<[u8]>> = None;
+impl Display for ShapeOverride {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "shape {} from {} replaces the one from {}", self.id, self.file, self.previous)?;
+        // the reference cube decides the direction of every axis, so swapping it changes the whole scene
+        if self.id == ShapeId::from(255u8) {
+            write!(f, ", which changes the cube the scene's axes are measured from")?;
+        }
+        Ok(())
+    }
+}
+
+/// Combines the shapes read from several components files, in the order they were given.
+/// A later file's shape replaces an earlier one with the same id, and every one replaced is given back for the caller to warn about.
+pub fn merge_shapes<N: Display>(sets: impl IntoIterator<Item = (N, ShapeRegistry)>) -> (ShapeRegistry, Vec<ShapeOverride>) {
+
+    let mut merged: BTreeMap<ShapeId, (String, Arc<Shape>)> = BTreeMap::new();
+    let mut overrides = vec![];
+
+    for (name, shapes) in sets {
+        let name = name.to_string();
+        for (id, shape) in shapes {
+            if let Some((previous, _)) = merged.get(&id) {
+                overrides.push(ShapeOverride { id: id.clone(), file: name.clone(), previous: previous.clone() });
+            }
+            merged.insert(id, (name.clone(), shape));
+        }
+    }
+
+    (merged.into_iter().map(|(id, (_, shape))| (id, shape)).collect(), overrides)
+}
+
+/// A `<g>` element which hasn't been closed yet.
+#[derive(Default)]
+struct GroupFrame {
+    /// The ids the group's shape is found at, or `None` if it's only there to organise other elements.
+    labels: Option<Vec<ShapeId>>,
+    components: Vec<ShapeComponent>,
+    /// Whether any of the groups inside this one was made into a shape.
+    /// Only the innermost labelled groups are shapes, so this makes any label on this group irrelevant.
+    contains_shapes: bool,
+    /// Every transform from this group and the ones around it, combined.
+    transform: Transform,
+}
+
+/// Reads an element's `transform` attribute, which is the identity if it doesn't have one.
+fn parse_transform(e: &BytesStart) -> Result<Transform, Error> {
     for attr in e.attributes().with_checks(false) {
-        let attr = attr.unwrap();
-        if attr.key.as_ref() == b"inkscape:label" {
-            group_name = Some(attr.value);
-            break;
+        let attr = attr?;
+        if attr.key.as_ref() == b"transform" {
+            let value = String::from_utf8_lossy(&attr.value);
+            return value.parse().map_err(|_| Error::BadAttribute {
+                name: String::from("transform"), value: value.into_owned(), position: 0,
+            });
         }
     }
-    let group_name = String::from_utf8(Vec::from(group_name.unwrap())).unwrap();
-    let mut groups = vec![];
-    for bit_string in group_name.split(';') {
-        let group_num = u8::from_str_radix(bit_string, 2).unwrap();
-        groups.push(group_num);
+    Ok(Transform::identity())
+}
+
+/// How many pixels are in each unit a length can be given in, as browsers and Inkscape count them.
+const UNITS: [(&str, f64); 7] = [
+    ("px", 1.0), ("in", 96.0), ("cm", 96.0 / 2.54), ("mm", 96.0 / 25.4), ("q", 96.0 / 101.6), ("pt", 96.0 / 72.0), ("pc", 16.0),
+];
+
+/// Reads the `width` or `height` of the document in pixels.
+/// Lengths relative to something outside the file, like a percentage, give `None`.
+fn parse_length(name: &str, value: &str) -> Result<Option<f64>, Error> {
+    let value = value.trim();
+    if value.ends_with('%') || value.ends_with("em") || value.ends_with("ex") {
+        return Ok(None);
+    }
+    let (number, scale) = UNITS.iter()
+        .find_map(|(unit, scale)| Some((value.strip_suffix(unit)?, *scale)))
+        .unwrap_or((value, 1.0));
+    match number.trim().parse::<f64>() {
+        Ok(length) if length > 0.0 => Ok(Some(length * scale)),
+        _ => Err(Error::BadAttribute { name: name.to_string(), value: value.to_string(), position: 0 }),
     }
-    groups
 }
 
-fn parse_component(e: BytesStart) -> ShapeComponent {
+/// Reads the root `<svg>` element's `viewBox`, `width` and `height` into the transform from its user units to pixels.
+/// Without a `viewBox` the user units already are pixels, whatever units the width and height are given in.
+fn parse_viewport(e: &BytesStart) -> Result<Transform, Error> {
+    let (mut view_box, mut width, mut height, mut aspect) = (None, None, None, None);
+    for attr in e.attributes().with_checks(false) {
+        let attr = attr?;
+        let value = String::from_utf8_lossy(&attr.value).into_owned();
+        match attr.key.as_ref() {
+            b"viewBox" => view_box = Some(value),
+            b"width" => width = Some(value),
+            b"height" => height = Some(value),
+            b"preserveAspectRatio" => aspect = Some(value),
+            _ => (),
+        }
+    }
+    let Some(view_box) = view_box else {
+        return Ok(Transform::identity());
+    };
+    let (numbers, skipped) = split_params(&view_box, false);
+    let [min_x, min_y, view_width, view_height] = numbers[..] else {
+        return Err(Error::BadAttribute { name: String::from("viewBox"), value: view_box, position: 0 });
+    };
+    if view_width <= 0.0 || view_height <= 0.0 || !skipped.is_empty() {
+        return Err(Error::BadAttribute { name: String::from("viewBox"), value: view_box, position: 0 });
+    }
+    let width = width.map(|width| parse_length("width", &width)).transpose()?.flatten();
+    let height = height.map(|height| parse_length("height", &height)).transpose()?.flatten();
+
+    // a side left out is as long as it has to be to keep the view box's shape
+    let (scale_x, scale_y) = match (width, height) {
+        (Some(width), Some(height)) => (width / view_width, height / view_height),
+        (Some(width), None) => (width / view_width, width / view_width),
+        (None, Some(height)) => (height / view_height, height / view_height),
+        (None, None) => (1.0, 1.0),
+    };
+    // unless it's told not to, the view box is scaled evenly, just enough to fit inside the document, or with `slice`, to cover it
+    let (scale_x, scale_y) = match aspect.as_deref().map(str::trim) {
+        Some(aspect) if aspect.starts_with("none") => (scale_x, scale_y),
+        Some(aspect) if aspect.ends_with("slice") => (f64::max(scale_x, scale_y), f64::max(scale_x, scale_y)),
+        _ => (f64::min(scale_x, scale_y), f64::min(scale_x, scale_y)),
+    };
+    Ok(Transform::scale(scale_x, scale_y) * Transform::translate(-min_x, -min_y))
+}
 
-    let mut normal = None;
-    let mut primitives = None;
+/// Reads the ids a group's shape should be found at.
+/// Groups without a label don't describe a shape and give `None`,
+/// as do Inkscape layers unless they're numbered.
+fn parse_group(e: BytesStart) -> Result<Option<Vec<ShapeId>>, Error> {
 
-    for attr in e.attributes() {
-        let attr = attr.unwrap();
+    let mut group_name: Option<Cow<[u8]>> = None;
+    let mut layer = false;
+    for attr in e.attributes().with_checks(false) {
+        let attr = attr?;
         match attr.key.as_ref() {
-            b"d" => {
-                let path = String::from_utf8(Vec::from(attr.value.as_ref())).unwrap();
-                let primitives_iter = PrimitiveIter::from_str(&path);
-                primitives = Some(primitives_iter.collect());
-            }
-            b"style" => {
-                let style_str = String::from_utf8(Vec::from(attr.value.as_ref())).unwrap();
-                let caps = &COLOUR_REGEX.captures(&style_str).unwrap();
+            b"inkscape:label" => group_name = Some(attr.value),
+            b"inkscape:groupmode" => layer = attr.value.as_ref() == b"layer",
+            _ => (),
+        }
+    }
+    let Some(group_name) = group_name else {
+        return Ok(None);
+    };
+    let group_name = String::from_utf8_lossy(&group_name);
+    let mut groups = vec![];
+    for label in group_name.split(';') {
+        match label.parse::<ShapeId>() {
+            // every layer has a name, so only numbered layers are taken to be shapes
+            Ok(ShapeId::Name(_)) | Err(_) if layer => return Ok(None),
+            Ok(id) => groups.push(id),
+            Err(_) => return Err(Error::BadLabel { value: group_name.into_owned(), position: 0 }),
+        }
+    }
+    Ok(Some(groups))
+}
 
-                let r = (i32::from_str_radix(&caps["r"], 16).unwrap() - 128) as f64;
-                let g = (i32::from_str_radix(&caps["g"], 16).unwrap() - 128) as f64;
-                let b = (i32::from_str_radix(&caps["b"], 16).unwrap() - 128) as f64;
+/// Reads a single face of a shape from a `<path>`, `<rect>`, `<polygon>` or `<polyline>`.
+/// Its normal is encoded in its fill colour, and its outline comes from whichever attributes the element uses.
+fn parse_component(e: BytesStart) -> Result<ShapeComponent, Error> {
 
-                let magnitude = f64::sqrt(r * r + g * g + b * b);
+    let mut attributes = HashMap::new();
+    let mut carried = vec![];
+    for attr in e.attributes() {
+        let attr = attr?;
+        let name = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+        let value = String::from_utf8_lossy(&attr.value).into_owned();
+        if CARRIED_ATTRIBUTES.contains(&name.as_str()) || name.starts_with("data-") {
+            // written back out escaped, so kept as the text it stands for
+            let text = attr.unescape_value().map_err(|error| Error::XmlError { error, position: 0 })?;
+            carried.push((name, text.into_owned()));
+        }
+        attributes.insert(attr.key.as_ref().to_vec(), value);
+    }
+    let number = |name: &str, default: Option<f64>| -> Result<f64, Error> {
+        match attributes.get(name.as_bytes()) {
+            Some(value) => value.trim().parse::<f64>().map_err(|_| Error::BadAttribute {
+                name: name.to_string(), value: value.clone(), position: 0
+            }),
+            None => default.ok_or(Error::MissingOutline { position: 0 }),
+        }
+    };
 
-                // accidentally got my dimensions the wrong way round
-                normal = Some(Vec3 {
-                    x: b / magnitude,
-                    y: g / magnitude,
-                    z: r / magnitude,
+    let transform = parse_transform(&e)?;
+    let (outline, mut primitives) = match e.name().as_ref() {
+        b"rect" => {
+            // rounded corners are left sharp
+            let origin = vect![number("x", Some(0.0))?, number("y", Some(0.0))?];
+            let size = vect![number("width", None)?, number("height", None)?];
+            (format!("rect at {} of size {}", origin, size), vec![ShapePrimitive::rect(origin, size)?])
+        }
+        // a polyline's fill is closed off the same way as a polygon's
+        b"polygon" | b"polyline" => {
+            let Some(points) = attributes.get(b"points".as_slice()) else {
+                return Err(Error::MissingOutline { position: 0 });
+            };
+            let (numbers, skipped) = split_params(points, false);
+            if let Some(token) = skipped.into_iter().next() {
+                return Err(Error::NotANumber { token, name: String::from("points"), position: 0 });
+            }
+            if numbers.len() % 2 != 0 {
+                return Err(Error::BadAttribute { name: String::from("points"), value: points.clone(), position: 0 });
+            }
+            let primitive = ShapePrimitive::from_points(numbers.into_iter().tuples().map(|(x, y)| vect![x, y]))?;
+            (points.clone(), vec![primitive])
+        }
+        _ => {
+            let Some(path) = attributes.get(b"d".as_slice()) else {
+                return Err(Error::MissingOutline { position: 0 });
+            };
+            // the outline is only walked once every command in it is known to be whole
+            let mut commands = FromSvgCommandIter::from_str(path);
+            let incomplete = commands.by_ref().filter(|command| !command.is_complete()).collect_vec();
+            if let Some(token) = commands.skipped().first() {
+                return Err(Error::NotANumber { token: token.clone(), name: String::from("d"), position: 0 });
+            }
+            if let Some(command) = incomplete.into_iter().next() {
+                return Err(Error::BadCommand {
+                    command: command.cmd_type, given: command.params.len(), path_d: path.clone(), position: 0
                 });
             }
-            _ => (),
-        };
-    }
-    if let (Some(normal), Some(primitives)) = (normal, primitives) {
-        ShapeComponent {
-            normal,
-            primitives,
+            let primitives = PrimitiveIter::from_str(path)
+                .map(|p| ShapePrimitive::from_points(p.points))
+                .collect::<Result<Vec<_>, _>>()?;
+            (path.clone(), primitives)
         }
+    };
+
+    for primitive in &mut primitives {
+        primitive.points_iter_mut().for_each(|p| *p = transform.apply(*p));
     }
-    else {
-        panic!("Something went wrong...");
+
+    let style = attributes.get(b"style".as_slice());
+    let fill = attributes.get(b"fill".as_slice());
+    // just like CSS, a fill in the style takes priority over the fill attribute
+    let colour = style
+        .and_then(|style| FILL_REGEX.captures(style))
+        .and_then(|caps| parse_css_colour(&caps["fill"]))
+        .or_else(|| parse_css_colour(fill?));
+    let Some([r, g, b]) = colour else {
+        return Err(match (style, fill) {
+            (None, None) => Error::MissingStyle { path_d: outline, position: 0 },
+            (Some(style), _) => Error::BadColour { style: style.clone(), position: 0 },
+            (None, Some(fill)) => Error::BadColour { style: format!("fill:{}", fill), position: 0 },
+        });
+    };
+    let [r, g, b] = [r, g, b].map(|c| (c as i32 - 128) as f64);
+    let stroke = style.and_then(|style| parse_stroke(style));
+
+    // accidentally got my dimensions the wrong way round
+    let normal = Vec3 { x: b, y: g, z: r };
+
+    // a sub-path wound the other way to the first, and inside one wound the same way, is a hole cut out of it
+    let direction = primitives.first().map(|first| first.signed_area().signum());
+    let is_hole = primitives.iter()
+        .map(|p| {
+            Some(p.signed_area().signum()) != direction && primitives.iter()
+                .any(|outer| Some(outer.signed_area().signum()) == direction && outer.encloses(p))
+        })
+        .collect_vec();
+    let (holes, primitives): (Vec<_>, Vec<_>) = primitives.into_iter()
+        .zip(is_hole)
+        .partition(|(_, is_hole)| *is_hole);
+    let holes = holes.into_iter().map(|(hole, _)| hole).collect();
+    let primitives = primitives.into_iter().map(|(primitive, _)| primitive).collect();
+
+    Ok(ShapeComponent::new(normal, primitives)?.with_holes(holes).with_attributes(carried).with_stroke(stroke))
+}
+
+/// Picks out how a face is outlined from its `style`, if it's outlined at all.
+/// Later declarations take priority, just like CSS.
+/// A width or line join without a stroke colour draws nothing, and editors leave those lying around on every face, so they're dropped.
+fn parse_stroke(style: &str) -> Option<ComponentStroke> {
+    let mut stroke = ComponentStroke::default();
+    for caps in STROKE_REGEX.captures_iter(style) {
+        let value = Some(caps["value"].trim().to_string());
+        match &caps["property"] {
+            "stroke" => stroke.colour = value,
+            "stroke-width" => stroke.width = value,
+            _ => stroke.linejoin = value,
+        }
     }
+    let colour = stroke.colour.as_deref()?;
+    (colour != "none").then_some(stroke)
 }
@@ -2,8 +2,15 @@
 #![allow(illegal_floating_point_literal_pattern)]
 
 use quick_xml::events::BytesStart;
-use crate::parser::{parse_component};
-use crate::shapes::{ShapeComponent, ShapePrimitive};
+use quick_xml::reader::Reader;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use crate::error::IsometricError;
+use crate::iter::{DEFAULT_CURVE_SEGMENTS, FromSvgCommandIter, PrimitiveIter};
+use crate::path::{Command, CommandType};
+use crate::parser::{Error, merge_shapes, parse_component, parse_shapes, ShapeOverride};
+use crate::shapes::{CircleDirection, ComponentStroke, Polygonal, ShapeError, ShapeId, ShapePrimitive};
 use crate::vector::{Vec2, Vec3};
 use crate::vectp;
 
@@ -12,11 +19,10 @@ fn test_parse_component_abs() {
     let mut event = BytesStart::new("path");
     event.push_attribute(("d", "M 46 33 65 38 V 19 L 51 4 38 18 Z"));
     event.push_attribute(("style", "fill:#80ff80"));
-    let parsed = parse_component(event);
-    assert_matches!(parsed, ShapeComponent {
-            normal: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
-            ref primitives,
-        } if matches!(**primitives, [
+    let parsed = parse_component(event).unwrap();
+    assert!(parsed.holes().is_empty() && parsed.attributes().is_empty() && parsed.stroke().is_none());
+    assert_matches!(parsed.normal(), Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    assert_matches!(*parsed.primitives(), [
             ShapePrimitive {
                 ref points
             }
@@ -26,18 +32,17 @@ fn test_parse_component_abs() {
             Vec2 { x: 65.0, y: 19.0 },
             Vec2 { x: 51.0, y:  4.0 },
             Vec2 { x: 38.0, y: 18.0 },
-        ])));
+        ]));
 }
 #[test]
 fn test_parse_component_rel() {
     let mut event = BytesStart::new("path");
     event.push_attribute(("d", "m 46 33 19 5 v -19 l -14 -15 -13 14 z"));
     event.push_attribute(("style", "fill:#80ff80"));
-    let parsed = parse_component(event);
-    assert_matches!(parsed, ShapeComponent {
-            normal: Vec3 { x: 0.0, y: 1.0, z: 0.0 },
-            ref primitives,
-        } if matches!(**primitives, [
+    let parsed = parse_component(event).unwrap();
+    assert!(parsed.holes().is_empty() && parsed.attributes().is_empty() && parsed.stroke().is_none());
+    assert_matches!(parsed.normal(), Vec3 { x: 0.0, y: 1.0, z: 0.0 });
+    assert_matches!(*parsed.primitives(), [
             ShapePrimitive {
                 ref points
             }
@@ -47,18 +52,17 @@ fn test_parse_component_rel() {
             Vec2 { x: 65.0, y: 19.0 },
             Vec2 { x: 51.0, y:  4.0 },
             Vec2 { x: 38.0, y: 18.0 },
-        ])));
+        ]));
 }
 #[test]
 fn test_parse_component_multiple() {
     let mut event = BytesStart::new("path");
     event.push_attribute(("d", "m 46 33 19 5 v -19 l -14 -15 -13 14 z M 11 59 32 45 h -9 L 16 30 v 4 z"));
     event.push_attribute(("style", "fill:#80ff80"));
-    let parsed = parse_component(event);
-    assert_matches!(parsed, ShapeComponent {
-            normal: vectp![0.0, 1.0, 0.0],
-            ref primitives,
-        } if matches!(**primitives, [
+    let parsed = parse_component(event).unwrap();
+    assert!(parsed.holes().is_empty() && parsed.attributes().is_empty() && parsed.stroke().is_none());
+    assert_matches!(parsed.normal(), vectp![0.0, 1.0, 0.0]);
+    assert_matches!(*parsed.primitives(), [
             ShapePrimitive {
                 points: ref first_points
             },
@@ -77,5 +81,402 @@ fn test_parse_component_multiple() {
             vectp![23.0, 45.0],
             vectp![16.0, 30.0],
             vectp![16.0, 34.0],
-        ])));
-}
\ No newline at end of file
+        ]));
+}
+#[test]
+fn test_parse_component_holes() {
+    let parse = |d: &str| {
+        let mut event = BytesStart::new("path");
+        event.push_attribute(("d", d));
+        event.push_attribute(("style", "fill:#80ff80"));
+        parse_component(event).unwrap()
+    };
+    // the inner square is wound the other way, so is cut out
+    let frame = parse("M 0 0 H 10 V 10 H 0 Z M 3 3 V 7 H 7 V 3 Z");
+    assert_eq!(frame.primitives().len(), 1);
+    assert_eq!(frame.holes().len(), 1);
+    assert_eq!(frame.holes()[0].points[0], Vec2 { x: 3.0, y: 3.0 });
+    // wound the same way, it's drawn on top
+    let stacked = parse("M 0 0 H 10 V 10 H 0 Z M 3 3 H 7 V 7 H 3 Z");
+    assert_eq!(stacked.primitives().len(), 2);
+    assert!(stacked.holes().is_empty());
+    // wound the other way, but off to the side, there's nothing for it to be cut out of
+    let apart = parse("M 0 0 H 10 V 10 H 0 Z M 20 3 V 7 H 27 V 3 Z");
+    assert_eq!(apart.primitives().len(), 2);
+    assert!(apart.holes().is_empty());
+}
+#[test]
+fn test_parse_component_cubic() {
+    let mut event = BytesStart::new("path");
+    event.push_attribute(("d", "M 0 0 C 0 10 10 10 10 0 Z m 20 0 c 0 10 10 10 10 0 z"));
+    event.push_attribute(("style", "fill:#80ff80"));
+    let parsed = parse_component(event).unwrap();
+    assert_eq!(parsed.primitives().len(), 2);
+    for (primitive, start) in parsed.primitives().iter().zip([0.0, 20.0]) {
+        // the start point, then the curve split into 8 lines
+        assert_eq!(primitive.points.len(), 1 + DEFAULT_CURVE_SEGMENTS);
+        assert_eq!(primitive.points[0], Vec2 { x: start, y: 0.0 });
+        assert_eq!(primitive.points[DEFAULT_CURVE_SEGMENTS / 2], Vec2 { x: start + 5.0, y: 7.5 });
+        assert_eq!(primitive.points[DEFAULT_CURVE_SEGMENTS], Vec2 { x: start + 10.0, y: 0.0 });
+    }
+    let points = PrimitiveIter::with_curve_segments("M 0 0 C 0 10 10 10 10 0 Z", 3).next().unwrap().points;
+    assert_eq!(points.len(), 4);
+}
+#[test]
+fn test_parse_component_quadratic() {
+    let mut event = BytesStart::new("path");
+    event.push_attribute(("d", "M0 0 Q 5 5 10 0 T 20 0 Z"));
+    event.push_attribute(("style", "fill:#80ff80"));
+    let parsed = parse_component(event).unwrap();
+    assert_eq!(parsed.primitives().len(), 1);
+    let points = &parsed.primitives()[0].points;
+    assert_eq!(points.len(), 1 + 2 * DEFAULT_CURVE_SEGMENTS);
+    // the smooth curve mirrors the first, so dips below where the first rose above
+    assert_eq!(points[DEFAULT_CURVE_SEGMENTS / 2], Vec2 { x: 5.0, y: 2.5 });
+    assert_eq!(points[DEFAULT_CURVE_SEGMENTS + DEFAULT_CURVE_SEGMENTS / 2], Vec2 { x: 15.0, y: -2.5 });
+    assert_eq!(points[2 * DEFAULT_CURVE_SEGMENTS], Vec2 { x: 20.0, y: 0.0 });
+
+    // without a quadratic curve before it, the control point is the start point, so every point stays on a straight line
+    let points = PrimitiveIter::with_curve_segments("m 0 0 t 10 10 l 10 0 z", 2).next().unwrap().points;
+    assert_eq!(points, vec![
+        Vec2 { x: 0.0, y: 0.0 },
+        Vec2 { x: 2.5, y: 2.5 },
+        Vec2 { x: 10.0, y: 10.0 },
+        Vec2 { x: 20.0, y: 10.0 },
+    ]);
+}
+#[test]
+fn test_parse_arc_flags() {
+    let bounds = |d: &str| {
+        let points = PrimitiveIter::from_str(d).next().unwrap().points;
+        assert_eq!(points.len(), 1 + DEFAULT_CURVE_SEGMENTS);
+        assert_eq!(points[DEFAULT_CURVE_SEGMENTS], Vec2 { x: 10.0, y: 0.0 });
+        points.iter().fold((f64::MAX, f64::MIN, f64::MAX, f64::MIN), |(l, r, t, b), p| {
+            (l.min(p.x), r.max(p.x), t.min(p.y), b.max(p.y))
+        })
+    };
+    // the flags can be written without anything between them
+    let all = [
+        bounds("M 0 0 A 10 5 0 0 0 10 0 Z"),
+        bounds("M 0 0 A 10 5 0 0 1 10 0 Z"),
+        bounds("M 0 0 A 10 5 0 1 0 10 0 Z"),
+        bounds("M 0 0 A 10 5 0 11 10 0 Z"),
+    ];
+    for (i, a) in all.iter().enumerate() {
+        for b in &all[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+    // the small arcs bulge out less than the large ones
+    assert!(all[0].3 - all[0].2 < all[2].3 - all[2].2);
+    // relative arcs with radii too small to reach are scaled up to a half-ellipse
+    let points = PrimitiveIter::with_curve_segments("m 0 0 a 1 1 0 0 1 10 0 z", 2).next().unwrap().points;
+    assert!((points[1] - Vec2 { x: 5.0, y: -5.0 }).magnitude() < 1e-9);
+}
+#[test]
+fn test_parse_numbers() {
+    let commands = FromSvgCommandIter::from_str("M1e-3 .5L2E2,-1.5e+1l.5-.5.25.25h-1.5.5z").collect_vec();
+    assert_matches!(&commands[..], [
+        Command { cmd_type: CommandType::MoveToAbs, params: m },
+        Command { cmd_type: CommandType::LineToAbs, params: l },
+        Command { cmd_type: CommandType::LineToRel, params: l_rel },
+        Command { cmd_type: CommandType::HorizRel, params: h },
+        Command { cmd_type: CommandType::ClosePath, params: z },
+    ] if m == &[0.001, 0.5]
+        && l == &[200.0, -15.0]
+        && l_rel == &[0.5, -0.5, 0.25, 0.25]
+        && h == &[-1.5, 0.5]
+        && z.is_empty()
+    );
+}
+#[test]
+fn test_parse_bad_number() {
+    // the stray token is skipped rather than taking the rest of the path with it
+    let mut iter = FromSvgCommandIter::from_str("M 0 0 L 1 x2 2 Z");
+    let commands = iter.by_ref().collect_vec();
+    assert_eq!(commands[1].params, vec![1.0, 2.0]);
+    assert_eq!(iter.skipped(), ["x2"]);
+
+    // which is an error when reading a face, naming the token
+    for (element, name, value) in [("path", "d", "M 0 0 L 1 x2 2 Z"), ("polygon", "points", "0 0 1 x2 2 0 1")] {
+        let mut event = BytesStart::new(element);
+        event.push_attribute((name, value));
+        event.push_attribute(("fill", "#80ff80"));
+        let error = parse_component(event).unwrap_err();
+        assert_matches!(&error, Error::NotANumber { token, name: n, .. } if token == "x2" && n == name);
+        assert!(error.to_string().starts_with(&format!("'x2' in the {} of the element", name)));
+    }
+}
+#[test]
+fn test_parse_errors() {
+    let parse = |svg: &str| {
+        let mut reader = Reader::from_str(svg);
+        reader.trim_text(true);
+        let Err(IsometricError::Parse(error)) = parse_shapes(&mut reader) else { panic!("expected a parse error") };
+        error
+    };
+    let svg = r#"<svg><g inkscape:label="11111111"><path d="M 0 0 1 0 0 1 z" style="fill:#80ff80"/></g><g inkscape:label="big cube"></g></svg>"#;
+    // the position is just after the offending tag
+    assert_matches!(parse(svg), Error::BadLabel { value, position } if value == "big cube" && position == svg.len() - "</g></svg>".len());
+    assert_matches!(
+        parse(r#"<svg><g inkscape:label="1"><path d="M 0 0 1 0 0 1 z"/></g></svg>"#),
+        Error::MissingStyle { path_d, .. } if path_d == "M 0 0 1 0 0 1 z"
+    );
+    assert_matches!(
+        parse(r#"<svg><g inkscape:label="1"><path d="M 0 0 1 0 0 1 z" style="fill:bluish"/></g></svg>"#),
+        Error::BadColour { style, .. } if style == "fill:bluish"
+    );
+    assert_matches!(
+        parse(r#"<svg><g inkscape:label="1"><path d="M 0 0 1 0 z" style="fill:#80ff80"/></g></svg>"#),
+        Error::BadShape { error: ShapeError::TooFewPoints { count: 2 }, .. }
+    );
+    // a command running out of numbers is an error, rather than reading past the end of them
+    let short = parse(r#"<svg><g inkscape:label="1"><path d="M 0 0 1 0 q 1 1" style="fill:#80ff80"/></g></svg>"#);
+    assert_matches!(short, Error::BadCommand { command: CommandType::QuadToRel, given: 2, .. });
+    assert!(short.to_string().starts_with("'q' in 'M 0 0 1 0 q 1 1' before position"));
+    assert!(short.to_string().ends_with("takes 4 numbers at a time, but was given 2"));
+    assert_matches!(
+        parse(r#"<svg><g inkscape:label="1"><path d="M 0 0 1 0 0 1 z 1" style="fill:#80ff80"/></g></svg>"#),
+        Error::BadCommand { command: CommandType::ClosePath, given: 1, .. }
+    );
+    assert_matches!(parse(r#"<svg><g inkscape:label="1"></svg>"#), Error::XmlError { .. });
+}
+#[test]
+fn test_parse_colour_formats() {
+    let normal = |style: &str| {
+        let mut event = BytesStart::new("path");
+        event.push_attribute(("d", "M 0 0 1 0 0 1 z"));
+        event.push_attribute(("style", style));
+        parse_component(event).unwrap().normal()
+    };
+    assert_eq!(normal("fill:#8F8"), normal("fill:#88ff88"));
+    assert_eq!(normal("fill:#80FF80"), normal("fill:#80ff80"));
+    assert_eq!(normal("fill-opacity:1;fill:lime;stroke:none"), normal("fill:#00ff00"));
+    assert_eq!(normal("fill: Red"), normal("fill:#ff0000"));
+}
+#[test]
+fn test_parse_fill_attribute() {
+    let normal = |attributes: &[(&str, &str)]| {
+        let mut event = BytesStart::new("path");
+        event.push_attribute(("d", "M 0 0 1 0 0 1 z"));
+        for attribute in attributes {
+            event.push_attribute(*attribute);
+        }
+        parse_component(event).unwrap().normal()
+    };
+    let expected = normal(&[("style", "fill:#80ff80")]);
+    assert_eq!(normal(&[("style", "fill:rgb(128,255,128)")]), expected);
+    assert_eq!(normal(&[("style", "fill:rgba(128, 255, 128, 0.5)")]), expected);
+    assert_eq!(normal(&[("style", "fill:rgb(50.2%, 100%, 50.2%)")]), expected);
+    assert_eq!(normal(&[("fill", "#80ff80")]), expected);
+    assert_eq!(normal(&[("style", "stroke:none"), ("fill", "#80ff80")]), expected);
+    // the style wins when both have a colour
+    assert_eq!(normal(&[("fill", "#ff8080"), ("style", "fill:#80ff80")]), expected);
+}
+
+#[test]
+fn test_parse_carried_attributes() {
+    let mut event = BytesStart::new("path");
+    event.push_attribute(("d", "M 0 0 1 0 0 1 z"));
+    event.push_attribute(("id", "path982"));
+    event.push_attribute(("style", "fill:#80ff80;stroke-width:1"));
+    event.push_attribute(("class", "roof"));
+    event.push_attribute(("sodipodi:nodetypes", "ccc"));
+    event.push_attribute(("stroke-dasharray", "2 1"));
+    event.push_attribute(("data-material", "slate & tar"));
+    let component = parse_component(event).unwrap();
+    // in the order they were written, without the outline, style, id or anything an editor added
+    assert_eq!(component.attributes(), [
+        (String::from("class"), String::from("roof")),
+        (String::from("stroke-dasharray"), String::from("2 1")),
+        (String::from("data-material"), String::from("slate & tar")),
+    ]);
+}
+#[test]
+fn test_parse_stroke() {
+    let stroke = |style: &str| {
+        let mut event = BytesStart::new("path");
+        event.push_attribute(("d", "M 0 0 1 0 0 1 z"));
+        event.push_attribute(("style", style));
+        parse_component(event).unwrap().stroke().cloned()
+    };
+    assert_eq!(stroke("fill:#80ff80;stroke:#202020;stroke-width: 0.5 ;stroke-linejoin:round"), Some(ComponentStroke {
+        colour: Some(String::from("#202020")),
+        width: Some(String::from("0.5")),
+        linejoin: Some(String::from("round")),
+    }));
+    // the last one given wins
+    assert_eq!(stroke("stroke:red;fill:#80ff80;stroke:blue").unwrap().colour.as_deref(), Some("blue"));
+    // nothing's drawn without a colour to draw it in
+    assert_eq!(stroke("fill:#80ff80;stroke-width:1.00157"), None);
+    assert_eq!(stroke("fill:#80ff80;stroke:none;stroke-width:1"), None);
+}
+#[test]
+fn test_parse_nested_groups() {
+    let svg = r#"<svg>
+        <g inkscape:groupmode="layer" inkscape:label="Layer 1">
+            <g inkscape:label="00000001;00000011"><path d="M 0 0 1 0 0 1 z" style="fill:#80ff80"/></g>
+            <g>
+                <g inkscape:label="00000010">
+                    <path d="M 0 0 1 0 0 1 z" style="fill:#80ff80"/>
+                    <g><path d="M 0 0 1 0 1 1 z" style="fill:#ff8080"/></g>
+                </g>
+            </g>
+        </g>
+    </svg>"#;
+    let mut reader = Reader::from_str(svg);
+    reader.trim_text(true);
+    let shapes = parse_shapes(&mut reader).unwrap();
+    let first = shapes[&ShapeId::Index(1)].clone();
+    let second = shapes[&ShapeId::Index(2)].clone();
+    assert!(Arc::ptr_eq(&first, &shapes[&ShapeId::Index(3)].clone()));
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert_eq!(first.component_iter().count(), 1);
+    // paths in unlabelled groups belong to the shape around them
+    assert_eq!(second.component_iter().count(), 2);
+    assert_eq!(shapes.len(), 3);
+}
+#[test]
+fn test_parse_rect_and_polygon() {
+    let svg = r##"<svg><g inkscape:label="00000001">
+        <path d="M 0 0 10 0 0 10 z" style="fill:#80ff80"/>
+        <rect x="1" y="2" width="3" height="4" rx="1" style="fill:#ff8080"/>
+        <polygon points="0,0 5,0 5,5" fill="#8080ff"/>
+        <polyline points="0 0 -5 0 -5 -5" fill="#80ff80"/>
+    </g></svg>"##;
+    let mut reader = Reader::from_str(svg);
+    reader.trim_text(true);
+    let shapes = parse_shapes(&mut reader).unwrap();
+    let shape = shapes[&ShapeId::Index(1)].clone();
+    let components = shape.component_iter().collect_vec();
+    assert_eq!(components.len(), 4);
+    assert_eq!(components[1].primitives()[0].points, vec![
+        Vec2 { x: 1.0, y: 2.0 }, Vec2 { x: 4.0, y: 2.0 }, Vec2 { x: 4.0, y: 6.0 }, Vec2 { x: 1.0, y: 6.0 },
+    ]);
+    assert_eq!(components[1].normal(), Vec3 { x: 0.0, y: 0.0, z: 1.0 });
+    assert_eq!(components[2].primitives()[0].points, vec![
+        Vec2 { x: 0.0, y: 0.0 }, Vec2 { x: 5.0, y: 0.0 }, Vec2 { x: 5.0, y: 5.0 },
+    ]);
+    assert_eq!(components[3].primitives()[0].points.len(), 3);
+
+    let mut event = BytesStart::new("rect");
+    event.push_attribute(("width", "3px"));
+    event.push_attribute(("height", "4"));
+    event.push_attribute(("style", "fill:#80ff80"));
+    assert_matches!(parse_component(event), Err(Error::BadAttribute { name, .. }) if name == "width");
+}
+#[test]
+fn test_parse_normalises_winding() {
+    // the outline is written clockwise, and the hole in it counter-clockwise
+    let svg = r##"<svg><g inkscape:label="1">
+        <path d="M 0 0 V 10 H 10 V 0 Z M 3 3 H 7 V 7 H 3 Z" style="fill:#80ff80"/>
+    </g></svg>"##;
+    let mut reader = Reader::from_str(svg);
+    reader.trim_text(true);
+    let shapes = parse_shapes(&mut reader).unwrap();
+    let shape = &shapes[&ShapeId::Index(1)];
+    let component = shape.component_iter().next().unwrap();
+    assert_eq!(component.primitives()[0].draw_direction(), CircleDirection::CounterClockwise);
+    assert_eq!(component.primitives()[0].points, vec![
+        Vec2 { x: 10.0, y: 0.0 }, Vec2 { x: 10.0, y: 10.0 }, Vec2 { x: 0.0, y: 10.0 }, Vec2 { x: 0.0, y: 0.0 },
+    ]);
+    assert_eq!(component.holes()[0].draw_direction(), CircleDirection::Clockwise);
+}
+#[test]
+fn test_parse_transforms() {
+    let points = |svg: &str| {
+        let mut reader = Reader::from_str(svg);
+        reader.trim_text(true);
+        let shapes = parse_shapes(&mut reader).unwrap();
+        let shape = shapes[&ShapeId::Index(1)].clone();
+        let points = shape.points_iter().collect_vec();
+        points
+    };
+    let plain = points(r##"<svg><g inkscape:label="1"><path d="M 0 0 2 0 0 2 z" fill="#80ff80"/></g></svg>"##);
+    let shifted = points(r##"<svg><g inkscape:label="1"><path d="M 0 0 2 0 0 2 z" fill="#80ff80" transform="translate(5,5)"/></g></svg>"##);
+    assert_eq!(shifted, plain.iter().map(|p| *p + (5.0, 5.0)).collect_vec());
+    // the layer's transform applies after the group's, which applies after the path's
+    let nested = points(r##"<svg><g transform="translate(5,5)"><g inkscape:label="1" transform="scale(2)">
+        <path d="M 0 0 2 0 0 2 z" fill="#80ff80" transform="translate(1 0)"/>
+    </g></g></svg>"##);
+    assert_eq!(nested, vec![Vec2 { x: 7.0, y: 5.0 }, Vec2 { x: 11.0, y: 5.0 }, Vec2 { x: 7.0, y: 9.0 }]);
+}
+#[test]
+fn test_parse_view_box() {
+    let points = |svg: &str| {
+        let mut reader = Reader::from_str(svg);
+        reader.trim_text(true);
+        let shapes = parse_shapes(&mut reader).unwrap();
+        let points = shapes[&ShapeId::Index(1)].points_iter().collect_vec();
+        points
+    };
+    let pixels = points(r##"<svg width="40" height="20" viewBox="0 0 40 20"><g inkscape:label="1"><path d="M 2 2 10 2 2 10 z" fill="#80ff80"/></g></svg>"##);
+    assert_eq!(pixels, vec![Vec2 { x: 2.0, y: 2.0 }, Vec2 { x: 10.0, y: 2.0 }, Vec2 { x: 2.0, y: 10.0 }]);
+    // the same drawing with everything in the file at a quarter of the size, and the view box shrunk to match
+    let quarter = points(r##"<svg width="40" height="20" viewBox="0 0 10 5"><g inkscape:label="1"><path d="M 0.5 0.5 2.5 0.5 0.5 2.5 z" fill="#80ff80"/></g></svg>"##);
+    assert_eq!(quarter, pixels);
+    // or moved away from the origin, in millimetres
+    let millimetres = points(r##"<svg width="10.583333mm" height="5.2916667mm" viewBox="100 100 10.583333 5.2916667">
+        <g inkscape:label="1"><path d="M 100.529167 100.529167 102.645833 100.529167 100.529167 102.645833 z" fill="#80ff80"/></g>
+    </svg>"##);
+    for (p, q) in millimetres.iter().zip(&pixels) {
+        assert!((*p - *q).magnitude() < 1e-4, "{} isn't {}", p, q);
+    }
+
+    // without a view box the file's units are already pixels, whatever its size is given in
+    assert_eq!(points(r##"<svg width="40mm" height="20mm"><g inkscape:label="1"><path d="M 2 2 10 2 2 10 z" fill="#80ff80"/></g></svg>"##), pixels);
+    // a view box the wrong shape is scaled evenly to fit, unless that's turned off
+    let fitted = points(r##"<svg width="80" height="20" viewBox="0 0 40 20"><g inkscape:label="1"><path d="M 2 2 10 2 2 10 z" fill="#80ff80"/></g></svg>"##);
+    assert_eq!(fitted, pixels);
+    let stretched = points(r##"<svg width="80" height="20" viewBox="0 0 40 20" preserveAspectRatio="none"><g inkscape:label="1"><path d="M 2 2 10 2 2 10 z" fill="#80ff80"/></g></svg>"##);
+    assert_eq!(stretched, vec![Vec2 { x: 4.0, y: 2.0 }, Vec2 { x: 20.0, y: 2.0 }, Vec2 { x: 4.0, y: 10.0 }]);
+
+    let mut reader = Reader::from_str(r#"<svg viewBox="0 0 10"></svg>"#);
+    assert_matches!(parse_shapes(&mut reader), Err(IsometricError::Parse(Error::BadAttribute { name, .. })) if name == "viewBox");
+    let mut reader = Reader::from_str(r#"<svg width="wide" viewBox="0 0 10 10"></svg>"#);
+    assert_matches!(parse_shapes(&mut reader), Err(IsometricError::Parse(Error::BadAttribute { name, .. })) if name == "width");
+}
+#[test]
+fn test_merge_shapes() {
+    let parse = |svg: &str| {
+        let mut reader = Reader::from_str(svg);
+        reader.trim_text(true);
+        parse_shapes(&mut reader).unwrap()
+    };
+    let first = parse(r##"<svg><g inkscape:label="1;10"><path d="M 0 0 2 0 0 2 z" fill="#80ff80"/></g></svg>"##);
+    let second = parse(r##"<svg><g inkscape:label="10;11"><path d="M 0 0 4 0 0 4 z" fill="#80ff80"/></g></svg>"##);
+    let kept = first[&ShapeId::Index(1)].clone();
+    let replacement = second[&ShapeId::Index(2)].clone();
+    let (merged, overrides) = merge_shapes([("first.svg", first), ("second.svg", second)]);
+    assert_eq!(overrides, [
+        ShapeOverride { id: ShapeId::Index(2), file: String::from("second.svg"), previous: String::from("first.svg") },
+    ]);
+    assert_eq!(overrides[0].to_string(), "shape 00000010 from second.svg replaces the one from first.svg");
+    let cube = ShapeOverride { id: ShapeId::from(255u8), ..overrides[0].clone() };
+    assert!(cube.to_string().ends_with("which changes the cube the scene's axes are measured from"));
+    assert!(Arc::ptr_eq(&merged[&ShapeId::Index(1)], &kept));
+    assert!(Arc::ptr_eq(&merged[&ShapeId::Index(2)], &replacement));
+    assert!(Arc::ptr_eq(&merged[&ShapeId::Index(3)], &replacement));
+    assert!(!merged.contains_key(&ShapeId::Index(0)));
+}
+#[test]
+fn test_parse_shape_ids() {
+    let svg = r##"<svg>
+        <g inkscape:label="11111111;300"><path d="M 0 0 1 0 0 1 z" fill="#80ff80"/></g>
+        <g inkscape:label="grass"><path d="M 0 0 2 0 0 2 z" fill="#80ff80"/></g>
+    </svg>"##;
+    let mut reader = Reader::from_str(svg);
+    reader.trim_text(true);
+    let shapes = parse_shapes(&mut reader).unwrap();
+    assert_eq!(shapes.keys().cloned().collect_vec(), vec![
+        ShapeId::Index(255),
+        ShapeId::Index(300),
+        ShapeId::Name(String::from("grass")),
+    ]);
+    assert!(Arc::ptr_eq(&shapes[&ShapeId::Index(255)], &shapes[&ShapeId::Index(300)]));
+
+    assert_eq!("00000011".parse::<ShapeId>(), Ok(ShapeId::Index(3)));
+    assert_eq!("12".parse::<ShapeId>(), Ok(ShapeId::Index(12)));
+    assert_eq!(ShapeId::Index(3).to_string(), "00000011");
+    assert!("3 blocks".parse::<ShapeId>().is_err());
+}
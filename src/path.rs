@@ -1,6 +1,71 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 use itertools::Itertools;
 
-#[derive(Debug)]
+use crate::num::Float;
+use crate::vect;
+use crate::vector::Vec2;
+
+mod tests;
+
+/// Settings for how paths are written.
+#[derive(Debug, Clone, Copy)]
+pub struct PathOptions {
+    /// Start every ring from its top-left vertex, so the same geometry always produces the same text.
+    pub canonical_start: bool,
+    pub merging: PathMerging,
+    pub anti_seam: AntiSeam,
+    /// Write each ring with relative commands after its first point, wherever that's shorter than writing it absolutely.
+    pub relative: bool,
+    /// Draw the last side of each ring back to its start before the `z`, for tools which don't close paths themselves.
+    pub explicit_close: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        PathOptions { canonical_start: true, merging: PathMerging::Off, anti_seam: AntiSeam::Off, relative: false, explicit_close: false }
+    }
+}
+
+/// How to cover the hairline gaps antialiasing leaves between faces which meet exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AntiSeam {
+    #[default]
+    Off,
+    /// Draw a line of the given width around each face in its own fill, unless it already has a stroke.
+    Stroke { width: f64 },
+    /// Grow each face outward by `distance`, so neighbouring faces overlap a little.
+    Outset { distance: f64 },
+}
+
+/// Which neighbouring paths with the same style get written as a single `<path>` with several subpaths.
+/// Only paths which are next to each other in paint order are ever merged, so the result looks the same.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum PathMerging {
+    /// Every face gets its own `<path>`.
+    #[default]
+    Off,
+    /// Merge within each shape's `<g>`.
+    Shapes,
+    /// Merge across the whole scene, leaving out the `<g>` around each shape.
+    Global,
+}
+
+impl FromStr for PathMerging {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(PathMerging::Off),
+            "shapes" => Ok(PathMerging::Shapes),
+            "global" => Ok(PathMerging::Global),
+            _ => Err(format!("'{}' is not a merging mode, expected one of \"off\", \"shapes\" or \"global\"", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CommandType {
     MoveToAbs,
     MoveToRel,
@@ -10,15 +75,72 @@ pub enum CommandType {
     VertRel,
     HorizAbs,
     HorizRel,
+    CurveToAbs,
+    CurveToRel,
+    QuadToAbs,
+    QuadToRel,
+    SmoothQuadToAbs,
+    SmoothQuadToRel,
+    ArcAbs,
+    ArcRel,
     ClosePath,
 }
 impl CommandType {
     pub fn is_relative(&self) -> bool {
         match self {
-            CommandType::MoveToRel | CommandType::LineToRel | CommandType::VertRel | CommandType::HorizRel => true,
+            CommandType::MoveToRel | CommandType::LineToRel | CommandType::VertRel | CommandType::HorizRel |
+            CommandType::CurveToRel | CommandType::QuadToRel | CommandType::SmoothQuadToRel |
+            CommandType::ArcRel => true,
             _ => false,
         }
     }
+    /// The same command with its coordinates given absolutely.
+    pub fn absolute(&self) -> CommandType {
+        match self {
+            CommandType::MoveToRel => CommandType::MoveToAbs,
+            CommandType::LineToRel => CommandType::LineToAbs,
+            CommandType::VertRel => CommandType::VertAbs,
+            CommandType::HorizRel => CommandType::HorizAbs,
+            CommandType::CurveToRel => CommandType::CurveToAbs,
+            CommandType::QuadToRel => CommandType::QuadToAbs,
+            CommandType::SmoothQuadToRel => CommandType::SmoothQuadToAbs,
+            CommandType::ArcRel => CommandType::ArcAbs,
+            absolute => *absolute,
+        }
+    }
+    /// The same command with its coordinates given relative to the current point.
+    pub fn relative(&self) -> CommandType {
+        match self {
+            CommandType::MoveToAbs => CommandType::MoveToRel,
+            CommandType::LineToAbs => CommandType::LineToRel,
+            CommandType::VertAbs => CommandType::VertRel,
+            CommandType::HorizAbs => CommandType::HorizRel,
+            CommandType::CurveToAbs => CommandType::CurveToRel,
+            CommandType::QuadToAbs => CommandType::QuadToRel,
+            CommandType::SmoothQuadToAbs => CommandType::SmoothQuadToRel,
+            CommandType::ArcAbs => CommandType::ArcRel,
+            relative => *relative,
+        }
+    }
+    /// How many numbers each repeat of the command takes.
+    pub fn param_count(&self) -> usize {
+        self.layout().0
+    }
+    /// How many numbers each repeat of the command takes, and which of them are `x` and `y` coordinates.
+    /// Every repeat moves the current point to its last coordinates.
+    fn layout(&self) -> (usize, &'static [Coordinate]) {
+        use Coordinate::{X, Y, Other};
+        match self.absolute() {
+            CommandType::MoveToAbs | CommandType::LineToAbs | CommandType::SmoothQuadToAbs => (2, &[X, Y]),
+            CommandType::VertAbs => (1, &[Y]),
+            CommandType::HorizAbs => (1, &[X]),
+            CommandType::CurveToAbs => (6, &[X, Y, X, Y, X, Y]),
+            CommandType::QuadToAbs => (4, &[X, Y, X, Y]),
+            // the radii, rotation and flags come before the end point
+            CommandType::ArcAbs => (7, &[Other, Other, Other, Other, Other, X, Y]),
+            _ => (0, &[]),
+        }
+    }
     pub fn from_opcode(opcode: &str) -> CommandType {
         match opcode {
             "M" => CommandType::MoveToAbs,
@@ -29,6 +151,14 @@ impl CommandType {
             "v" => CommandType::VertRel,
             "H" => CommandType::HorizAbs,
             "h" => CommandType::HorizRel,
+            "C" => CommandType::CurveToAbs,
+            "c" => CommandType::CurveToRel,
+            "Q" => CommandType::QuadToAbs,
+            "q" => CommandType::QuadToRel,
+            "T" => CommandType::SmoothQuadToAbs,
+            "t" => CommandType::SmoothQuadToRel,
+            "A" => CommandType::ArcAbs,
+            "a" => CommandType::ArcRel,
             "Z" => CommandType::ClosePath,
             "z" => CommandType::ClosePath,
             _ => panic!("That's not a valid SVG command type"),
@@ -44,27 +174,104 @@ impl CommandType {
             CommandType::VertRel => 'v',
             CommandType::HorizAbs => 'H',
             CommandType::HorizRel => 'h',
+            CommandType::CurveToAbs => 'C',
+            CommandType::CurveToRel => 'c',
+            CommandType::QuadToAbs => 'Q',
+            CommandType::QuadToRel => 'q',
+            CommandType::SmoothQuadToAbs => 'T',
+            CommandType::SmoothQuadToRel => 't',
+            CommandType::ArcAbs => 'A',
+            CommandType::ArcRel => 'a',
             CommandType::ClosePath => 'z',
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Command {
+impl Display for CommandType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_opcode())
+    }
+}
+
+/// What one of a command's numbers means.
+#[derive(Clone, Copy)]
+enum Coordinate {
+    X,
+    Y,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command<F: Float = f64> {
     pub cmd_type: CommandType,
-    pub params: Vec<f64>,
+    pub params: Vec<F>,
 }
-impl Command {
-    pub fn new(cmd_type: &str, params: Vec<f64>) -> Command {
+impl<F: Float> Command<F> {
+    pub fn new(cmd_type: &str, params: Vec<F>) -> Command<F> {
         let cmd_type = CommandType::from_opcode(cmd_type);
         Command { cmd_type, params }
     }
     pub fn is_relative(&self) -> bool {
         self.cmd_type.is_relative()
     }
-    pub fn shift(&mut self, x: f64, y: f64) {
+    /// Whether the command has the numbers it needs, which is some whole number of repeats of it, or none at all for a `z`.
+    pub fn is_complete(&self) -> bool {
+        match self.cmd_type.param_count() {
+            0 => self.params.is_empty(),
+            stride => !self.params.is_empty() && self.params.chunks_exact(stride).remainder().is_empty(),
+        }
+    }
+    /// The same command with absolute coordinates, when the pen starts at `current_point`, along with where it leaves the pen.
+    /// A `z` leaves the pen where it is, as where its subpath started isn't known here; `absolutise` keeps track of that.
+    pub fn to_absolute(&self, current_point: Vec2<F>) -> (Command<F>, Vec2<F>) {
+        self.convert(self.cmd_type.absolute(), current_point)
+    }
+    /// The same command with coordinates relative to the pen, when it starts at `current_point`, along with where it leaves the pen.
+    /// As with `to_absolute`, a `z` leaves the pen where it is.
+    pub fn to_relative(&self, current_point: Vec2<F>) -> (Command<F>, Vec2<F>) {
+        self.convert(self.cmd_type.relative(), current_point)
+    }
+    /// Rewrites the command as `cmd_type`, which has to be the same command, only absolute or relative.
+    fn convert(&self, cmd_type: CommandType, current_point: Vec2<F>) -> (Command<F>, Vec2<F>) {
+        let (stride, layout) = cmd_type.layout();
+        if stride == 0 {
+            return (Command { cmd_type, params: self.params.clone() }, current_point);
+        }
+        let (from_relative, to_relative) = (self.is_relative(), cmd_type.is_relative());
+        let mut current = current_point;
+        let mut params = Vec::with_capacity(self.params.len());
+        for repeat in self.params.chunks(stride) {
+            // each repeat is measured from where the one before it finished
+            let start = current;
+            for (&value, coordinate) in repeat.iter().zip(layout) {
+                let origin = match coordinate {
+                    Coordinate::X => start.x,
+                    Coordinate::Y => start.y,
+                    Coordinate::Other => {
+                        params.push(value);
+                        continue;
+                    }
+                };
+                let absolute = if from_relative { origin + value } else { value };
+                match coordinate {
+                    Coordinate::X => current.x = absolute,
+                    _ => current.y = absolute,
+                }
+                params.push(match (from_relative, to_relative) {
+                    (false, true) => absolute - origin,
+                    (true, false) => absolute,
+                    _ => value,
+                });
+            }
+        }
+        (Command { cmd_type, params }, current)
+    }
+    /// Moves an absolute command by `(x, y)`. Relative commands move along with the pen, so are left as they are,
+    /// except for the `m` starting a path, which `shift_path` takes care of.
+    pub fn shift(&mut self, x: F, y: F) {
         match self.cmd_type {
-            CommandType::MoveToAbs | CommandType::LineToAbs => {
+            CommandType::MoveToAbs | CommandType::LineToAbs | CommandType::CurveToAbs |
+            CommandType::QuadToAbs | CommandType::SmoothQuadToAbs => {
                 for (px, py) in self.params.iter_mut().tuples::<(_, _)>() {
                     *px += x;
                     *py += y;
@@ -80,7 +287,62 @@ impl Command {
                     *px += x;
                 }
             }
+            CommandType::ArcAbs => {
+                // only the end point of each arc is a position, the rest are its radii, rotation and flags
+                for arc in self.params.chunks_mut(7) {
+                    if let [.., px, py] = arc {
+                        *px += x;
+                        *py += y;
+                    }
+                }
+            }
             _ => (),
         };
     }
 }
+
+/// Writes the opcode followed by the numbers, with a space between each number and none at the end.
+impl<F: Float> Display for Command<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.cmd_type, self.params.iter().join(" "))
+    }
+}
+
+/// Writes `commands` out as a `d` attribute. Each opcode separates its numbers from the command before, so nothing goes between them.
+pub fn commands_to_d<F: Float>(commands: &[Command<F>]) -> String {
+    commands.iter().map(Command::to_string).collect()
+}
+
+/// Moves a whole path by `offset`.
+///
+/// Absolute commands are each shifted, and relative ones are left alone as they move with the pen,
+/// apart from a leading `m`, whose first point is measured from the origin so is really absolute.
+pub fn shift_path<F: Float>(commands: &mut [Command<F>], offset: Vec2<F>) {
+    for (i, command) in commands.iter_mut().enumerate() {
+        match command.cmd_type {
+            CommandType::MoveToRel if i == 0 && command.params.len() >= 2 => {
+                command.params[0] += offset.x;
+                command.params[1] += offset.y;
+            }
+            _ => command.shift(offset.x, offset.y),
+        }
+    }
+}
+
+/// Makes every command of a path absolute, following the pen from the origin and back to the start of each subpath at a `z`.
+pub fn absolutise<F: Float>(commands: impl Iterator<Item = Command<F>>) -> Vec<Command<F>> {
+    let origin = vect![F::zero(), F::zero()];
+    let (mut current, mut start) = (origin, origin);
+    commands
+        .map(|command| {
+            let (absolute, end) = command.to_absolute(current);
+            current = end;
+            match absolute.cmd_type {
+                CommandType::MoveToAbs if absolute.params.len() >= 2 => start = vect![absolute.params[0], absolute.params[1]],
+                CommandType::ClosePath => current = start,
+                _ => (),
+            }
+            absolute
+        })
+        .collect()
+}
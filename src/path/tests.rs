@@ -0,0 +1,140 @@
+#![cfg(test)]
+
+use crate::iter::{FromSvgCommandIter, SvgPointIter, ToDStringIter};
+use crate::path::{absolutise, Command, CommandType, commands_to_d, shift_path};
+use crate::vect;
+use crate::vector::Vec2;
+
+fn commands(d: &str) -> Vec<Command> {
+    FromSvgCommandIter::from_str(d).collect()
+}
+
+fn d_string(commands: Vec<Command>) -> String {
+    ToDStringIter::from_commands(commands.into_iter()).collect()
+}
+
+#[test]
+fn test_to_absolute() {
+    let start = vect![10.0, 20.0];
+    let (line, end) = Command::new("l", vec![1.0, 2.0, 3.0, 4.0]).to_absolute(start);
+    assert_eq!(line, Command::new("L", vec![11.0, 22.0, 14.0, 26.0]));
+    assert_eq!(end, vect![14.0, 26.0]);
+
+    let (vertical, end) = Command::new("v", vec![-5.0]).to_absolute(start);
+    assert_eq!(vertical, Command::new("V", vec![15.0]));
+    assert_eq!(end, vect![10.0, 15.0]);
+
+    // every point of a curve is measured from where it starts, and the next curve from where that one ends
+    let (curve, end) = Command::new("c", vec![1.0, 0.0, 2.0, 1.0, 2.0, 2.0, 0.0, 1.0, 0.0, 2.0, 1.0, 2.0]).to_absolute(start);
+    assert_eq!(curve, Command::new("C", vec![11.0, 20.0, 12.0, 21.0, 12.0, 22.0, 12.0, 23.0, 12.0, 24.0, 13.0, 24.0]));
+    assert_eq!(end, vect![13.0, 24.0]);
+
+    // only the end of an arc is a position
+    let (arc, end) = Command::new("a", vec![5.0, 5.0, 30.0, 0.0, 1.0, 10.0, 0.0]).to_absolute(start);
+    assert_eq!(arc, Command::new("A", vec![5.0, 5.0, 30.0, 0.0, 1.0, 20.0, 20.0]));
+    assert_eq!(end, vect![20.0, 20.0]);
+
+    // absolute commands come back as they were, and a close doesn't know where to go back to
+    let (absolute, end) = Command::new("M", vec![1.0, 2.0]).to_absolute(start);
+    assert_eq!(absolute, Command::new("M", vec![1.0, 2.0]));
+    assert_eq!(end, vect![1.0, 2.0]);
+    assert_eq!(Command::<f64>::new("z", vec![]).to_absolute(start), (Command::new("z", vec![]), start));
+}
+
+#[test]
+fn test_to_relative() {
+    let start = vect![10.0, 20.0];
+    let (line, end) = Command::new("L", vec![11.0, 22.0, 14.0, 26.0]).to_relative(start);
+    assert_eq!(line, Command::new("l", vec![1.0, 2.0, 3.0, 4.0]));
+    assert_eq!(end, vect![14.0, 26.0]);
+
+    let (horizontal, end) = Command::new("H", vec![4.0, 8.0]).to_relative(start);
+    assert_eq!(horizontal, Command::new("h", vec![-6.0, 4.0]));
+    assert_eq!(end, vect![8.0, 20.0]);
+
+    let (quad, end) = Command::new("Q", vec![12.0, 20.0, 12.0, 22.0]).to_relative(start);
+    assert_eq!(quad, Command::new("q", vec![2.0, 0.0, 2.0, 2.0]));
+    assert_eq!(end, vect![12.0, 22.0]);
+
+    let (relative, _) = Command::new("t", vec![1.0, 1.0]).to_relative(start);
+    assert_eq!(relative.cmd_type, CommandType::SmoothQuadToRel);
+    assert_eq!(relative.params, vec![1.0, 1.0]);
+}
+
+#[test]
+fn test_absolutise_round_trip() {
+    let d = "m 46 33 19 5 v -19 l -14 -15 -13 14 z";
+    let absolute = absolutise(commands(d).into_iter());
+    assert!(absolute.iter().all(|command| !command.is_relative()));
+    assert_eq!(d_string(absolute.clone()), "M46 33 65 38 V19 L51 4 38 18 z");
+
+    // and back again, following the pen the same way
+    let mut current = vect![0.0, 0.0];
+    let relative: Vec<Command> = absolute.iter()
+        .map(|command| {
+            let (relative, end) = command.to_relative(current);
+            current = end;
+            relative
+        })
+        .collect();
+    assert_eq!(relative, commands(d));
+}
+
+#[test]
+fn test_absolutise_subpaths() {
+    // after a close, the pen is back where the subpath started, so the next move is measured from there
+    let d = "m 46 33 19 5 v -19 l -14 -15 -13 14 z m 10 0 h 5 v 5 z l 1 1";
+    let absolute = absolutise(commands(d).into_iter());
+    assert_eq!(d_string(absolute), "M46 33 65 38 V19 L51 4 38 18 zM56 33 H61 V38 zL57 34 ");
+}
+
+#[test]
+fn test_display() {
+    assert_eq!(CommandType::HorizRel.to_string(), "h");
+    assert_eq!(Command::new("M", vec![1.5, -2.0, 3.0, 4.0]).to_string(), "M1.5 -2 3 4");
+    assert_eq!(Command::<f64>::new("z", vec![]).to_string(), "z");
+    assert_eq!(commands_to_d(&commands("m 46 33 19 5 v -19 z")), "m46 33 19 5v-19z");
+    assert_eq!(commands_to_d::<f64>(&[]), "");
+}
+
+#[test]
+fn test_display_round_trip() {
+    let paths = [
+        "m 46 33 19 5 v -19 l -14 -15 -13 14 z M 11 59 32 45 h -9 L 16 30 v 4 z",
+        "M0,0 C 1,0 2,1 2,2 3,4 5,5 5,5 Q 6 6 7 5 T 9 5 A 2 3 30 0 1 12 8 a 1 1 0 1 0 -2 -2 Z",
+        "M-0.5-1.25l.5.5 1e1 -2E-1h-3.75z",
+    ];
+    for d in paths {
+        let written = commands_to_d(&commands(d));
+        assert_eq!(commands(&written), commands(d), "{} was written as {}", d, written);
+        let points: Vec<_> = SvgPointIter::from_str(d).collect();
+        assert_eq!(SvgPointIter::from_str(&written).collect::<Vec<_>>(), points);
+    }
+}
+
+#[test]
+fn test_shift_path() {
+    let offset = vect![10.0, 10.0];
+    let paths = [
+        "m 46 33 19 5 v -19 l -14 -15 -13 14 z",
+        "m 46 33 19 5 v -19 l -14 -15 -13 14 z M 11 59 32 45 h -9 L 16 30 v 4 z m 5 5 l 1 1 z",
+        "M 0 0 a 1 1 0 0 1 2 0 A 1 1 0 0 1 4 0 z",
+    ];
+    for d in paths {
+        let mut shifted = commands(d);
+        shift_path(&mut shifted, offset);
+        let expected: Vec<(Vec2<f64>, bool)> = SvgPointIter::from_str(d).map(|(p, ret)| (p + offset, ret)).collect();
+        let written = commands_to_d(&shifted);
+        let points: Vec<(Vec2<f64>, bool)> = SvgPointIter::from_str(&written).collect();
+        assert_eq!(points.len(), expected.len(), "{} was shifted to {}", d, written);
+        // the curves are worked out from the moved points, so can come out a hair different
+        for ((point, ret), (expected_point, expected_ret)) in points.into_iter().zip(expected) {
+            assert!((point - expected_point).magnitude() < 1e-9 && ret == expected_ret, "{} was shifted to {}", d, written);
+        }
+    }
+
+    // only the first pair of the leading move is absolute, the rest are lines from it
+    let mut path = commands("m 1 2 3 4 z m 1 1");
+    shift_path(&mut path, offset);
+    assert_eq!(commands_to_d(&path), "m11 12 3 4zm1 1");
+}
@@ -0,0 +1,727 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use itertools::{iproduct, Itertools};
+use quick_xml::events::Event;
+use quick_xml::writer::Writer;
+
+use crate::colour::{self, Checker, ColourVariation, Effects, FaceInfo, Light, Lighting, RenderMode, Shader};
+use crate::dump::SceneDump;
+use crate::error::{IsometricError, RunError};
+use crate::grid::Grid;
+use crate::iter::{object_svg_iter, Canvas, DocumentOptions, Length};
+use crate::path::PathOptions;
+use crate::shadow::Shadow;
+use crate::shapes::{self, Bounds, Polygonal, PlacedShape, ShapeId, ShapeRegistry, VertexSnap};
+use crate::stats::{Phase, RenderStats};
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+use crate::view::View;
+use crate::{combine_shapes, get_objects, merge_regions, place_footprints, reference_cube, shadow, spread_tile_colours, validation};
+use crate::{CubeAxes, Groups, Layout, ReferenceCube, Report, LIGHT_VECTOR, SCENE_COLOUR};
+
+pub(crate) mod tests;
+
+/// A grid of shapes and everything about how it's drawn, ready to render.
+///
+/// Scenes can be put together without any config at all:
+///
+/// ```
+/// use isometric::scene::Scene;
+/// use isometric::vect;
+/// use isometric::vector::Vec3;
+/// use quick_xml::reader::Reader;
+/// use quick_xml::writer::Writer;
+///
+/// let shapes = isometric::parser::parse_shapes(&mut Reader::from_file("components.svg").unwrap()).unwrap();
+/// let mut scene = Scene::new(shapes);
+/// scene.set_grid_size(vect![3, 1, 1]);
+/// for x in 0..3 {
+///     scene.place(vect![x, 0, 0], 255u8).unwrap();
+/// }
+/// scene.light(vect![0.2, 1.0, 0.4]).unwrap();
+/// scene.colour(vect![0.2, 0.6, 0.9]);
+///
+/// let mut output = vec![];
+/// scene.render(Writer::new(&mut output)).unwrap();
+/// assert!(String::from_utf8(output).unwrap().contains("<path"));
+/// ```
+pub struct Scene {
+    shapes: ShapeRegistry,
+    /// The shape in each cell, in world coordinates.
+    grid: Grid,
+    options: RenderOptions,
+}
+
+/// Everything about how a grid is drawn, other than the shapes in it.
+/// The defaults are the same as for a config which only gives a grid.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// The full cube the direction of each axis is measured from.
+    pub reference_shape: ShapeId,
+    /// Cells drawn as one shape between them, by the name of their group.
+    pub connections: HashMap<String, Vec<Vec3<usize>>>,
+    /// Whether groups sharing a member are merged, rather than an error.
+    pub merge_equalities: bool,
+    /// Whether neighbouring cells with the same shape are drawn as one.
+    pub auto_merge: bool,
+    pub view: View,
+    /// How many cells along each axis shapes covering more than one take up.
+    pub footprints: HashMap<ShapeId, Vec3<usize>>,
+    /// Which shapes hide what's behind them, and how much.
+    pub materials: Materials,
+    /// What's done with hidden faces, where the tolerance is relative to the size of the reference cube.
+    pub occlusion: Occlusion,
+    /// The empty space left around the board on every side.
+    pub margin: f64,
+    /// Whether the image is cut down to the box around everything drawn, with `margin` left around that,
+    /// rather than fitting the whole grid.
+    pub crop: bool,
+    /// How wide and high the image is shown, either of which is kept in proportion to the other if left out.
+    pub output_size: (Option<Length>, Option<Length>),
+    /// Whether faces pointing the same way are fused with their neighbours.
+    pub merge_faces: bool,
+    /// The lights, shining from directions in the world which don't have to be normalised.
+    pub lighting: Lighting,
+    /// The colour of every shape not given one in `shape_colours` or `tile_colours`.
+    pub colour: Vec3<f64>,
+    /// The colour of every shape with the id, such as the entries of the `palette`.
+    pub shape_colours: HashMap<ShapeId, Vec3<f64>>,
+    /// The colour of the shape in each cell, which takes precedence over `shape_colours`.
+    pub tile_colours: HashMap<Vec3<usize>, Vec3<f64>>,
+    pub variation: Option<ColourVariation>,
+    /// Every other tile tinted like a chessboard, before any `variation`.
+    pub checker: Option<Checker>,
+    /// Whether points are rounded to whole numbers, dropping any shapes which round away to nothing.
+    pub integer_coordinates: bool,
+    /// Everything drawn over the colours, where a shadow only gives its colour and opacity, as its outline depends on the scene.
+    pub effects: Effects,
+    /// Whether faces are filled in, or only their outlines drawn.
+    pub render_mode: RenderMode,
+    pub path_options: PathOptions,
+    pub document: DocumentOptions,
+    /// Where to write what the scene looks like just before it's drawn as JSON, if anywhere.
+    pub dump_scene: Option<PathBuf>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            reference_shape: ShapeId::from(255u8),
+            connections: HashMap::new(),
+            merge_equalities: false,
+            auto_merge: false,
+            view: View::default(),
+            footprints: HashMap::new(),
+            materials: Materials { opaque: HashSet::from([ShapeId::from(255u8)]), opacity: HashMap::new() },
+            occlusion: Occlusion::Delete { epsilon: shapes::DEFAULT_EPSILON },
+            margin: 0.0,
+            crop: false,
+            output_size: (None, None),
+            merge_faces: false,
+            lighting: Lighting::new(vec![Light::new(LIGHT_VECTOR)]),
+            colour: SCENE_COLOUR,
+            shape_colours: HashMap::new(),
+            tile_colours: HashMap::new(),
+            variation: None,
+            checker: None,
+            integer_coordinates: false,
+            effects: Effects::default(),
+            render_mode: RenderMode::Solid,
+            path_options: PathOptions::default(),
+            document: DocumentOptions::default(),
+            dump_scene: None,
+        }
+    }
+}
+
+/// How much each shape hides of what's behind it.
+#[derive(Debug, Clone)]
+pub struct Materials {
+    /// Shapes which fill their whole cell, so hide everything behind them.
+    pub opaque: HashSet<ShapeId>,
+    /// How opaque each shape which can be seen through is, where anything missing is entirely opaque.
+    pub opacity: HashMap<ShapeId, f64>,
+}
+
+impl Materials {
+    pub(crate) fn opacity(&self, id: &ShapeId) -> f64 {
+        self.opacity.get(id).copied().unwrap_or(1.0)
+    }
+    /// Whether whatever's behind the shape shows through it, so it can't be allowed to hide anything.
+    pub(crate) fn translucent(&self, id: &ShapeId) -> bool {
+        self.opacity(id) < 1.0
+    }
+    /// Whether the shape hides everything behind it.
+    pub(crate) fn covers(&self, id: &ShapeId) -> bool {
+        self.opaque.contains(id) && !self.translucent(id)
+    }
+}
+
+/// What to do with the parts of shapes hidden behind the shapes drawn after them.
+/// Points within `epsilon` of a shape's edge count as being on it when working out what it hides.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Occlusion {
+    /// Draw everything, relying on the later shapes to paint over the rest.
+    Keep,
+    /// Delete primitives which are hidden completely, drawing the rest whole.
+    Delete { epsilon: f64 },
+    /// Delete hidden primitives, and cut the hidden parts away from any which are only partly covered.
+    Clip { epsilon: f64 },
+}
+
+impl Occlusion {
+    /// The same, with the tolerance multiplied by `factor`.
+    pub(crate) fn scaled(self, factor: f64) -> Occlusion {
+        match self {
+            Occlusion::Keep => Occlusion::Keep,
+            Occlusion::Delete { epsilon } => Occlusion::Delete { epsilon: epsilon * factor },
+            Occlusion::Clip { epsilon } => Occlusion::Clip { epsilon: epsilon * factor },
+        }
+    }
+    /// The tolerance to check for hidden shapes with, unless they're all being kept.
+    pub(crate) fn epsilon(self) -> Option<f64> {
+        match self {
+            Occlusion::Keep => None,
+            Occlusion::Delete { epsilon } | Occlusion::Clip { epsilon } => Some(epsilon),
+        }
+    }
+}
+
+impl Scene {
+    /// An empty scene drawn with `shapes`, lit and coloured the same as a config which only gives a grid.
+    /// The grid starts out with no cells at all, so needs giving a size before anything is placed.
+    pub fn new(shapes: ShapeRegistry) -> Scene {
+        Scene { shapes, grid: Grid::new(vect![0, 0, 0]), options: RenderOptions::default() }
+    }
+    /// A scene put together all in one go, rather than a cell at a time.
+    pub fn builder() -> SceneBuilder {
+        SceneBuilder { shapes: ShapeRegistry::new(), grid: Grid::new(vect![0, 0, 0]), options: RenderOptions::default(), light: None, colour: None }
+    }
+    /// Everything else about how the scene is drawn.
+    pub fn options_mut(&mut self) -> &mut RenderOptions {
+        &mut self.options
+    }
+    /// Resizes the grid to `size`, keeping whatever's been placed or connected that still fits.
+    pub fn set_grid_size(&mut self, size: Vec3<usize>) {
+        let mut grid = Grid::new(size);
+        for (cell, id) in self.grid.iter_occupied() {
+            if grid.contains(cell) {
+                grid.set(cell, id.clone());
+            }
+        }
+        for members in self.options.connections.values_mut() {
+            members.retain(|m| grid.contains(*m));
+        }
+        self.grid = grid;
+    }
+    /// Puts the shape labelled `id` at `cell`, replacing anything already there.
+    pub fn place(&mut self, cell: Vec3<usize>, id: impl Into<ShapeId>) -> Result<(), RunError> {
+        let id = id.into();
+        if !self.grid.contains(cell) {
+            return Err(RunError::InvalidPlacement { cell, reason: format!("{} is outside grid_size {}", cell, self.grid.size()) });
+        }
+        if !self.shapes.contains_key(&id) {
+            return Err(RunError::InvalidPlacement { cell, reason: format!("no shape has the label {}", id) });
+        }
+        self.grid.set(cell, id);
+        Ok(())
+    }
+    /// Draws the shape in each of `members` as one, as an `equalities` group does.
+    /// The group is named after how many were connected before it.
+    pub fn connect(&mut self, members: &[Vec3<usize>]) -> Result<(), RunError> {
+        let group = self.options.connections.len().to_string();
+        check_members(&self.grid, &group, members)?;
+        self.options.connections.insert(group, members.to_vec());
+        Ok(())
+    }
+    /// Lights the scene with a single white light shining from `direction`, which can't be zero.
+    pub fn light(&mut self, direction: Vec3<f64>) -> Result<(), RunError> {
+        if direction.square_magnitude() == 0.0 {
+            return Err(RunError::InvalidKey { key: String::from("light"), reason: String::from("the light has to come from some direction, but was zero") });
+        }
+        self.options.lighting.lights = vec![Light::new(direction)];
+        Ok(())
+    }
+    /// Draws every shape in `colour`.
+    pub fn colour(&mut self, colour: Vec3<f64>) {
+        self.options.colour = colour;
+    }
+    /// Writes what the scene looks like just before it's drawn to `path` as JSON each time it's rendered, which needs the `serde` feature.
+    pub fn dump_scene(&mut self, path: impl Into<PathBuf>) {
+        self.options.dump_scene = Some(path.into());
+    }
+    /// Renders the scene into `writer`, colouring faces with the default shader.
+    pub fn render<W: Write>(&self, writer: Writer<W>) -> Result<RenderStats, IsometricError> {
+        Ok(render_grid(&self.grid, &self.shapes, &self.options, writer)?)
+    }
+    /// Renders the scene into `writer`, with `shader` giving each face its style.
+    pub fn render_with<W: Write>(&self, writer: Writer<W>, shader: &Shader) -> Result<RenderStats, IsometricError> {
+        Ok(render_grid_with(&self.grid, &self.shapes, &self.options, writer, shader)?)
+    }
+}
+
+/// A [`Scene`] built from a grid filled in beforehand, without any config.
+///
+/// ```
+/// use isometric::grid::Grid;
+/// use isometric::scene::Scene;
+/// use isometric::shapes::ShapeId;
+/// use isometric::vect;
+/// use isometric::vector::Vec3;
+/// use quick_xml::reader::Reader;
+/// use quick_xml::writer::Writer;
+///
+/// let mut grid = Grid::new(vect![2, 1, 1]);
+/// grid.set(vect![0, 0, 0], ShapeId::from(255u8));
+/// let scene = Scene::builder()
+///     .components(isometric::parser::parse_shapes(&mut Reader::from_file("components.svg").unwrap()).unwrap())
+///     .grid(grid)
+///     .light(vect![0.2, 1.0, 0.4])
+///     .colour(vect![0.2, 0.6, 0.9])
+///     .build()
+///     .unwrap();
+///
+/// let mut output = vec![];
+/// scene.render(Writer::new(&mut output)).unwrap();
+/// ```
+pub struct SceneBuilder {
+    shapes: ShapeRegistry,
+    grid: Grid,
+    options: RenderOptions,
+    light: Option<Vec3<f64>>,
+    colour: Option<Vec3<f64>>,
+}
+
+impl SceneBuilder {
+    /// The shapes drawn in the scene, which starts out with none.
+    pub fn components(mut self, shapes: ShapeRegistry) -> Self {
+        self.shapes = shapes;
+        self
+    }
+    /// The shape in each cell, in world coordinates.
+    pub fn grid(mut self, grid: Grid) -> Self {
+        self.grid = grid;
+        self
+    }
+    /// Everything about how the scene is drawn, other than any light or colour given to the builder, which win whichever order they're given in.
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+    /// Lights the scene with a single white light shining from `direction`, which can't be zero.
+    pub fn light(mut self, direction: Vec3<f64>) -> Self {
+        self.light = Some(direction);
+        self
+    }
+    /// Draws every shape in `colour`.
+    pub fn colour(mut self, colour: Vec3<f64>) -> Self {
+        self.colour = Some(colour);
+        self
+    }
+    /// The scene, put together with the same setters as one built a cell at a time.
+    /// Every cell of the grid has to have one of the shapes, every connected cell has to be inside it, and no light can be zero.
+    pub fn build(self) -> Result<Scene, RunError> {
+        let mut scene = Scene::new(self.shapes);
+        *scene.options_mut() = self.options;
+        if let Some(direction) = self.light {
+            scene.light(direction)?;
+        }
+        if let Some(colour) = self.colour {
+            scene.colour(colour);
+        }
+        if let Some((index, _)) = scene.options.lighting.lights.iter().find_position(|light| light.direction.square_magnitude() == 0.0) {
+            let key = format!("lighting.lights[{}].direction", index);
+            return Err(RunError::InvalidKey { key, reason: String::from("the light has to come from some direction, but was zero") });
+        }
+        // resizing drops any members outside the grid, so they're checked before they'd go unnoticed
+        for (group, members) in scene.options.connections.iter().sorted_by_key(|(group, _)| *group) {
+            check_members(&self.grid, group, members)?;
+        }
+        scene.set_grid_size(self.grid.size());
+        for (cell, id) in self.grid.iter_occupied() {
+            scene.place(cell, id.clone())?;
+        }
+        Ok(scene)
+    }
+}
+
+/// Makes sure every one of `members` of `group` is inside `grid`.
+fn check_members(grid: &Grid, group: &str, members: &[Vec3<usize>]) -> Result<(), RunError> {
+    match members.iter().find_position(|m| !grid.contains(**m)) {
+        Some((index, member)) => Err(RunError::InvalidMember {
+            group: group.to_string(), index, reason: format!("{} is outside grid_size {}", member, grid.size()),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// What changes in a scene from one frame of an animation to the next.
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    /// The shape put in each cell, replacing whatever was there. `ShapeId::EMPTY` empties the cell.
+    pub tiles: Vec<(Vec3<usize>, ShapeId)>,
+    /// The lights from this frame on, if they change.
+    pub lights: Option<Vec<Light>>,
+    /// The colour of every shape not given one of its own from this frame on, if it changes.
+    pub colour: Option<Vec3<f64>>,
+}
+
+/// A scene drawn frame after frame, each one made by changing the frame before it, such as a building going up a few blocks at a time.
+///
+/// The axes are worked out from the reference cube once for the whole sequence.
+/// Each frame is drawn from scratch, but the cells it changed are kept track of,
+/// so the shapes placed everywhere else could one day be carried over from the frame before.
+pub struct FrameSequence<'a> {
+    shapes: &'a ShapeRegistry,
+    reference: ReferenceCube,
+    grid: Grid,
+    options: RenderOptions,
+    /// The cells whose shape the last frame changed.
+    changed: HashSet<Vec3<usize>>,
+}
+
+impl<'a> FrameSequence<'a> {
+    /// Starts with `grid` as the first frame, drawn with `shapes` as `options` describes.
+    pub fn new(shapes: &'a ShapeRegistry, grid: Grid, options: RenderOptions) -> Result<FrameSequence<'a>, RunError> {
+        let reference = reference_cube(shapes, &options.reference_shape)?;
+        let changed = grid.iter_occupied().map(|(cell, _)| cell).collect();
+        Ok(FrameSequence { shapes, reference, grid, options, changed })
+    }
+    /// Moves on to the next frame by making the changes in `frame`.
+    /// A tile outside the grid, or with no such shape, is an error, which leaves the frame as it was.
+    pub fn advance(&mut self, frame: &Frame) -> Result<(), RunError> {
+        for (cell, id) in &frame.tiles {
+            if !self.grid.contains(*cell) {
+                return Err(RunError::InvalidPlacement { cell: *cell, reason: format!("{} is outside grid_size {}", cell, self.grid.size()) });
+            }
+            if *id != ShapeId::EMPTY && !self.shapes.contains_key(id) {
+                return Err(RunError::InvalidPlacement { cell: *cell, reason: format!("no shape has the label {}", id) });
+            }
+        }
+        self.changed.clear();
+        for (cell, id) in &frame.tiles {
+            if self.grid.get(*cell) != id {
+                self.grid.set(*cell, id.clone());
+                self.changed.insert(*cell);
+            }
+        }
+        if let Some(lights) = &frame.lights {
+            self.options.lighting.lights = lights.clone();
+        }
+        if let Some(colour) = frame.colour {
+            self.options.colour = colour;
+        }
+        Ok(())
+    }
+    /// The shape in each cell as of the current frame.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+    /// How the current frame is drawn.
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+    /// The cells whose shape the last frame changed, which for the first frame is every occupied cell.
+    pub fn changed(&self) -> &HashSet<Vec3<usize>> {
+        &self.changed
+    }
+    /// Renders the current frame into `writer`, with `shader` giving each face its style, telling `progress` how far through it is as it goes.
+    pub fn render_reporting<W: Write>(&self, writer: Writer<W>, shader: &Shader, progress: &mut dyn FnMut(Phase, usize, usize)) -> Result<RenderStats, IsometricError> {
+        Ok(render_with_reference(&self.grid, self.shapes, &self.reference, &self.options, writer, shader, progress)?)
+    }
+}
+
+/// One of the objects a scene is made of, as `split_objects` finds them.
+#[derive(Debug, Clone)]
+pub struct SceneObject {
+    /// The name of the group it was drawn from, or `tile-x-y-z` after its cell for a tile on its own.
+    pub name: String,
+    /// Only the object's own tiles, in a grid the same size as the scene's.
+    pub grid: Grid,
+    pub options: RenderOptions,
+}
+
+/// Splits the scene in `grid` into the objects it's made of: each `equalities` group, each region `auto_merge` finds,
+/// and with `loose_tiles` set, every other tile on its own.
+/// Each is drawn without the rest of the scene, so nothing else hides any of it, and cropped down to just what's drawn.
+/// Groups without any tiles are left out, as is `dump_scene`, since every object would write over the last.
+pub fn split_objects(grid: &Grid, options: &RenderOptions, loose_tiles: bool) -> Result<Vec<SceneObject>, RunError> {
+    let connections = validation::validate_connections(grid, options.connections.clone(), options.merge_equalities)
+        .map_err(RunError::InvalidEqualities)?;
+    let regions = if options.auto_merge {
+        validation::contiguous_regions(grid, &connections)
+    }
+    else {
+        vec![]
+    };
+    let grouped = connections.iter()
+        .chain(regions.iter())
+        .flat_map(|(_, members)| members.iter().copied())
+        .collect::<HashSet<_>>();
+
+    let object = |name: String, members: Vec<Vec3<usize>>, connected: bool| {
+        let members = members.into_iter().filter(|m| grid.get(*m) != &ShapeId::EMPTY).collect_vec();
+        if members.is_empty() {
+            return None;
+        }
+        let mut object_grid = Grid::sparse(grid.size());
+        for member in &members {
+            object_grid.set(*member, grid.get(*member).clone());
+        }
+        // a region is only ever tiles next to each other, so `auto_merge` finds it again by itself
+        let connections = if connected { HashMap::from([(name.clone(), members)]) } else { HashMap::new() };
+        let options = RenderOptions { connections, merge_equalities: false, crop: true, dump_scene: None, ..options.clone() };
+        Some(SceneObject { name, grid: object_grid, options })
+    };
+    let loose = grid.iter_occupied()
+        .map(|(cell, _)| cell)
+        .filter(|cell| loose_tiles && !grouped.contains(cell))
+        .sorted_by_key(|cell| (cell.x, cell.y, cell.z))
+        .map(|cell| (format!("tile-{}-{}-{}", cell.x, cell.y, cell.z), vec![cell], false))
+        .collect_vec();
+    let objects = connections.into_iter()
+        .map(|(name, members)| (name, members, true))
+        .chain(regions.into_iter().map(|(name, members)| (name, members, false)))
+        .chain(loose)
+        .filter_map(|(name, members, connected)| object(name, members, connected))
+        .collect();
+    Ok(objects)
+}
+
+/// Renders the shapes placed in `grid` into `writer`, as `options` describes, colouring faces with the default shader.
+/// Gives back how much work it took.
+pub fn render_grid<W: Write>(grid: &Grid, shapes: &ShapeRegistry, options: &RenderOptions, writer: Writer<W>) -> Result<RenderStats, RunError> {
+    render_grid_with(grid, shapes, options, writer, &colour::default_shader)
+}
+
+/// Like `render_grid`, with `shader` giving each face its style.
+pub fn render_grid_with<W: Write>(grid: &Grid, shapes: &ShapeRegistry, options: &RenderOptions, writer: Writer<W>, shader: &Shader) -> Result<RenderStats, RunError> {
+    render_grid_reporting(grid, shapes, options, writer, shader, &mut |_, _, _| ())
+}
+
+/// Like `render_grid_with`, telling `progress` how far through it is as it goes.
+pub fn render_grid_reporting<W: Write>(grid: &Grid, shapes: &ShapeRegistry, options: &RenderOptions, writer: Writer<W>, shader: &Shader, progress: &mut dyn FnMut(Phase, usize, usize)) -> Result<RenderStats, RunError> {
+    let reference = reference_cube(shapes, &options.reference_shape)?;
+    render_with_reference(grid, shapes, &reference, options, writer, shader, progress)
+}
+
+/// Like `render_grid_reporting`, with the axes already worked out from the reference cube.
+fn render_with_reference<W: Write>(grid: &Grid, shapes: &ShapeRegistry, reference: &ReferenceCube, options: &RenderOptions, mut writer: Writer<W>, shader: &Shader, progress: &mut dyn FnMut(Phase, usize, usize)) -> Result<RenderStats, RunError> {
+    let (cube, axes) = reference.clone();
+
+    let grid_size = grid.size();
+    let connections = validation::validate_connections(grid, options.connections.clone(), options.merge_equalities)
+        .map_err(RunError::InvalidEqualities)?;
+    let warnings = validation::unoccupied_members(grid, &connections);
+    let regions = if options.auto_merge {
+        validation::contiguous_regions(grid, &connections)
+    }
+    else {
+        vec![]
+    };
+
+    // everything up to here works in world coordinates so diagnostics match the config,
+    // but placing shapes needs the viewer to be looking from the corner the components were drawn from
+    let view = options.view;
+    let mut view_grid = view.rotate_grid(grid);
+    let footprints = place_footprints(&options.footprints, grid, view)?;
+    // a shape covering several cells is placed from the corner of its box nearest the origin of the view,
+    // which some views turn away from the corner it was given at in the world
+    for footprint in &footprints {
+        let given = view.to_view(footprint.anchor, grid_size);
+        let id = view_grid.get(given).clone();
+        view_grid.set(given, ShapeId::EMPTY);
+        view_grid.set(footprint.view_corner, id);
+    }
+    let view_cell = |cell: Vec3<usize>| match footprints.iter().find(|f| f.anchor == cell) {
+        Some(footprint) => footprint.view_corner,
+        None => view.to_view(cell, grid_size),
+    };
+    let view_connections = connections.iter()
+        .map(|(_, members)| members.iter().map(|m| view_cell(*m)).collect_vec())
+        .collect_vec();
+
+    let materials = &options.materials;
+
+    // the tolerance is given relative to the cube, so it works the same however big the components are drawn
+    let occlusion = options.occlusion.scaled(f64::max(cube.width(), cube.height()));
+
+    // shadows come from everything in the grid, including the cells covered by shapes bigger than one
+    let view_size = view_grid.size();
+    let shadow_casters = if options.effects.shadow.is_some() {
+        view_grid.iter_occupied()
+            .map(|(cell, _)| cell)
+            .chain(footprints.iter().flat_map(|f| iproduct!(0..f.size.x, 0..f.size.y, 0..f.size.z).map(|(x, y, z)| f.view_corner + (x, y, z))))
+            .collect_vec()
+    }
+    else {
+        vec![]
+    };
+
+    let layout = Layout { axes, cube_size: vect![cube.width(), cube.height()], cube_centre: cube.centre(), margin: options.margin };
+
+    // shapes covering several cells are known by their corner nearest the origin in the world, not the view
+    let world_cell = |view_cell: Vec3<usize>| match footprints.iter().find(|f| f.view_corner == view_cell) {
+        Some(footprint) => footprint.anchor,
+        None => view.to_world(view_cell, grid_size),
+    };
+
+    let mut stats = RenderStats { warnings, ..RenderStats::default() };
+    let mut dump = options.dump_scene.as_ref().map(|_| SceneDump::new(shapes));
+    let report = Report { instances: dump.as_mut().map(|d| &mut d.instances), stats: &mut stats, progress };
+    let objects = get_objects(view_grid, shapes.clone(), &layout, &Groups { connections: &view_connections, footprints: &footprints }, materials, occlusion, report);
+    if let (Some(path), Some(mut dump)) = (&options.dump_scene, dump) {
+        for instance in &mut dump.instances {
+            instance.cell = world_cell(instance.cell);
+            instance.culled_by.iter_mut().for_each(|cell| *cell = world_cell(*cell));
+        }
+        dump.instances.sort_by_key(|instance| (instance.cell.x, instance.cell.y, instance.cell.z));
+        dump.write(path)?;
+    }
+    let start = Instant::now();
+    progress(Phase::Merging, 0, objects.len());
+    let view_regions = regions.iter()
+        .map(|(_, members)| members.iter().map(|m| view_cell(*m)).collect_vec())
+        .collect_vec();
+    let objects = merge_regions(objects, &view_regions);
+
+    let objects = if options.merge_faces {
+        // a face seen through another would show where they were joined, so translucent faces are left alone
+        let translucent = objects.iter()
+            .map(|(_, view_cell)| *view_cell)
+            .filter(|view_cell| materials.translucent(grid.get(world_cell(*view_cell))))
+            .collect();
+        combine_shapes(objects, &translucent)
+    }
+    else {
+        objects
+    };
+    progress(Phase::Merging, objects.len(), objects.len());
+    stats.timings.push((Phase::Merging, start.elapsed()));
+
+    // the lights are fixed in the world, so move with the view just like the grid does
+    let lighting = Lighting {
+        lights: options.lighting.lights().map(|light| Light { direction: view.rotate(light.direction).normalise(), ..light }).collect(),
+        ..options.lighting.clone()
+    };
+    let tile_colours = spread_tile_colours(options.tile_colours.clone(), &connections)?;
+    // the depth of the cell nearest the viewer, which every distance back is counted from
+    let front = (view_size.x + view_size.y + view_size.z).saturating_sub(3);
+
+    let objects = objects.into_iter()
+        .map(|(shape, view_cell)| {
+            let cell = world_cell(view_cell);
+            let colour = tile_colours.get(&cell)
+                .or_else(|| options.shape_colours.get(grid.get(cell)))
+                .cloned()
+                .unwrap_or(options.colour);
+            let colour = match &options.checker {
+                Some(checker) => checker.apply(colour, cell),
+                None => colour,
+            };
+            let colour = match &options.variation {
+                Some(variation) => variation.apply(colour, cell),
+                None => colour,
+            };
+            let depth = view_cell.x + view_cell.y + view_cell.z;
+            let distance = front.saturating_sub(depth);
+            let group = connections.iter()
+                .chain(regions.iter())
+                .find(|(_, members)| members.contains(&cell))
+                .map(|(name, _)| name.clone());
+            let opacity = materials.opacity(grid.get(cell));
+            PlacedShape { shape, cell, depth, distance, colour, opacity, group }
+        })
+        .collect_vec();
+
+    let mut objects = if options.integer_coordinates {
+        // every shape is rounded from the same map, so faces meeting at a vertex still meet once it's rounded
+        let snap = VertexSnap::new(objects.iter().flat_map(|object| object.shape.points_iter()));
+        objects.into_iter()
+            .filter_map(|object| Some(PlacedShape { shape: object.shape.round_points(&snap)?, ..object }))
+            .collect_vec()
+    }
+    else {
+        objects
+    };
+
+    let mut effects = options.effects.clone();
+    // a wireframe has nothing filled in, which would leave a shadow as its only solid part
+    let wireframe_shader;
+    let shader: &Shader = match options.render_mode {
+        RenderMode::Solid => shader,
+        RenderMode::Wireframe { colour, width } => {
+            effects.shadow = None;
+            wireframe_shader = move |face: &FaceInfo| face.component.generate_wireframe_css(colour, width);
+            &wireframe_shader
+        }
+    };
+    if let Some(shadow) = &mut effects.shadow {
+        // only the brightest light is strong enough to cast a shadow worth drawing
+        let light = lighting.lights().max_by(|a, b| a.intensity.total_cmp(&b.intensity)).unwrap();
+        let cells = shadow::shadowed_cells(shadow_casters, view_size, light.direction);
+        let CubeAxes { x: x_vec, y: y_vec, z: z_vec } = axes;
+        let origin = layout.origin(view_size);
+        // cells are drawn around their centres, so the ground under the corner of the grid is half a cell back along each axis
+        let project = |x: f64, z: f64| origin + x_vec * (x - 0.5) - y_vec * 0.5 + z_vec * (z - 0.5);
+        shadow.primitives = shadow::outline_cells(&cells, project);
+    }
+    let size = if options.crop {
+        crop(&mut objects, effects.shadow.as_mut(), options.margin)
+    }
+    else {
+        layout.image_size(view_size)
+    };
+    let canvas = Canvas { size, display: display_size(&options.output_size, size) };
+
+    // every face makes at most one path, so that's as many as there could be
+    let faces = objects.iter().map(|object| object.shape.component_iter().count()).sum();
+    let start = Instant::now();
+    progress(Phase::Writing, 0, faces);
+    for event in object_svg_iter(&objects, &canvas, &lighting, effects, options.path_options, shader, options.document) {
+        if matches!(&event, Event::Empty(tag) if tag.name().as_ref() == b"path") {
+            stats.paths_emitted += 1;
+            progress(Phase::Writing, usize::min(stats.paths_emitted, faces), faces);
+        }
+        writer.write_event(event)?;
+    }
+    progress(Phase::Writing, faces, faces);
+    stats.timings.push((Phase::Writing, start.elapsed()));
+    Ok(stats)
+}
+
+/// Moves `objects` and the outline of any `shadow` so the box around all of them starts `margin` in from the top left corner.
+/// Gives back how big an image just fits them with `margin` left on every side.
+fn crop(objects: &mut [PlacedShape], shadow: Option<&mut Shadow>, margin: f64) -> Vec2<f64> {
+    let primitives = shadow.map(|shadow| &mut shadow.primitives[..]).unwrap_or_default();
+    let mut points = objects.iter()
+        .flat_map(|object| object.shape.points_iter())
+        .chain(primitives.iter().flat_map(|primitive| primitive.points_iter()))
+        .peekable();
+    if points.peek().is_none() {
+        return vect![2.0 * margin, 2.0 * margin];
+    }
+    let bounds = Bounds::around(points);
+    let offset = vect![margin, margin] - bounds.min;
+    for object in objects.iter_mut() {
+        object.shape.shift(offset);
+    }
+    for primitive in primitives.iter_mut() {
+        primitive.shift(offset);
+    }
+    vect![bounds.width() + 2.0 * margin, bounds.height() + 2.0 * margin]
+}
+
+/// How big an image `size` across is shown, given its width and height, either of which is kept in proportion if left out.
+/// Without either, the image is shown one pixel per unit.
+fn display_size((width, height): &(Option<Length>, Option<Length>), size: Vec2<f64>) -> Option<(Length, Length)> {
+    match (width.clone(), height.clone()) {
+        (Some(width), Some(height)) => Some((width, height)),
+        (Some(width), None) => Some((width.clone(), width.scale(size.y / size.x))),
+        (None, Some(height)) => Some((height.scale(size.x / size.y), height)),
+        (None, None) => None,
+    }
+}
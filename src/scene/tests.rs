@@ -0,0 +1,221 @@
+#![cfg(test)]
+
+use std::collections::{HashMap, HashSet};
+
+use itertools::{iproduct, Itertools};
+use quick_xml::reader::Reader;
+use quick_xml::writer::Writer;
+
+use crate::colour::Light;
+use crate::grid::Grid;
+use crate::parser;
+use crate::colour;
+use crate::error::RunError;
+use crate::scene::{render_grid, split_objects, Frame, FrameSequence, Materials, Occlusion, RenderOptions, Scene};
+use crate::shadow::Shadow;
+use crate::shapes::{ShapeId, ShapeRegistry};
+use crate::vect;
+use crate::vector::Vec3;
+
+/// The shapes in `components.svg`, which the tests here and in other modules draw their scenes from.
+pub(crate) fn shapes() -> ShapeRegistry {
+    let mut reader = Reader::from_str(include_str!("../../components.svg"));
+    reader.trim_text(true);
+    parser::parse_shapes(&mut reader).unwrap()
+}
+
+fn render(grid: &Grid, options: &RenderOptions) -> String {
+    let mut output = vec![];
+    render_grid(grid, &shapes(), options, Writer::new(&mut output)).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_render_grid() {
+    let mut grid = Grid::new(vect![2, 1, 1]);
+    grid.set(vect![0, 0, 0], ShapeId::from(255u8));
+    grid.set(vect![1, 0, 0], ShapeId::from(255u8));
+    assert_eq!(render(&grid, &RenderOptions::default()).matches("<g id=").count(), 2);
+
+    // connected, both cells are drawn as one shape
+    let mut options = RenderOptions::default();
+    options.connections.insert(String::from("pair"), vec![vect![0, 0, 0], vect![1, 0, 0]]);
+    let connected = render(&grid, &options);
+    assert_eq!(connected.matches("<g id=").count(), 1);
+    assert!(connected.contains(r#"data-group="pair""#));
+
+    // white cubes lit from straight above have white tops
+    options.colour = vect![1.0, 1.0, 1.0];
+    options.lighting.lights = vec![Light::new(vect![0.0, 1.0, 0.0])];
+    assert!(render(&grid, &options).contains("fill:#ffffff"));
+}
+
+#[test]
+fn test_render_options_materials() {
+    let hidden = |options: &RenderOptions| render(&block(2), options).matches("<path").count();
+    let culled = hidden(&RenderOptions::default());
+    // keeping hidden faces draws more of the block
+    let options = RenderOptions { occlusion: Occlusion::Keep, ..RenderOptions::default() };
+    assert!(hidden(&options) > culled);
+    // and so does a cube which can be seen through, as it can't hide anything
+    let mut options = RenderOptions::default();
+    options.materials.opacity.insert(ShapeId::from(255u8), 0.5);
+    assert!(hidden(&options) > culled);
+    // cubes not known to fill their cells are checked rather than skipped over when they're buried, which draws the same block
+    let options = RenderOptions { materials: Materials { opaque: HashSet::new(), opacity: HashMap::new() }, ..RenderOptions::default() };
+    assert_eq!(render(&block(3), &options), render(&block(3), &RenderOptions::default()));
+}
+
+/// A solid block of cubes, `size` cells across.
+pub(crate) fn block(size: usize) -> Grid {
+    let mut grid = Grid::new(vect![size, size, size]);
+    for (x, y, z) in iproduct!(0..size, 0..size, 0..size) {
+        grid.set(vect![x, y, z], ShapeId::from(255u8));
+    }
+    grid
+}
+
+/// The `d` of every path in `svg`, in order.
+fn path_data(svg: &str) -> Vec<&str> {
+    svg.split(" d=\"").skip(1).map(|rest| rest.split('"').next().unwrap()).collect()
+}
+
+#[test]
+fn test_split_objects() {
+    let grid = block(2);
+    let mut options = RenderOptions::default();
+    options.connections.insert(String::from("column"), vec![vect![0, 0, 0], vect![0, 1, 0]]);
+
+    let objects = split_objects(&grid, &options, false).unwrap();
+    assert_eq!(objects.len(), 1);
+    let column = &objects[0];
+    assert_eq!(column.name, "column");
+    assert_eq!(column.grid.iter_occupied().map(|(cell, _)| cell).sorted_by_key(|c| c.y).collect_vec(), [vect![0, 0, 0], vect![0, 1, 0]]);
+    assert_eq!(column.options.connections, HashMap::from([(String::from("column"), vec![vect![0, 0, 0], vect![0, 1, 0]])]));
+    assert!(column.options.crop);
+
+    let objects = split_objects(&grid, &options, true).unwrap();
+    let names = objects.iter().map(|object| object.name.as_str()).collect_vec();
+    assert_eq!(names, ["column", "tile-0-0-1", "tile-0-1-1", "tile-1-0-0", "tile-1-0-1", "tile-1-1-0", "tile-1-1-1"]);
+    for object in &objects[1..] {
+        // every tile is hidden somewhere in the block, but drawn whole on its own
+        let stats = render_grid(&object.grid, &shapes(), &object.options, Writer::new(vec![])).unwrap();
+        assert_eq!((stats.shapes_culled, stats.paths_emitted), (0, 3), "{}", object.name);
+        assert!(object.options.connections.is_empty());
+    }
+
+    // with nothing connected by hand, the whole block is one region
+    let options = RenderOptions { auto_merge: true, ..RenderOptions::default() };
+    let objects = split_objects(&grid, &options, true).unwrap();
+    assert_eq!(objects.iter().map(|object| object.name.as_str()).collect_vec(), ["auto-0-0-0"]);
+    assert_eq!(objects[0].grid.iter_occupied().count(), 8);
+}
+
+#[test]
+fn test_crop() {
+    let options = RenderOptions { margin: 2.0, ..RenderOptions::default() };
+    let mut alone = Grid::new(vect![1, 1, 1]);
+    alone.set(vect![0, 0, 0], ShapeId::from(255u8));
+    let expected = render(&alone, &options);
+
+    // the same cube in the far corner of a bigger grid is drawn in exactly the same place once cropped
+    let mut grid = Grid::new(vect![4, 2, 4]);
+    grid.set(vect![3, 1, 3], ShapeId::from(255u8));
+    let uncropped = render(&grid, &options);
+    let options = RenderOptions { crop: true, ..options };
+    let cropped = render(&grid, &options);
+    assert_ne!(path_data(&uncropped), path_data(&expected));
+    assert_eq!(path_data(&cropped), path_data(&expected));
+    assert_eq!(cropped.split("<g").next(), expected.split("<g").next());
+
+    // the shadow is part of what's drawn, so the image grows to fit it
+    let mut shadowed = options.clone();
+    shadowed.effects.shadow = Some(Shadow { primitives: vec![], colour: vect![0.0, 0.0, 0.0], opacity: 0.5 });
+    let header = |svg: &str| svg.split("<g").next().unwrap().to_string();
+    assert_ne!(header(&render(&grid, &shadowed)), header(&cropped));
+
+    // with nothing drawn, there's only the margin
+    assert!(render(&Grid::new(vect![4, 2, 4]), &options).contains(r#"viewBox="0 0 4 4""#));
+}
+
+#[test]
+fn test_frame_sequence() {
+    let shapes = shapes();
+    let mut grid = Grid::new(vect![3, 1, 1]);
+    grid.set(vect![0, 0, 0], ShapeId::from(255u8));
+    let mut sequence = FrameSequence::new(&shapes, grid, RenderOptions::default()).unwrap();
+    assert_eq!(sequence.changed(), &HashSet::from([vect![0, 0, 0]]));
+
+    // putting back what's already there doesn't count as a change
+    let frame = Frame { tiles: vec![(vect![0, 0, 0], ShapeId::from(255u8)), (vect![2, 0, 0], ShapeId::from(255u8))], ..Frame::default() };
+    sequence.advance(&frame).unwrap();
+    assert_eq!(sequence.changed(), &HashSet::from([vect![2, 0, 0]]));
+    let mut output = vec![];
+    sequence.render_reporting(Writer::new(&mut output), &colour::default_shader, &mut |_, _, _| ()).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), render(sequence.grid(), sequence.options()));
+
+    let frame = Frame { tiles: vec![(vect![2, 0, 0], ShapeId::EMPTY)], colour: Some(vect![1.0, 0.0, 0.0]), ..Frame::default() };
+    sequence.advance(&frame).unwrap();
+    assert_eq!(sequence.grid().iter_occupied().count(), 1);
+    assert_eq!(sequence.options().colour, vect![1.0, 0.0, 0.0]);
+
+    // a frame which can't be made leaves everything as it was
+    let frame = Frame { tiles: vec![(vect![1, 0, 0], ShapeId::from(255u8)), (vect![3, 0, 0], ShapeId::from(255u8))], ..Frame::default() };
+    assert!(matches!(sequence.advance(&frame), Err(RunError::InvalidPlacement { .. })));
+    assert_eq!(sequence.grid().iter_occupied().count(), 1);
+}
+
+#[test]
+fn test_scene_builder() {
+    let mut scene = Scene::new(shapes());
+    scene.set_grid_size(vect![2, 1, 2]);
+    scene.place(vect![0, 0, 0], 255u8).unwrap();
+    scene.place(vect![1, 0, 1], 255u8).unwrap();
+    scene.light(vect![0.2, 1.0, 0.4]).unwrap();
+    scene.colour(vect![0.2, 0.6, 0.9]);
+    let mut expected = vec![];
+    scene.render(Writer::new(&mut expected)).unwrap();
+
+    let mut grid = Grid::new(vect![2, 1, 2]);
+    grid.set(vect![0, 0, 0], ShapeId::from(255u8));
+    grid.set(vect![1, 0, 1], ShapeId::from(255u8));
+    // the light and colour win over the options, even given before them
+    let built = Scene::builder()
+        .components(shapes())
+        .light(vect![0.2, 1.0, 0.4])
+        .grid(grid.clone())
+        .colour(vect![0.2, 0.6, 0.9])
+        .options(RenderOptions::default())
+        .build()
+        .unwrap();
+    let mut output = vec![];
+    built.render(Writer::new(&mut output)).unwrap();
+    assert_eq!(output, expected);
+
+    // connections given with the options are checked against the grid, whichever comes first
+    let mut options = RenderOptions::default();
+    options.connections.insert(String::from("pair"), vec![vect![0, 0, 0], vect![0, 0, 2]]);
+    let outside = Scene::builder().components(shapes()).grid(grid.clone()).options(options.clone()).build().err();
+    assert_matches!(outside, Some(RunError::InvalidMember { group, index: 1, .. }) if group == "pair");
+    let outside = Scene::builder().components(shapes()).options(options).grid(grid.clone()).build().err();
+    assert_matches!(outside, Some(RunError::InvalidMember { group, index: 1, .. }) if group == "pair");
+
+    // as is the light, which has to come from somewhere
+    let dark = Scene::builder().components(shapes()).grid(grid.clone()).light(vect![0.0, 0.0, 0.0]).build().err();
+    assert_matches!(dark, Some(RunError::InvalidKey { key, .. }) if key == "light");
+    // setting it on the scene leaves the light it had before
+    assert_matches!(scene.light(vect![0.0, 0.0, 0.0]), Err(RunError::InvalidKey { key, .. }) if key == "light");
+    let mut output = vec![];
+    scene.render(Writer::new(&mut output)).unwrap();
+    assert_eq!(output, expected);
+    let mut options = RenderOptions::default();
+    options.lighting.lights.push(Light::new(vect![0.0, 0.0, 0.0]));
+    let dark = Scene::builder().components(shapes()).grid(grid.clone()).options(options).build().err();
+    assert_matches!(dark, Some(RunError::InvalidKey { key, .. }) if key == "lighting.lights[1].direction");
+
+    // every shape in the grid has to be one of the components
+    grid.set(vect![1, 0, 0], ShapeId::from(7u8));
+    let unknown = Scene::builder().components(shapes()).grid(grid).build().err();
+    assert_matches!(unknown, Some(RunError::InvalidPlacement { cell: Vec3 { x: 1, y: 0, z: 0 }, .. }));
+    assert!(Scene::builder().grid(Grid::new(vect![1, 1, 1])).build().is_ok());
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use config::{Config, ConfigError, Value};
+use itertools::Itertools;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use crate::error::RunError;
+use crate::vector::Vec3;
+
+mod tests;
+
+/// Every option the config can give, read in one go, with what each is when it's left out.
+///
+/// Only `grid_size` and the cells to put in it are needed, as `tiles`, `fills` or both.
+/// Entries which can be written in several ways, like tiles, shape ids and colours, are kept as they were written
+/// and read properly while the scene is put together, so any problem with them can say which entry it was.
+/// Colours are three channels, running from 0 to 255 if they're all whole numbers and from 0 to 1 otherwise.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The SVG files the shapes are drawn in, either one path or a list of them. Without any, it's `./components.svg`.
+    #[serde(deserialize_with = "one_or_several")]
+    pub components: Vec<String>,
+    /// How many cells the grid has along each axis. Needed unless `auto_size` is set.
+    pub grid_size: Option<Vec3<usize>>,
+    /// Whether the grid is made just big enough for every tile and fill, and at least as big as `grid_size` if that's given.
+    pub auto_size: bool,
+    /// Each a coordinate, optionally followed by the shape to put there. Needed unless there are `fills` or the grid is generated.
+    pub tiles: Option<Vec<Value>>,
+    /// Each two opposite corners of a box, optionally followed by the shape to fill it with.
+    pub fills: Vec<Value>,
+    /// Cells worked out procedurally, which `fills` and `tiles` go on top of.
+    pub generate: Option<GenerateSettings>,
+    /// Groups of cells drawn as one shape between them, each a list of coordinates.
+    pub equalities: HashMap<String, Value>,
+    /// Whether groups sharing a member are merged, rather than an error.
+    pub merge_equalities: bool,
+    /// Whether neighbouring cells with the same shape are drawn as one.
+    pub auto_merge: bool,
+    /// How many cells each shape covering more than one takes up along the axes it's drawn with, keyed by its shape id.
+    pub footprints: HashMap<String, Value>,
+    /// The full cube the direction of each axis is measured from. 255 when not given.
+    pub reference_shape: Option<Value>,
+    /// Names for shapes drawn in a colour and opacity of their own, which tiles, fills and generators can use like a shape's label.
+    pub palette: HashMap<String, PaletteSettings>,
+    /// Shapes which fill their whole cell, so hide everything behind them.
+    pub opaque_shapes: Vec<Value>,
+    /// How opaque each shape which can be seen through is, from 0 to 1, keyed by its shape id. Overrides the `palette`.
+    pub opacity: HashMap<String, Value>,
+    /// How close points have to be to count as the same when hiding faces.
+    pub epsilon: f64,
+    /// Whether faces hidden behind others are left in, rather than deleted.
+    pub keep_hidden_faces: bool,
+    /// Whether faces partly hidden behind others are cut down to what can be seen.
+    pub clip_hidden_faces: bool,
+    /// Which corner the grid is viewed from, one of "ne", "nw", "se" or "sw".
+    pub view: Option<String>,
+    /// The empty space left around the scene on every side.
+    pub margin: f64,
+    /// Whether the image is cut down to just what's drawn in it, rather than fitting the whole grid.
+    pub crop: bool,
+    /// How wide the image is shown, such as "400px" or "50%".
+    pub output_width: Option<String>,
+    /// How high the image is shown, in the same form as `output_width`.
+    pub output_height: Option<String>,
+    /// Whether faces pointing the same way in neighbouring cells are fused into one.
+    pub merge_faces: bool,
+    /// The colour every face is drawn in before it's lit.
+    pub scene_colour: Option<Vec<Value>>,
+    /// Colours for particular cells in place of the `palette` or `scene_colour`.
+    pub tile_colours: Vec<TileColourSettings>,
+    /// Where the one light comes from when there isn't a list of `lights`.
+    pub light_vector: Option<Vec3<f64>>,
+    /// Every light shining on the scene.
+    pub lights: Vec<LightSettings>,
+    /// How much of the colour shows on every face, even those facing away from the lights.
+    pub ambient: f64,
+    /// How much of the colour a face pointing straight at a light gets on top of `ambient`.
+    pub diffuse: f64,
+    /// A colour for the ambient light, added on in place of the face's colour when given.
+    pub ambient_colour: Option<Vec<Value>>,
+    /// Whether light is added up in linear space rather than sRGB.
+    pub gamma_correct: bool,
+    pub colour_variation: Option<ColourVariationSettings>,
+    pub checker: Option<CheckerSettings>,
+    pub fog: Option<FogSettings>,
+    pub gradient: Option<GradientSettings>,
+    /// The colour of the lines drawn round each face. Setting any of the stroke options turns them on.
+    pub stroke_colour: Option<Vec<Value>>,
+    pub stroke_width: Option<f64>,
+    /// Which edges the lines are drawn along, either "primitives" or "silhouette".
+    pub stroke_mode: Option<String>,
+    /// How faces are drawn, either "solid" or "wireframe", which only draws their outlines.
+    pub render_mode: Option<String>,
+    /// The colour of a wireframe's lines.
+    pub wireframe_colour: Option<Vec<Value>>,
+    /// How wide a wireframe's lines are.
+    pub wireframe_width: Option<f64>,
+    /// Whether a wireframe also draws the faces hidden behind others, rather than only what can be seen.
+    pub wireframe_hidden_faces: bool,
+    pub shadow: Option<ShadowSettings>,
+    /// Whether points are rounded to whole numbers, dropping any shapes which round away to nothing.
+    pub integer_coordinates: bool,
+    /// Whether every ring starts from its top-left vertex, so the same geometry always produces the same text.
+    pub canonical_paths: bool,
+    /// Which neighbouring paths with the same style are merged, one of "off", "shapes" or "global".
+    pub merge_paths: Option<String>,
+    /// How the seams between faces are covered, one of "off", "stroke" or "outset".
+    pub anti_seam: String,
+    /// How wide the cover over the seams is.
+    pub anti_seam_width: f64,
+    /// Whether rings are written with relative commands wherever that's shorter.
+    pub relative_paths: bool,
+    /// Whether the last side of each ring is drawn back to its start before the `z`.
+    pub explicit_close: bool,
+    /// Whether each object is tagged with the cell it came from.
+    pub cell_attributes: bool,
+    /// Whether shapes drawn exactly the same more than once are written once and placed with a `<use>` each time.
+    pub reuse_shapes: bool,
+    /// Which coordinate objects are gathered into layers by, one of "off", "x", "y", "z" or "depth".
+    pub layers: Option<String>,
+    /// Whether a `fill` attribute on a face in the components file is kept in place of its shading.
+    pub preserve_fill: bool,
+    /// Whether a stroke on a face in the components file is lit the same way as its fill.
+    pub shade_strokes: bool,
+    /// Whether the scene builds itself up a step at a time when it's opened.
+    pub animate: bool,
+    /// How many seconds each step of the build-up takes.
+    pub step_duration: f64,
+    /// Which shapes are put in place first when animated, one of "depth" or "layer".
+    pub order: Option<String>,
+    /// Whether each element is put on its own line, indented by how deeply it's nested.
+    pub pretty_print: bool,
+    /// Where to write what the scene looks like just before it's drawn, as JSON.
+    pub dump_scene: Option<String>,
+    /// Each frame of an animation after the first, which is the scene as the rest of the config gives it.
+    pub frames: Vec<FrameSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            components: vec![],
+            grid_size: None,
+            auto_size: false,
+            tiles: None,
+            fills: vec![],
+            generate: None,
+            equalities: HashMap::new(),
+            merge_equalities: false,
+            auto_merge: false,
+            footprints: HashMap::new(),
+            reference_shape: None,
+            palette: HashMap::new(),
+            opaque_shapes: vec![Value::from(255)],
+            opacity: HashMap::new(),
+            epsilon: crate::shapes::DEFAULT_EPSILON,
+            keep_hidden_faces: false,
+            clip_hidden_faces: false,
+            view: None,
+            margin: 0.0,
+            crop: false,
+            output_width: None,
+            output_height: None,
+            merge_faces: false,
+            scene_colour: None,
+            tile_colours: vec![],
+            light_vector: None,
+            lights: vec![],
+            ambient: 0.0,
+            diffuse: 1.0,
+            ambient_colour: None,
+            gamma_correct: false,
+            colour_variation: None,
+            checker: None,
+            fog: None,
+            gradient: None,
+            stroke_colour: None,
+            stroke_width: None,
+            stroke_mode: None,
+            render_mode: None,
+            wireframe_colour: None,
+            wireframe_width: None,
+            wireframe_hidden_faces: false,
+            shadow: None,
+            integer_coordinates: false,
+            canonical_paths: true,
+            merge_paths: None,
+            anti_seam: String::from("off"),
+            anti_seam_width: 0.5,
+            relative_paths: false,
+            explicit_close: false,
+            cell_attributes: true,
+            reuse_shapes: false,
+            layers: None,
+            preserve_fill: false,
+            shade_strokes: false,
+            animate: false,
+            step_duration: 0.1,
+            order: None,
+            pretty_print: false,
+            dump_scene: None,
+            frames: vec![],
+        }
+    }
+}
+
+/// Reads either a single path or a list of them.
+fn one_or_several<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Paths {
+        One(String),
+        Several(Vec<String>),
+    }
+    match Paths::deserialize(deserializer) {
+        Ok(Paths::One(path)) => Ok(vec![path]),
+        Ok(Paths::Several(paths)) => Ok(paths),
+        Err(_) => Err(de::Error::custom("expected a path or a list of paths")),
+    }
+}
+
+/// The `generate` table, where `kind` is one of "noise", "checkerboard" or "slab", and the rest only go with some kinds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GenerateSettings {
+    pub kind: Option<String>,
+    /// What the noise is worked out from, for "noise".
+    pub seed: u64,
+    /// The tallest a column can be, for "noise". The grid's height when not given.
+    pub max_height: Option<usize>,
+    /// How many cells apart the hills and valleys are, roughly, for "noise".
+    pub scale: f64,
+    /// How many layers are filled, for "slab".
+    pub height: usize,
+    /// The shape everything is made of. 255 when not given.
+    pub shape: Option<Value>,
+    /// The shape in between `shape` on a "checkerboard", which leaves gaps there when not given.
+    pub other: Option<Value>,
+}
+
+impl Default for GenerateSettings {
+    fn default() -> Self {
+        GenerateSettings { kind: None, seed: 0, max_height: None, scale: 4.0, height: 1, shape: None, other: None }
+    }
+}
+
+/// One entry of the `palette`, drawn as `shape`, or 255 when not given.
+/// Leaving out the colour or opacity draws it the same as the shape would be.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PaletteSettings {
+    pub shape: Option<Value>,
+    pub colour: Option<Vec<Value>>,
+    pub opacity: Option<f64>,
+}
+
+/// One entry of `tile_colours`, where both fields are needed.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TileColourSettings {
+    pub tile: Option<Vec3<usize>>,
+    pub colour: Option<Vec<Value>>,
+}
+
+/// One entry of `frames`, as changes to the frame before it.
+/// The lights and colour carry on into the frames after, until another frame changes them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FrameSettings {
+    /// Put down on top of the frame before, in the same form as `tiles`.
+    pub tiles: Vec<Value>,
+    /// Cells emptied from the frame before.
+    pub remove: Vec<Vec3<usize>>,
+    /// In place of the lights so far, the same as the scene's own `light_vector`.
+    pub light_vector: Option<Vec3<f64>>,
+    /// In place of the lights so far, the same as the scene's own `lights`.
+    pub lights: Vec<LightSettings>,
+    /// In place of the `scene_colour` so far.
+    pub scene_colour: Option<Vec<Value>>,
+}
+
+/// One entry of `lights`, which needs its `direction`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LightSettings {
+    pub direction: Option<Vec3<f64>>,
+    pub intensity: f64,
+    /// White when not given.
+    pub colour: Option<Vec<Value>>,
+}
+
+impl Default for LightSettings {
+    fn default() -> Self {
+        LightSettings { direction: None, intensity: 1.0, colour: None }
+    }
+}
+
+/// The `colour_variation` table, which turns the variation on by being there at all.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ColourVariationSettings {
+    pub amplitude: f64,
+    pub seed: u64,
+}
+
+/// The `checker` table, which turns the tint on by being there at all.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CheckerSettings {
+    /// What every other tile's colour is multiplied by. Black when not given.
+    pub tint: Option<Vec<Value>>,
+    /// How much of the tint shows, between -1 and 1, where below 0 lightens the tiles instead.
+    pub strength: f64,
+    /// The coordinates added up to pick out the tinted tiles, such as "xz" for the ground or "xyz" for every cell.
+    pub axes: String,
+}
+
+impl Default for CheckerSettings {
+    fn default() -> Self {
+        CheckerSettings { tint: None, strength: 0.15, axes: String::from("xz") }
+    }
+}
+
+/// The `fog` table, where every field is needed.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FogSettings {
+    pub colour: Option<Vec<Value>>,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+/// The `gradient` table, which turns the gradient on by being there at all.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GradientSettings {
+    pub darkening: f64,
+}
+
+impl Default for GradientSettings {
+    fn default() -> Self {
+        GradientSettings { darkening: 0.2 }
+    }
+}
+
+/// The `shadow` table, which turns shadows on by being there at all. Black when no `colour` is given.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    pub opacity: f64,
+    pub colour: Option<Vec<Value>>,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings { opacity: 0.3, colour: None }
+    }
+}
+
+impl Settings {
+    /// Reads every option from `config`, leaving out anything it doesn't know.
+    pub fn read(config: &Config) -> Result<Settings, RunError> {
+        config.clone().try_deserialize::<Settings>().map_err(|why| match why {
+            ConfigError::Type { key: Some(ref key), .. } => RunError::from_config(&dotted(key), why),
+            why => {
+                // some errors, like a coordinate with too few values, don't say where they came from,
+                // so each key is read on its own to find the first which fails
+                config.clone().try_deserialize::<HashMap<String, Value>>().unwrap_or_default().into_iter()
+                    .sorted_by(|a, b| a.0.cmp(&b.0))
+                    .find_map(|(key, value)| {
+                        let alone = Value::new(None, HashMap::from([(key.clone(), value)]));
+                        let reason = alone.try_deserialize::<Settings>().err()?.to_string();
+                        Some(RunError::InvalidKey { key, reason })
+                    })
+                    .unwrap_or_else(|| RunError::InvalidKey { key: String::from("config"), reason: why.to_string() })
+            }
+        })
+    }
+}
+
+/// Puts back the dot the config crate leaves out between an index and the field after it, as in `lights[0]intensity`.
+fn dotted(key: &str) -> String {
+    let mut dotted = String::new();
+    let mut chars = key.chars().peekable();
+    while let Some(c) = chars.next() {
+        dotted.push(c);
+        if c == ']' && chars.peek().is_some_and(|next| *next != '[' && *next != '.') {
+            dotted.push('.');
+        }
+    }
+    dotted
+}
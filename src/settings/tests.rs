@@ -0,0 +1,56 @@
+#![cfg(test)]
+
+use config::{Config, FileFormat};
+
+use crate::error::RunError;
+use crate::settings::{dotted, Settings};
+use crate::vect;
+use crate::vector::Vec3;
+
+fn read(toml: &str) -> Result<Settings, RunError> {
+    Settings::read(&Config::builder().add_source(config::File::from_str(toml, FileFormat::Toml)).build().unwrap())
+}
+
+#[test]
+fn test_defaults() {
+    let settings = read("grid_size = [1, 2, 3]\ntiles = [[0, 0, 0]]").unwrap();
+    assert_eq!(settings.grid_size, Some(vect![1, 2, 3]));
+    assert_eq!(settings.tiles.map(|tiles| tiles.len()), Some(1));
+    assert!(settings.equalities.is_empty());
+    assert!(settings.components.is_empty());
+    assert_eq!(settings.opaque_shapes.len(), 1);
+    assert_eq!(settings.diffuse, 1.0);
+    assert!(settings.canonical_paths);
+    assert!(settings.cell_attributes);
+    assert!(settings.fog.is_none());
+
+    // a table which is there at all takes the defaults for anything it leaves out
+    let settings = read("[gradient]\n[shadow]\ncolour = [0, 0, 255]").unwrap();
+    assert_eq!(settings.gradient.map(|gradient| gradient.darkening), Some(0.2));
+    assert_eq!(settings.shadow.map(|shadow| shadow.opacity), Some(0.3));
+}
+
+#[test]
+fn test_components() {
+    assert_eq!(read("components = \"a.svg\"").unwrap().components, vec!["a.svg"]);
+    assert_eq!(read("components = [\"a.svg\", \"b.svg\"]").unwrap().components, vec!["a.svg", "b.svg"]);
+    assert_matches!(read("components = { a = 1 }"), Err(RunError::InvalidKey { key, .. }) if key == "components");
+}
+
+#[test]
+fn test_read_errors() {
+    assert_matches!(read("margin = \"wide\""), Err(RunError::InvalidKey { key, .. }) if key == "margin");
+    assert_matches!(read("[fog]\nstart = \"near\""), Err(RunError::InvalidKey { key, .. }) if key == "fog.start");
+    let light = read("lights = [{ direction = [0, 1, 0] }, { intensity = \"bright\" }]");
+    assert_matches!(light, Err(RunError::InvalidKey { key, .. }) if key == "lights[1].intensity");
+    // errors which don't say which key they came from are still put down to the right one
+    assert_matches!(read("grid_size = [1, 2]\nmargin = 1"), Err(RunError::InvalidKey { key, .. }) if key == "grid_size");
+}
+
+#[test]
+fn test_dotted() {
+    assert_eq!(dotted("lights[0]intensity"), "lights[0].intensity");
+    assert_eq!(dotted("lights[0].intensity"), "lights[0].intensity");
+    assert_eq!(dotted("tiles[1][2]"), "tiles[1][2]");
+    assert_eq!(dotted("margin"), "margin");
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeSet;
+
+use crate::shapes::ShapePrimitive;
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+
+mod tests;
+
+/// Shadows cast onto the ground, drawn underneath everything else.
+#[derive(Debug, Clone)]
+pub struct Shadow {
+    /// The outline of all the shadowed ground, none of which overlaps.
+    pub primitives: Vec<ShapePrimitive>,
+    pub colour: Vec3<f64>,
+    /// How much of the ground the shadow hides, from 0 to 1.
+    pub opacity: f64,
+}
+
+/// The cells of the ground, given as `(x, z)`, in the shadow of any of `cells` when lit from `light`.
+///
+/// The ground is the bottom of the grid, and only the part of it under the grid counts.
+/// A cell of the ground is shadowed when its centre is. Light from level with the ground or below it casts no shadows at all.
+pub fn shadowed_cells(cells: impl IntoIterator<Item = Vec3<usize>>, size: Vec3<usize>, light: Vec3<f64>) -> BTreeSet<(usize, usize)> {
+    let mut shadowed = BTreeSet::new();
+    if light.y <= 0.0 {
+        return shadowed;
+    }
+    // how far along the ground the shadow moves for each cell of height
+    let slope = vect![-light.x / light.y, -light.z / light.y];
+
+    for cell in cells {
+        // the shadow of a cube is its base slid along the ground, from the shadow of its bottom to the shadow of its top
+        let start = vect![cell.x as f64, cell.z as f64] + slope * cell.y as f64;
+        let end = start + slope;
+        let range = |from: f64, to: f64, size: usize| {
+            let low = f64::min(from, to).floor().max(0.0) as usize;
+            let high = (f64::max(from, to) + 1.0).ceil().clamp(0.0, size as f64) as usize;
+            low..high
+        };
+        for x in range(start.x, end.x, size.x) {
+            for z in range(start.y, end.y, size.z) {
+                if in_swept_square(vect![x as f64 + 0.5, z as f64 + 0.5] - start, slope) {
+                    shadowed.insert((x, z));
+                }
+            }
+        }
+    }
+    shadowed
+}
+
+/// Whether `p` is in the unit square from the origin swept along `direction`,
+/// which is whether `p - direction * t` is in the square for some `t` from 0 to 1.
+fn in_swept_square(p: Vec2<f64>, direction: Vec2<f64>) -> bool {
+    let mut t = (0.0, 1.0);
+    for (p, d) in [(p.x, direction.x), (p.y, direction.y)] {
+        if d == 0.0 {
+            if !(0.0..=1.0).contains(&p) {
+                return false;
+            }
+            continue;
+        }
+        let (a, b) = ((p - 1.0) / d, p / d);
+        t = (f64::max(t.0, f64::min(a, b)), f64::min(t.1, f64::max(a, b)));
+    }
+    t.0 <= t.1
+}
+
+/// Outlines `cells` of the ground, joining cells next to each other along `x` so there are fewer primitives to draw.
+/// `project` gives where a point on the ground, as `(x, z)`, ends up in the image.
+pub fn outline_cells(cells: &BTreeSet<(usize, usize)>, project: impl Fn(f64, f64) -> Vec2<f64>) -> Vec<ShapePrimitive> {
+    let mut runs: Vec<(usize, usize, usize)> = vec![];
+    // keyed by `z` first, so each row along `x` comes in order
+    let by_row: BTreeSet<(usize, usize)> = cells.iter().map(|(x, z)| (*z, *x)).collect();
+    for (z, x) in by_row {
+        match runs.last_mut() {
+            Some((run_z, _, end)) if *run_z == z && *end == x => *end += 1,
+            _ => runs.push((z, x, x + 1)),
+        }
+    }
+    runs.into_iter()
+        .map(|(z, start, end)| {
+            let (z, start, end) = (z as f64, start as f64, end as f64);
+            ShapePrimitive { points: vec![project(start, z), project(end, z), project(end, z + 1.0), project(start, z + 1.0)] }
+        })
+        .collect()
+}
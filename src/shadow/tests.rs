@@ -0,0 +1,51 @@
+#![cfg(test)]
+
+use std::collections::BTreeSet;
+
+use crate::shadow::{in_swept_square, outline_cells, shadowed_cells};
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+
+#[test]
+fn test_in_swept_square() {
+    assert!(in_swept_square(vect![0.5, 0.5], vect![0.0, 0.0]));
+    assert!(!in_swept_square(vect![1.5, 0.5], vect![0.0, 0.0]));
+    // swept two along x, the square covers everything up to 3
+    assert!(in_swept_square(vect![2.5, 0.5], vect![2.0, 0.0]));
+    assert!(!in_swept_square(vect![3.5, 0.5], vect![2.0, 0.0]));
+    // swept diagonally, the corners the sweep moves away from are left out
+    assert!(in_swept_square(vect![1.5, 1.5], vect![1.0, 1.0]));
+    assert!(!in_swept_square(vect![1.8, 0.2], vect![1.0, 1.0]));
+}
+
+#[test]
+fn test_shadowed_cells() {
+    let size = vect![4, 4, 4];
+    // lit from straight above, a cube only shades the ground under it
+    let above = shadowed_cells([vect![1, 3, 2]], size, vect![0.0, 1.0, 0.0]);
+    assert_eq!(above, BTreeSet::from([(1, 2)]));
+
+    // lit at 45 degrees from +x, the bottom of a cube two up is shadowed two back along x, and its top three back
+    let slanted = shadowed_cells([vect![3, 2, 1]], size, vect![1.0, 1.0, 0.0]);
+    assert_eq!(slanted, BTreeSet::from([(0, 1), (1, 1)]));
+
+    // shadows stop at the edge of the grid
+    let clipped = shadowed_cells([vect![0, 3, 0]], size, vect![1.0, 1.0, 1.0]);
+    assert!(clipped.is_empty());
+
+    // light from under the ground can't cast anything onto it
+    assert!(shadowed_cells([vect![1, 1, 1]], size, vect![0.0, -1.0, 0.0]).is_empty());
+    assert!(shadowed_cells([vect![1, 1, 1]], size, vect![1.0, 0.0, 0.0]).is_empty());
+}
+
+#[test]
+fn test_outline_cells() {
+    let cells = BTreeSet::from([(0, 0), (1, 0), (2, 0), (0, 1), (2, 1)]);
+    let outline = outline_cells(&cells, |x, z| vect![x, z]);
+    // the first row is one run, and the gap splits the second in two
+    assert_eq!(outline.len(), 3);
+    let points: Vec<Vec<Vec2<f64>>> = outline.into_iter().map(|p| p.points).collect();
+    assert_eq!(points[0], vec![vect![0.0, 0.0], vect![3.0, 0.0], vect![3.0, 1.0], vect![0.0, 1.0]]);
+    assert_eq!(points[1], vec![vect![0.0, 1.0], vect![1.0, 1.0], vect![1.0, 2.0], vect![0.0, 2.0]]);
+    assert_eq!(points[2], vec![vect![2.0, 1.0], vect![3.0, 1.0], vect![3.0, 2.0], vect![2.0, 2.0]]);
+}
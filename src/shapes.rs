@@ -1,41 +1,67 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
 use itertools::Itertools;
 
+use crate::colour::{self, Lighting};
+use crate::num::Float;
 use crate::vector::{Vec2, Vec3};
-use crate::iter::ToDStringIter;
+use crate::iter::{path_event, relative_commands, ToDStringIter, ToSvgCommandIter};
+use crate::path::{AntiSeam, PathOptions};
 use crate::{vect, vectp};
 
 mod tests;
 
-fn inclusive_contains(a: &impl Polygonal, p: Vec2<f64>) -> bool {
-    match get_containment(a, p) {
+fn inclusive_contains<F: Float>(a: &impl Polygonal<F>, p: Vec2<F>, epsilon: F) -> bool {
+    match get_containment(a, p, FillRule::EvenOdd, epsilon) {
         Containment::Outside => false,
         _ => true,
     }
 }
 
-fn exclusive_contains(a: &impl Polygonal, p: Vec2<f64>) -> bool {
-    match get_containment(a, p) {
-        Containment::Inside => true,
-        _ => false,
-    }
-}
-
-fn on_edge(a: &impl Polygonal, p: Vec2<f64>) -> bool {
-    match get_containment(a, p) {
+fn on_edge<F: Float>(a: &impl Polygonal<F>, p: Vec2<F>, epsilon: F) -> bool {
+    match get_containment(a, p, FillRule::EvenOdd, epsilon) {
         Containment::Edge => true,
         _ => false,
     }
 }
 
-#[derive(Eq, PartialEq)]
-enum Containment {
+/// Where a point is relative to a shape.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Containment {
     Inside,
     Edge,
     Outside,
 }
 
-fn get_containment(a: &impl Polygonal, p: Vec2<f64>) -> Containment {
-    let mut direction = vect![1.0, 0.0];
+/// How to decide which parts of a shape whose outline crosses itself, or which has several outlines, are inside it.
+/// These are the same as SVG's `fill-rule`, and give the same answer for a single outline which doesn't cross itself.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum FillRule {
+    /// Inside if a ray from the point crosses the outline an odd number of times.
+    #[default]
+    EvenOdd,
+    /// Inside if the outline winds around the point at all, counting each loop by its direction.
+    NonZero,
+}
+
+/// Where `p` is relative to `a`. Anything within `epsilon` of an edge counts as being on it,
+/// as the floating point error built up moving shapes around can leave a shared point a hair to either side.
+pub(crate) fn get_containment<F: Float>(a: &impl Polygonal<F>, p: Vec2<F>, rule: FillRule, epsilon: F) -> Containment {
+    if near_edge(a, p, epsilon) {
+        return Containment::Edge;
+    }
+    match rule {
+        FillRule::EvenOdd => ray_cast(a, p),
+        FillRule::NonZero => winding_number(a, p),
+    }
+}
+
+fn ray_cast<F: Float>(a: &impl Polygonal<F>, p: Vec2<F>) -> Containment {
+    let (zero, one) = (F::zero(), F::one());
+    let mut direction = vect![one, zero];
     let mut intersections = 0;
     let Some(mut sp_0) = a.points_iter().last() else {
         return Containment::Outside;
@@ -47,18 +73,18 @@ fn get_containment(a: &impl Polygonal, p: Vec2<f64>) -> Containment {
         // this will happen if the direction we choose is parallel to the line we want to check against.
         // Easiest way around it is just try again in a different direction!
         if lambda.is_nan() || mu.is_nan() {
-            direction = direction.rot(1.0);
+            direction = direction.rot(one);
             vect![lambda, mu] = intersection_parameters(sp_1, edge, p, direction);
         }
         // boundary
-        if 0.0 <= lambda && lambda <= 1.0 && mu == 0.0 {
+        if zero <= lambda && lambda <= one && mu == zero {
             return Containment::Edge;
         }
         if (
-            0.0 < lambda && lambda < 1.0 ||
+            zero < lambda && lambda < one ||
             // if we intersect a corner, use the cross product to see if we actually go through it
-            lambda == 0.0 && Vec2::cross(prev_edge, direction).signum() == Vec2::cross(edge, direction).signum()
-        ) && mu > 0.0
+            lambda == zero && Vec2::cross(prev_edge, direction).signum() == Vec2::cross(edge, direction).signum()
+        ) && mu > zero
         {
             intersections += 1;
         }
@@ -72,279 +98,1175 @@ fn get_containment(a: &impl Polygonal, p: Vec2<f64>) -> Containment {
     }
 }
 
-fn obscures(a: &impl Polygonal, b: &impl Polygonal) -> bool {
+fn winding_number<F: Float>(a: &impl Polygonal<F>, p: Vec2<F>) -> Containment {
+    let zero = F::zero();
+    let mut winding = 0;
+    for (sp_1, sp_2) in a.lines_iter() {
+        // which side of the edge the point is on, with 0 meaning it's in line with it
+        let side = Vec2::cross(sp_2 - sp_1, p - sp_1);
+        if side == zero && (sp_1.x - p.x) * (sp_2.x - p.x) <= zero && (sp_1.y - p.y) * (sp_2.y - p.y) <= zero {
+            return Containment::Edge;
+        }
+        // count the edges crossing the horizontal through p, upwards on one side and downwards on the other
+        if sp_1.y <= p.y && p.y < sp_2.y && side > zero {
+            winding += 1;
+        }
+        else if sp_2.y <= p.y && p.y < sp_1.y && side < zero {
+            winding -= 1;
+        }
+    }
+    if winding != 0 {
+        Containment::Inside
+    }
+    else {
+        Containment::Outside
+    }
+}
+
+/// Whether `a` covers every part of `b`.
+/// With a concave `a`, `b` can have every one of its points inside while an edge cuts across a notch,
+/// so each edge of `b` is also split wherever it meets the edges of `a`, and each piece checked.
+/// A hole in `a` can also sit entirely inside `b` without touching its outline, which leaves a corner of the hole inside `b`.
+fn obscures<F: Float>(a: &impl Polygonal<F>, b: &impl Polygonal<F>, epsilon: F) -> bool {
+    let (zero, one) = (F::zero(), F::one());
     for point in b.points_iter() {
-        if !inclusive_contains(a, point) {
+        if !inclusive_contains(a, point, epsilon) {
             return false;
         }
     }
+    if a.hole_points_iter().any(|point| get_containment(b, point, FillRule::EvenOdd, epsilon) == Containment::Inside) {
+        return false;
+    }
+    for (start, end) in b.lines_iter() {
+        let direction = end - start;
+        let crossings = a.lines_iter()
+            .map(|(a_start, a_end)| intersection_parameters(start, direction, a_start, a_end - a_start))
+            .filter(|vectp![lambda, mu]| zero < *lambda && *lambda < one && zero <= *mu && *mu <= one)
+            .map(|vectp![lambda, _]| lambda);
+        let cuts = std::iter::once(zero).chain(crossings).chain(std::iter::once(one))
+            .sorted_by(F::total_cmp)
+            .collect_vec();
+        for (from, to) in cuts.into_iter().tuple_windows() {
+            if !inclusive_contains(a, start + direction * ((from + to) / F::from_f64(2.0)), epsilon) {
+                return false;
+            }
+        }
+    }
     true
 }
 
-pub trait Polygonal {
+/// Whether `p` is no further than `epsilon` from the outline of `a`.
+fn near_edge<F: Float>(a: &impl Polygonal<F>, p: Vec2<F>, epsilon: F) -> bool {
+    a.lines_iter().any(|(start, end)| {
+        let edge = end - start;
+        let t = (Vec2::dot(p - start, edge) / edge.square_magnitude()).clamp(F::zero(), F::one());
+        (start + edge * t - p).square_magnitude() <= epsilon * epsilon
+    })
+}
+
+/// How close points have to be to an edge to count as on it, as a fraction of the size of the reference cube.
+pub const DEFAULT_EPSILON: f64 = 1e-6;
 
-    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<f64>> + '_>;
-    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<f64>> + '_>;
-    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<f64>, Vec2<f64>)> + '_>;
-    fn left(&self) -> f64 {
-        self.points_iter().map(|p| p.x).reduce(f64::min).unwrap()
+pub trait Polygonal<F: Float = f64> {
+
+    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_>;
+    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<F>> + '_>;
+    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<F>, Vec2<F>)> + '_>;
+    /// The points around any holes cut out of the shape, which are also part of `points_iter`.
+    fn hole_points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
+        Box::new(std::iter::empty())
+    }
+    /// The box around every point, which there has to be at least one of.
+    fn bounds(&self) -> Bounds<F> {
+        Bounds::around(self.points_iter())
+    }
+    fn left(&self) -> F {
+        self.bounds().min.x
     }
-    fn right(&self) -> f64 {
-        self.points_iter().map(|p| p.x).reduce(f64::max).unwrap()
+    fn right(&self) -> F {
+        self.bounds().max.x
     }
-    fn top(&self) -> f64 {
-        self.points_iter().map(|p| p.y).reduce(f64::min).unwrap()
+    fn top(&self) -> F {
+        self.bounds().min.y
     }
-    fn bottom(&self) -> f64 {
-        self.points_iter().map(|p| p.y).reduce(f64::max).unwrap()
+    fn bottom(&self) -> F {
+        self.bounds().max.y
     }
-    fn shift(&mut self, offset: Vec2<f64>) {
+    fn shift(&mut self, offset: Vec2<F>) {
         self.points_iter_mut().for_each(|p| *p += offset);
     }
-    fn width(&self) -> f64 {
-        self.right() - self.left()
+    fn width(&self) -> F {
+        self.bounds().width()
     }
-    fn height(&self) -> f64 {
-        self.bottom() - self.top()
+    fn height(&self) -> F {
+        self.bounds().height()
     }
-    fn centre(&self) -> Vec2<f64> {
-        vect![self.left() + self.right(), self.top() + self.bottom()] / 2.0
+    fn centre(&self) -> Vec2<F> {
+        self.bounds().centre()
     }
-    fn move_to(&mut self, point: Vec2<f64>) {
+    fn move_to(&mut self, point: Vec2<F>) {
         self.shift(point - self.centre())
     }
+    /// Where `p` is relative to this shape, using `rule` to decide which parts of it are inside.
+    /// Points exactly on an edge are on it; the occlusion code uses the same test, only with some leeway either side.
+    fn contains(&self, p: Vec2<F>, rule: FillRule) -> Containment where Self: Sized {
+        get_containment(self, p, rule, F::zero())
+    }
+}
+
+/// The box around a shape, from its top left corner to its bottom right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds<F: Float = f64> {
+    pub min: Vec2<F>,
+    pub max: Vec2<F>,
+}
+
+impl<F: Float> Bounds<F> {
+    /// The box around every one of `points`, found in one pass over them. There has to be at least one.
+    pub fn around(mut points: impl Iterator<Item = Vec2<F>>) -> Self {
+        let first = points.next().unwrap();
+        let (min, max) = points.fold((first, first), |(min, max), p| (min.min(p), max.max(p)));
+        Bounds { min, max }
+    }
+    pub fn width(&self) -> F {
+        self.max.x - self.min.x
+    }
+    pub fn height(&self) -> F {
+        self.max.y - self.min.y
+    }
+    pub fn centre(&self) -> Vec2<F> {
+        vect![self.min.x + self.max.x, self.min.y + self.max.y] / F::from_f64(2.0)
+    }
+    /// The same box moved by `offset`.
+    /// Moving every point the same way keeps the same ones furthest out, so this is exactly the box around the moved points.
+    pub fn shifted(self, offset: Vec2<F>) -> Self {
+        Bounds { min: self.min + offset, max: self.max + offset }
+    }
+    /// Whether the two boxes share any point, including along their edges.
+    pub fn overlaps(&self, other: &Bounds<F>) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x && self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+}
+
+/// Reasons a shape couldn't be built from the points and normals given for it.
+#[derive(Debug, PartialEq)]
+pub enum ShapeError {
+    /// A primitive needs at least three points to enclose anything.
+    TooFewPoints { count: usize },
+    /// Two consecutive points of a primitive are the same.
+    RepeatedPoint { index: usize, point: Vec2<f64> },
+    /// A component's normal has no direction, so it can't be lit.
+    ZeroNormal,
+}
+
+impl Display for ShapeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShapeError::TooFewPoints { count } => write!(f, "a primitive needs at least 3 points, but {} were given", count),
+            ShapeError::RepeatedPoint { index, point } => write!(f, "points {} and {} are both {}", index, index + 1, point),
+            ShapeError::ZeroNormal => write!(f, "a component's normal can't be zero"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct ShapePrimitive {
-    pub points: Vec<Vec2<f64>>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ShapePrimitive<F: Float = f64> {
+    pub points: Vec<Vec2<F>>,
 }
 
-impl Polygonal for ShapePrimitive {
+impl<F: Float> Polygonal<F> for ShapePrimitive<F> {
 
-    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<f64>> + '_> {
+    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
         Box::new(self.points.iter().cloned())
     }
-    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<f64>> + '_> {
+    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<F>> + '_> {
         Box::new(self.points.iter_mut())
     }
-    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<f64>, Vec2<f64>)> + '_> {
+    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<F>, Vec2<F>)> + '_> {
         Box::new(self.points.iter().cloned().circular_tuple_windows())
     }
 }
-impl ShapePrimitive {
+impl<F: Float> ShapePrimitive<F> {
 
-    pub fn del_if_obscured_by(self, other: &impl Polygonal) -> Option<Self> {
-        Some(self).del_if_obscured_by(other)
+    /// Creates a primitive from the points around its outline.
+    /// There must be at least three points, and no point may repeat the one before it.
+    /// The outline closes itself, so points at the end coming back to the first are dropped, rather than leaving a side with no length.
+    pub fn from_points(points: impl IntoIterator<Item = Vec2<F>>) -> Result<Self, ShapeError> {
+        let mut points = points.into_iter().collect_vec();
+        while points.len() > 1 && points.last() == points.first() {
+            points.pop();
+        }
+        if points.len() < 3 {
+            return Err(ShapeError::TooFewPoints { count: points.len() });
+        }
+        if let Some((index, (&point, _))) = points.iter().tuple_windows().find_position(|(a, b)| a == b) {
+            return Err(ShapeError::RepeatedPoint { index, point: vect![point.x.to_f64(), point.y.to_f64()] });
+        }
+        Ok(ShapePrimitive { points })
     }
-    pub fn del_points_obscured_by(self, other: &impl Polygonal) -> Option<Self> {
-        Some(self).del_points_obscured_by(other)
+    /// An axis-aligned rectangle with its top-left corner at `origin`.
+    pub fn rect(origin: Vec2<F>, size: Vec2<F>) -> Result<Self, ShapeError> {
+        ShapePrimitive::from_points([
+            origin,
+            origin + vect![size.x, F::zero()],
+            origin + size,
+            origin + vect![F::zero(), size.y],
+        ])
     }
-    pub fn generate_d(&self) -> String {
-        let iter = ToDStringIter::from_vec(&self.points);
-        iter.collect()
+    pub fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Option<Self> {
+        Some(self).del_if_obscured_by(other, epsilon)
     }
-    pub fn combine_common_edges(&self, other: &ShapePrimitive) -> Option<ShapePrimitive> {
-
-        let cmn1 = self.points.iter().cloned().enumerate().find_or_first(|(_, p)| other.points.contains(p));
-        let Some((mut my_i1, mut cmn1)) = cmn1 else {
-            return None;
+    pub fn generate_d(&self, options: &PathOptions) -> String {
+        let mut canonical = self.clone();
+        if options.canonical_start {
+            canonical.canonicalise();
+        }
+        let commands = || if options.explicit_close {
+            ToSvgCommandIter::with_explicit_close(&canonical.points)
+        }
+        else {
+            ToSvgCommandIter::from_vec(&canonical.points)
         };
-        if my_i1 == 0 {
-            my_i1 = self.points.len() - 1;
-            while other.points.contains(&self.points[my_i1]) {
-                cmn1 = self.points[my_i1];
-                my_i1 -= 1;
-                if my_i1 == 0 {
-                    return Some(self.clone());
-                }
-            }
-            my_i1 = (my_i1 + 1) % self.points.len();
+        let absolute: String = ToDStringIter::from_commands(commands()).collect();
+        if !options.relative {
+            return absolute;
+        }
+        let relative: String = ToDStringIter::from_commands(relative_commands(commands())).collect();
+        if relative.len() < absolute.len() { relative } else { absolute }
+    }
+    /// Rotates the points so the ring starts from its top-most, then left-most vertex, keeping the winding.
+    pub fn canonicalise(&mut self) {
+        let first = self.points.iter()
+            .position_min_by(|a, b| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+        if let Some(first) = first {
+            self.points.rotate_left(first);
         }
+    }
+    /// Fuses this primitive with `other` along the edges they share, giving the outline around both.
+    ///
+    /// The shared edges have to be one unbroken run, and each edge has to be shared end to end,
+    /// so primitives which only touch at a corner aren't fused. Fusing a primitive with itself gives it back as it was.
+    /// The outline is drawn the same way round as `self`, whichever way `other` was drawn.
+    pub fn combine_common_edges(&self, other: &ShapePrimitive<F>) -> Option<ShapePrimitive<F>> {
 
-        let mut my_i2 = (my_i1 + 1) % self.points.len();
-        let mut cmn2 = self.points[my_i2];
-        while other.points.contains(&self.points[my_i2]) {
-            cmn2 = self.points[my_i2];
-            my_i2 = (my_i2 + 1) % self.points.len();
-            if my_i2 == my_i1 {
-                return Some(self.clone());
-            }
+        let backwards = self.draw_direction() != other.draw_direction();
+        let mut other = other.points.clone();
+        if backwards {
+            other.reverse();
         }
-        if my_i2 == 0 {
-            my_i2 = self.points.len() - 1;
+        let len = self.points.len();
+        let other_len = other.len();
+        let position_in_other = |p: Vec2<F>| other.iter().position(|q| *q == p);
+
+        // drawn the same way round, an edge shared by neighbouring primitives runs backwards along the other one
+        let shared = (0..len)
+            .map(|i| {
+                let (Some(start), Some(end)) = (position_in_other(self.points[i]), position_in_other(self.points[(i + 1) % len])) else {
+                    return false;
+                };
+                (end + 1) % other_len == start
+            })
+            .collect_vec();
+
+        let same_outline = other_len == len && (0..len).all(|i| {
+            position_in_other(self.points[i]).map(|start| other[(start + 1) % other_len]) == Some(self.points[(i + 1) % len])
+        });
+        if same_outline {
+            return Some(self.clone());
         }
-        else {
-            my_i2 -= 1;
+        // the run of shared edges starts just after an edge which isn't shared
+        let first = (0..len).find(|i| shared[*i] && !shared[(i + len - 1) % len])?;
+        let run = (0..len).take_while(|n| shared[(first + n) % len]).count();
+        if shared.iter().filter(|s| **s).count() != run {
+            // fusing along two separate runs would leave a hole
+            return None;
         }
 
-        if my_i1 == my_i2 { return None; }
+        // the outline follows `self` from the far end of the run right back round to the start of it,
+        // then follows `other` round between the same two points
+        let start = first;
+        let end = (first + run) % len;
+        let other_start = position_in_other(self.points[start]).unwrap();
+        let other_end = position_in_other(self.points[end]).unwrap();
 
-        let their_i1 = other.points.iter().cloned().enumerate().find_or_first(|(_, p)| *p == cmn1).unwrap().0;
-        let their_i2 = other.points.iter().cloned().enumerate().find_or_first(|(_, p)| *p == cmn2).unwrap().0;
+        let mut points = (0..=(start + len - end) % len).map(|n| self.points[(end + n) % len]).collect_vec();
+        let other_count = (other_end + other_len - other_start) % other_len;
+        points.extend((1..other_count).map(|n| other[(other_start + n) % other_len]));
 
-        let backwards = self.draw_direction() != other.draw_direction();
-
-        let mut points = vec![self.points[my_i2]];
-        let mut index = (my_i2 + 1) % self.points.len();
-
-        #[derive(PartialEq)]
-        enum Which {
-            Me,
-            Them,
-        }
-        let mut which = Which::Me;
-        while index != my_i2 || which != Which::Me {
-            match which {
-                Which::Me => {
-                    points.push(self.points[index]);
-                    index = (index + 1) % self.points.len();
-                    if index == my_i1 {
-                        index = their_i1;
-                        which = Which::Them;
-                    }
-                },
-                Which::Them => {
-                    points.push(other.points[index]);
-                    if backwards {
-                        if index == 0 {
-                            index = other.points.len() - 1;
-                        }
-                        else {
-                            index -= 1;
-                        }
-                    }
-                    else {
-                        index = (index + 1) % other.points.len();
-                    }
-                    if index == their_i2 {
-                        index = my_i2;
-                        which = Which::Me;
-                    }
-                },
+        // where the two outlines meet, the corners often end up in a straight line
+        let mut fused = ShapePrimitive { points };
+        fused.remove_straight_corner(self.points[start]);
+        fused.remove_straight_corner(self.points[end]);
+        let mut points = fused.points.into_iter().dedup().collect_vec();
+        while points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() < 3 {
+            return None;
+        }
+        Some(ShapePrimitive { points })
+    }
+    /// Takes `point` out of the outline if the edges either side of it carry on in the same direction.
+    fn remove_straight_corner(&mut self, point: Vec2<F>) {
+        let len = self.points.len();
+        let Some(i) = self.points.iter().position(|p| *p == point) else { return; };
+        let before = self.points[(i + len - 1) % len];
+        let after = self.points[(i + 1) % len];
+        let (in_edge, out_edge) = (point - before, after - point);
+        if len > 3 && Vec2::cross(in_edge, out_edge) == F::zero() && Vec2::dot(in_edge, out_edge) > F::zero() {
+            self.points.remove(i);
+        }
+    }
+    /// Takes out points repeating the one before them, and corners turning through less than `epsilon`,
+    /// measured as the sine of the angle between the edges either side. The primitive is never left with fewer than three points.
+    pub fn simplify(&mut self, epsilon: F) {
+        let redundant = |points: &[Vec2<F>], i: usize| {
+            let len = points.len();
+            let (before, point, after) = (points[(i + len - 1) % len], points[i], points[(i + 1) % len]);
+            if point == before {
+                return true;
+            }
+            let (in_edge, out_edge) = (point - before, after - point);
+            if out_edge == vect![F::zero(), F::zero()] {
+                // the repeat gets taken out when it comes round
+                return false;
+            }
+            let turn = Vec2::cross(in_edge, out_edge) / (in_edge.magnitude() * out_edge.magnitude());
+            turn.abs() < epsilon && Vec2::dot(in_edge, out_edge) > F::zero()
+        };
+        // step back after taking a point out, as the one before it might have been straightened out too
+        let mut i = 0;
+        let mut unchanged = 0;
+        while self.points.len() > 3 && unchanged < self.points.len() {
+            if redundant(&self.points, i) {
+                self.points.remove(i);
+                i = (i + self.points.len() - 1) % self.points.len();
+                unchanged = 0;
+            }
+            else {
+                i = (i + 1) % self.points.len();
+                unchanged += 1;
             }
         }
+    }
+    /// A copy of the primitive grown by `distance` on every side, or shrunk if it's negative, keeping each edge parallel to where it was.
+    /// Corners move no more than `MITER_LIMIT` times `distance`, so thin spikes don't shoot off into the distance.
+    /// If any edge would end up turned around, which is where the outline would cross itself, the primitive is given back unchanged.
+    pub fn outset(&self, distance: F) -> ShapePrimitive<F> {
+        let mut ring = self.clone();
+        ring.simplify(F::zero());
+        let winding = ring.signed_area().signum();
+        if distance == F::zero() || winding == F::zero() {
+            return self.clone();
+        }
+        // the outside is to the right of each edge when the primitive is drawn counter-clockwise, and to the left otherwise
+        let outward = |a: Vec2<F>, b: Vec2<F>| (b - a).normalise().perp() * -winding;
+        let moved = ring.points.iter().cloned()
+            .circular_tuple_windows()
+            .map(|(before, point, after)| {
+                let (n1, n2) = (outward(before, point), outward(point, after));
+                // where the two edges either side meet once they've both been moved out
+                let mut offset = (n1 + n2) / (F::one() + Vec2::dot(n1, n2)).max(F::EPSILON) * distance;
+                let limit = F::from_f64(MITER_LIMIT) * distance.abs();
+                if offset.magnitude() > limit {
+                    offset = offset.normalise() * limit;
+                }
+                point + offset
+            })
+            .collect_vec();
+        // `circular_tuple_windows` starts from the second point
+        let mut outset = ShapePrimitive { points: moved };
+        outset.points.rotate_right(1);
+        let turned = ring.lines_iter().zip(outset.lines_iter())
+            .any(|((a, b), (c, d))| Vec2::dot(b - a, d - c) <= F::zero());
+        if turned || outset.signed_area().signum() != winding {
+            return self.clone();
+        }
+        outset
+    }
+    /// Whether every corner of the primitive turns the same way, ignoring any straight ones.
+    pub fn is_convex(&self) -> bool {
+        let turns = self.points.iter().cloned()
+            .circular_tuple_windows()
+            .map(|(a, b, c)| Vec2::cross(b - a, c - b))
+            .filter(|turn| *turn != F::zero())
+            .collect_vec();
+        turns.iter().all(|turn| *turn > F::zero()) || turns.iter().all(|turn| *turn < F::zero())
+    }
+    /// Convex pieces which between them cover exactly what the primitive does, each drawn the same way round as it.
+    ///
+    /// The outline is cut into triangles by clipping off ears, then neighbouring triangles are joined back together
+    /// wherever what they'd make is still convex, which leaves no more than four times as many pieces as there need to be.
+    /// The outline has to be simple. A convex primitive, including one whose points all lie on a line, is given back whole,
+    /// as is one crossing itself so no ear can be found, and fewer than three points give no pieces at all.
+    pub fn decompose_convex(&self) -> Vec<ShapePrimitive<F>> {
+        if self.points.len() < 3 {
+            return vec![];
+        }
+        if self.is_convex() {
+            return vec![self.clone()];
+        }
+        // straight corners would be the tips of ears with no area
+        let mut ring = self.clone();
+        ring.simplify(F::zero());
+        let winding = ring.signed_area().signum();
+        let points = &ring.points;
+        // how far `c` is to the inside of the line from `a` to `b`, whichever way round the outline goes
+        let turn = |a: usize, b: usize, c: usize| Vec2::cross(points[b] - points[a], points[c] - points[b]) * winding;
 
-        Some(ShapePrimitive { points })
+        let mut left = (0..points.len()).collect_vec();
+        let mut pieces: Vec<Vec<usize>> = vec![];
+        while left.len() > 3 {
+            let len = left.len();
+            let ear = (0..len).find(|&i| {
+                let (a, b, c) = (left[(i + len - 1) % len], left[i], left[(i + 1) % len]);
+                // nothing else can be inside the triangle, or even on its edges, or cutting it off would cut through the outline
+                turn(a, b, c) > F::zero() && left.iter()
+                    .filter(|&&p| p != a && p != b && p != c)
+                    .all(|&p| turn(a, b, p) < F::zero() || turn(b, c, p) < F::zero() || turn(c, a, p) < F::zero())
+            });
+            let Some(i) = ear else { return vec![self.clone()]; };
+            pieces.push(vec![left[(i + len - 1) % len], left[i], left[(i + 1) % len]]);
+            left.remove(i);
+        }
+        pieces.push(left);
+
+        // joining two pieces along the edge they share only ever changes the corners at either end of it
+        let joined = |a: &[usize], b: &[usize]| -> Option<Vec<usize>> {
+            let (i, j) = (0..a.len()).cartesian_product(0..b.len())
+                .find(|&(i, j)| a[i] == b[(j + 1) % b.len()] && a[(i + 1) % a.len()] == b[j])?;
+            // round `a` from the far end of the shared edge back to its start, then round `b` between the two
+            let mut ring = (1..=a.len()).map(|n| a[(i + n) % a.len()]).collect_vec();
+            ring.extend((2..b.len()).map(|n| b[(j + n) % b.len()]));
+            let convex = ring.iter().cloned().circular_tuple_windows().all(|(p, q, r)| turn(p, q, r) >= F::zero());
+            convex.then_some(ring)
+        };
+        let mut merged = true;
+        while merged {
+            merged = false;
+            for (i, j) in (0..pieces.len()).tuple_combinations() {
+                if let Some(ring) = joined(&pieces[i], &pieces[j]) {
+                    pieces[i] = ring;
+                    pieces.remove(j);
+                    merged = true;
+                    break;
+                }
+            }
+        }
+        pieces.into_iter()
+            .map(|piece| ShapePrimitive { points: piece.into_iter().map(|i| points[i]).collect() })
+            .collect()
+    }
+    /// The parts of this primitive outside of `other`, which has to be convex.
+    ///
+    /// Each edge of `other` cuts off the part of what's left lying beyond it, so the pieces tile the remainder exactly.
+    /// The primitive comes back whole if it doesn't overlap `other`, and there's nothing left if it's entirely covered.
+    pub fn subtract(&self, other: &ShapePrimitive<F>) -> Vec<ShapePrimitive<F>> {
+        // the inside of `other` is to the left of each edge when it's drawn counter-clockwise, and to the right otherwise
+        let winding = other.signed_area().signum();
+        let mut pieces = vec![];
+        let mut remaining = self.points.clone();
+        for (a, b) in other.lines_iter() {
+            let inside = |p: Vec2<F>| Vec2::cross(b - a, p - a) * winding;
+            pieces.extend(clipped_primitive(clip_to_half_plane(&remaining, |p| -inside(p))));
+            remaining = clip_to_half_plane(&remaining, inside);
+            if remaining.is_empty() {
+                break;
+            }
+        }
+        // cutting along edges which only touch the primitive would split it up for nothing
+        if clipped_primitive(remaining).is_none() {
+            return vec![self.clone()];
+        }
+        pieces
+    }
+    /// Whether this primitive and `other` cover any of the same area, rather than just touching.
+    /// This can only be worked out when one of them is convex. Otherwise they're assumed to overlap if their bounds do.
+    pub fn overlaps(&self, other: &ShapePrimitive<F>) -> bool {
+        if !self.bounds().overlaps(&other.bounds()) {
+            return false;
+        }
+        let (shape, clip) = match (self.is_convex(), other.is_convex()) {
+            (_, true) => (self, other),
+            (true, false) => (other, self),
+            (false, false) => return true,
+        };
+        let outside: F = shape.subtract(clip).iter().map(|p| p.signed_area().abs()).sum();
+        shape.signed_area().abs() - outside > F::from_f64(AREA_EPSILON)
     }
-    fn draw_direction(&self) -> CircleDirection {
+    /// Whether every part of `other` is inside this primitive, counting its edges as inside.
+    pub fn encloses(&self, other: &ShapePrimitive<F>) -> bool {
+        obscures(self, other, F::zero())
+    }
+    /// What's left of this primitive once everything `other` covers is cut away.
+    fn visible_parts(&self, other: &Shape<F>, epsilon: F) -> Vec<ShapePrimitive<F>> {
+        let mut pieces = vec![self.clone()];
+        for component in &other.components {
+            // cutting away a whole primitive would take whatever shows through its holes with it
+            if !component.holes.is_empty() {
+                pieces.retain(|piece| !obscures(component, piece, epsilon));
+                continue;
+            }
+            for clip in &component.primitives {
+                if clip.is_convex() {
+                    pieces = pieces.iter().flat_map(|piece| piece.subtract(clip)).collect();
+                }
+                else {
+                    pieces.retain(|piece| !obscures(clip, piece, epsilon));
+                }
+            }
+        }
+        pieces
+    }
+    /// The area enclosed by the primitive, which is positive when drawn counter-clockwise.
+    pub fn signed_area(&self) -> F {
+        self.lines_iter().map(|(p1, p2)| Vec2::cross(p1, p2)).sum::<F>() / F::from_f64(2.0)
+    }
+    /// Rounds every point to the integer point `snap` gives it, merging neighbouring points that end up in the same place.
+    /// Gives `None` if the primitive collapses or turns inside out as a result.
+    pub fn round_points(mut self, snap: &VertexSnap<F>) -> Option<Self> {
+        let area = self.signed_area();
+        self.points = self.points.into_iter()
+            .map(|p| snap.round(p))
+            .dedup()
+            .collect();
+        while self.points.len() > 1 && self.points.first() == self.points.last() {
+            self.points.pop();
+        }
+        let rounded_area = self.signed_area();
+        if self.points.len() < 3 || rounded_area == F::zero() || rounded_area.signum() != area.signum() {
+            None
+        }
+        else {
+            Some(self)
+        }
+    }
+    /// Which way round the outline goes, judged by which way it turns overall.
+    pub fn draw_direction(&self) -> CircleDirection {
         let line_vectors: Vec<_> = self.points.iter().cloned().circular_tuple_windows().map(|(p1, p2)| p2 - p1).collect();
-        let mut angle = 0.0;
-        for (line1, line2) in line_vectors.into_iter().circular_tuple_windows::<(Vec2<f64>, Vec2<f64>)>() {
-            angle += f64::asin(Vec2::cross(line1, line2) / (line1.magnitude() * line2.magnitude()));
+        let mut angle = F::zero();
+        for (line1, line2) in line_vectors.into_iter().circular_tuple_windows::<(Vec2<F>, Vec2<F>)>() {
+            angle += F::asin(Vec2::cross(line1, line2) / (line1.magnitude() * line2.magnitude()));
         }
-        if angle > 0.0 {
+        if angle > F::zero() {
             CircleDirection::CounterClockwise
         }
         else {
             CircleDirection::Clockwise
         }
     }
+    /// Reverses the outline if it isn't already drawn in `direction`.
+    pub fn set_direction(&mut self, direction: CircleDirection) {
+        if self.draw_direction() != direction {
+            self.points.reverse();
+        }
+    }
 }
 
-#[derive(PartialEq)]
-enum CircleDirection {
+/// Which way round an outline is drawn, with counter-clockwise being the way the angle between the x and y axes is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircleDirection {
     Clockwise,
     CounterClockwise,
 }
 
 #[derive(Debug, Clone)]
-pub struct ShapeComponent {
-    // TODO: having everything in here public is *fine*, but should probably be changed at some point.
-    pub normal: Vec3<f64>,
-    pub primitives: Vec<ShapePrimitive>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ShapeComponent<F: Float = f64> {
+    normal: Vec3<F>,
+    primitives: Vec<ShapePrimitive<F>>,
+    holes: Vec<ShapePrimitive<F>>,
+    attributes: Vec<(String, String)>,
+    stroke: Option<ComponentStroke>,
+    /// The box around the component, worked out the first time it's asked for and forgotten whenever any points might change.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bounds: OnceLock<Bounds<F>>,
+}
+
+/// The parts of a face's `style` in the components file which say how it's outlined, each kept as it was written.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ComponentStroke {
+    pub colour: Option<String>,
+    pub width: Option<String>,
+    pub linejoin: Option<String>,
+}
+
+impl ComponentStroke {
+    /// The declarations for each part given, each starting with `;` to go after a fill.
+    pub fn css(&self) -> String {
+        [("stroke", &self.colour), ("stroke-width", &self.width), ("stroke-linejoin", &self.linejoin)].into_iter()
+            .filter_map(|(property, value)| Some(format!(";{}:{}", property, value.as_ref()?)))
+            .collect()
+    }
 }
 
-impl Polygonal for ShapeComponent {
+impl<F: Float> Polygonal<F> for ShapeComponent<F> {
 
-    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<f64>> + '_> {
-        Box::new(self.primitives.iter().map(|p| p.points_iter()).flatten())
+    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
+        Box::new(self.primitives.iter().chain(&self.holes).flat_map(|p| p.points_iter()))
+    }
+    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<F>> + '_> {
+        self.bounds = OnceLock::new();
+        Box::new(self.primitives.iter_mut().chain(&mut self.holes).flat_map(|p| p.points_iter_mut()))
+    }
+    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<F>, Vec2<F>)> + '_> {
+        Box::new(self.primitives.iter().chain(&self.holes).flat_map(|p| p.lines_iter()))
     }
-    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<f64>> + '_> {
-        Box::new(self.primitives.iter_mut().map(|p| p.points_iter_mut()).flatten())
+    fn hole_points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
+        Box::new(self.holes.iter().flat_map(|p| p.points_iter()))
     }
-    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<f64>, Vec2<f64>)> + '_> {
-        Box::new(self.primitives.iter().map(|p| p.lines_iter()).flatten())
+    fn bounds(&self) -> Bounds<F> {
+        *self.bounds.get_or_init(|| Bounds::around(self.points_iter()))
+    }
+    /// Moves the box along with the points, rather than forgetting it.
+    fn shift(&mut self, offset: Vec2<F>) {
+        let bounds = self.bounds.take().map(|bounds| bounds.shifted(offset));
+        self.primitives.iter_mut().chain(&mut self.holes).for_each(|p| p.shift(offset));
+        if let Some(bounds) = bounds {
+            self.bounds = OnceLock::from(bounds);
+        }
     }
 }
-impl ShapeComponent {
+impl<F: Float> ShapeComponent<F> {
 
-    pub fn del_if_obscured_by(self, other: &impl Polygonal) -> Option<Self> {
-        Some(self).del_if_obscured_by(other)
+    /// Creates a component facing along `normal`, which doesn't need to be normalised beforehand.
+    pub fn new(normal: Vec3<F>, primitives: Vec<ShapePrimitive<F>>) -> Result<Self, ShapeError> {
+        if normal.square_magnitude() == F::zero() {
+            return Err(ShapeError::ZeroNormal);
+        }
+        Ok(ShapeComponent { normal: normal.normalise(), primitives, holes: vec![], attributes: vec![], stroke: None, bounds: OnceLock::new() })
+    }
+    /// Cuts `holes` out of the component's primitives.
+    pub fn with_holes(mut self, holes: Vec<ShapePrimitive<F>>) -> Self {
+        self.holes = holes;
+        self.bounds = OnceLock::new();
+        self
+    }
+    /// Writes each of `attributes` on the paths drawn for the component.
+    pub fn with_attributes(mut self, attributes: Vec<(String, String)>) -> Self {
+        self.attributes = attributes;
+        self
+    }
+    /// Outlines the paths drawn for the component with `stroke`, if there is one.
+    pub fn with_stroke(mut self, stroke: Option<ComponentStroke>) -> Self {
+        self.stroke = stroke;
+        self
+    }
+    /// The direction the component faces, which is always normalised.
+    pub fn normal(&self) -> Vec3<F> {
+        self.normal
     }
-    pub fn del_points_obscured_by(self, other: &impl Polygonal) -> Option<Self> {
-        Some(self).del_points_obscured_by(other)
+    pub fn primitives(&self) -> &[ShapePrimitive<F>] {
+        &self.primitives
     }
-    pub fn generate_d(&self) -> String {
+    /// The primitives, to be changed in place, which forgets the box around the component.
+    pub fn primitives_mut(&mut self) -> &mut Vec<ShapePrimitive<F>> {
+        self.bounds = OnceLock::new();
+        &mut self.primitives
+    }
+    /// Openings cut out of the primitives, like a skylight in a roof.
+    /// Their outlines are part of the component's, so points inside a hole are outside the component.
+    pub fn holes(&self) -> &[ShapePrimitive<F>] {
+        &self.holes
+    }
+    /// The holes, to be changed in place, which forgets the box around the component.
+    pub fn holes_mut(&mut self) -> &mut Vec<ShapePrimitive<F>> {
+        self.bounds = OnceLock::new();
+        &mut self.holes
+    }
+    /// Attributes carried over from the element the face was read from, like a `class`, written on every path drawn for it.
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+    /// The line the face was drawn with in its `style`, such as for a window frame, if it had one.
+    pub fn stroke(&self) -> Option<&ComponentStroke> {
+        self.stroke.as_ref()
+    }
+    /// Whether the two are drawn with the same attributes and stroke, so can be drawn as one.
+    pub fn styled_like(&self, other: &ShapeComponent<F>) -> bool {
+        self.attributes == other.attributes && self.stroke == other.stroke
+    }
+    /// A copy of this component made of `primitives` instead, keeping only the holes which are still inside one of them.
+    fn with_primitives(&self, primitives: Vec<ShapePrimitive<F>>, epsilon: F) -> Self {
+        let holes = self.holes.iter()
+            .filter(|hole| primitives.iter().any(|p| obscures(p, *hole, epsilon)))
+            .cloned()
+            .collect();
+        ShapeComponent { primitives, holes, bounds: OnceLock::new(), ..self.clone() }
+    }
+    /// A component made of a single four-sided primitive.
+    pub fn quad(p1: Vec2<F>, p2: Vec2<F>, p3: Vec2<F>, p4: Vec2<F>, normal: Vec3<F>) -> Result<Self, ShapeError> {
+        ShapeComponent::new(normal, vec![ShapePrimitive::from_points([p1, p2, p3, p4])?])
+    }
+    pub fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Option<Self> {
+        Some(self).del_if_obscured_by(other, epsilon)
+    }
+    pub fn del_whats_obscured_by(self, other: &Shape<F>, epsilon: F) -> Option<Self> {
+        Some(self).del_whats_obscured_by(other, epsilon)
+    }
+    pub fn round_points(self, snap: &VertexSnap<F>) -> Option<Self> {
+        let primitives = self.primitives.into_iter().filter_map(|p| p.round_points(snap)).collect_vec();
+        let holes = self.holes.into_iter().filter_map(|p| p.round_points(snap)).collect_vec();
+        if primitives.is_empty() {
+            None
+        }
+        else {
+            Some(ShapeComponent { primitives, holes, bounds: OnceLock::new(), ..self })
+        }
+    }
+    pub fn generate_d(&self, options: &PathOptions) -> String {
         // I mean this works, but it can definitely be done better
         let mut result = String::new();
-        for primitive in &self.primitives {
-            result += &primitive.generate_d();
+        match options.anti_seam {
+            AntiSeam::Outset { distance } => {
+                // holes grow by shrinking, so the face around them still gets bigger
+                for primitive in &self.primitives {
+                    result += &primitive.outset(F::from_f64(distance)).generate_d(options);
+                }
+                for hole in &self.holes {
+                    result += &hole.outset(F::from_f64(-distance)).generate_d(options);
+                }
+            }
+            _ => for primitive in self.primitives.iter().chain(&self.holes) {
+                result += &primitive.generate_d(options);
+            }
         }
         result
     }
-    pub fn generate_path<'a, 'b>(&'a self, light_vector: Vec3<f64>, object_colour: Vec3<f64>) -> quick_xml::events::Event<'b> {
-        let mut tag_bytes = quick_xml::events::BytesStart::new("path");
-        let d = self.generate_d();
-        tag_bytes.push_attribute(("d", d.as_str()));
-        tag_bytes.push_attribute(("style", self.generate_css(light_vector, object_colour).as_str()));
-        quick_xml::events::Event::Empty(tag_bytes)
+    /// Creates a `<path>` element for this component, with `style` as its style attribute, along with any attributes it carries.
+    pub fn generate_path<'a>(&self, style: &str, options: &PathOptions) -> quick_xml::events::Event<'a> {
+        path_event(&self.generate_d(options), style, &self.attributes)
     }
-    fn generate_css(&self, light_vector: Vec3<f64>, object_colour: Vec3<f64>) -> String {
-        let mut brightness = Vec3::dot(self.normal, light_vector);
-        brightness = f64::max(brightness, 0.0);
-        let object_colour = object_colour * brightness;
-        // little bit funky but it works out fine
-        let object_colour = object_colour * 256.0;
-        format!("fill:#{:02x}{:02x}{:02x}", object_colour.x as u8, object_colour.y as u8, object_colour.z as u8)
+    /// The colour of this component under `lighting`, with each light added together and clamped so no channel goes past full.
+    pub fn shade(&self, lighting: &Lighting, object_colour: Vec3<f64>) -> Vec3<f64> {
+        let decode = |colour: Vec3<f64>| if lighting.gamma_correct { colour::to_linear(colour) } else { colour };
+        let object_colour = decode(object_colour);
+        let ambient = match lighting.ambient_colour {
+            Some(sky) => decode(sky) * lighting.ambient,
+            None => object_colour * lighting.ambient,
+        };
+        let shaded = lighting.lights().fold(ambient, |total, light| {
+            let normal = vect![self.normal.x.to_f64(), self.normal.y.to_f64(), self.normal.z.to_f64()];
+            let mut brightness = Vec3::dot(normal, light.direction);
+            brightness = f64::max(brightness, 0.0);
+            total + object_colour * decode(light.colour) * (light.intensity * lighting.diffuse * brightness)
+        });
+        let shaded = colour::clamp(shaded);
+        if lighting.gamma_correct { colour::from_linear(shaded) } else { shaded }
+    }
+    /// The style for this component filled with `fill`.
+    /// The colour will usually come from `shade`, with any other effects applied on top.
+    /// Any stroke the face was drawn with in the components file goes after the fill.
+    pub fn generate_css(&self, fill: Vec3<f64>) -> String {
+        self.with_stroke_css(self.with_fill_rule(format!("fill:{}", colour::hex(fill))))
+    }
+    /// The style for this component drawn as only its outline, in `colour` and `width` wide.
+    pub fn generate_wireframe_css(&self, colour: Vec3<f64>, width: f64) -> String {
+        format!("fill:none;stroke:{};stroke-width:{}", colour::hex(colour), width)
+    }
+    /// The style for this component filled with the gradient with the given `id`.
+    pub fn generate_gradient_css(&self, id: &str) -> String {
+        self.with_stroke_css(self.with_fill_rule(format!("fill:url(#{})", id)))
+    }
+    fn with_stroke_css(&self, css: String) -> String {
+        match &self.stroke {
+            Some(stroke) => css + &stroke.css(),
+            None => css,
+        }
+    }
+    fn with_fill_rule(&self, css: String) -> String {
+        // the holes are only left unfilled if every crossing of an outline flips between inside and out
+        if self.holes.is_empty() {
+            css
+        }
+        else {
+            css + ";fill-rule:evenodd"
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct Shape {
-    components: Vec<ShapeComponent>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Shape<F: Float = f64> {
+    components: Vec<ShapeComponent<F>>,
+    /// The box around the shape, worked out the first time it's asked for and forgotten whenever any points might change.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bounds: OnceLock<Bounds<F>>,
 }
 
-impl Polygonal for Shape {
-    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<f64>> + '_> {
+impl<F: Float> Polygonal<F> for Shape<F> {
+    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
         Box::new(self.components.iter().map(|p| p.points_iter()).flatten())
     }
-    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<f64>> + '_> {
+    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<F>> + '_> {
+        self.bounds = OnceLock::new();
         Box::new(self.components.iter_mut().map(|p| p.points_iter_mut()).flatten())
     }
-    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<f64>, Vec2<f64>)> + '_> {
+    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<F>, Vec2<F>)> + '_> {
         Box::new(self.components.iter().map(|p| p.lines_iter()).flatten())
     }
+    fn hole_points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
+        Box::new(self.components.iter().flat_map(|p| p.hole_points_iter()))
+    }
+    fn bounds(&self) -> Bounds<F> {
+        *self.bounds.get_or_init(|| Bounds::around(self.points_iter()))
+    }
+    /// Moves the box along with the points, rather than forgetting it.
+    fn shift(&mut self, offset: Vec2<F>) {
+        let bounds = self.bounds.take().map(|bounds| bounds.shifted(offset));
+        self.components.iter_mut().for_each(|c| c.shift(offset));
+        if let Some(bounds) = bounds {
+            self.bounds = OnceLock::from(bounds);
+        }
+    }
 }
-impl Shape {
-    pub fn new(components: Vec<ShapeComponent>) -> Shape {
-        Shape { components }
+impl<F: Float> Shape<F> {
+    pub fn new(components: Vec<ShapeComponent<F>>) -> Shape<F> {
+        Shape { components, bounds: OnceLock::new() }
     }
-    pub fn component_iter(&self) -> impl Iterator<Item = &ShapeComponent> {
+    /// Starts building a shape one component at a time.
+    ///
+    /// A unit cube, drawn as its three visible faces:
+    /// ```
+    /// use isometric::shapes::{Shape, ShapeComponent};
+    /// use isometric::vect;
+    /// use isometric::vector::{Vec2, Vec3};
+    ///
+    /// let top = ShapeComponent::quad(
+    ///     vect![0.0, 5.0], vect![9.0, 0.0], vect![18.0, 5.0], vect![9.0, 10.0],
+    ///     vect![0.0, 1.0, 0.0],
+    /// ).unwrap();
+    /// let left = ShapeComponent::quad(
+    ///     vect![0.0, 5.0], vect![9.0, 10.0], vect![9.0, 20.0], vect![0.0, 15.0],
+    ///     vect![1.0, 0.0, 0.0],
+    /// ).unwrap();
+    /// let right = ShapeComponent::quad(
+    ///     vect![9.0, 10.0], vect![18.0, 5.0], vect![18.0, 15.0], vect![9.0, 20.0],
+    ///     vect![0.0, 0.0, 1.0],
+    /// ).unwrap();
+    ///
+    /// let cube = Shape::builder()
+    ///     .component(top)
+    ///     .component(left)
+    ///     .component(right)
+    ///     .build();
+    /// assert_eq!(cube.component_iter().count(), 3);
+    /// ```
+    pub fn builder() -> ShapeBuilder<F> {
+        ShapeBuilder::default()
+    }
+    pub fn component_iter(&self) -> impl Iterator<Item = &ShapeComponent<F>> {
         self.components.iter()
     }
-    pub fn into_component_iter(self) -> impl Iterator<Item = ShapeComponent> {
+    pub fn into_component_iter(self) -> impl Iterator<Item = ShapeComponent<F>> {
         self.components.into_iter()
     }
-    pub fn del_if_obscured_by(self, other: &impl Polygonal) -> Option<Self> {
-        Some(self).del_if_obscured_by(other)
+    pub fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Option<Self> {
+        Some(self).del_if_obscured_by(other, epsilon)
+    }
+    pub fn del_whats_obscured_by(self, other: &Shape<F>, epsilon: F) -> Option<Self> {
+        Some(self).del_whats_obscured_by(other, epsilon)
+    }
+    /// Turns every primitive to be drawn counter-clockwise, and every hole clockwise,
+    /// so they don't need to be checked wherever it matters which way round they go.
+    pub fn normalise_winding(&mut self) {
+        // turning primitives round keeps the same points, so the box around them stays as it was
+        for component in &mut self.components {
+            component.primitives.iter_mut().for_each(|p| p.set_direction(CircleDirection::CounterClockwise));
+            component.holes.iter_mut().for_each(|p| p.set_direction(CircleDirection::Clockwise));
+        }
+    }
+    /// Every edge drawn by just one of the shape's primitives or holes, which together make up its outline.
+    /// Edges are matched end to end, either way round, so faces only sharing part of an edge still get a line between them.
+    pub fn boundary_edges(&self) -> Vec<(Vec2<F>, Vec2<F>)> {
+        // adding zero turns -0 into 0, so the two compare the same by their bits
+        let key = |p: Vec2<F>| ((p.x + F::zero()).to_f64().to_bits(), (p.y + F::zero()).to_f64().to_bits());
+        let edge_key = |(a, b): (Vec2<F>, Vec2<F>)| if key(a) <= key(b) { (key(a), key(b)) } else { (key(b), key(a)) };
+        let mut counts: HashMap<_, usize> = HashMap::new();
+        for line in self.lines_iter() {
+            *counts.entry(edge_key(line)).or_default() += 1;
+        }
+        self.lines_iter()
+            .filter(|line| key(line.0) != key(line.1) && counts[&edge_key(*line)] == 1)
+            .collect()
+    }
+    /// A path along `boundary_edges`, with edges joined end to end into as few runs as it can easily manage.
+    /// Runs which get back to where they started are closed.
+    pub fn generate_outline_d(&self) -> String {
+        let key = |p: Vec2<F>| ((p.x + F::zero()).to_f64().to_bits(), (p.y + F::zero()).to_f64().to_bits());
+        let edges = self.boundary_edges();
+        let mut from: HashMap<_, Vec<usize>> = HashMap::new();
+        for (i, (a, b)) in edges.iter().enumerate() {
+            from.entry(key(*a)).or_default().push(i);
+            from.entry(key(*b)).or_default().push(i);
+        }
+        let mut used = vec![false; edges.len()];
+        let mut d = String::new();
+        for start in 0..edges.len() {
+            if used[start] { continue; }
+            used[start] = true;
+            let (first, mut end) = edges[start];
+            d += &format!("M{} {} {} {}", first.x, first.y, end.x, end.y);
+            while let Some(&next) = from[&key(end)].iter().find(|&&i| !used[i]) {
+                used[next] = true;
+                let (a, b) = edges[next];
+                end = if key(a) == key(end) { b } else { a };
+                if key(end) == key(first) {
+                    d += " z";
+                    break;
+                }
+                d += &format!(" {} {}", end.x, end.y);
+            }
+        }
+        d
+    }
+    /// Rounds every point in the shape to the integer point `snap` gives it, dropping anything which collapses as a result.
+    pub fn round_points(self, snap: &VertexSnap<F>) -> Option<Self> {
+        let components = self.components.into_iter().filter_map(|c| c.round_points(snap)).collect_vec();
+        if components.is_empty() {
+            None
+        }
+        else {
+            Some(Shape::new(components))
+        }
+    }
+    /// How many primitives and holes make up the shape, across all its components.
+    pub(crate) fn part_count(&self) -> usize {
+        self.components.iter().map(|c| c.primitives.len() + c.holes.len()).sum()
+    }
+}
+
+/// Collects components for a `Shape`, created with `Shape::builder`.
+#[derive(Debug, Clone, Default)]
+pub struct ShapeBuilder<F: Float = f64> {
+    components: Vec<ShapeComponent<F>>,
+}
+
+impl<F: Float> ShapeBuilder<F> {
+    pub fn component(mut self, component: ShapeComponent<F>) -> Self {
+        self.components.push(component);
+        self
+    }
+    pub fn components(mut self, components: impl IntoIterator<Item = ShapeComponent<F>>) -> Self {
+        self.components.extend(components);
+        self
+    }
+    pub fn build(self) -> Shape<F> {
+        Shape::new(self.components)
+    }
+}
+
+/// A shape moved into place in the scene.
+/// Most of them are only ever moved, so until part of one is deleted it shares its points with the shape it was placed from,
+/// moving them as they're read instead.
+#[derive(Debug, Clone)]
+pub enum ShapeInstance<F: Float = f64> {
+    Shared { template: Arc<Shape<F>>, offset: Vec2<F> },
+    Owned(Shape<F>),
+}
+
+impl<F: Float> Polygonal<F> for ShapeInstance<F> {
+    fn points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
+        match self {
+            ShapeInstance::Shared { template, offset } => Box::new(template.points_iter().map(move |p| p + *offset)),
+            ShapeInstance::Owned(shape) => shape.points_iter(),
+        }
+    }
+    fn points_iter_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec2<F>> + '_> {
+        self.to_mut().points_iter_mut()
+    }
+    fn lines_iter(&self) -> Box<dyn Iterator<Item = (Vec2<F>, Vec2<F>)> + '_> {
+        match self {
+            ShapeInstance::Shared { template, offset } => Box::new(template.lines_iter().map(move |(a, b)| (a + *offset, b + *offset))),
+            ShapeInstance::Owned(shape) => shape.lines_iter(),
+        }
+    }
+    fn hole_points_iter(&self) -> Box<dyn Iterator<Item = Vec2<F>> + '_> {
+        match self {
+            ShapeInstance::Shared { template, offset } => Box::new(template.hole_points_iter().map(move |p| p + *offset)),
+            ShapeInstance::Owned(shape) => shape.hole_points_iter(),
+        }
+    }
+    /// A shared instance's box is its template's, moved, so is only ever worked out once for every copy.
+    fn bounds(&self) -> Bounds<F> {
+        match self {
+            ShapeInstance::Shared { template, offset } => template.bounds().shifted(*offset),
+            ShapeInstance::Owned(shape) => shape.bounds(),
+        }
+    }
+}
+
+impl<F: Float> ShapeInstance<F> {
+    /// `template` moved so its centre is at `point`, as `move_to` would, without copying it.
+    pub fn moved_to(template: Arc<Shape<F>>, point: Vec2<F>) -> Self {
+        let offset = point - template.centre();
+        ShapeInstance::Shared { template, offset }
+    }
+    /// The instance's own copy of its shape, made first if it's still sharing its template's.
+    pub fn to_mut(&mut self) -> &mut Shape<F> {
+        if let ShapeInstance::Shared { .. } = self {
+            *self = ShapeInstance::Owned(self.clone().into_shape());
+        }
+        match self {
+            ShapeInstance::Owned(shape) => shape,
+            ShapeInstance::Shared { .. } => unreachable!(),
+        }
+    }
+    /// The shape moved into place, copying the template's points if they're still shared.
+    pub fn into_shape(self) -> Shape<F> {
+        match self {
+            ShapeInstance::Shared { template, offset } => {
+                let mut shape = Shape::clone(&template);
+                shape.shift(offset);
+                shape
+            }
+            ShapeInstance::Owned(shape) => shape,
+        }
+    }
+    /// How many primitives and holes make up the shape, without moving a shared one into place to count them.
+    pub(crate) fn part_count(&self) -> usize {
+        match self {
+            ShapeInstance::Shared { template, .. } => template.part_count(),
+            ShapeInstance::Owned(shape) => shape.part_count(),
+        }
+    }
+    /// Deletes every primitive `other` hides completely, as `Shape::del_if_obscured_by` does.
+    /// A shared instance keeps sharing its template unless something is actually deleted.
+    pub fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Option<Self> {
+        match self {
+            ShapeInstance::Shared { .. } => {
+                let moved = self.clone().into_shape();
+                let parts = moved.part_count();
+                match moved.del_if_obscured_by(other, epsilon) {
+                    // deleting only ever takes parts away, so the same number left means nothing was
+                    Some(left) if left.part_count() == parts => Some(self),
+                    left => left.map(ShapeInstance::Owned),
+                }
+            }
+            ShapeInstance::Owned(shape) => shape.del_if_obscured_by(other, epsilon).map(ShapeInstance::Owned),
+        }
+    }
+}
+
+/// Shapes built from single precision floats, for sharing geometry with code that uses them.
+/// Rendering only takes `f64` shapes, which is what leaving out the parameter gives.
+pub type Shape32 = Shape<f32>;
+pub type ShapeComponent32 = ShapeComponent<f32>;
+pub type ShapePrimitive32 = ShapePrimitive<f32>;
+pub type Shape64 = Shape<f64>;
+pub type ShapeComponent64 = ShapeComponent<f64>;
+pub type ShapePrimitive64 = ShapePrimitive<f64>;
+
+/// What a shape is known by, both in the components file and in the grid.
+///
+/// Labels made up of only `0`s and `1`s are read as binary, like the original 8 bit labels,
+/// other numbers are read as decimal, and anything else which looks like an identifier is a name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ShapeId {
+    Index(u32),
+    Name(String),
+}
+
+impl ShapeId {
+    /// The id of an empty cell, where nothing is drawn.
+    pub const EMPTY: ShapeId = ShapeId::Index(0);
+}
+
+impl Default for ShapeId {
+    fn default() -> Self {
+        ShapeId::EMPTY
+    }
+}
+
+/// Written as a string, the same way it's labelled in the components file.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ShapeId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl From<u8> for ShapeId {
+    fn from(index: u8) -> Self {
+        ShapeId::Index(index as u32)
+    }
+}
+
+impl From<u32> for ShapeId {
+    fn from(index: u32) -> Self {
+        ShapeId::Index(index)
+    }
+}
+
+impl FromStr for ShapeId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if !s.is_empty() && s.chars().all(|c| c == '0' || c == '1') {
+            return u32::from_str_radix(s, 2).map(ShapeId::Index).map_err(|why| format!("'{}' {}", s, why));
+        }
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+            return s.parse().map(ShapeId::Index).map_err(|why| format!("'{}' {}", s, why));
+        }
+        let mut chars = s.chars();
+        match chars.next() {
+            Some(c) if c.is_alphabetic() || c == '_' => (),
+            _ => return Err(format!("'{}' is neither a number nor a name", s)),
+        }
+        if chars.any(|c| !(c.is_alphanumeric() || c == '_' || c == '-')) {
+            return Err(format!("'{}' is neither a number nor a name", s));
+        }
+        Ok(ShapeId::Name(s.to_string()))
     }
-    pub fn del_points_obscured_by(self, other: &impl Polygonal) -> Option<Self> {
-        Some(self).del_points_obscured_by(other)
+}
+
+/// Indices are written in binary, so they read back the same way.
+impl Display for ShapeId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShapeId::Index(index) => write!(f, "{:08b}", index),
+            ShapeId::Name(name) => write!(f, "{}", name),
+        }
     }
 }
 
-pub trait OptObscurable {
-    fn del_if_obscured_by(self, other: &impl Polygonal) -> Self;
+/// Every shape read from the components files, by each id it was labelled with.
+/// Shapes given several labels are shared between them, and none are ever changed once read, so they can be shared between threads.
+pub type ShapeRegistry = BTreeMap<ShapeId, Arc<Shape>>;
+
+/// A shape as it's drawn in the output, along with where it came from.
+#[derive(Debug, Clone)]
+pub struct PlacedShape {
+    pub shape: Shape,
+    /// The cell the shape was placed at. Connected shapes use the first cell of their connection to be drawn.
+    pub cell: Vec3<usize>,
+    /// Where the shape comes in the order shapes are drawn, as `x + y + z` of its cell in the view, which grows towards the viewer.
+    pub depth: usize,
+    /// How many cells back from the front of the grid the shape is, counted along the viewing direction.
+    pub distance: usize,
+    pub colour: Vec3<f64>,
+    /// How much of what's behind the shape it hides, from 0 for not at all to 1 for everything.
+    pub opacity: f64,
+    /// The name of the equality group the shape was placed from, if any.
+    pub group: Option<String>,
+}
+
+pub trait OptObscurable<F: Float = f64> {
+    /// Deletes every primitive of `self` which `other` hides completely.
+    /// Points within `epsilon` of the edge of `other` count as hidden.
+    fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Self;
 }
 
-impl OptObscurable for Option<Shape> {
-    fn del_if_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptObscurable<F> for Option<Shape<F>> {
+    fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Self {
         match self {
             Some(s) => {
                 let mut new_components = vec![];
                 for component in s.components {
-                    if let Some(new_component) = component.del_if_obscured_by(other) {
+                    if let Some(new_component) = component.del_if_obscured_by(other, epsilon) {
                         new_components.push(new_component);
                     }
                 }
@@ -352,7 +1274,7 @@ impl OptObscurable for Option<Shape> {
                     None
                 }
                 else {
-                    let s = Shape { components: new_components };
+                    let s = Shape::new(new_components);
                     Some(s)
                 }
             }
@@ -361,15 +1283,16 @@ impl OptObscurable for Option<Shape> {
     }
 }
 
-impl OptObscurable for Option<&mut Shape> {
-    fn del_if_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptObscurable<F> for Option<&mut Shape<F>> {
+    fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Self {
         match self {
             Some(s) => {
                 s.components = s.components.clone().into_iter()
-                    .map(|c| Some(c).del_if_obscured_by(other))
+                    .map(|c| Some(c).del_if_obscured_by(other, epsilon))
                     .filter(|c| c.is_some())
                     .map(|c| c.unwrap())
                     .collect();
+                s.bounds = OnceLock::new();
 
                 if s.components.len() == 0 {
                     None
@@ -383,13 +1306,13 @@ impl OptObscurable for Option<&mut Shape> {
     }
 }
 
-impl OptObscurable for Option<ShapeComponent> {
-    fn del_if_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptObscurable<F> for Option<ShapeComponent<F>> {
+    fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Self {
         match self {
-            Some(s) => {
+            Some(mut s) => {
                 let mut new_primitives = vec![];
-                for primitive in s.primitives {
-                    if let Some(new_primitive) = primitive.del_if_obscured_by(other) {
+                for primitive in std::mem::take(&mut s.primitives) {
+                    if let Some(new_primitive) = primitive.del_if_obscured_by(other, epsilon) {
                         new_primitives.push(new_primitive);
                     }
                 }
@@ -397,7 +1320,7 @@ impl OptObscurable for Option<ShapeComponent> {
                     None
                 }
                 else {
-                    let s = ShapeComponent { primitives: new_primitives, normal: s.normal };
+                    let s = s.with_primitives(new_primitives, epsilon);
                     Some(s)
                 }
             }
@@ -406,15 +1329,16 @@ impl OptObscurable for Option<ShapeComponent> {
     }
 }
 
-impl OptObscurable for Option<&mut ShapeComponent> {
-    fn del_if_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptObscurable<F> for Option<&mut ShapeComponent<F>> {
+    fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Self {
         match self {
             Some(s) => {
-                s.primitives = s.primitives.clone().into_iter()
-                    .map(|p| Some(p).del_if_obscured_by(other))
+                let primitives = s.primitives.clone().into_iter()
+                    .map(|p| Some(p).del_if_obscured_by(other, epsilon))
                     .filter(|p| p.is_some())
                     .map(|p| p.unwrap())
                     .collect();
+                *s = s.with_primitives(primitives, epsilon);
 
                 if s.primitives.len() == 0 {
                     None
@@ -428,11 +1352,11 @@ impl OptObscurable for Option<&mut ShapeComponent> {
     }
 }
 
-impl OptObscurable for Option<ShapePrimitive> {
-    fn del_if_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptObscurable<F> for Option<ShapePrimitive<F>> {
+    fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Self {
         match self {
             Some(s) => {
-                if obscures(other, &s) {
+                if obscures(other, &s, epsilon) {
                     None
                 }
                 else {
@@ -444,11 +1368,11 @@ impl OptObscurable for Option<ShapePrimitive> {
     }
 }
 
-impl OptObscurable for Option<&mut ShapePrimitive> {
-    fn del_if_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptObscurable<F> for Option<&mut ShapePrimitive<F>> {
+    fn del_if_obscured_by(self, other: &impl Polygonal<F>, epsilon: F) -> Self {
         match self {
             Some(s) => {
-                if obscures(other, s) {
+                if obscures(other, s, epsilon) {
                     None
                 }
                 else {
@@ -460,28 +1384,29 @@ impl OptObscurable for Option<&mut ShapePrimitive> {
     }
 }
 
-pub trait OptReducible {
-    /// This method deletes all points of `self` completely obscured by `other`, excluding edge points.
-    /// For the sake of how this method is used, for sequences of three edge points, the centre is removed.
-    /// In future, this should be replaced with a set-difference operation.
-    fn del_points_obscured_by(self, other: &impl Polygonal) -> Self;
+pub trait OptReducible<F: Float = f64> {
+    /// Cuts away every part of `self` covered by `other`, leaving only what can still be seen around it.
+    /// Primitives are clipped against each primitive of `other` in turn, so may be split into several pieces.
+    /// Clipping needs convex primitives to clip against, so anything non-convex in `other` only deletes what it hides completely.
+    /// Components with holes aren't clipped either, as a hole poking out of what's left of its primitives would be filled in.
+    fn del_whats_obscured_by(self, other: &Shape<F>, epsilon: F) -> Self;
 }
 
-impl OptReducible for Option<Shape> {
-    fn del_points_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptReducible<F> for Option<Shape<F>> {
+    fn del_whats_obscured_by(self, other: &Shape<F>, epsilon: F) -> Self {
         match self {
             Some(s) => {
                 let mut new_components = vec![];
                 for component in s.components {
-                    if let Some(new_component) = component.del_points_obscured_by(other) {
+                    if let Some(new_component) = component.del_whats_obscured_by(other, epsilon) {
                         new_components.push(new_component);
                     }
                 }
-                if new_components.len() == 0 {
+                if new_components.is_empty() {
                     None
                 }
                 else {
-                    let s = Shape { components: new_components };
+                    let s = Shape::new(new_components);
                     Some(s)
                 }
             }
@@ -490,17 +1415,16 @@ impl OptReducible for Option<Shape> {
     }
 }
 
-impl OptReducible for Option<&mut Shape> {
-    fn del_points_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptReducible<F> for Option<&mut Shape<F>> {
+    fn del_whats_obscured_by(self, other: &Shape<F>, epsilon: F) -> Self {
         match self {
             Some(s) => {
                 s.components = s.components.clone().into_iter()
-                    .map(|c| Some(c).del_points_obscured_by(other))
-                    .filter(|c| c.is_some())
-                    .map(|c| c.unwrap())
+                    .filter_map(|c| c.del_whats_obscured_by(other, epsilon))
                     .collect();
+                s.bounds = OnceLock::new();
 
-                if s.components.len() == 0 {
+                if s.components.is_empty() {
                     None
                 }
                 else {
@@ -512,21 +1436,19 @@ impl OptReducible for Option<&mut Shape> {
     }
 }
 
-impl OptReducible for Option<ShapeComponent> {
-    fn del_points_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptReducible<F> for Option<ShapeComponent<F>> {
+    fn del_whats_obscured_by(self, other: &Shape<F>, epsilon: F) -> Self {
         match self {
+            Some(s) if !s.holes.is_empty() => s.del_if_obscured_by(other, epsilon),
             Some(s) => {
-                let mut new_primitives = vec![];
-                for primitive in s.primitives {
-                    if let Some(new_primitive) = primitive.del_points_obscured_by(other) {
-                        new_primitives.push(new_primitive);
-                    }
-                }
-                if new_primitives.len() == 0 {
+                let new_primitives = s.primitives.iter()
+                    .flat_map(|primitive| primitive.visible_parts(other, epsilon))
+                    .collect_vec();
+                if new_primitives.is_empty() {
                     None
                 }
                 else {
-                    let s = ShapeComponent { primitives: new_primitives, normal: s.normal };
+                    let s = ShapeComponent { primitives: new_primitives, holes: vec![], bounds: OnceLock::new(), ..s };
                     Some(s)
                 }
             }
@@ -535,17 +1457,17 @@ impl OptReducible for Option<ShapeComponent> {
     }
 }
 
-impl OptReducible for Option<&mut ShapeComponent> {
-    fn del_points_obscured_by(self, other: &impl Polygonal) -> Self {
+impl<F: Float> OptReducible<F> for Option<&mut ShapeComponent<F>> {
+    fn del_whats_obscured_by(self, other: &Shape<F>, epsilon: F) -> Self {
         match self {
+            Some(s) if !s.holes.is_empty() => Some(s).del_if_obscured_by(other, epsilon),
             Some(s) => {
-                s.primitives = s.primitives.clone().into_iter()
-                    .map(|p| Some(p).del_points_obscured_by(other))
-                    .filter(|p| p.is_some())
-                    .map(|p| p.unwrap())
+                s.primitives = s.primitives.iter()
+                    .flat_map(|primitive| primitive.visible_parts(other, epsilon))
                     .collect();
+                s.bounds = OnceLock::new();
 
-                if s.primitives.len() == 0 {
+                if s.primitives.is_empty() {
                     None
                 }
                 else {
@@ -557,67 +1479,94 @@ impl OptReducible for Option<&mut ShapeComponent> {
     }
 }
 
-impl OptReducible for Option<ShapePrimitive> {
-    fn del_points_obscured_by(self, other: &impl Polygonal) -> Self {
-        match self {
-            Some(mut s) => {
-                s.points = s.points.into_iter()
-                    .circular_tuple_windows::<(_, _, _)>()
-                    .filter(|(l, c, r)|
-                        !(inclusive_contains(other, (*l + *c) / 2.0) && inclusive_contains(other, (*c + *r) / 2.0))
-                    )
-                    .map(|tup| tup.1)
-                    .filter(|p| !exclusive_contains(other, *p))
-                    .collect();
-                if s.points.len() <= 2 {
-                    None
-                }
-                else {
-                    Some(s)
-                }
-            }
-            None => self,
+/// Pieces of a clipped primitive with less area than this are slivers left by floating point error, and are dropped.
+pub(crate) const AREA_EPSILON: f64 = 1e-6;
+
+/// Corners of fused faces turning through less than this, as the sine of the angle, are taken to be straight.
+pub(crate) const TURN_EPSILON: f64 = 1e-9;
+
+/// The furthest a corner moves when outsetting a primitive, as a multiple of the distance its edges move.
+pub const MITER_LIMIT: f64 = 4.0;
+
+/// Sutherland-Hodgman clipping of `points` to the half of the plane where `side` isn't negative.
+/// Any point the boundary crosses an edge at is added in between the edge's ends.
+fn clip_to_half_plane<F: Float>(points: &[Vec2<F>], side: impl Fn(Vec2<F>) -> F) -> Vec<Vec2<F>> {
+    let mut clipped = vec![];
+    for (start, end) in points.iter().cloned().circular_tuple_windows() {
+        let (start_side, end_side) = (side(start), side(end));
+        if (start_side < F::zero()) != (end_side < F::zero()) {
+            clipped.push(start + (end - start) * (start_side / (start_side - end_side)));
+        }
+        if end_side >= F::zero() {
+            clipped.push(end);
         }
     }
+    clipped
 }
 
-impl OptReducible for Option<&mut ShapePrimitive> {
-    fn del_points_obscured_by(self, other: &impl Polygonal) -> Self {
-        match self {
-            Some(mut s) => {
-                s.points = s.points.iter().cloned()
-                    .circular_tuple_windows::<(_, _, _)>()
-                    .filter(|(l, c, r)|
-                        !(inclusive_contains(other, (*l + *c) / 2.0) && inclusive_contains(other, (*c + *r) / 2.0))
-                    )
-                    .map(|tup| tup.1)
-                    .filter(|p| !exclusive_contains(other, *p))
-                    .collect();
-                if s.points.len() <= 2 {
-                    None
-                }
-                else {
-                    Some(s)
-                }
-            }
-            None => self,
-        }
+/// Tidies up a clipped polygon into a primitive, unless there's nothing left of it.
+fn clipped_primitive<F: Float>(points: Vec<Vec2<F>>) -> Option<ShapePrimitive<F>> {
+    let mut points = points.into_iter().dedup().collect_vec();
+    while points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let primitive = ShapePrimitive { points };
+    if primitive.points.len() < 3 || primitive.signed_area().abs() < F::from_f64(AREA_EPSILON) {
+        None
+    }
+    else {
+        Some(primitive)
     }
 }
 
-/// This function is perhaps the biggest bodge in this program.
-/// All it does is apply `del_points_obscured_by` using the individual components of `obscurer`.
-/// This is just an approximation of a set difference, and should thereby be replaced with one
-/// along with `del_points_obscured_by` because my goodness is this a mess...
-pub fn delete_the_stragglers<'a, 'b>(mut original: Option<&'a mut Shape>, obscurer: &'b Shape) -> Option<&'a mut Shape> {
-    for component in &obscurer.components {
-        original = original.del_points_obscured_by(component);
+/// How many cells of the grid vertices are matched up on there are to each unit.
+const SNAP: i64 = 1_000_000;
+
+/// Where every vertex of a scene is rounded to, so every copy of a vertex shared between faces rounds to the same point.
+///
+/// Copies of a shared vertex can drift apart by floating point error as shapes are moved around,
+/// and rounded one coordinate at a time, copies either side of a half would go different ways and leave a crack.
+/// Instead vertices are matched up on a fine grid, with copies which have drifted into a neighbouring cell of it
+/// taken to be the same vertex, and every copy goes wherever the first of them was rounded to.
+#[derive(Debug, Clone, Default)]
+pub struct VertexSnap<F: Float = f64> {
+    rounded: HashMap<(i64, i64), Vec2<F>>,
+}
+
+impl<F: Float> VertexSnap<F> {
+    /// The map for every one of `points`, in the order given.
+    pub fn new(points: impl IntoIterator<Item = Vec2<F>>) -> Self {
+        let mut snap = VertexSnap { rounded: HashMap::new() };
+        for p in points {
+            let cell = snap_cell(p);
+            let rounded = snap.find(cell).unwrap_or_else(|| vect![snap_round(p.x), snap_round(p.y)]);
+            snap.rounded.entry(cell).or_insert(rounded);
+        }
+        snap
+    }
+    /// Where `p` is rounded to, which for a point the map wasn't made with is the nearest integer point.
+    pub fn round(&self, p: Vec2<F>) -> Vec2<F> {
+        self.find(snap_cell(p)).unwrap_or_else(|| vect![snap_round(p.x), snap_round(p.y)])
+    }
+    /// Where the vertex in `cell` is rounded to, or else one in a neighbouring cell, if either has been seen.
+    fn find(&self, (x, y): (i64, i64)) -> Option<Vec2<F>> {
+        itertools::iproduct!([0, -1, 1], [0, -1, 1]).find_map(|(dx, dy)| self.rounded.get(&(x + dx, y + dy)).copied())
     }
-    original
+}
+
+/// The cell of the grid vertices are matched up on that `p` is in.
+fn snap_cell<F: Float>(p: Vec2<F>) -> (i64, i64) {
+    ((p.x.to_f64() * SNAP as f64).round() as i64, (p.y.to_f64() * SNAP as f64).round() as i64)
+}
+
+/// Rounds `v` to the nearest integer, first snapping it to the grid vertices are matched up on.
+fn snap_round<F: Float>(v: F) -> F {
+    let snapped = (v.to_f64() * SNAP as f64).round() as i64;
+    F::from_f64((snapped + SNAP / 2).div_euclid(SNAP) as f64)
 }
 
 // game devs hmu
-fn intersection_parameters(p_1: Vec2<f64>, d_1: Vec2<f64>, p_2: Vec2<f64>, d_2: Vec2<f64>) -> Vec2<f64> {
+fn intersection_parameters<F: Float>(p_1: Vec2<F>, d_1: Vec2<F>, p_2: Vec2<F>, d_2: Vec2<F>) -> Vec2<F> {
     let lambda = Vec2::cross(p_2 - p_1, d_2) / Vec2::cross(d_1, d_2);
     let mu = Vec2::cross(p_1 - p_2, d_1) / Vec2::cross(d_2, d_1);
 
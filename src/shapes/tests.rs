@@ -1,14 +1,18 @@
 #![cfg(test)]
 
-use std::ops::Neg;
+use std::sync::Arc;
 
-use crate::shapes::{CircleDirection, Containment, get_containment, obscures, Polygonal, ShapePrimitive};
+use itertools::{iproduct, Itertools};
+
+use crate::num::Float;
+use crate::shapes::{clip_to_half_plane, Bounds, CircleDirection, Containment, DEFAULT_EPSILON, FillRule, get_containment, MITER_LIMIT, obscures, OptObscurable, OptReducible, Polygonal, Shape, Shape32, ShapeComponent, ShapeError, ShapeInstance, ShapePrimitive, ShapePrimitive32, VertexSnap};
+use crate::iter::{PrimitiveIter, ToDStringIter};
+use crate::path::PathOptions;
 use crate::vect;
-use crate::vector::Vec2;
+use crate::vector::{Vec2, Vec3};
+
+const EPSILON: f64 = 1e-9;
 
-fn rot90<T: Neg<Output = T> + Copy>(v: Vec2<T>) -> Vec2<T> {
-    vect![-v.y, v.x]
-}
 fn gen_square(size: f64) -> ShapePrimitive {
     ShapePrimitive { points: vec![
         Vec2 { x: size, y: size },
@@ -62,68 +66,194 @@ fn test_combination() {
         vect![4.41, -2.96],
     ] };
 
-    assert!(obscures(&result, &expected));
-    assert!(obscures(&expected, &result));
+    assert!(obscures(&result, &expected, EPSILON));
+    assert!(obscures(&expected, &result, EPSILON));
+}
+
+fn rect(x: f64, y: f64, width: f64, height: f64) -> ShapePrimitive {
+    ShapePrimitive::rect(vect![x, y], vect![width, height]).unwrap()
+}
+fn assert_no_repeats(primitive: &ShapePrimitive) {
+    let len = primitive.points.len();
+    for i in 0..len {
+        assert_ne!(primitive.points[i], primitive.points[(i + 1) % len], "repeated point in {:?}", primitive.points);
+    }
+}
+#[test]
+fn test_combine_rectangles() {
+    let left = rect(0.0, 0.0, 1.0, 2.0);
+    let right = rect(1.0, 0.0, 3.0, 2.0);
+    let outer = rect(0.0, 0.0, 4.0, 2.0);
+    for (a, b) in [(&left, &right), (&right, &left)] {
+        let fused = a.combine_common_edges(b).unwrap();
+        assert_no_repeats(&fused);
+        assert_eq!(fused.points.len(), 4);
+        assert!(obscures(&fused, &outer, EPSILON));
+        assert!(obscures(&outer, &fused, EPSILON));
+        assert_eq!(fused.signed_area(), a.signed_area().signum() * 8.0);
+    }
+}
+#[test]
+fn test_combine_partial_edge() {
+    // the short rectangle only covers part of the tall one's side, so the corner where they meet stays
+    let tall = rect(0.0, 0.0, 1.0, 2.0);
+    let short = ShapePrimitive { points: vec![vect![1.0, 0.0], vect![2.0, 0.0], vect![2.0, 1.0], vect![1.0, 1.0]] };
+    let tall = ShapePrimitive { points: vec![tall.points[0], tall.points[1], vect![1.0, 1.0], tall.points[2], tall.points[3]] };
+    let fused = tall.combine_common_edges(&short).unwrap();
+    assert_no_repeats(&fused);
+    assert_eq!(fused.signed_area().abs(), 3.0);
+    assert_eq!(fused.points.len(), 6);
+}
+#[test]
+fn test_combine_opposite_winding() {
+    let left = rect(0.0, 0.0, 1.0, 1.0);
+    let mut right = rect(1.0, 0.0, 1.0, 1.0);
+    right.points.reverse();
+    let fused = left.combine_common_edges(&right).unwrap();
+    assert_no_repeats(&fused);
+    assert_eq!(fused.points.len(), 4);
+    // the result is drawn the same way round as the first primitive
+    assert_eq!(fused.signed_area(), left.signed_area() * 2.0);
+}
+#[test]
+fn test_combine_corner_only() {
+    let a = rect(0.0, 0.0, 1.0, 1.0);
+    let b = rect(1.0, 1.0, 1.0, 1.0);
+    assert!(a.combine_common_edges(&b).is_none());
+    let c = rect(5.0, 5.0, 1.0, 1.0);
+    assert!(a.combine_common_edges(&c).is_none());
+}
+#[test]
+fn test_combine_self() {
+    let a = rect(0.0, 0.0, 1.0, 1.0);
+    assert_eq!(a.combine_common_edges(&a).unwrap().points, a.points);
+}
+#[test]
+fn test_combine_two_runs() {
+    // a U with a block in its gap touching both arms would enclose a hole, so isn't fused
+    let u: ShapePrimitive = ShapePrimitive { points: vec![
+        vect![0.0, 0.0], vect![1.0, 0.0], vect![1.0, 2.0], vect![2.0, 2.0], vect![2.0, 0.0],
+        vect![3.0, 0.0], vect![3.0, 3.0], vect![0.0, 3.0],
+    ] };
+    let block = ShapePrimitive { points: vec![vect![1.0, 0.0], vect![2.0, 0.0], vect![2.0, 1.0], vect![1.0, 1.0]] };
+    assert!(u.combine_common_edges(&block).is_none());
+    // but filling the whole gap leaves one run along three sides
+    let gap = ShapePrimitive { points: vec![vect![1.0, 2.0], vect![1.0, 0.0], vect![2.0, 0.0], vect![2.0, 2.0]] };
+    let fused = u.combine_common_edges(&gap).unwrap();
+    assert_eq!(fused.signed_area().abs(), 9.0);
+    assert_eq!(fused.points.len(), 4);
 }
 
 #[test]
 fn test_contains() {
     let shape = gen_square(1.0);
     // a square contains its centre
-    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 0.0 }) == Containment::Inside);
+    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 0.0 }, FillRule::EvenOdd, EPSILON) == Containment::Inside);
     // a square contains its boundary
-    assert!(get_containment(&shape, Vec2 { x: 1.0, y: 0.0 }) == Containment::Edge);
+    assert!(get_containment(&shape, Vec2 { x: 1.0, y: 0.0 }, FillRule::EvenOdd, EPSILON) == Containment::Edge);
     // check opposite boundary, where there exists the possibility of two intersections
-    assert!(get_containment(&shape, Vec2 { x: -1.0, y: 0.0 }) == Containment::Edge);
+    assert!(get_containment(&shape, Vec2 { x: -1.0, y: 0.0 }, FillRule::EvenOdd, EPSILON) == Containment::Edge);
     // check points outside the boundaries of the square
     let mut point = Vec2 { x: 2.0, y: 0.0 };
     for _ in 0..4 {
-        assert!(get_containment(&shape, point) == Containment::Outside);
-        point = rot90(point);
+        assert!(get_containment(&shape, point, FillRule::EvenOdd, EPSILON) == Containment::Outside);
+        point = point.perp();
     }
 }
 #[test]
 fn test_contains_parallel() {
     let shape = gen_square(1.0);
     // parallel edge cases
-    assert!( get_containment(&shape, Vec2 { x: 0.0, y: 1.0 }) == Containment::Edge);
-    assert!( get_containment(&shape, Vec2 { x: 0.0, y: -1.0 }) == Containment::Edge);
+    assert!( get_containment(&shape, Vec2 { x: 0.0, y: 1.0 }, FillRule::EvenOdd, EPSILON) == Containment::Edge);
+    assert!( get_containment(&shape, Vec2 { x: 0.0, y: -1.0 }, FillRule::EvenOdd, EPSILON) == Containment::Edge);
 }
 #[test]
 fn test_contains_corner() {
     let shape = gen_45square(1.0);
     // sanity check
-    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 0.5 }) == Containment::Inside);
-    assert!(get_containment(&shape, Vec2 { x:-1.0, y: 0.5 }) == Containment::Outside);
-    assert!(get_containment(&shape, Vec2 { x: 1.0, y: 0.5 }) == Containment::Outside);
+    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 0.5 }, FillRule::EvenOdd, EPSILON) == Containment::Inside);
+    assert!(get_containment(&shape, Vec2 { x:-1.0, y: 0.5 }, FillRule::EvenOdd, EPSILON) == Containment::Outside);
+    assert!(get_containment(&shape, Vec2 { x: 1.0, y: 0.5 }, FillRule::EvenOdd, EPSILON) == Containment::Outside);
 
     // check line intersecting right corner
-    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 0.0 }) == Containment::Inside);
-    assert!(get_containment(&shape, Vec2 { x: 1.0, y: 0.0 }) == Containment::Edge);
-    assert!(get_containment(&shape, Vec2 { x:-1.0, y: 0.0 }) == Containment::Edge);
-    assert!(get_containment(&shape, Vec2 { x:-2.0, y: 0.0 }) == Containment::Outside);
+    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 0.0 }, FillRule::EvenOdd, EPSILON) == Containment::Inside);
+    assert!(get_containment(&shape, Vec2 { x: 1.0, y: 0.0 }, FillRule::EvenOdd, EPSILON) == Containment::Edge);
+    assert!(get_containment(&shape, Vec2 { x:-1.0, y: 0.0 }, FillRule::EvenOdd, EPSILON) == Containment::Edge);
+    assert!(get_containment(&shape, Vec2 { x:-2.0, y: 0.0 }, FillRule::EvenOdd, EPSILON) == Containment::Outside);
 
     // check line intersecting top corner
-    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 1.0 }) == Containment::Edge);
-    assert!(get_containment(&shape, Vec2 { x:-1.0, y: 1.0 }) == Containment::Outside);
+    assert!(get_containment(&shape, Vec2 { x: 0.0, y: 1.0 }, FillRule::EvenOdd, EPSILON) == Containment::Edge);
+    assert!(get_containment(&shape, Vec2 { x:-1.0, y: 1.0 }, FillRule::EvenOdd, EPSILON) == Containment::Outside);
+}
+
+#[test]
+fn test_contains_non_zero_convex() {
+    // for an outline which doesn't cross itself, either rule should give the same answer whichever way it winds
+    let mut reversed = gen_45square(1.0);
+    reversed.points.reverse();
+    let hexagon = ShapePrimitive { points: (0..6).map(|i| {
+        let angle = i as f64 * std::f64::consts::FRAC_PI_3;
+        vect![angle.cos(), angle.sin()] * 1.5
+    }).collect() };
+    for shape in [gen_square(1.0), gen_45square(1.0), reversed, hexagon] {
+        for (x, y) in iproduct!(-8..=8, -8..=8) {
+            let point = vect![x as f64 / 4.0, y as f64 / 4.0];
+            assert_eq!(
+                shape.contains(point, FillRule::NonZero),
+                shape.contains(point, FillRule::EvenOdd),
+                "at {:?}", point,
+            );
+        }
+    }
+}
+
+#[test]
+fn test_contains_non_zero_star() {
+    // a pentagram, whose middle is wound around twice
+    let star = ShapePrimitive { points: (0..5).map(|i| {
+        let angle = (i * 2) as f64 * 2.0 * std::f64::consts::PI / 5.0;
+        vect![angle.sin(), -angle.cos()]
+    }).collect() };
+    assert_eq!(star.contains(vect![0.0, 0.0], FillRule::EvenOdd), Containment::Outside);
+    assert_eq!(star.contains(vect![0.0, 0.0], FillRule::NonZero), Containment::Inside);
+    // the points of the star are only wound around once
+    assert_eq!(star.contains(vect![0.0, -0.8], FillRule::EvenOdd), Containment::Inside);
+    assert_eq!(star.contains(vect![0.0, -0.8], FillRule::NonZero), Containment::Inside);
+    assert_eq!(star.contains(vect![0.0, -1.0], FillRule::NonZero), Containment::Edge);
+    assert_eq!(star.contains(vect![2.0, 0.0], FillRule::NonZero), Containment::Outside);
 }
 
+#[test]
+fn test_contains_epsilon() {
+    let square = ShapePrimitive::rect(vect![0.0, 0.0], vect![1.0, 1.0]).unwrap();
+    let hair_outside = vect![1.0 + 1e-9, 0.5];
+    assert!(get_containment(&square, hair_outside, FillRule::EvenOdd, DEFAULT_EPSILON) == Containment::Edge);
+    assert!(get_containment(&square, hair_outside, FillRule::EvenOdd, 0.0) == Containment::Outside);
+    assert!(get_containment(&square, vect![1.1, 0.5], FillRule::EvenOdd, DEFAULT_EPSILON) == Containment::Outside);
+    // near a corner, rather than a side
+    assert!(get_containment(&square, vect![-1e-9, -1e-9], FillRule::EvenOdd, DEFAULT_EPSILON) == Containment::Edge);
+
+    let mut nudged = square.clone();
+    nudged.shift(vect![1e-9, 0.0]);
+    assert!( obscures(&square, &nudged, DEFAULT_EPSILON));
+    assert!(!obscures(&square, &nudged, 0.0));
+}
 #[test]
 fn test_obscures() {
     let inner = gen_45square(1.0);
     let outer = gen_45square(2.0);
-    assert!( obscures(&outer, &inner));
-    assert!(!obscures(&inner, &outer));
+    assert!( obscures(&outer, &inner, EPSILON));
+    assert!(!obscures(&inner, &outer, EPSILON));
 }
 #[test]
 fn test_obscures_self() {
     let shape = gen_square(1.0);
     let rotated = gen_90square(1.0);
-    assert!( obscures(&shape, &shape));
-    assert!( obscures(&shape, &rotated));
-    assert!( obscures(&rotated, &shape));
+    assert!( obscures(&shape, &shape, EPSILON));
+    assert!( obscures(&shape, &rotated, EPSILON));
+    assert!( obscures(&rotated, &shape, EPSILON));
     let shape = gen_45square(1.0);
-    assert!( obscures(&shape, &shape));
+    assert!( obscures(&shape, &shape, EPSILON));
 }
 #[test]
 fn test_not_obscures() {
@@ -131,8 +261,8 @@ fn test_not_obscures() {
     a.shift(Vec2 { x: 2.0, y: 0.0 });
     let mut b = gen_45square(1.0);
     b.shift(Vec2 { x: -2.0, y: 0.0 });
-    assert!(!obscures(&a, &b));
-    assert!(!obscures(&b, &a));
+    assert!(!obscures(&a, &b, EPSILON));
+    assert!(!obscures(&b, &a, EPSILON));
 }
 #[test]
 fn test_partial_obscures() {
@@ -140,11 +270,612 @@ fn test_partial_obscures() {
     a.shift(Vec2 { x: 1.0, y: 0.0 });
     let mut b = gen_45square(2.0);
     b.shift(Vec2 { x: -1.0, y: 0.0 });
-    assert!(!obscures(&a, &b));
-    assert!(!obscures(&b, &a));
+    assert!(!obscures(&a, &b, EPSILON));
+    assert!(!obscures(&b, &a, EPSILON));
+}
+fn gen_l_shape() -> ShapePrimitive {
+    ShapePrimitive { points: vec![
+        vect![0.0, 0.0], vect![2.0, 0.0], vect![2.0, 1.0], vect![1.0, 1.0], vect![1.0, 2.0], vect![0.0, 2.0],
+    ] }
+}
+#[test]
+fn test_obscures_concave() {
+    let l_shape = gen_l_shape();
+    // every corner is inside one arm or the other, but the long sides cross the notch
+    let across = ShapePrimitive { points: vec![
+        vect![1.8, 0.4], vect![1.9, 0.5], vect![0.5, 1.9], vect![0.4, 1.8],
+    ] };
+    assert!(!obscures(&l_shape, &across, EPSILON));
+    let in_arm = ShapePrimitive::rect(vect![0.2, 0.2], vect![1.6, 0.6]).unwrap();
+    assert!(obscures(&l_shape, &in_arm, EPSILON));
+    // running along the edges of the notch is still covered
+    assert!(obscures(&l_shape, &l_shape, EPSILON));
+    let corner = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![2.0, 0.0], vect![2.0, 1.0], vect![1.0, 1.0], vect![0.0, 1.0]] };
+    assert!(obscures(&l_shape, &corner, EPSILON));
 }
 #[test]
 fn test_orbit_direction() {
     let sq = gen_45square(2.0);
     assert!(sq.draw_direction() == CircleDirection::CounterClockwise)
-}
\ No newline at end of file
+}
+#[test]
+fn test_round_points_shared_edge() {
+    // two squares sharing an edge which has drifted either side of a half
+    let left = ShapePrimitive { points: vec![
+        vect![0.0, 0.0], vect![2.4999999999, 0.0], vect![2.4999999999, 2.0], vect![0.0, 2.0],
+    ] };
+    let right = ShapePrimitive { points: vec![
+        vect![2.5000000001, 0.0], vect![5.0, 0.0], vect![5.0, 2.0], vect![2.5000000001, 2.0],
+    ] };
+    let snap = VertexSnap::new(left.points_iter().chain(right.points_iter()));
+    let left = left.round_points(&snap).unwrap();
+    let right = right.round_points(&snap).unwrap();
+    assert_eq!(left.points[1], right.points[0]);
+    assert_eq!(left.points[2], right.points[3]);
+
+    // drifted far enough that each copy on its own would round the other way, so they have to be matched up
+    let left = ShapePrimitive { points: vec![
+        vect![0.0, 0.0], vect![2.4999994, 0.0], vect![2.4999994, 2.0], vect![0.0, 2.0],
+    ] };
+    let right = ShapePrimitive { points: vec![
+        vect![2.5000001, 0.0], vect![5.0, 0.0], vect![5.0, 2.0], vect![2.5000001, 2.0],
+    ] };
+    let unmatched = VertexSnap::default();
+    assert_ne!(unmatched.round(left.points[1]), unmatched.round(right.points[0]));
+    let snap = VertexSnap::new(left.points_iter().chain(right.points_iter()));
+    let left = left.round_points(&snap).unwrap();
+    let right = right.round_points(&snap).unwrap();
+    assert_eq!(left.points[1], vect![2.0, 0.0]);
+    assert_eq!(left.points[1], right.points[0]);
+    assert_eq!(left.points[2], right.points[3]);
+}
+#[test]
+fn test_round_points_collapse() {
+    // a sliver which rounds down to a line
+    let sliver = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![4.0, 0.1], vect![8.0, 0.0]] };
+    assert!(sliver.round_points(&VertexSnap::default()).is_none());
+    // merged neighbours are removed, including around the wrap
+    let square = ShapePrimitive { points: vec![
+        vect![0.1, 0.0], vect![3.0, 0.0], vect![3.0, 3.0], vect![0.0, 3.0], vect![0.0, 0.2],
+    ] };
+    let square = square.round_points(&VertexSnap::default()).unwrap();
+    assert_eq!(square.points, vec![vect![0.0, 0.0], vect![3.0, 0.0], vect![3.0, 3.0], vect![0.0, 3.0]]);
+}
+#[test]
+fn test_canonical_start() {
+    let square = gen_square(1.0);
+    let mut rotated = square.clone();
+    rotated.points.rotate_left(2);
+    let options = PathOptions { canonical_start: true, ..PathOptions::default() };
+    assert_eq!(square.generate_d(&options), rotated.generate_d(&options));
+    assert!(square.generate_d(&options).starts_with("M-1 -1 "));
+    let options = PathOptions { canonical_start: false, ..PathOptions::default() };
+    assert_ne!(square.generate_d(&options), rotated.generate_d(&options));
+}
+
+#[test]
+fn test_relative_paths() {
+    let options = PathOptions { relative: true, ..PathOptions::default() };
+    // far from the origin, small steps are shorter to write than where they end up
+    let far = ShapePrimitive { points: vec![vect![1000.0, 2000.0], vect![1035.0, 2020.0], vect![1035.0, 2060.0], vect![1000.0, 2040.0]] };
+    assert_eq!(far.generate_d(&options), "M1000 2000 l35 20 v40 l-35 -20 z");
+    // close to it, the absolute form is kept as it's no longer
+    let near = gen_square(1.0);
+    assert_eq!(near.generate_d(&options), near.generate_d(&PathOptions::default()));
+
+    // reading the relative form back gives the same points, to within rounding
+    let awkward = ShapePrimitive { points: vec![
+        vect![1000.1, 2000.7], vect![1035.3, 2000.7], vect![1035.3, 2060.9], vect![1012.45, 2071.05], vect![1000.1, 2040.3],
+    ] };
+    let d: String = ToDStringIter::relative_from_vec(&awkward.points).collect();
+    assert!(d.contains('h') && d.contains('v') && d.contains('l'));
+    let read = PrimitiveIter::from_str(&d).flat_map(|primitive| primitive.points).collect::<Vec<_>>();
+    assert_eq!(read.len(), awkward.points.len());
+    for (read, original) in read.into_iter().zip(awkward.points) {
+        assert!((read - original).magnitude() < EPSILON, "{} read back as {}", original, read);
+    }
+}
+
+#[test]
+fn test_closing_paths() {
+    let options = PathOptions { canonical_start: false, ..PathOptions::default() };
+    let square = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![2.0, 0.0], vect![2.0, 2.0], vect![0.0, 2.0]] };
+    assert_eq!(square.generate_d(&options), "M0 0 H2 V2 H0 z");
+    let relative = PathOptions { relative: true, ..options };
+    let far = ShapePrimitive { points: square.points.iter().map(|p| *p + (1000.0, 1000.0)).collect() };
+    assert_eq!(far.generate_d(&relative), "M1000 1000 h2 v2 h-2 z");
+
+    // coming back to the start is left to the `z`
+    let returning = ShapePrimitive { points: [square.points.clone(), vec![vect![0.0, 0.0]]].concat() };
+    assert_eq!(returning.generate_d(&options), "M0 0 H2 V2 H0 z");
+    let triangle = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![2.0, 1.0], vect![1.0, 3.0], vect![0.0, 0.0]] };
+    assert_eq!(triangle.generate_d(&options), "M0 0 2 1 1 3 z");
+
+    // unless it's asked for, and then only once
+    let explicit = PathOptions { explicit_close: true, ..options };
+    assert_eq!(square.generate_d(&explicit), "M0 0 H2 V2 H0 V0 z");
+    assert_eq!(returning.generate_d(&explicit), "M0 0 H2 V2 H0 V0 z");
+    assert_eq!(far.generate_d(&PathOptions { relative: true, ..explicit }), "M1000 1000 h2 v2 h-2 v-2 z");
+
+    // however it's closed, reading it back gives the same ring, where coming back to the start adds no new point
+    let open = |points: &[Vec2<f64>]| match points.last() == points.first() {
+        true => points[..points.len() - 1].to_vec(),
+        false => points.to_vec(),
+    };
+    for options in [options, relative, explicit] {
+        for ring in [&square, &returning, &triangle, &far] {
+            let d = ring.generate_d(&options);
+            let read = PrimitiveIter::from_str(&d).flat_map(|primitive| primitive.points).collect::<Vec<_>>();
+            assert_eq!(open(&read), open(&ring.points), "{} didn't read back as the same ring", d);
+        }
+    }
+}
+
+#[test]
+fn test_from_points_validation() {
+    assert_eq!(
+        ShapePrimitive::from_points([vect![0.0, 0.0], vect![1.0, 0.0]]).unwrap_err(),
+        ShapeError::TooFewPoints { count: 2 },
+    );
+    assert_eq!(
+        ShapePrimitive::from_points([vect![0.0, 0.0], vect![1.0, 0.0], vect![1.0, 0.0], vect![0.0, 1.0]]).unwrap_err(),
+        ShapeError::RepeatedPoint { index: 1, point: vect![1.0, 0.0] },
+    );
+    assert_eq!(ShapePrimitive::rect(vect![0.0, 0.0], vect![2.0, 0.0]).unwrap_err(), ShapeError::RepeatedPoint { index: 1, point: vect![2.0, 0.0] });
+    // a last point back at the start is the same outline, without the side that goes nowhere
+    let closed = ShapePrimitive::from_points([vect![0.0, 0.0], vect![1.0, 0.0], vect![0.0, 1.0], vect![0.0, 0.0]]).unwrap();
+    assert_eq!(closed.points, vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![0.0, 1.0]]);
+    assert!(closed.lines_iter().all(|(a, b)| a != b));
+    assert_eq!(
+        ShapePrimitive::from_points([vect![0.0, 0.0], vect![1.0, 0.0], vect![0.0, 0.0]]).unwrap_err(),
+        ShapeError::TooFewPoints { count: 2 },
+    );
+    let rect = ShapePrimitive::rect(vect![1.0, 2.0], vect![3.0, 4.0]).unwrap();
+    assert_eq!(rect.points, vec![vect![1.0, 2.0], vect![4.0, 2.0], vect![4.0, 6.0], vect![1.0, 6.0]]);
+}
+
+#[test]
+fn test_component_normal() {
+    let square = gen_square(1.0);
+    assert_eq!(ShapeComponent::new(vect![0.0, 0.0, 0.0], vec![square.clone()]).unwrap_err(), ShapeError::ZeroNormal);
+    let component = ShapeComponent::new(vect![0.0, 3.0, 4.0], vec![square]).unwrap();
+    assert_eq!(component.normal, Vec3 { x: 0.0, y: 0.6, z: 0.8 });
+}
+
+fn total_area(pieces: &[ShapePrimitive]) -> f64 {
+    pieces.iter().map(|p| p.signed_area().abs()).sum()
+}
+#[test]
+fn test_clip_to_half_plane() {
+    // keeping everything right of x = 0 cuts the square in half, adding a point at each end of the cut
+    let clipped = clip_to_half_plane(&gen_square(1.0).points, |p| p.x);
+    assert_eq!(clipped, vec![
+        vect![0.0, 1.0], vect![0.0, -1.0], vect![1.0, -1.0], vect![1.0, 1.0],
+    ]);
+    assert!(clip_to_half_plane(&gen_square(1.0).points, |p| p.x - 2.0).is_empty());
+}
+#[test]
+fn test_simplify() {
+    // a repeated point, and a point halfway along the bottom edge
+    let mut square = ShapePrimitive { points: vec![
+        vect![0.0, 0.0], vect![0.0, 0.0], vect![1.0, 0.0], vect![2.0, 0.0], vect![2.0, 2.0], vect![0.0, 2.0],
+    ] };
+    square.simplify(1e-9);
+    assert_eq!(square.points, vec![vect![0.0, 0.0], vect![2.0, 0.0], vect![2.0, 2.0], vect![0.0, 2.0]]);
+    // straight corners where the ring wraps around, from repeating the first point at the end and starting halfway along an edge
+    let mut wrapped = ShapePrimitive { points: vec![
+        vect![1.0, 0.0], vect![2.0, 0.0], vect![2.0, 2.0], vect![0.0, 2.0], vect![0.0, 0.0], vect![1.0, 0.0],
+    ] };
+    wrapped.simplify(1e-9);
+    assert_eq!(wrapped.points.len(), 4);
+    assert_eq!(wrapped.signed_area(), 4.0);
+    // corners which only just turn are kept unless the tolerance allows for them
+    let bent = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 1e-3], vect![2.0, 0.0], vect![1.0, 1.0]] };
+    let mut kept = bent.clone();
+    kept.simplify(1e-9);
+    assert_eq!(kept.points.len(), 4);
+    let mut straightened = bent;
+    straightened.simplify(1e-2);
+    assert_eq!(straightened.points.len(), 3);
+    // a spike back along an edge isn't a straight corner
+    let mut spike = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![2.0, 0.0], vect![1.0, 0.0], vect![1.0, 1.0]] };
+    spike.simplify(1e-9);
+    assert_eq!(spike.points.len(), 4);
+    // a triangle is never simplified any further
+    let mut triangle = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![2.0, 0.0]] };
+    triangle.simplify(1e-9);
+    assert_eq!(triangle.points.len(), 3);
+}
+
+#[test]
+fn test_is_convex() {
+    assert!(gen_square(1.0).is_convex());
+    assert!(gen_90square(1.0).is_convex());
+    assert!(!gen_l_shape().is_convex());
+    // a point along a straight edge doesn't stop a shape being convex
+    let square = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![2.0, 0.0], vect![2.0, 2.0], vect![0.0, 2.0]] };
+    assert!(square.is_convex());
+}
+/// Checks `pieces` are all convex, drawn the same way round as `primitive`, and cover exactly the same area without overlapping.
+fn assert_tiles(primitive: &ShapePrimitive, pieces: &[ShapePrimitive]) {
+    for piece in pieces {
+        assert!(piece.is_convex(), "{:?} isn't convex", piece.points);
+        assert_eq!(piece.signed_area().signum(), primitive.signed_area().signum());
+    }
+    let area: f64 = pieces.iter().map(|p| p.signed_area()).sum();
+    assert!((area - primitive.signed_area()).abs() < EPSILON, "{} != {}", area, primitive.signed_area());
+    for (a, b) in pieces.iter().tuple_combinations() {
+        assert!(!a.overlaps(b), "{:?} overlaps {:?}", a.points, b.points);
+    }
+}
+#[test]
+fn test_decompose_convex() {
+    // the corner of the L can only go with one of the arms
+    let l_shape = gen_l_shape();
+    let pieces = l_shape.decompose_convex();
+    assert_eq!(pieces.len(), 2);
+    assert!(pieces.iter().all(|p| p.points.len() == 4));
+    assert_tiles(&l_shape, &pieces);
+    let mut backwards = l_shape.clone();
+    backwards.points.reverse();
+    assert_tiles(&backwards, &backwards.decompose_convex());
+
+    // a comb with a straight run along its back, and a star, which has to be cut up around every point
+    let comb = ShapePrimitive { points: vec![
+        vect![0.0, 0.0], vect![1.0, 0.0], vect![1.0, 2.0], vect![2.0, 2.0], vect![2.0, 0.0], vect![3.0, 0.0], vect![3.0, 2.0],
+        vect![4.0, 2.0], vect![4.0, 0.0], vect![5.0, 0.0], vect![5.0, 3.0], vect![2.5, 3.0], vect![0.0, 3.0],
+    ] };
+    let pieces = comb.decompose_convex();
+    assert!(pieces.len() >= 4);
+    assert_tiles(&comb, &pieces);
+    let star = ShapePrimitive { points: (0..10).map(|i| {
+        let angle = i as f64 * std::f64::consts::PI / 5.0;
+        let radius = if i % 2 == 0 { 3.0 } else { 1.0 };
+        vect![radius * angle.cos(), radius * angle.sin()]
+    }).collect() };
+    let pieces = star.decompose_convex();
+    assert!(pieces.len() >= 5);
+    assert_tiles(&star, &pieces);
+
+    // convex and degenerate outlines are left as they are
+    assert_eq!(gen_square(1.0).decompose_convex()[0].points, gen_square(1.0).points);
+    let line = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0], vect![2.0, 0.0]] };
+    assert_eq!(line.decompose_convex()[0].points, line.points);
+    assert!(ShapePrimitive { points: vec![vect![0.0, 0.0], vect![1.0, 0.0]] }.decompose_convex().is_empty());
+}
+#[test]
+fn test_subtract() {
+    let mut a = gen_square(1.0);
+    a.shift(vect![1.0, 0.0]);
+    // half of the square is left, whichever way round the clipping square is drawn
+    for b in [gen_square(1.0), gen_90square(1.0)] {
+        let pieces = a.subtract(&b);
+        assert!((total_area(&pieces) - 2.0).abs() < 1e-9);
+        assert!(pieces.iter().flat_map(|p| &p.points).all(|p| p.x >= 1.0));
+    }
+}
+#[test]
+fn test_subtract_hole() {
+    // cutting out the middle leaves a ring, made of several pieces as a primitive can't have a hole in it
+    let pieces = gen_square(2.0).subtract(&gen_45square(1.0));
+    assert!(pieces.len() > 1);
+    assert!((total_area(&pieces) - (16.0 - 2.0)).abs() < 1e-9);
+}
+#[test]
+fn test_subtract_untouched() {
+    let mut a = gen_square(1.0);
+    a.shift(vect![3.0, 0.0]);
+    let pieces = a.subtract(&gen_square(1.0));
+    assert_eq!(pieces.len(), 1);
+    assert_eq!(pieces[0].points, a.points);
+    // sharing an edge doesn't cover any of it either
+    a.shift(vect![-1.0, 0.0]);
+    assert_eq!(a.subtract(&gen_square(1.0))[0].points, a.points);
+    // but sitting inside leaves nothing
+    assert!(gen_45square(1.0).subtract(&gen_square(1.0)).is_empty());
+}
+#[test]
+fn test_del_whats_obscured_by() {
+    let normal = vect![0.0, 0.0, 1.0];
+    let mut cover = gen_square(1.0);
+    cover.shift(vect![1.0, 1.0]);
+    let cover = Shape::new(vec![ShapeComponent::new(normal, vec![cover]).unwrap()]);
+    let component = ShapeComponent::new(normal, vec![gen_square(1.0)]).unwrap();
+    // a quarter of the square is covered
+    let visible = component.del_whats_obscured_by(&cover, EPSILON).unwrap();
+    assert!((total_area(&visible.primitives) - 3.0).abs() < 1e-9);
+    let hidden = ShapeComponent::new(normal, vec![gen_45square(0.5)]).unwrap();
+    let cover = Shape::new(vec![ShapeComponent::new(normal, vec![gen_square(1.0)]).unwrap()]);
+    assert!(hidden.del_whats_obscured_by(&cover, EPSILON).is_none());
+}
+#[test]
+fn test_holes() {
+    let normal = vect![0.0, 1.0, 0.0];
+    let mut skylight = gen_square(1.0);
+    skylight.points.reverse();
+    let frame = ShapeComponent::new(normal, vec![gen_square(3.0)]).unwrap().with_holes(vec![skylight]);
+    assert_eq!(frame.contains(vect![0.0, 0.0], FillRule::EvenOdd), Containment::Outside);
+    assert_eq!(frame.contains(vect![2.0, 0.0], FillRule::EvenOdd), Containment::Inside);
+    assert_eq!(frame.contains(vect![1.0, 0.0], FillRule::EvenOdd), Containment::Edge);
+
+    // something showing through the hole isn't hidden, but something behind the frame is
+    let through = ShapeComponent::new(normal, vec![gen_45square(0.5)]).unwrap();
+    assert!(through.del_if_obscured_by(&frame, EPSILON).is_some());
+    let mut behind = gen_square(0.5);
+    behind.shift(vect![2.0, 2.0]);
+    assert!(behind.del_if_obscured_by(&frame, EPSILON).is_none());
+    // or behind the hole as well, with a bit sticking through
+    let across = ShapeComponent::new(normal, vec![gen_square(2.0)]).unwrap();
+    assert!(across.clone().del_if_obscured_by(&frame, EPSILON).is_some());
+    let cover = Shape::new(vec![frame.clone()]);
+    let visible = across.del_whats_obscured_by(&cover, EPSILON).unwrap();
+    assert_eq!(visible.primitives.len(), 1);
+
+    // the hole is drawn as part of the path, and left unfilled
+    assert_eq!(frame.generate_d(&PathOptions::default()).matches('M').count(), 2);
+    assert!(frame.generate_css(vect![0.5, 0.5, 0.5]).ends_with(";fill-rule:evenodd"));
+    let solid = ShapeComponent::new(normal, vec![gen_square(3.0)]).unwrap();
+    assert!(!solid.generate_css(vect![0.5, 0.5, 0.5]).contains("fill-rule"));
+    // and moves with the rest of the component
+    let rounded = frame.round_points(&VertexSnap::default()).unwrap();
+    assert_eq!(rounded.holes.len(), 1);
+}
+#[test]
+fn test_holes_dropped_with_primitive() {
+    let normal = vect![0.0, 1.0, 0.0];
+    let mut skylight = gen_square(1.0);
+    skylight.points.reverse();
+    let mut other = gen_square(1.0);
+    other.shift(vect![10.0, 0.0]);
+    let frame = ShapeComponent::new(normal, vec![gen_square(3.0), other]).unwrap().with_holes(vec![skylight]);
+    // once the primitive with the hole is hidden, the hole would only be drawn filled in over whatever's behind it
+    let cover = ShapeComponent::new(normal, vec![gen_square(4.0)]).unwrap();
+    let left = frame.del_if_obscured_by(&cover, EPSILON).unwrap();
+    assert_eq!(left.primitives.len(), 1);
+    assert!(left.holes.is_empty());
+}
+#[test]
+fn test_overlaps() {
+    let mut a = gen_square(1.0);
+    assert!(a.overlaps(&gen_45square(1.0)));
+    // touching along an edge doesn't cover anything
+    a.shift(vect![2.0, 0.0]);
+    assert!(!a.overlaps(&gen_square(1.0)));
+    a.shift(vect![-0.5, 0.0]);
+    assert!(a.overlaps(&gen_square(1.0)));
+}
+
+#[test]
+fn test_boundary_edges() {
+    let quad = |points: [(f64, f64); 4], normal: Vec3<f64>| ShapeComponent::quad(
+        points[0].into(), points[1].into(), points[2].into(), points[3].into(), normal,
+    ).unwrap();
+    let cube = Shape::builder()
+        .component(quad([(0.0, 20.0), (35.0, 0.0), (70.0, 20.0), (35.0, 40.0)], vect![0.0, 1.0, 0.0]))
+        .component(quad([(0.0, 20.0), (35.0, 40.0), (35.0, 80.0), (0.0, 60.0)], vect![1.0, 0.0, 0.0]))
+        .component(quad([(35.0, 40.0), (70.0, 20.0), (70.0, 60.0), (35.0, 80.0)], vect![0.0, 0.0, 1.0]))
+        .build();
+    // the three edges meeting in the middle are each shared by two faces
+    let edges = cube.boundary_edges();
+    assert_eq!(edges.len(), 6);
+    assert!(edges.iter().all(|(a, b)| *a != vect![35.0, 40.0] && *b != vect![35.0, 40.0]));
+    // and the rest join up into a single hexagon
+    let d = cube.generate_outline_d();
+    assert_eq!(d.matches('M').count(), 1);
+    assert!(d.ends_with(" z"));
+    let points = d.trim_start_matches('M').trim_end_matches(" z").split(' ').count();
+    assert_eq!(points, 12);
+
+    // a lone face is its own outline
+    let square = Shape::new(vec![ShapeComponent::new(vect![0.0, 1.0, 0.0], vec![gen_square(1.0)]).unwrap()]);
+    assert_eq!(square.boundary_edges().len(), 4);
+}
+
+#[test]
+fn test_outset() {
+    // a square grows the same whichever way round it's drawn
+    let square = gen_square(1.0);
+    let grown = square.outset(0.5);
+    assert!((grown.signed_area().abs() - 9.0).abs() < EPSILON);
+    let mut clockwise = square.clone();
+    clockwise.set_direction(CircleDirection::Clockwise);
+    assert!((clockwise.outset(0.5).signed_area().abs() - 9.0).abs() < EPSILON);
+    assert!((square.outset(-0.5).signed_area().abs() - 1.0).abs() < EPSILON);
+
+    // a thin spike only moves so far past its tip
+    let sliver = ShapePrimitive { points: vec![vect![0.0, 0.0], vect![100.0, 1.0], vect![0.0, 2.0]] };
+    let outset = sliver.outset(0.5);
+    for (before, after) in sliver.points.iter().zip(&outset.points) {
+        assert!((*after - *before).magnitude() <= MITER_LIMIT * 0.5 + EPSILON);
+    }
+    assert_eq!(outset.draw_direction(), sliver.draw_direction());
+
+    // shrinking past nothing would turn it inside out, so it's left alone
+    assert_eq!(square.outset(-2.0).points, square.points);
+    // as would growing into a notch narrower than the growth
+    let notched = ShapePrimitive { points: vec![
+        vect![0.0, 0.0], vect![10.0, 0.0], vect![10.0, 10.0], vect![5.1, 10.0], vect![5.1, 2.0], vect![4.9, 2.0], vect![4.9, 10.0], vect![0.0, 10.0],
+    ] };
+    assert_eq!(notched.outset(0.5).points, notched.points);
+    assert_ne!(notched.outset(0.05).points, notched.points);
+}
+
+#[test]
+fn test_bounds() {
+    let mut shape = gen_45square(2.0);
+    shape.shift(vect![1.0, 0.5]);
+    assert_eq!(shape.bounds(), Bounds { min: vect![-1.0, -1.5], max: vect![3.0, 2.5] });
+    assert_eq!((shape.width(), shape.height()), (4.0, 4.0));
+    assert_eq!((shape.left(), shape.top(), shape.right(), shape.bottom()), (-1.0, -1.5, 3.0, 2.5));
+    assert_eq!(shape.centre(), vect![1.0, 0.5]);
+}
+
+#[test]
+fn test_bounds_overlap() {
+    let a = Bounds { min: vect![0.0, 0.0], max: vect![2.0, 2.0] };
+    assert!(a.overlaps(&Bounds { min: vect![1.0, 1.0], max: vect![3.0, 3.0] }));
+    // shapes which only touch can still hide a point on the shared edge
+    assert!(a.overlaps(&Bounds { min: vect![2.0, 0.0], max: vect![4.0, 2.0] }));
+    assert!(!a.overlaps(&Bounds { min: vect![2.5, 0.0], max: vect![4.0, 2.0] }));
+    assert!(!a.overlaps(&Bounds { min: vect![0.0, -3.0], max: vect![2.0, -1.0] }));
+    assert_eq!(a.shifted(vect![1.0, -1.0]), Bounds { min: vect![1.0, -1.0], max: vect![3.0, 1.0] });
+    assert_eq!(a.centre(), vect![1.0, 1.0]);
+}
+
+#[test]
+fn test_cached_bounds() {
+    let mut square = gen_square(1.0);
+    square.shift(vect![8.0, 0.0]);
+    let front = ShapeComponent::new(vect![0.0, 0.0, 1.0], vec![square]).unwrap();
+    let side = ShapeComponent::new(vect![1.0, 0.0, 0.0], vec![gen_45square(4.0)]).unwrap();
+    let mut shape = Shape::new(vec![front, side]);
+    // the box is asked for before every change, so there's always one cached for the change to leave stale
+    let check = |shape: &Shape| assert_eq!(shape.bounds(), Bounds::around(shape.points_iter()));
+
+    check(&shape);
+    shape.shift(vect![0.3, -0.7]);
+    check(&shape);
+    shape.move_to(vect![10.0, 5.0]);
+    check(&shape);
+    shape.points_iter_mut().for_each(|p| *p = *p * 2.0);
+    check(&shape);
+    shape.normalise_winding();
+    check(&shape);
+
+    // a copy keeps the box it was made with, without sharing what happens to the original after
+    let copy = shape.clone();
+    shape.shift(vect![1.0, 1.0]);
+    check(&copy);
+    check(&shape);
+
+    // covering the small square off to the side leaves only the diamond, which is a smaller box
+    let mut cover = gen_square(3.0);
+    cover.move_to(shape.component_iter().next().unwrap().centre());
+    let cut = shape.clone().del_if_obscured_by(&cover, EPSILON).unwrap();
+    check(&cut);
+    assert_eq!(cut.component_iter().count(), 1);
+    let mut in_place = shape.clone();
+    Some(&mut in_place).del_if_obscured_by(&cover, EPSILON).unwrap();
+    check(&in_place);
+    let cover = Shape::new(vec![ShapeComponent::new(vect![0.0, 1.0, 0.0], vec![cover]).unwrap()]);
+    let clipped = shape.clone().del_whats_obscured_by(&cover, EPSILON).unwrap();
+    check(&clipped);
+    let mut in_place = shape.clone();
+    Some(&mut in_place).del_whats_obscured_by(&cover, EPSILON).unwrap();
+    check(&in_place);
+    check(&shape.clone().round_points(&VertexSnap::new(shape.points_iter())).unwrap());
+
+    // an instance's own copy starts off with the box of the shared one
+    let mut instance = ShapeInstance::moved_to(Arc::new(shape.clone()), vect![-4.0, 2.0]);
+    let shared = instance.bounds();
+    assert_eq!(shared, Bounds::around(instance.points_iter()));
+    assert_eq!(instance.to_mut().bounds(), shared);
+    instance.shift(vect![0.5, 0.5]);
+    assert_eq!(instance.bounds(), Bounds::around(instance.points_iter()));
+}
+
+#[test]
+fn test_cached_component_bounds() {
+    let mut square = gen_square(1.0);
+    square.shift(vect![8.0, 0.0]);
+    let mut component = ShapeComponent::new(vect![0.0, 0.0, 1.0], vec![gen_45square(4.0), square]).unwrap();
+    // the box is asked for before every change, so there's always one cached for the change to leave stale
+    let check = |component: &ShapeComponent| assert_eq!(component.bounds(), Bounds::around(component.points_iter()));
+
+    check(&component);
+    component.shift(vect![0.3, -0.7]);
+    check(&component);
+    component.move_to(vect![10.0, 5.0]);
+    check(&component);
+    component.points_iter_mut().for_each(|p| *p = *p * 2.0);
+    check(&component);
+    component.primitives_mut().pop();
+    check(&component);
+    let mut hole = gen_square(20.0);
+    hole.move_to(component.centre());
+    component.holes_mut().push(hole);
+    check(&component);
+    let mut component = component.with_holes(vec![]);
+    check(&component);
+    let mut square = gen_square(1.0);
+    square.move_to(vect![100.0, 100.0]);
+    component.primitives_mut().push(square);
+    check(&component);
+
+    // covering the square off to the side leaves only the diamond, which is a smaller box
+    let mut cover = gen_square(3.0);
+    cover.move_to(vect![100.0, 100.0]);
+    let cut = component.clone().del_if_obscured_by(&cover, EPSILON).unwrap();
+    check(&cut);
+    let mut in_place = component.clone();
+    Some(&mut in_place).del_if_obscured_by(&cover, EPSILON).unwrap();
+    check(&in_place);
+    let cover = Shape::new(vec![ShapeComponent::new(vect![0.0, 1.0, 0.0], vec![cover]).unwrap()]);
+    let clipped = component.clone().del_whats_obscured_by(&cover, EPSILON).unwrap();
+    check(&clipped);
+    let mut in_place = component.clone();
+    Some(&mut in_place).del_whats_obscured_by(&cover, EPSILON).unwrap();
+    check(&in_place);
+    check(&component.clone().round_points(&VertexSnap::new(component.points_iter())).unwrap());
+}
+
+/// A square of side `size` with its corner at the origin, in whichever precision is asked for.
+fn generic_square<F: Float>(size: F) -> ShapePrimitive<F> {
+    let zero = F::zero();
+    ShapePrimitive::rect(vect![zero, zero], vect![size, size]).unwrap()
+}
+
+#[test]
+fn test_f32_shapes() {
+    let square: ShapePrimitive32 = generic_square(2.0);
+    assert_eq!(square.contains(vect![1.0, 1.0], FillRule::EvenOdd), Containment::Inside);
+    assert_eq!(square.contains(vect![2.0, 1.0], FillRule::EvenOdd), Containment::Edge);
+    assert_eq!(square.contains(vect![3.0, 1.0], FillRule::NonZero), Containment::Outside);
+    assert_eq!(get_containment(&square, vect![2.0000005, 1.0], FillRule::EvenOdd, 1e-6), Containment::Edge);
+    assert_eq!(square.signed_area(), 4.0);
+    assert_eq!(square.centre(), vect![1.0, 1.0]);
+
+    // single precision numbers are written out as they're written in, not as their nearest f64
+    let small: ShapePrimitive32 = generic_square(0.1);
+    assert_eq!(small.generate_d(&PathOptions::default()), "M0 0 H0.1 V0.1 H0 z");
+    assert_eq!(generic_square(0.1f64).generate_d(&PathOptions::default()), "M0 0 H0.1 V0.1 H0 z");
+
+    // the whole shape, hiding one primitive with another
+    let component = ShapeComponent::new(vect![0.0f32, 1.0, 0.0], vec![generic_square(1.0)]).unwrap();
+    let shape: Shape32 = Shape::new(vec![component]);
+    assert!(shape.clone().del_if_obscured_by(&square, 1e-6).is_none());
+    assert!(shape.del_if_obscured_by(&small, 1e-6).is_some());
+}
+
+#[test]
+fn test_shape_instance() {
+    let front = ShapeComponent::new(vect![0.0, 0.0, 1.0], vec![gen_square(1.0)]).unwrap();
+    let side = ShapeComponent::new(vect![1.0, 0.0, 0.0], vec![gen_square(1.0), gen_45square(4.0)]).unwrap();
+    let template = Arc::new(Shape::new(vec![front, side]));
+
+    // reading a shared instance gives exactly the points of a moved copy
+    let instance = ShapeInstance::moved_to(template.clone(), vect![10.0, 3.0]);
+    let mut moved = Shape::clone(&template);
+    moved.move_to(vect![10.0, 3.0]);
+    assert_eq!(instance.points_iter().collect::<Vec<_>>(), moved.points_iter().collect::<Vec<_>>());
+    assert_eq!(instance.lines_iter().collect::<Vec<_>>(), moved.lines_iter().collect::<Vec<_>>());
+    assert_eq!(instance.bounds(), moved.bounds());
+
+    // it only takes a copy once part of it is actually deleted
+    let far = Shape::new(vec![ShapeComponent::new(vect![0.0, 0.0, 1.0], vec![gen_square(1.0)]).unwrap()]);
+    let kept = instance.clone().del_if_obscured_by(&far, EPSILON).unwrap();
+    assert!(matches!(&kept, ShapeInstance::Shared { template: shared, .. } if Arc::ptr_eq(shared, &template)));
+
+    let mut cover = gen_square(2.0);
+    cover.shift(vect![10.0, 3.0]);
+    let cut = kept.del_if_obscured_by(&cover, EPSILON).unwrap();
+    assert!(matches!(&cut, ShapeInstance::Owned(_)));
+    assert_eq!(cut.points_iter().count(), 4);
+    assert!(cut.del_if_obscured_by(&gen_square(20.0), EPSILON).is_none());
+
+    // changing a shared instance leaves the template alone
+    let mut instance = instance;
+    instance.shift(vect![1.0, 0.0]);
+    assert_eq!(template.centre(), vect![0.0, 0.0]);
+    assert_eq!(instance.centre(), vect![11.0, 3.0]);
+}
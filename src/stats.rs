@@ -0,0 +1,77 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::validation::ConnectionWarning;
+
+mod tests;
+
+/// The stages a render goes through, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Moving the shape in each cell into place in the image.
+    Placing,
+    /// Going through the cells back to front, deleting whatever's hidden by the shapes in front of it.
+    Culling,
+    /// Joining up connected regions and neighbouring faces.
+    Merging,
+    /// Styling each face and writing it out as a path.
+    Writing,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Phase::Placing => "placing",
+            Phase::Culling => "culling",
+            Phase::Merging => "merging",
+            Phase::Writing => "writing",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Told how far through each phase a render is, as how much of it is done out of how much there is in total.
+/// Each phase is reported as starting with none of it done, and once more when it's all done.
+pub type Progress = dyn FnMut(Phase, usize, usize);
+
+/// How much work a render did, for finding out where the time goes on big scenes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderStats {
+    /// Every occupied cell gone through, whether or not anything was drawn for it.
+    pub cells_visited: usize,
+    /// Every shape moved into place, counting shapes connected across several cells once.
+    pub shapes_placed: usize,
+    /// Shapes with nothing left to draw once whatever was in front of them was taken away.
+    pub shapes_culled: usize,
+    /// How many primitives and holes shapes which were still drawn lost to whatever was in front of them.
+    /// Clipping can cut a primitive into more pieces than it started as, which counts as nothing lost.
+    pub primitives_clipped: usize,
+    /// The `<path>`s in the output, including those drawn once and reused for several shapes.
+    pub paths_emitted: usize,
+    /// How long each phase took, in the order they ran.
+    pub timings: Vec<(Phase, Duration)>,
+    /// Anything odd about the scene which didn't stop it being drawn, for the caller to show however it likes.
+    pub warnings: Vec<ConnectionWarning>,
+}
+
+impl RenderStats {
+    /// How long `phase` took, if it ran at all.
+    pub fn timing(&self, phase: Phase) -> Option<Duration> {
+        self.timings.iter().find(|(p, _)| *p == phase).map(|(_, time)| *time)
+    }
+}
+
+/// One count or timing per line, the way `--verbose` shows them. Warnings are left out, as they're shown whether or not it's verbose.
+impl Display for RenderStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "cells visited: {}", self.cells_visited)?;
+        writeln!(f, "shapes placed: {}", self.shapes_placed)?;
+        writeln!(f, "shapes culled: {}", self.shapes_culled)?;
+        writeln!(f, "primitives clipped: {}", self.primitives_clipped)?;
+        write!(f, "paths emitted: {}", self.paths_emitted)?;
+        for (phase, time) in &self.timings {
+            write!(f, "\n{}: {:.1} ms", phase, time.as_secs_f64() * 1000.0)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+#![cfg(test)]
+
+use std::time::Duration;
+
+use itertools::Itertools;
+use quick_xml::writer::Writer;
+
+use crate::colour;
+use crate::grid::Grid;
+use crate::scene::{render_grid, render_grid_reporting, RenderOptions};
+use crate::scene::tests::{block, shapes};
+use crate::shapes::ShapeId;
+use crate::stats::{Phase, RenderStats};
+use crate::validation::ConnectionWarning;
+use crate::vect;
+use crate::vector::Vec3;
+
+#[test]
+fn test_block_stats() {
+    let stats = render_grid(&block(2), &shapes(), &RenderOptions::default(), Writer::new(vec![])).unwrap();
+    assert_eq!(stats.cells_visited, 8);
+    assert_eq!(stats.shapes_placed, 8);
+    // the cube at the back is hidden on every side, and the other seven each lose the faces they share with a neighbour
+    assert_eq!(stats.shapes_culled, 1);
+    assert_eq!(stats.primitives_clipped, 9);
+    // four faces showing on each of the three sides
+    assert_eq!(stats.paths_emitted, 12);
+    let phases = stats.timings.iter().map(|(phase, _)| *phase).collect::<Vec<_>>();
+    assert_eq!(phases, [Phase::Placing, Phase::Culling, Phase::Merging, Phase::Writing]);
+}
+
+#[test]
+fn test_warnings() {
+    let mut grid = Grid::new(vect![2, 1, 1]);
+    grid.set(vect![0, 0, 0], ShapeId::from(255u8));
+    let mut options = RenderOptions::default();
+    options.connections.insert(String::from("a"), vec![vect![0, 0, 0], vect![1, 0, 0]]);
+    // an empty member doesn't stop the render, but is given back rather than printed
+    let stats = render_grid(&grid, &shapes(), &options, Writer::new(vec![])).unwrap();
+    assert_eq!(stats.warnings, [ConnectionWarning::UnoccupiedMember { group: String::from("a"), coordinate: vect![1, 0, 0] }]);
+    assert!(render_grid(&block(2), &shapes(), &RenderOptions::default(), Writer::new(vec![])).unwrap().warnings.is_empty());
+}
+
+#[test]
+fn test_progress() {
+    let mut calls = vec![];
+    render_grid_reporting(&block(2), &shapes(), &RenderOptions::default(), Writer::new(vec![]), &colour::default_shader, &mut |phase, done, total| {
+        calls.push((phase, done, total));
+    }).unwrap();
+    for phase in [Phase::Placing, Phase::Culling, Phase::Merging, Phase::Writing] {
+        let reported = calls.iter().filter(|(p, _, _)| *p == phase).collect::<Vec<_>>();
+        let (_, first, _) = reported.first().unwrap();
+        let (_, last, total) = reported.last().unwrap();
+        assert_eq!(*first, 0, "{}", phase);
+        assert_eq!(last, total, "{}", phase);
+        assert!(reported.iter().all(|(_, done, total)| done <= total), "{}", phase);
+    }
+    assert!(calls.contains(&(Phase::Culling, 0, 8)));
+    // phases are only ever reported in order
+    let order = calls.iter().map(|(phase, _, _)| *phase).dedup().count();
+    assert_eq!(order, 4);
+}
+
+#[test]
+fn test_display() {
+    let stats = RenderStats {
+        cells_visited: 8,
+        shapes_placed: 8,
+        shapes_culled: 1,
+        primitives_clipped: 9,
+        paths_emitted: 12,
+        timings: vec![(Phase::Placing, Duration::from_micros(1300)), (Phase::Writing, Duration::from_millis(3))],
+        warnings: vec![],
+    };
+    assert_eq!(stats.to_string(), "\
+cells visited: 8
+shapes placed: 8
+shapes culled: 1
+primitives clipped: 9
+paths emitted: 12
+placing: 1.3 ms
+writing: 3.0 ms");
+    assert_eq!(stats.timing(Phase::Writing), Some(Duration::from_millis(3)));
+    assert_eq!(stats.timing(Phase::Culling), None);
+}
@@ -0,0 +1,1617 @@
+#![cfg(test)]
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::rc::Rc;
+
+use config::{Config, FileFormat};
+use itertools::Itertools;
+use quick_xml::reader::Reader;
+use quick_xml::writer::Writer;
+use regex::Regex;
+
+use crate::{bounding_box, combine_shapes, component_files, fit_grid_size, fuse_faces, map_cells, parser, render_catalogue, Renderer};
+use crate::error::{IsometricError, RunError};
+use crate::colour::{self, Effects, FaceInfo, Lighting};
+use crate::iter::{layers, object_svg_iter, Canvas, DocumentOptions, PrimitiveIter};
+use crate::path::PathOptions;
+use crate::scene::Scene;
+use crate::shapes::{Bounds, PlacedShape, Shape, ShapeComponent, ShapeId, ShapePrimitive};
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+
+#[test]
+fn test_fit_grid_size() {
+    let tiles = [vect![0, 0, 0], vect![7, 1, 3], vect![2, 2, 9]];
+    assert_eq!(fit_grid_size(&tiles, vect![0, 0, 0]), vect![8, 3, 10]);
+}
+#[test]
+fn test_fit_grid_size_minimum() {
+    let tiles = [vect![0, 0, 0], vect![7, 1, 3], vect![2, 2, 9]];
+    assert_eq!(fit_grid_size(&tiles, vect![5, 5, 5]), vect![8, 5, 10]);
+    assert_eq!(fit_grid_size(&[], vect![5, 5, 5]), vect![5, 5, 5]);
+}
+
+#[test]
+fn test_render_catalogue() {
+    let mut reader = Reader::from_str(include_str!("../components.svg"));
+    reader.trim_text(true);
+    let shapes = parser::parse_shapes(&mut reader).unwrap();
+    let mut output = vec![];
+    render_catalogue(&shapes, Writer::new(&mut output)).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    let distinct = shapes.values()
+        .enumerate()
+        .filter(|(i, s)| shapes.values().position(|t| std::sync::Arc::ptr_eq(s, t)) == Some(*i))
+        .count();
+    assert_eq!(output.matches("<text").count(), distinct);
+    // shapes with several labels are listed under all of them
+    assert!(output.contains(">01111111;11111111</text>"));
+}
+
+/// Renders `config` against the repository's components file.
+fn render(renderer: &Renderer, config: &str) -> String {
+    try_render(renderer, config).unwrap()
+}
+
+fn try_render(renderer: &Renderer, config: &str) -> Result<String, IsometricError> {
+    let mut reader = Reader::from_str(include_str!("../components.svg"));
+    reader.trim_text(true);
+    let settings = Config::builder()
+        .add_source(config::File::from_str(config, FileFormat::Toml))
+        .build().unwrap();
+    let mut output = vec![];
+    renderer.run(reader, Writer::new(&mut output), settings)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn test_shader_by_level() {
+    let mut renderer = Renderer::new();
+    renderer.set_shader(Box::new(|face| match face.cell.y {
+        0 => String::from("fill:red"),
+        _ => String::from("fill:blue"),
+    }));
+    // a tower two cubes high, next to a single cube
+    let output = render(&renderer, r#"
+        grid_size = [2, 2, 1]
+        tiles = [[0, 0, 0], [0, 1, 0], [1, 0, 0]]
+        equalities = {}
+    "#);
+    // the lower cube of the tower only shows its left face, the rest are all there
+    assert_eq!(output.matches("fill:red").count(), 4);
+    assert_eq!(output.matches("fill:blue").count(), 3);
+    assert!(!output.contains("fill:#"));
+}
+
+/// Every point of every primitive in the output, in a consistent order.
+fn output_points(output: &str) -> Vec<(f64, f64)> {
+    let d_regex = Regex::new(r#" d="([^"]*)""#).unwrap();
+    d_regex.captures_iter(output)
+        .flat_map(|caps| PrimitiveIter::from_str(&caps[1]).collect_vec())
+        .flat_map(|primitive| primitive.points)
+        .map(|p| (p.x, p.y))
+        .sorted_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)))
+        .collect()
+}
+
+#[test]
+fn test_merge_paths() {
+    let renderer = Renderer::new();
+    let scene = r#"
+        grid_size = [3, 1, 3]
+        tiles = [[0, 0, 0], [1, 0, 0], [2, 0, 0], [0, 0, 1], [1, 0, 1], [2, 0, 1], [0, 0, 2], [1, 0, 2], [2, 0, 2]]
+        equalities = {}
+    "#;
+    let separate = render(&renderer, &format!("merge_paths = \"off\"\n{}", scene));
+    let grouped = render(&renderer, &format!("merge_paths = \"shapes\"\n{}", scene));
+    let merged = render(&renderer, &format!("merge_paths = \"global\"\n{}", scene));
+
+    assert!(grouped.matches("<path").count() <= separate.matches("<path").count());
+    assert!(merged.matches("<path").count() < separate.matches("<path").count());
+    assert!(!merged.contains("<g"));
+    assert_eq!(output_points(&grouped), output_points(&separate));
+    assert_eq!(output_points(&merged), output_points(&separate));
+}
+
+#[test]
+fn test_missing_key() {
+    let result = try_render(&Renderer::new(), r#"
+        tiles = [[0, 0, 0]]
+        equalities = {}
+    "#);
+    assert_matches!(result, Err(IsometricError::Run(RunError::MissingKey { key })) if key == "grid_size");
+}
+
+#[test]
+fn test_minimal_config() {
+    // everything but the grid and what's in it can be left out
+    let minimal = render(&Renderer::new(), r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+    "#);
+    let explicit = render(&Renderer::new(), r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = {}
+        light_vector = [0.3, 0.7, 0.5]
+        scene_colour = [0.6, 0.2, 0.9]
+        margin = 0.0
+    "#);
+    assert_eq!(minimal, explicit);
+
+    let untiled = try_render(&Renderer::new(), "grid_size = [1, 1, 1]");
+    assert_matches!(untiled, Err(IsometricError::Run(RunError::MissingKey { key })) if key == "tiles");
+}
+
+#[test]
+fn test_generate() {
+    let scene = r#"
+        grid_size = [4, 4, 4]
+        generate = { kind = "noise", seed = 42, max_height = 3 }
+    "#;
+    let generated = render(&Renderer::new(), scene);
+    assert!(generated.contains("<path"));
+    assert_eq!(render(&Renderer::new(), scene), generated);
+    assert_ne!(render(&Renderer::new(), &scene.replace("42", "43")), generated);
+
+    // explicit tiles go on top of whatever was generated
+    let slab = "grid_size = [2, 2, 2]\ngenerate = { kind = \"slab\" }\n";
+    let on_top = render(&Renderer::new(), &format!("{}tiles = [[1, 1, 1]]", slab));
+    let explicit = render(&Renderer::new(), "grid_size = [2, 2, 2]\ntiles = [[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1], [1, 1, 1]]");
+    assert_eq!(on_top, explicit);
+
+    let unknown = try_render(&Renderer::new(), "grid_size = [2, 2, 2]\ngenerate = { kind = \"maze\" }");
+    assert_matches!(unknown, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "generate.kind");
+    let kindless = try_render(&Renderer::new(), "grid_size = [2, 2, 2]\ngenerate = { seed = 1 }");
+    assert_matches!(kindless, Err(IsometricError::Run(RunError::MissingKey { key })) if key == "generate.kind");
+    let missing = try_render(&Renderer::new(), "grid_size = [2, 2, 2]\ngenerate = { kind = \"slab\", shape = 99 }");
+    assert_matches!(missing, Err(IsometricError::Run(RunError::MissingShape { key, .. })) if key == "generate.shape");
+}
+
+#[test]
+fn test_invalid_key() {
+    let result = try_render(&Renderer::new(), r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+        view = "up"
+    "#);
+    assert_matches!(result, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "view");
+}
+
+#[test]
+fn test_invalid_tile() {
+    let result = try_render(&Renderer::new(), r#"
+        grid_size = [2, 2, 2]
+        tiles = [[0, 0, 0], [1, 1], [1, 1, 1]]
+        equalities = {}
+    "#);
+    assert_matches!(result, Err(IsometricError::Run(RunError::InvalidTile { index: 1, .. })));
+    let result = try_render(&Renderer::new(), r#"
+        grid_size = [2, 2, 2]
+        tiles = [[0, 0, 0], [1, 1, 1], [0, 2, 0]]
+        equalities = {}
+    "#);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "invalid tile at tiles[2]: (0, 2, 0) is outside grid_size (2, 2, 2)",
+    );
+}
+
+#[test]
+fn test_missing_reference_shape() {
+    let result = try_render(&Renderer::new(), r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+        reference_shape = 1
+    "#);
+    assert_matches!(result, Err(IsometricError::Run(RunError::MissingReferenceShape { id: ShapeId::Index(1), found })) if found.contains(&ShapeId::from(255u8)));
+}
+
+/// Renders a one tile scene with `components` in place of the repository's components file.
+fn try_render_components(components: &str) -> Result<String, IsometricError> {
+    try_render_scene(components, "grid_size = [1, 1, 1]\ntiles = [[0, 0, 0]]\nequalities = {}")
+}
+
+/// Renders `config` with `components` in place of the repository's components file.
+fn try_render_scene(components: &str, config: &str) -> Result<String, IsometricError> {
+    let mut reader = Reader::from_str(components);
+    reader.trim_text(true);
+    let settings = Config::builder()
+        .add_source(config::File::from_str(config, FileFormat::Toml))
+        .build().unwrap();
+    let mut output = vec![];
+    Renderer::new().run(reader, Writer::new(&mut output), settings)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn test_reference_shape_errors() {
+    let missing = try_render_components(r##"<svg><g inkscape:label="00000011"><path d="M 0 0 1 0 0 1 z" fill="#80ff80"/></g></svg>"##);
+    assert_eq!(
+        missing.unwrap_err().to_string(),
+        "no shape labelled 11111111 was found for reference_shape. \
+        A full cube has to be drawn there, as the direction of each axis is measured from its faces. \
+        Shapes were found labelled 00000011",
+    );
+    let empty = try_render_components("<svg></svg>");
+    assert_matches!(empty, Err(IsometricError::Run(RunError::MissingReferenceShape { found, .. })) if found.is_empty());
+
+    // a cube with its top and right side, but nothing on the left
+    let incomplete = try_render_components(r##"<svg><g inkscape:label="11111111">
+        <path d="M 0 0 2 -1 4 0 2 1 z" fill="#80ff80"/>
+        <path d="M 2 1 4 0 4 2 2 3 z" fill="#8080ff"/>
+    </g></svg>"##);
+    assert_matches!(incomplete, Err(IsometricError::Run(RunError::IncompleteReferenceShape { id: ShapeId::Index(255), missing })) if missing.len() == 1 && missing[0].starts_with("+z"));
+}
+
+#[test]
+fn test_error_kinds() {
+    // a components file which can't be read is told apart from a scene which can't be drawn, and both read as the error they wrap
+    let unreadable = try_render_components(r#"<svg><g inkscape:label="big cube"></g></svg>"#).unwrap_err();
+    assert!(unreadable.to_string().starts_with("group before position"));
+    assert_matches!(unreadable, IsometricError::Parse(parser::Error::BadLabel { value, .. }) if value == "big cube");
+    let unusable = try_render(&Renderer::new(), "tiles = []").unwrap_err();
+    assert_eq!(unusable.to_string(), "missing required setting grid_size");
+    assert_matches!(unusable, IsometricError::Run(RunError::MissingKey { .. }));
+}
+
+#[test]
+fn test_carried_attributes() {
+    let components = |top: &str| format!(r##"<svg><g inkscape:label="11111111">
+        <path d="M 0,20 35,0 70,20 35,40 Z" style="fill:#80ff80" {}/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" style="fill:#ff8080"/>
+        <path d="M 35,40 V 80 L 70,60 V 20 Z" style="fill:#8080ff"/>
+    </g></svg>"##, top);
+    let scene = "grid_size = [2, 1, 1]\ntiles = [[0, 0, 0], [1, 0, 0]]";
+
+    // every copy of the top carries its class, and nothing else does
+    let roofed = try_render_scene(&components(r##"class="roof" fill="#123456""##), scene).unwrap();
+    assert_eq!(roofed.matches(r#"class="roof""#).count(), 2);
+    // the shading wins over a fill carried with it
+    assert!(!roofed.contains("#123456"));
+
+    // which is the other way round when it's preserved
+    let preserved = try_render_scene(&components(r##"class="roof" fill="#123456""##), &format!("preserve_fill = true\n{}", scene)).unwrap();
+    assert_eq!(preserved.matches(r##"fill="#123456""##).count(), 2);
+    assert_eq!(preserved.matches("style=\"fill:").count(), preserved.matches("<path").count() - 2);
+
+    // paths with the same style aren't merged together unless they carry the same attributes too
+    let halves = |class: &str| format!(r##"<svg><g inkscape:label="11111111">
+        <path d="M 0,20 35,0 35,40 Z" style="fill:#80ff80" {}/>
+        <path d="M 35,0 70,20 35,40 Z" style="fill:#80ff80"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" style="fill:#ff8080"/>
+        <path d="M 35,40 V 80 L 70,60 V 20 Z" style="fill:#8080ff"/>
+    </g></svg>"##, class);
+    let merged = try_render_scene(&halves(""), "merge_paths = \"shapes\"\ngrid_size = [1, 1, 1]\ntiles = [[0, 0, 0]]").unwrap();
+    assert_eq!(merged.matches("<path").count(), 3);
+    let split = try_render_scene(&halves(r#"class="roof""#), "merge_paths = \"shapes\"\ngrid_size = [1, 1, 1]\ntiles = [[0, 0, 0]]").unwrap();
+    assert_eq!(split.matches("<path").count(), 4);
+
+    // nor when they overlap, as they could cancel each other out where they cross
+    let overlapping = halves("").replace("</svg>", r##"<g inkscape:label="pyramid">
+        <path d="M 0,20 35,0 35,40 Z" style="fill:#80ff80"/>
+        <path d="M 10,20 35,5 35,35 Z" style="fill:#80ff80"/>
+    </g></svg>"##);
+    for merging in ["shapes", "global"] {
+        let config = format!("merge_paths = \"{}\"\ngrid_size = [1, 1, 1]\ntiles = [[0, 0, 0, \"pyramid\"]]", merging);
+        assert_eq!(try_render_scene(&overlapping, &config).unwrap().matches("<path").count(), 2);
+    }
+}
+
+#[test]
+fn test_component_strokes() {
+    let components = r##"<svg><g inkscape:label="11111111">
+        <path d="M 0,20 35,0 70,20 35,40 Z" style="fill:#80ff80;stroke:#202020;stroke-width:2;stroke-linejoin:round"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" style="fill:#ff8080;stroke-width:1.00157"/>
+        <path d="M 35,40 V 80 L 70,60 V 20 Z" style="fill:#8080ff"/>
+    </g></svg>"##;
+    let scene = "grid_size = [2, 1, 1]\ntiles = [[0, 0, 0], [1, 0, 0]]";
+
+    // the stroke goes after the fill on every copy of the top, and a width on its own is left behind
+    let stroked = try_render_scene(components, scene).unwrap();
+    let top = Regex::new(r#"style="fill:#[0-9a-f]{6};stroke:#202020;stroke-width:2;stroke-linejoin:round""#).unwrap();
+    assert_eq!(top.find_iter(&stroked).count(), 2);
+    assert_eq!(stroked.matches("stroke").count(), 6);
+
+    // lit like the top's fill when shaded, so no longer the colour it was given
+    let shaded = try_render_scene(components, &format!("shade_strokes = true\n{}", scene)).unwrap();
+    assert!(!shaded.contains("#202020"));
+    assert_eq!(shaded.matches(";stroke:#").count(), 2);
+}
+
+#[test]
+fn test_reference_shape_negative_faces() {
+    let cube = |top: &str, left: &str, right: &str| format!(r##"<svg><g inkscape:label="11111111">
+        <path d="M 0,20 35,0 70,20 35,40 Z" fill="{}"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" fill="{}"/>
+        <path d="M 70,20 35,40 V 80 L 70,60 Z" fill="{}"/>
+    </g></svg>"##, top, left, right);
+    let without_fills = |output: String| Regex::new("style=\"[^\"]*\"").unwrap().replace_all(&output, "").into_owned();
+    // the faces facing the other way along each axis are the same size, so give the same axes
+    let positive = try_render_components(&cube("#80ff80", "#8080ff", "#ff8080")).unwrap();
+    let negative = try_render_components(&cube("#800080", "#808000", "#008080")).unwrap();
+    assert_eq!(without_fills(positive.clone()), without_fills(negative));
+    let mixed = try_render_components(&cube("#800080", "#8080ff", "#008080")).unwrap();
+    assert_eq!(without_fills(positive), without_fills(mixed));
+
+    // a second face for an axis has to agree with the first
+    let agreeing = try_render_components(r##"<svg><g inkscape:label="11111111">
+        <path d="M 0,20 35,0 70,20 35,40 Z" fill="#80ff80"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" fill="#8080ff"/>
+        <path d="M 70,20 35,40 V 80 L 70,60 Z" fill="#ff8080"/>
+        <path d="M 0,60 35,40 70,60 35,80 Z" fill="#800080"/>
+    </g></svg>"##);
+    assert!(agreeing.is_ok());
+    let squashed = try_render_components(r##"<svg><g inkscape:label="11111111">
+        <path d="M 0,20 35,0 70,20 35,40 Z" fill="#80ff80"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" fill="#8080ff"/>
+        <path d="M 70,20 35,40 V 80 L 70,60 Z" fill="#ff8080"/>
+        <path d="M 0,60 35,50 70,60 35,70 Z" fill="#800080"/>
+    </g></svg>"##);
+    assert_matches!(squashed, Err(IsometricError::Run(RunError::MismatchedReferenceShape { id: ShapeId::Index(255), axis })) if axis.starts_with("+y"));
+}
+
+/// Every distinct fill in the output, in the order they first appear.
+fn output_fills(output: &str) -> Vec<&str> {
+    output.match_indices("fill:#").map(|(i, _)| &output[i..i + 12]).unique().collect()
+}
+
+#[test]
+fn test_light_vector() {
+    let scene = r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let default = render(&renderer, scene);
+    let from_above = render(&renderer, &format!("light_vector = [0.0, 2.0, 0.0]\n{}", scene));
+    let from_side = render(&renderer, &format!("light_vector = [0.2, 0.9, 0.1]\n{}", scene));
+    assert_ne!(output_fills(&from_above), output_fills(&default));
+    assert_ne!(output_fills(&from_above), output_fills(&from_side));
+
+    let zero = try_render(&renderer, &format!("light_vector = [0.0, 0.0, 0.0]\n{}", scene));
+    assert_matches!(zero, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "light_vector");
+}
+
+#[test]
+fn test_checker() {
+    let scene = r#"
+        grid_size = [2, 1, 2]
+        tiles = [[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1]]
+        light_vector = [0.0, 1.0, 0.0]
+        scene_colour = [255, 255, 255]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let tops = |output: &str| Regex::new(r#"<g id="tile-(\d)-0-(\d)"[^>]*>(.*?)</g>"#).unwrap()
+        .captures_iter(output)
+        .map(|caps| (caps[1].to_string() + &caps[2], caps[3].contains("fill:#ffffff")))
+        .sorted()
+        .collect_vec();
+    let plain = render(&renderer, scene);
+    assert!(tops(&plain).iter().all(|(_, white)| *white));
+    let checked = render(&renderer, &format!("checker = {{ strength = 0.5 }}\n{}", scene));
+    assert_eq!(tops(&checked), [
+        (String::from("00"), true),
+        (String::from("01"), false),
+        (String::from("10"), false),
+        (String::from("11"), true),
+    ]);
+    assert!(checked.contains("fill:#808080"));
+
+    let invalid = try_render(&renderer, &format!("checker = {{ axes = \"xw\" }}\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "checker.axes");
+    let repeated = try_render(&renderer, &format!("checker = {{ axes = \"xx\" }}\n{}", scene));
+    assert_matches!(repeated, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "checker.axes");
+    let strong = try_render(&renderer, &format!("checker = {{ strength = 2 }}\n{}", scene));
+    assert_matches!(strong, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "checker.strength");
+}
+
+#[test]
+fn test_gamma_correct() {
+    let scene = r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        scene_colour = [255, 255, 255]
+        light_vector = [0.6, 0.8, 0]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let plain = render(&renderer, scene);
+    assert_eq!(output_fills(&plain), vec!["fill:#cccccc", "fill:#999999", "fill:#000000"]);
+    let corrected = render(&renderer, &format!("gamma_correct = true\n{}", scene));
+    assert_eq!(output_fills(&corrected), vec!["fill:#e7e7e7", "fill:#cbcbcb", "fill:#000000"]);
+}
+
+#[test]
+fn test_fog_recedes() {
+    let scene = r#"
+        grid_size = [6, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0], [2, 0, 0], [3, 0, 0], [4, 0, 0], [5, 0, 0]]
+    "#;
+    // the fill of the top of the cube at each x, which comes first in its group
+    let tops = |output: &str| (0..6)
+        .map(|x| {
+            let start = output.find(&format!(r#"<g id="tile-{}-0-0""#, x)).unwrap();
+            output_fills(&output[start..])[0].to_string()
+        })
+        .collect_vec();
+    let renderer = Renderer::new();
+    let clear = tops(&render(&renderer, scene));
+    let fogged = tops(&render(&renderer, &format!("fog = {{ colour = [255, 255, 255], start = 0, end = 5 }}\n{}", scene)));
+    // the cube at the back is lost in the fog, and the one at the front is seen clearly
+    assert_eq!(fogged[0], "fill:#ffffff");
+    assert_eq!(fogged[5], clear[5]);
+    // with every cube in between further into it than the one in front
+    let whiteness = |fill: &str| (0..3).map(|i| u32::from_str_radix(&fill[6 + 2 * i..8 + 2 * i], 16).unwrap()).sum::<u32>();
+    assert!(fogged.windows(2).all(|pair| whiteness(&pair[0]) > whiteness(&pair[1])));
+}
+
+#[test]
+fn test_gradient_fills() {
+    let scene = r#"
+        grid_size = [3, 1, 1]
+        tiles = [[0, 0, 0], [2, 0, 0]]
+        equalities = { "gradient-7627b0" = [[2, 0, 0]] }
+    "#;
+    let renderer = Renderer::new();
+    let flat = render(&renderer, scene);
+    assert!(!flat.contains("<defs>"));
+
+    let graded = render(&renderer, &format!("gradient = {{ darkening = 0.5 }}\n{}", scene));
+    assert!(!graded.contains("fill:#"));
+    // both cubes share their three gradients, which each get darker toward the bottom
+    assert_eq!(graded.matches("<linearGradient").count(), 3);
+    assert_eq!(graded.matches("fill:url(#gradient-").count(), 6);
+    assert!(graded.contains(r##"<linearGradient id="gradient-7627b0" x1="0" y1="0" x2="0" y2="1"><stop offset="0" stop-color="#7627b0"/><stop offset="1" stop-color="#3b1458"/></linearGradient>"##));
+    assert!(graded.find("</defs>").unwrap() < graded.find("<g ").unwrap());
+    // ids have to stay unique across the whole document
+    let ids = Regex::new(r#" id="([^"]*)""#).unwrap().captures_iter(&graded).map(|c| c[1].to_string()).collect_vec();
+    assert_eq!(ids.iter().unique().count(), ids.len());
+
+    // a shader not using the gradients leaves them out
+    let mut plain = Renderer::new();
+    plain.set_shader(Box::new(|face| face.component.generate_css(face.colour)));
+    let unused = render(&plain, &format!("gradient = {{}}\n{}", scene));
+    assert_eq!(unused, flat);
+
+    let invalid = try_render(&renderer, &format!("gradient = {{ darkening = 2.0 }}\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "gradient.darkening");
+}
+
+#[test]
+fn test_stroke() {
+    let scene = r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let plain = render(&renderer, scene);
+    assert!(!plain.contains("stroke"));
+
+    // every face gets the stroke added on to its fill
+    let primitives = render(&renderer, &format!("stroke_colour = [32, 16, 0]\nstroke_width = 2\n{}", scene));
+    assert_eq!(primitives.matches("<path").count(), plain.matches("<path").count());
+    assert_eq!(primitives.matches(";stroke:#201000;stroke-width:2\"").count(), plain.matches("<path").count());
+    assert_eq!(output_points(&primitives), output_points(&plain));
+
+    // each cube gets one extra path around its outside, and the faces are left as they were
+    let silhouette = render(&renderer, &format!("stroke_mode = \"silhouette\"\n{}", scene));
+    assert_eq!(silhouette.matches("<path").count(), plain.matches("<path").count() + 2);
+    assert_eq!(silhouette.matches(r#"style="fill:none;stroke:#000000;stroke-width:1""#).count(), 2);
+    assert_eq!(Regex::new(r#" style="fill:none;[^"]*""#).unwrap().replace_all(&silhouette, "").matches("stroke").count(), 0);
+
+    let invalid = try_render(&renderer, &format!("stroke_mode = \"dashed\"\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "stroke_mode");
+    let thin = try_render(&renderer, &format!("stroke_width = 0\n{}", scene));
+    assert_matches!(thin, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "stroke_width");
+}
+
+#[test]
+fn test_wireframe() {
+    let scene = "grid_size = [2, 1, 1]\ntiles = [[0, 0, 0], [1, 0, 0]]\n";
+    let renderer = Renderer::new();
+    let solid = render(&renderer, scene);
+
+    // the same faces as the solid render, with only their outlines drawn
+    let wireframe = render(&renderer, &format!("render_mode = \"wireframe\"\n{}", scene));
+    assert_eq!(output_points(&wireframe), output_points(&solid));
+    assert_eq!(wireframe.matches(r#"style="fill:none;stroke:#000000;stroke-width:1""#).count(), solid.matches("<path").count());
+
+    // or with the faces a solid render hides as well
+    let hidden = render(&renderer, &format!("render_mode = \"wireframe\"\nwireframe_hidden_faces = true\nwireframe_colour = [255, 0, 0]\nwireframe_width = 2\n{}", scene));
+    assert_eq!(hidden.matches(r#"style="fill:none;stroke:#ff0000;stroke-width:2""#).count(), 6);
+
+    // shadows would be the only thing filled in
+    let shadowed = render(&renderer, &format!("render_mode = \"wireframe\"\nshadow = {{}}\n{}", scene));
+    assert_eq!(shadowed, wireframe);
+
+    let unknown = try_render(&renderer, &format!("render_mode = \"dotted\"\n{}", scene));
+    assert_matches!(unknown, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "render_mode");
+    let solid_options = try_render(&renderer, &format!("wireframe_width = 2\n{}", scene));
+    assert_matches!(solid_options, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "wireframe_width");
+    let thin = try_render(&renderer, &format!("render_mode = \"wireframe\"\nwireframe_width = 0\n{}", scene));
+    assert_matches!(thin, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "wireframe_width");
+    let clipped = try_render(&renderer, &format!("render_mode = \"wireframe\"\nwireframe_hidden_faces = true\nclip_hidden_faces = true\n{}", scene));
+    assert_matches!(clipped, Err(IsometricError::Run(RunError::InvalidKey { key, reason })) if key == "clip_hidden_faces" && reason.contains("wireframe_hidden_faces"));
+    let seams = try_render(&renderer, &format!("render_mode = \"wireframe\"\nanti_seam = \"outset\"\n{}", scene));
+    assert_matches!(seams, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "anti_seam");
+}
+
+#[test]
+fn test_anti_seam() {
+    let scene = r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let plain = render(&renderer, scene);
+
+    let stroked = render(&renderer, &format!("anti_seam = \"stroke\"\n{}", scene));
+    assert_eq!(output_points(&stroked), output_points(&plain));
+    for caps in Regex::new(r#"style="fill:(#[0-9a-f]{6});stroke:(#[0-9a-f]{6});stroke-width:0.5""#).unwrap().captures_iter(&stroked) {
+        assert_eq!(caps[1], caps[2]);
+    }
+    assert_eq!(stroked.matches("stroke-width:0.5").count(), plain.matches("<path").count());
+    // faces which already have an outline keep it
+    let outlined = render(&renderer, &format!("anti_seam = \"stroke\"\nstroke_colour = [0, 0, 0]\n{}", scene));
+    assert!(!outlined.contains("stroke-width:0.5"));
+
+    // every face grows by half the width, moving its corners but drawing the same paths
+    let outset = render(&renderer, &format!("anti_seam = \"outset\"\nanti_seam_width = 1.0\n{}", scene));
+    assert_eq!(outset.matches("<path").count(), plain.matches("<path").count());
+    assert!(!outset.contains("stroke"));
+    assert_ne!(output_points(&outset), output_points(&plain));
+
+    let invalid = try_render(&renderer, &format!("anti_seam = \"glue\"\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "anti_seam");
+}
+
+#[test]
+fn test_shadow() {
+    // lit from straight above, each cube shades the ground right under it
+    let scene = r#"
+        light_vector = [0, 1, 0]
+        grid_size = [3, 3, 3]
+        tiles = [[0, 2, 0], [1, 1, 0], [2, 0, 2]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let plain = render(&renderer, scene);
+    let shaded = render(&renderer, &format!("shadow = {{ opacity = 0.5, colour = [0, 0, 64] }}\n{}", scene));
+    // all of the shadow is drawn as one path before anything else, so shadows never darken each other
+    // the two cubes at the back are next to each other along x, so their shadows are drawn as one, and the last is right under its cube
+    let shadow = r#"<path d="M105 120 175 160 140 180 70 140 zM105 200 140 220 105 240 70 220 z" style="fill:#000040;fill-opacity:0.5"/>"#;
+    assert!(shaded.starts_with(&format!("{}{}", &plain[..plain.find("<g").unwrap()], shadow)));
+    assert_eq!(shaded.replace(shadow, ""), plain);
+
+    let invalid = try_render(&renderer, &format!("shadow = {{ opacity = -0.5 }}\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "shadow.opacity");
+}
+
+#[test]
+fn test_margin_and_size() {
+    let scene = r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let plain = render(&renderer, scene);
+    assert!(plain.contains(r#"<svg width="70" height="80" viewBox="0 0 70 80""#));
+
+    // the margin pads every side, moving everything in from the top left
+    let padded = render(&renderer, &format!("margin = 10\n{}", scene));
+    assert!(padded.contains(r#"<svg width="90" height="100" viewBox="0 0 90 100""#));
+    let moved = output_points(&plain).into_iter().map(|(x, y)| (x + 10.0, y + 10.0)).collect_vec();
+    assert_eq!(output_points(&padded), moved);
+
+    // the image can be shown at any size without changing what it's drawn in, and one side follows the other
+    let sized = render(&renderer, &format!("output_width = \"35mm\"\n{}", scene));
+    assert!(sized.contains(r#"<svg width="35mm" height="40mm" viewBox="0 0 70 80""#));
+    let both = render(&renderer, &format!("output_width = 140\noutput_height = \"2in\"\n{}", scene));
+    assert!(both.contains(r#"<svg width="140" height="2in" viewBox="0 0 70 80""#));
+
+    let negative = try_render(&renderer, &format!("margin = -1\n{}", scene));
+    assert_matches!(negative, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "margin");
+    let unknown = try_render(&renderer, &format!("output_height = \"3 furlongs\"\n{}", scene));
+    assert_matches!(unknown, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "output_height");
+}
+
+#[test]
+fn test_pretty_print() {
+    let scene = r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let compact = render(&renderer, scene);
+    assert!(compact.starts_with(r#"<?xml version="1.0" encoding="UTF-8"?><svg "#));
+    assert!(!compact.contains('\n'));
+
+    // only the space between elements changes, never what's in them
+    let pretty = render(&renderer, &format!("pretty_print = true\n{}", scene));
+    assert!(pretty.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg "));
+    assert!(pretty.contains("\n  <g id=\"tile-0-0-0\" data-x=\"0\" data-y=\"0\" data-z=\"0\">\n    <path d="));
+    assert_eq!(pretty.lines().map(str::trim).collect::<String>(), compact);
+}
+
+#[test]
+fn test_relative_paths() {
+    let renderer = Renderer::new();
+    let absolute = render(&renderer, include_str!("../config.toml"));
+    let relative = render(&renderer, &format!("relative_paths = true\n{}", include_str!("../config.toml")));
+    assert!(relative.len() < absolute.len());
+    assert!(relative.contains(" l") || relative.contains(" v") || relative.contains(" h"));
+    assert_eq!(output_points(&relative), output_points(&absolute));
+}
+
+#[test]
+fn test_lights() {
+    let scene = r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        scene_colour = [200, 100, 50]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    // a list with just the default light draws the same as no list at all
+    let default = render(&renderer, scene);
+    let listed = render(&renderer, &format!("lights = [{{ direction = [0.3, 0.7, 0.5] }}]\n{}", scene));
+    assert_eq!(listed, default);
+    let empty = render(&renderer, &format!("lights = []\n{}", scene));
+    assert_eq!(empty, default);
+
+    // a key light from above with a dim red fill from the side lights one side but not the other
+    let lit = render(&renderer, &format!(r#"
+        lights = [{{ direction = [0, 1, 0] }}, {{ direction = [1, 0, 0], intensity = 0.5, colour = [255, 0, 0] }}]
+        {}"#, scene));
+    assert_eq!(output_fills(&lit).len(), 3);
+    assert_eq!(lit.matches("fill:#000000").count(), 1);
+    assert_eq!(lit.matches("fill:#640000").count(), 1);
+
+    let both = try_render(&renderer, &format!("light_vector = [0, 1, 0]\nlights = [{{ direction = [0, 1, 0] }}]\n{}", scene));
+    assert_matches!(both, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "light_vector");
+    let zero = try_render(&renderer, &format!("lights = [{{ direction = [0, 1, 0] }}, {{ direction = [0, 0, 0] }}]\n{}", scene));
+    assert_matches!(zero, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "lights[1].direction");
+    let undirected = try_render(&renderer, &format!("lights = [{{ intensity = 0.5 }}]\n{}", scene));
+    assert_matches!(undirected, Err(IsometricError::Run(RunError::MissingKey { key })) if key == "lights[0].direction");
+}
+
+#[test]
+fn test_ambient_lighting() {
+    // lit from directly above, the sides of the cube are perpendicular to the light
+    let scene = r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        light_vector = [0, 1, 0]
+        scene_colour = [200, 100, 50]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let unlit = render(&renderer, scene);
+    assert_eq!(unlit.matches("fill:#000000").count(), 2);
+    let ambient = render(&renderer, &format!("ambient = 0.3\n{}", scene));
+    assert_eq!(ambient.matches("fill:#3c1e0f").count(), 2);
+    assert_eq!(ambient.matches("fill:#ff8241").count(), 1);
+    let tinted = render(&renderer, &format!("ambient = 0.5\nambient_colour = [0, 0, 255]\n{}", scene));
+    assert_eq!(tinted.matches("fill:#000080").count(), 2);
+
+    let negative = try_render(&renderer, &format!("diffuse = -1.0\n{}", scene));
+    assert_matches!(negative, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "diffuse");
+}
+
+#[test]
+fn test_scene_colour() {
+    let scene = r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let default = render(&renderer, scene);
+    let floats = render(&renderer, &format!("scene_colour = [0.6, 0.2, 0.9]\n{}", scene));
+    let ints = render(&renderer, &format!("scene_colour = [255, 128, 0]\n{}", scene));
+    assert_eq!(output_fills(&floats), output_fills(&default));
+    assert_ne!(output_fills(&ints), output_fills(&default));
+    // a single decimal point makes the whole colour run from 0 to 1
+    let mixed = try_render(&renderer, &format!("scene_colour = [153, 51, 229.5]\n{}", scene));
+    assert_matches!(mixed, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "scene_colour");
+}
+
+#[test]
+fn test_tile_colours() {
+    // lit from directly above, the top faces come out in exactly their tile's colour
+    let scene = r#"
+        grid_size = [3, 1, 3]
+        tiles = [[0, 0, 0], [0, 0, 2], [2, 0, 2]]
+        light_vector = [0, 1, 0]
+        scene_colour = [0.5, 0.5, 0.5]
+        equalities = { pair = [[0, 0, 2], [2, 0, 2]] }
+    "#;
+    let renderer = Renderer::new();
+    let plain = render(&renderer, scene);
+    assert_eq!(plain.matches("fill:#808080").count(), 2);
+
+    // the pair is drawn from [0, 0, 2], but takes its colour from [2, 0, 2]
+    let coloured = render(&renderer, &format!("tile_colours = [{{ tile = [2, 0, 2], colour = [255, 0, 0] }}]\n{}", scene));
+    assert_eq!(coloured.matches("fill:#808080").count(), 1);
+    assert_eq!(coloured.matches("fill:#ff0000").count(), 1);
+
+    let conflicting = try_render(&renderer, &format!(
+        "tile_colours = [{{ tile = [2, 0, 2], colour = [255, 0, 0] }}, {{ tile = [0, 0, 2], colour = [0, 0, 255] }}]\n{}", scene
+    ));
+    assert_matches!(conflicting, Err(IsometricError::Run(RunError::ConflictingColours { group, .. })) if group == "pair");
+}
+
+#[test]
+fn test_palette() {
+    // lit from directly above, the top faces come out in exactly their entry's colour
+    let palette = r#"
+        light_vector = [0, 1, 0]
+        scene_colour = [0.5, 0.5, 0.5]
+        palette.water = { shape = 255, colour = [40, 90, 200], opacity = 0.7 }
+        palette.grass = { colour = [60, 160, 60] }
+    "#;
+    let renderer = Renderer::new();
+    let coloured = render(&renderer, &format!(r#"{}
+        grid_size = [3, 1, 3]
+        tiles = [[0, 0, 0, "water"], [2, 0, 0, "grass"], [0, 0, 2]]
+        fills = [[[2, 0, 2], [2, 0, 2], "grass"]]
+    "#, palette));
+    assert_eq!(coloured.matches("fill:#285ac8").count(), 1);
+    assert_eq!(coloured.matches("fill:#3ca03c").count(), 2);
+    assert_eq!(coloured.matches("fill:#808080").count(), 1);
+    // only the water is see-through, and only its faces say so
+    assert_eq!(coloured.matches(";fill-opacity:0.7").count(), 3);
+
+    // tile_colours still pick out single cells
+    let overridden = render(&renderer, &format!(r#"{}
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0, "grass"]]
+        tile_colours = [{{ tile = [0, 0, 0], colour = [255, 0, 0] }}]
+    "#, palette));
+    assert_eq!(overridden.matches("fill:#ff0000").count(), 1);
+
+    // names which aren't in the palette are reported where they're used
+    let unknown = try_render(&renderer, &format!("{}\ngrid_size = [2, 1, 1]\ntiles = [[0, 0, 0], [1, 0, 0, \"lava\"]]", palette));
+    assert_matches!(unknown, Err(IsometricError::Run(RunError::InvalidTile { index: 1, reason })) if reason.contains("lava"));
+    let generated = try_render(&renderer, &format!("{}\ngrid_size = [2, 1, 1]\ngenerate = {{ kind = \"slab\", shape = \"lava\" }}", palette));
+    assert_matches!(generated, Err(IsometricError::Run(RunError::MissingShape { key, .. })) if key == "generate.shape");
+
+    let missing = try_render(&renderer, "palette.water = { shape = 99 }\ngrid_size = [1, 1, 1]\ntiles = [[0, 0, 0]]");
+    assert_matches!(missing, Err(IsometricError::Run(RunError::MissingShape { key, .. })) if key == "palette.water.shape");
+    let cloudy = try_render(&renderer, "palette.water = { opacity = 2 }\ngrid_size = [1, 1, 1]\ntiles = [[0, 0, 0]]");
+    assert_matches!(cloudy, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "palette.water.opacity");
+    let taken = try_render(&renderer, "palette.11111111 = {}\ngrid_size = [1, 1, 1]\ntiles = [[0, 0, 0]]");
+    assert_matches!(taken, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "palette.11111111");
+}
+
+#[test]
+fn test_component_files() {
+    let files = |config: &str| {
+        let settings = Config::builder()
+            .add_source(config::File::from_str(config, FileFormat::Toml))
+            .build().unwrap();
+        component_files(&settings)
+    };
+    assert_eq!(files("").unwrap(), Vec::<String>::new());
+    assert_eq!(files(r#"components = "cubes.svg""#).unwrap(), vec!["cubes.svg"]);
+    assert_eq!(files(r#"components = ["cubes.svg", "props.svg"]"#).unwrap(), vec!["cubes.svg", "props.svg"]);
+    assert_matches!(files("components = { a = 1 }"), Err(RunError::InvalidKey { .. }));
+}
+
+#[test]
+fn test_tile_shape_ids() {
+    // 00011011 is a quarter of the cube, so has fewer faces
+    let cubes = render(&Renderer::new(), r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+    "#);
+    let quarters = render(&Renderer::new(), r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0, "00011011"]]
+        equalities = {}
+    "#);
+    assert_ne!(cubes, quarters);
+    let numbered = render(&Renderer::new(), r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0, 27]]
+        equalities = {}
+    "#);
+    assert_eq!(quarters, numbered);
+    let explicit = render(&Renderer::new(), r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0, 255]]
+        equalities = {}
+    "#);
+    assert_eq!(cubes, explicit);
+}
+
+#[test]
+fn test_fills() {
+    let listed = (0..10).cartesian_product(0..10).map(|(x, z)| format!("[{}, 0, {}]", x, z)).join(", ");
+    let listed = render(&Renderer::new(), &format!("grid_size = [10, 1, 10]\ntiles = [{}]\nequalities = {{}}", listed));
+    // corners can be given in any order, and the tiles list can be left out
+    let filled = render(&Renderer::new(), r#"
+        grid_size = [10, 1, 10]
+        fills = [[[9, 0, 0], [0, 0, 9]]]
+        equalities = {}
+    "#);
+    assert_eq!(listed, filled);
+
+    // tiles are placed over fills
+    let carved = render(&Renderer::new(), r#"
+        grid_size = [2, 1, 1]
+        fills = [[[0, 0, 0], [1, 0, 0], "00011011"]]
+        tiles = [[1, 0, 0, 255]]
+        equalities = {}
+    "#);
+    let mixed = render(&Renderer::new(), r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0, "00011011"], [1, 0, 0]]
+        equalities = {}
+    "#);
+    assert_eq!(carved, mixed);
+
+    let outside = try_render(&Renderer::new(), r#"
+        grid_size = [2, 2, 2]
+        fills = [[[0, 0, 0], [1, 2, 1]]]
+        equalities = {}
+    "#);
+    assert_eq!(outside.unwrap_err().to_string(), "invalid fill at fills[0]: (1, 2, 1) is outside grid_size (2, 2, 2)");
+}
+
+#[test]
+fn test_tile_shape_errors() {
+    let unassigned = try_render(&Renderer::new(), r#"
+        grid_size = [2, 2, 2]
+        tiles = [[0, 0, 0], [1, 0, 1, 2]]
+        equalities = {}
+    "#);
+    assert_eq!(
+        unassigned.unwrap_err().to_string(),
+        "invalid tile at tiles[1]: (1, 0, 1) uses shape 00000010, but no shape has that label",
+    );
+    let negative = try_render(&Renderer::new(), r#"
+        grid_size = [2, 2, 2]
+        tiles = [[0, 0, 0, -3]]
+        equalities = {}
+    "#);
+    assert_matches!(negative, Err(IsometricError::Run(RunError::InvalidTile { index: 0, reason })) if reason.starts_with("(0, 0, 0) is given shape -3"));
+}
+
+#[test]
+fn test_mixed_tile_shapes() {
+    let components = parser::parse_shapes(&mut Reader::from_str(include_str!("../components.svg"))).unwrap();
+    let faces = |id: u8| {
+        let shape = &components[&ShapeId::from(id)];
+        shape.component_iter().count()
+    };
+    let output = render(&Renderer::new(), r#"
+        grid_size = [3, 1, 1]
+        tiles = [[0, 0, 0, "00011011"], [2, 0, 0, 255]]
+        equalities = {}
+    "#);
+    // nothing overlaps, so every face of both shapes is drawn
+    assert_eq!(output.matches("<path").count(), faces(0b00011011) + faces(255));
+}
+
+#[test]
+fn test_coordinate_problems_reported_together() {
+    let result = try_render(&Renderer::new(), r#"
+        grid_size = [5, 5, 5]
+        tiles = [[0, 0, 0], [5, 0, 0], [1, 1], [4, 4, 4]]
+        equalities = { a = [[0, 0, 0], [0, 9, 0]] }
+    "#);
+    let Err(IsometricError::Run(RunError::Several(problems))) = result else { panic!("expected several problems, got {:?}", result) };
+    assert_eq!(problems.len(), 3);
+    assert_matches!(&problems[0], RunError::InvalidTile { index: 2, .. });
+    assert_eq!(problems[1].to_string(), "invalid tile at tiles[1]: (5, 0, 0) is outside grid_size (5, 5, 5)");
+    assert_eq!(problems[2].to_string(), "invalid member at equalities.a[1]: (0, 9, 0) is outside grid_size (5, 5, 5)");
+}
+
+#[test]
+fn test_auto_merge() {
+    let config = |auto_merge: bool| format!(r#"
+        grid_size = [2, 1, 2]
+        tiles = [[0, 0, 0], [1, 0, 0], [1, 0, 1]]
+        equalities = {{}}
+        auto_merge = {}
+    "#, auto_merge);
+    let separate = render(&Renderer::new(), &config(false));
+    assert_eq!(separate.matches("<g ").count(), 3);
+    // the L becomes one object, with a single path for each direction its faces point
+    let merged = render(&Renderer::new(), &config(true));
+    assert_eq!(merged.matches("<g ").count(), 1);
+    assert_eq!(merged.matches("<path").count(), 3);
+}
+
+#[test]
+fn test_auto_merge_explicit_groups() {
+    // the explicit group claims the middle cube, leaving only one cube on each side
+    let output = render(&Renderer::new(), r#"
+        grid_size = [3, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0], [2, 0, 0]]
+        equalities = { middle = [[1, 0, 0]] }
+        auto_merge = true
+    "#);
+    assert_eq!(output.matches("<g ").count(), 3);
+}
+
+#[test]
+fn test_object_ids() {
+    let output = render(&Renderer::new(), r#"
+        grid_size = [3, 1, 3]
+        tiles = [[0, 0, 0], [2, 0, 2], [0, 0, 2], [2, 0, 0]]
+        equalities = { "1st floor" = [[0, 0, 0]], "1st/floor" = [[2, 0, 2]] }
+    "#);
+    let ids = Regex::new(r#"<g id="([^"]*)""#).unwrap()
+        .captures_iter(&output)
+        .map(|c| c[1].to_string())
+        .sorted()
+        .collect_vec();
+    assert_eq!(ids, vec!["_1st_floor", "_1st_floor-2", "tile-0-0-2", "tile-2-0-0"]);
+}
+
+#[test]
+fn test_cell_attributes() {
+    let scene = r#"
+        grid_size = [3, 1, 3]
+        tiles = [[0, 0, 0], [2, 0, 2], [0, 0, 2]]
+        equalities = { stairs = [[2, 0, 2], [0, 0, 2]] }
+    "#;
+    let renderer = Renderer::new();
+    let output = render(&renderer, scene);
+    assert!(output.contains(r#"<g id="tile-0-0-0" data-x="0" data-y="0" data-z="0">"#));
+    // a group's shape is drawn once, and gives the cell it was placed from
+    assert_eq!(output.matches("data-group").count(), 1);
+    assert!(output.contains(r#"<g id="stairs" data-x="0" data-y="0" data-z="2" data-group="stairs">"#));
+
+    let minimal = render(&renderer, &format!("cell_attributes = false\n{}", scene));
+    assert!(!minimal.contains("data-"));
+    assert_eq!(Regex::new(r#" data-[a-z]+="[^"]*""#).unwrap().replace_all(&output, ""), minimal);
+}
+
+#[test]
+fn test_reuse_shapes() {
+    let scene = r#"
+        grid_size = [3, 1, 3]
+        tiles = [[0, 0, 0], [2, 0, 0], [0, 0, 2], [2, 0, 2], [1, 0, 2]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let plain = render(&renderer, scene);
+    let reused = render(&renderer, &format!("reuse_shapes = true\n{}", scene));
+
+    // the three whole cubes are one shape, and the two which lost their right side to the cube next to them another
+    assert_eq!(reused.matches("<g id=\"shape-").count(), 2);
+    assert_eq!(reused.matches("<use ").count(), 5);
+    assert!(!reused[reused.find("</defs>").unwrap()..].contains("<path"));
+
+    // putting every shape back where it's used draws the same paths as before
+    let paths = |output: &str, offset: (f64, f64)| -> Vec<(String, Vec<(f64, f64)>)> {
+        Regex::new(r#"<path d="([^"]*)" style="([^"]*)""#).unwrap()
+            .captures_iter(output)
+            .map(|caps| {
+                let points = PrimitiveIter::from_str(&caps[1]).flat_map(|p| p.points).map(|p| (p.x + offset.0, p.y + offset.1));
+                (caps[2].to_string(), points.collect())
+            })
+            .collect()
+    };
+    let symbols: HashMap<String, String> = Regex::new(r#"<g id="(shape-\d+)">(.*?)</g>"#).unwrap()
+        .captures_iter(&reused)
+        .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+        .collect();
+    let placed = Regex::new(r##"<use id="[^"]*" href="#([^"]*)" x="([^"]*)" y="([^"]*)""##).unwrap()
+        .captures_iter(&reused)
+        .flat_map(|caps| paths(&symbols[&caps[1]], (caps[2].parse().unwrap(), caps[3].parse().unwrap())))
+        .collect_vec();
+    assert_eq!(placed, paths(&plain, (0.0, 0.0)));
+
+    let global = try_render(&renderer, &format!("reuse_shapes = true\nmerge_paths = \"global\"\n{}", scene));
+    assert_matches!(global, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "reuse_shapes");
+}
+
+#[test]
+fn test_layers() {
+    let scene = r#"
+        grid_size = [2, 2, 2]
+        tiles = [[0, 1, 0], [1, 0, 0], [1, 0, 1]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let flat = render(&renderer, scene);
+    assert!(flat.find("tile-0-1-0").unwrap() < flat.find("tile-1-0-0").unwrap());
+    let layered = render(&renderer, &format!("layers = \"y\"\n{}", scene));
+    assert!(layered.contains(r#"xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape""#));
+    let labels = Regex::new(r#"inkscape:label="([^"]*)""#).unwrap()
+        .captures_iter(&layered)
+        .map(|caps| caps[1].to_string())
+        .collect_vec();
+    assert_eq!(labels, vec!["y=0", "y=1"]);
+    assert!(layered.find("tile-0-1-0").unwrap() > layered.find("tile-1-0-0").unwrap());
+    // the cubes are only moved between others they don't overlap, so everything's drawn the same
+    assert_eq!(output_points(&layered), output_points(&flat));
+
+    let invalid = try_render(&renderer, &format!("layers = \"w\"\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "layers");
+}
+
+#[test]
+fn test_animation() {
+    let scene = r#"
+        grid_size = [2, 2, 2]
+        tiles = [[0, 1, 0], [1, 0, 0], [1, 0, 1]]
+        equalities = {}
+    "#;
+    let renderer = Renderer::new();
+    let still = render(&renderer, scene);
+    assert!(!still.contains("<style") && !still.contains("class="));
+
+    let animated = render(&renderer, &format!("animate = true\nstep_duration = 0.25\n{}", scene));
+    let classes = |output: &str| Regex::new(r#"<g id="([^"]*)" class="([^"]*)""#).unwrap()
+        .captures_iter(output)
+        .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+        .collect_vec();
+    // the first two are just as far back as each other, so fade in together
+    assert_eq!(classes(&animated), [
+        ("tile-0-1-0".to_string(), "step-0".to_string()),
+        ("tile-1-0-0".to_string(), "step-0".to_string()),
+        ("tile-1-0-1".to_string(), "step-1".to_string()),
+    ]);
+    assert!(animated.contains(".step-1{animation:build-up 250ms linear 250ms backwards}"));
+    assert!(!animated.contains(".step-2"));
+    // every object ends up where it'd be without the animation
+    assert_eq!(Regex::new(r#"<style>.*</style>| class="[^"]*""#).unwrap().replace_all(&animated, ""), still);
+
+    let layered = render(&renderer, &format!("animate = true\norder = \"layer\"\n{}", scene));
+    assert_eq!(classes(&layered), [
+        ("tile-0-1-0".to_string(), "step-1".to_string()),
+        ("tile-1-0-0".to_string(), "step-0".to_string()),
+        ("tile-1-0-1".to_string(), "step-0".to_string()),
+    ]);
+
+    let global = try_render(&renderer, &format!("animate = true\nmerge_paths = \"global\"\n{}", scene));
+    assert_matches!(global, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "animate");
+    let instant = try_render(&renderer, &format!("animate = true\nstep_duration = 0\n{}", scene));
+    assert_matches!(instant, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "step_duration");
+    let invalid = try_render(&renderer, &format!("animate = true\norder = \"random\"\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "order");
+}
+
+#[test]
+fn test_objects_streamed() {
+    let shapes = (0..3).map(|i| PlacedShape {
+        shape: Shape::new(vec![ShapeComponent::new(vect![0.0, 1.0, 0.0], vec![ShapePrimitive::rect(vect![i as f64 * 2.0, 0.0], vect![1.0, 1.0]).unwrap()]).unwrap()]),
+        cell: vect![i, 0, 0],
+        depth: i,
+        distance: 2 - i,
+        colour: vect![0.5, 0.5, 0.5],
+        opacity: 1.0,
+        group: None,
+    }).collect_vec();
+    let styled = Rc::new(Cell::new(0));
+    let counter = styled.clone();
+    let shader = move |face: &FaceInfo| {
+        counter.set(counter.get() + 1);
+        colour::default_shader(face)
+    };
+    let lighting = Lighting::default();
+    let mut events = object_svg_iter(&shapes, &Canvas::new(6.0, 1.0), &lighting, Effects::default(), PathOptions::default(), &shader, DocumentOptions::default());
+    // nothing is styled until the shape it belongs to is asked for
+    assert_eq!(events.by_ref().take(3).count(), 3);
+    assert_eq!(styled.get(), 1);
+    assert_eq!(events.count(), 9);
+    assert_eq!(styled.get(), 3);
+}
+
+#[test]
+fn test_layers_keep_overlaps_in_order() {
+    let square = |x: f64, level: usize| PlacedShape {
+        shape: Shape::new(vec![ShapeComponent::new(vect![0.0, 1.0, 0.0], vec![ShapePrimitive::rect(vect![x, 0.0], vect![2.0, 2.0]).unwrap()]).unwrap()]),
+        cell: vect![0, level, 0],
+        depth: 0,
+        distance: 0,
+        colour: vect![0.0, 0.0, 0.0],
+        opacity: 1.0,
+        group: None,
+    };
+    let by_y = |shape: &PlacedShape| shape.cell.y;
+    // squares side by side can be gathered up by level
+    let touching = [square(0.0, 1), square(2.0, 0), square(4.0, 1)];
+    assert_eq!(layers(&touching, by_y), vec![(0, vec![1]), (1, vec![0, 2])]);
+    // but once one is drawn over another it shouldn't be, the levels are left in order
+    let overlapping = [square(0.0, 1), square(1.0, 0), square(4.0, 1)];
+    assert_eq!(layers(&overlapping, by_y), vec![(1, vec![0]), (0, vec![1]), (1, vec![2])]);
+}
+
+#[test]
+fn test_deterministic_output() {
+    // everything read from a table in the config, or fused together, in one scene
+    let scene = r#"
+        grid_size = [4, 2, 4]
+        fills = [[[0, 0, 0], [3, 0, 1]]]
+        tiles = [[0, 1, 0], [3, 1, 0], [0, 0, 3], [3, 0, 3], [1, 1, 1]]
+        equalities = { b = [[0, 1, 0], [3, 1, 0]], a = [[0, 0, 3], [3, 0, 3]], c = [[1, 1, 1]] }
+        tile_colours = [{ tile = [0, 1, 0], colour = [255, 0, 0] }]
+        opacity = { 255 = 1.0 }
+        auto_merge = true
+        merge_faces = true
+    "#;
+    let renderer = Renderer::new();
+    let first = render(&renderer, scene);
+    for _ in 0..8 {
+        assert_eq!(render(&renderer, scene), first);
+    }
+
+    let invalid = r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+        opacity = { 3 = 2.0, 1 = 2.0, 2 = 2.0 }
+    "#;
+    for _ in 0..8 {
+        assert_matches!(try_render(&renderer, invalid), Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "opacity.1");
+    }
+}
+
+#[test]
+fn test_buried_cells_skipped() {
+    let scene = r#"
+        grid_size = [4, 4, 4]
+        fills = [[[0, 0, 0], [3, 3, 3]]]
+        tiles = [[3, 3, 3, "00011011"]]
+        equalities = {}
+    "#;
+    // with nothing counted as opaque, every cell goes through the occlusion checks
+    let unskipped = render(&Renderer::new(), &format!("opaque_shapes = []\n{}", scene));
+    assert_eq!(render(&Renderer::new(), scene), unskipped);
+}
+
+#[test]
+fn test_keep_hidden_faces() {
+    let config = |keep: bool| format!(r#"
+        grid_size = [2, 2, 2]
+        fills = [[[0, 0, 0], [1, 1, 1]]]
+        equalities = {{ pair = [[0, 0, 0], [0, 0, 1]] }}
+        keep_hidden_faces = {}
+    "#, keep);
+    let culled = render(&Renderer::new(), &config(false));
+    let kept = render(&Renderer::new(), &config(true));
+    // every cube gets all three of its faces, even the one in the middle of the block
+    assert_eq!(kept.matches("<path").count(), 7 * 3);
+    assert!(culled.matches("<path").count() < 7 * 3);
+    // the pair is still drawn just the once
+    assert_eq!(kept.matches("<g ").count(), 7);
+}
+
+#[test]
+fn test_clip_hidden_faces() {
+    let config = |clip: bool| format!(r#"
+        grid_size = [2, 2, 1]
+        tiles = [[0, 0, 0], [1, 1, 0]]
+        equalities = {{}}
+        clip_hidden_faces = {}
+    "#, clip);
+    let whole = render(&Renderer::new(), &config(false));
+    let clipped = render(&Renderer::new(), &config(true));
+    // the back cube's top and right faces are each half hidden, so are cut down to triangles
+    assert_eq!(whole.matches("<path").count(), clipped.matches("<path").count());
+    assert!(whole.contains(r#"<path d="M35 40 70 60 35 80 0 60 z""#));
+    assert!(clipped.contains(r#"<path d="M35 40 V80 L0 60 z""#));
+    assert!(clipped.contains(r#"<path d="M35 80 70 100 35 120 z""#));
+
+    let both = try_render(&Renderer::new(), r#"
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+        keep_hidden_faces = true
+        clip_hidden_faces = true
+    "#);
+    assert_matches!(both, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "clip_hidden_faces");
+}
+
+#[test]
+fn test_merge_faces() {
+    let config = |merge: bool| format!(r#"
+        grid_size = [10, 1, 10]
+        fills = [[[0, 0, 0], [9, 0, 9]]]
+        equalities = {{}}
+        merge_faces = {}
+    "#, merge);
+    let separate = render(&Renderer::new(), &config(false));
+    assert_eq!(separate.matches("<path").count(), 100 + 10 + 10);
+    // the whole slab is one top and two sides
+    let merged = render(&Renderer::new(), &config(true));
+    assert_eq!(merged.matches("<path").count(), 3);
+    assert_eq!(merged.matches("<g ").count(), 1);
+}
+#[test]
+fn test_merge_faces_painted_over() {
+    let face = |x: f64, y: f64| Shape::new(vec![
+        ShapeComponent::new(vect![0.0, 1.0, 0.0], vec![ShapePrimitive::rect(vect![x, y], vect![1.0, 1.0]).unwrap()]).unwrap(),
+    ]);
+    let left = (face(0.0, 0.0), vect![0, 0, 0]);
+    let right = (face(1.0, 0.0), vect![1, 0, 0]);
+    // something far away in the grid, but drawn over the left face
+    let cover = (face(0.5, 0.5), vect![5, 5, 5]);
+
+    let fused = combine_shapes(vec![left.clone(), right.clone()], &HashSet::new());
+    assert_eq!(fused.len(), 1);
+    assert_eq!(fused[0].1, vect![1, 0, 0]);
+    assert_eq!(fused[0].0.component_iter().next().unwrap().primitives().len(), 1);
+    // moving the left face up to where the right one is drawn would put it on top of the cover
+    let blocked = combine_shapes(vec![left, cover, right], &HashSet::new());
+    assert_eq!(blocked.len(), 3);
+}
+
+#[test]
+fn test_fuse_faces() {
+    let square = |x: f64| ShapePrimitive::rect(vect![x, 0.0], vect![1.0, 1.0]).unwrap();
+    let mut row = VecDeque::from([square(0.0), square(1.0), square(2.0)]);
+    fuse_faces(&mut row);
+    assert_eq!(row.len(), 1);
+    assert_eq!(row[0].signed_area().abs(), 3.0);
+    assert_eq!(row[0].points.len(), 4);
+    // a face which can't be fused with anything doesn't stop the rest from being fused
+    let mut scattered = VecDeque::from([square(5.0), square(0.0), square(2.0), square(1.0)]);
+    fuse_faces(&mut scattered);
+    assert_eq!(scattered.len(), 2);
+    // a slanted strip like the top of a row of cubes, whose corners won't line up exactly once they're multiplied out
+    let step = vect![3f64.sqrt() / 2.0, 0.5];
+    let tile = |n: usize| ShapePrimitive::from_points([
+        step * n as f64, step * (n + 1) as f64, step * (n + 1) as f64 + vect![0.0, 1.0], step * n as f64 + vect![0.0, 1.0],
+    ]).unwrap();
+    let mut strip = (0..12).map(tile).collect::<VecDeque<_>>();
+    fuse_faces(&mut strip);
+    assert_eq!(strip.len(), 1);
+    assert_eq!(strip[0].points.len(), 4);
+}
+
+#[test]
+fn test_epsilon_setting() {
+    let negative = try_render(&Renderer::new(), r#"
+        epsilon = -0.5
+        grid_size = [1, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = {}
+    "#);
+    assert_matches!(negative, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "epsilon");
+    // a generous tolerance doesn't hide anything which is properly visible
+    let generous = render(&Renderer::new(), r#"
+        epsilon = 0.001
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = {}
+    "#);
+    assert_eq!(generous.matches("<path").count(), 5);
+}
+
+#[test]
+fn test_bounding_box() {
+    let square = ShapePrimitive::rect(vect![1.0, 2.0], vect![3.0, 4.0]).unwrap();
+    assert_eq!(bounding_box(&square), Bounds { min: vect![1.0, 2.0], max: vect![4.0, 6.0] });
+    // nothing at all is inside out, so doesn't overlap even itself
+    let empty = bounding_box(&Shape::new(vec![]));
+    assert!(!empty.overlaps(&empty));
+}
+
+/// The reference cube, and a box two cubes long along x drawn from the same corner.
+const BED_COMPONENTS: &str = r##"<svg>
+    <g inkscape:label="11111111">
+        <path d="M 0,20 35,0 70,20 35,40 Z" fill="#80ff80"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" fill="#8080ff"/>
+        <path d="M 70,20 35,40 V 80 L 70,60 Z" fill="#ff8080"/>
+    </g>
+    <g inkscape:label="bed">
+        <path d="M 0,20 35,0 105,40 70,60 Z" fill="#80ff80"/>
+        <path d="M 0,20 V 60 L 70,100 V 60 Z" fill="#8080ff"/>
+        <path d="M 105,40 70,60 V 100 L 105,80 Z" fill="#ff8080"/>
+    </g>
+</svg>"##;
+
+#[test]
+fn test_footprints() {
+    let bed = try_render_scene(BED_COMPONENTS, r#"
+        grid_size = [3, 2, 1]
+        tiles = [[0, 0, 0, "bed"]]
+        footprints = { bed = [2, 1, 1] }
+        equalities = {}
+    "#).unwrap();
+    let cubes = try_render_scene(BED_COMPONENTS, r#"
+        grid_size = [3, 2, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = {}
+    "#).unwrap();
+    // the bed is one object, drawn over both of the cells it covers
+    assert_eq!(bed.matches("<g ").count(), 1);
+    assert!(bed.contains(r#"<g id="tile-0-0-0" data-x="0" data-y="0" data-z="0">"#));
+    let bounds = |output: &str| {
+        let points = output_points(output);
+        let (xs, ys): (Vec<f64>, Vec<f64>) = points.into_iter().unzip();
+        (xs.iter().copied().fold(f64::INFINITY, f64::min), ys.iter().copied().fold(f64::INFINITY, f64::min),
+         xs.iter().copied().fold(f64::NEG_INFINITY, f64::max), ys.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+    };
+    assert_eq!(bounds(&bed), bounds(&cubes));
+
+    // a cube on the far end of the bed has to be drawn after all of it
+    let stacked = try_render_scene(BED_COMPONENTS, r#"
+        grid_size = [3, 2, 1]
+        tiles = [[0, 0, 0, "bed"], [1, 1, 0]]
+        footprints = { bed = [2, 1, 1] }
+        equalities = {}
+    "#).unwrap();
+    assert!(stacked.find("tile-0-0-0").unwrap() < stacked.find("tile-1-1-0").unwrap());
+
+    // from every corner, the bed covers the same cells a pair of cubes would, which are along z in the views turned a quarter round
+    for (view, other) in [("se", "[1, 0, 0]"), ("nw", "[1, 0, 0]"), ("ne", "[0, 0, 1]"), ("sw", "[0, 0, 1]")] {
+        let bed = try_render_scene(BED_COMPONENTS, &format!(r#"
+            view = "{}"
+            grid_size = [3, 2, 3]
+            tiles = [[0, 0, 0, "bed"]]
+            footprints = {{ bed = [2, 1, 1] }}
+            equalities = {{}}
+        "#, view)).unwrap();
+        let cubes = try_render_scene(BED_COMPONENTS, &format!(r#"
+            view = "{}"
+            grid_size = [3, 2, 3]
+            tiles = [[0, 0, 0], {}]
+            equalities = {{}}
+        "#, view, other)).unwrap();
+        assert_eq!(bounds(&bed), bounds(&cubes), "{}", view);
+    }
+}
+
+#[test]
+fn test_translucent_footprints_unfused() {
+    // the bed's box starts from a different corner in the world than in the view, which is where its material has to be found from
+    let bed = |view: &str, merge: bool| {
+        let output = try_render_scene(BED_COMPONENTS, &format!(r#"
+            view = "{}"
+            merge_faces = {}
+            grid_size = [3, 1, 3]
+            tiles = [[0, 0, 0, "bed"], [2, 0, 0], [0, 0, 2], [1, 0, 1]]
+            footprints = {{ bed = [2, 1, 1] }}
+            opacity = {{ bed = 0.5 }}
+            equalities = {{}}
+        "#, view, merge)).unwrap();
+        let start = output.find(r#"<g id="tile-0-0-0""#).unwrap();
+        let end = start + output[start..].find("</g>").unwrap();
+        output[start..end].to_string()
+    };
+    for view in ["se", "nw"] {
+        assert_eq!(bed(view, true), bed(view, false), "{}", view);
+    }
+}
+
+#[test]
+fn test_footprint_errors() {
+    let overlapping = try_render_scene(BED_COMPONENTS, r#"
+        grid_size = [3, 2, 1]
+        tiles = [[0, 0, 0, "bed"], [1, 0, 0]]
+        footprints = { bed = [2, 1, 1] }
+        equalities = {}
+    "#);
+    assert_matches!(overlapping, Err(IsometricError::Run(RunError::InvalidFootprint { cell, .. })) if cell == vect![0, 0, 0]);
+    let outside = try_render_scene(BED_COMPONENTS, r#"
+        grid_size = [3, 2, 1]
+        tiles = [[2, 0, 0, "bed"]]
+        footprints = { bed = [2, 1, 1] }
+        equalities = {}
+    "#);
+    assert_matches!(outside, Err(IsometricError::Run(RunError::InvalidFootprint { cell, .. })) if cell == vect![2, 0, 0]);
+    let both = try_render_scene(BED_COMPONENTS, r#"
+        grid_size = [3, 2, 1]
+        tiles = [[0, 0, 0, "bed"], [1, 0, 0, "bed"]]
+        footprints = { bed = [2, 1, 1] }
+        equalities = {}
+    "#);
+    assert_matches!(both, Err(IsometricError::Run(RunError::InvalidFootprint { .. } | RunError::Several(_))));
+}
+
+/// The reference cube, and the same cube again to be drawn as glass.
+const GLASS_COMPONENTS: &str = r##"<svg>
+    <g inkscape:label="11111111">
+        <path d="M 0,20 35,0 70,20 35,40 Z" fill="#80ff80"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" fill="#8080ff"/>
+        <path d="M 70,20 35,40 V 80 L 70,60 Z" fill="#ff8080"/>
+    </g>
+    <g inkscape:label="glass">
+        <path d="M 0,20 35,0 70,20 35,40 Z" fill="#80ff80"/>
+        <path d="M 0,20 V 60 L 35,80 V 40 Z" fill="#8080ff"/>
+        <path d="M 70,20 35,40 V 80 L 70,60 Z" fill="#ff8080"/>
+    </g>
+</svg>"##;
+
+#[test]
+fn test_translucent_shapes() {
+    // the glass cube sits right in front of the stone one, covering it exactly
+    let scene = r#"
+        grid_size = [2, 2, 2]
+        tiles = [[0, 0, 0], [1, 1, 1, "glass"]]
+        equalities = {}
+    "#;
+    let solid = try_render_scene(GLASS_COMPONENTS, scene).unwrap();
+    assert_eq!(solid.matches("<path").count(), 3);
+    assert!(!solid.contains("fill-opacity"));
+
+    for mode in ["", "clip_hidden_faces = true", "merge_faces = true"] {
+        let glass = try_render_scene(GLASS_COMPONENTS, &format!("opacity = {{ glass = 0.5 }}\n{}\n{}", mode, scene)).unwrap();
+        // the stone cube shows through the glass, which is drawn over it
+        assert_eq!(glass.matches("<path").count(), 6, "{}", mode);
+        assert_eq!(glass.matches(";fill-opacity:0.5").count(), 3, "{}", mode);
+        assert!(glass.find("tile-0-0-0").unwrap() < glass.find("tile-1-1-1").unwrap());
+    }
+
+    // glass all around a cube doesn't bury it, even when it fills its cell
+    let surrounded = try_render_scene(GLASS_COMPONENTS, r#"
+        opacity = { glass = 0.5 }
+        opaque_shapes = [255, "glass"]
+        grid_size = [2, 2, 2]
+        tiles = [[0, 0, 0], [1, 0, 0, "glass"], [0, 1, 0, "glass"], [0, 0, 1, "glass"], [1, 1, 0, "glass"], [1, 0, 1, "glass"], [0, 1, 1, "glass"], [1, 1, 1, "glass"]]
+        equalities = {}
+    "#).unwrap();
+    assert!(surrounded.contains("tile-0-0-0"));
+
+    let invalid = try_render_scene(GLASS_COMPONENTS, &format!("opacity = {{ glass = 1.5 }}\n{}", scene));
+    assert_matches!(invalid, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "opacity.glass");
+}
+
+#[test]
+fn test_scene_matches_config() {
+    let mut reader = Reader::from_str(include_str!("../components.svg"));
+    reader.trim_text(true);
+    let mut scene = Scene::new(parser::parse_shapes(&mut reader).unwrap());
+    scene.set_grid_size(vect![3, 2, 2]);
+    for cell in [vect![0, 0, 0], vect![1, 0, 0], vect![2, 0, 0], vect![0, 1, 1]] {
+        scene.place(cell, 255u8).unwrap();
+    }
+    scene.connect(&[vect![1, 0, 0], vect![2, 0, 0]]).unwrap();
+    scene.light(vect![0.2, 1.0, 0.4]).unwrap();
+    scene.colour(vect![0.2, 0.6, 0.9]);
+    let mut output = vec![];
+    scene.render(Writer::new(&mut output)).unwrap();
+
+    let expected = try_render(&Renderer::new(), r#"
+        grid_size = [3, 2, 2]
+        tiles = [[0, 0, 0], [1, 0, 0], [2, 0, 0], [0, 1, 1]]
+        equalities = { 0 = [[1, 0, 0], [2, 0, 0]] }
+        light_vector = [0.2, 1.0, 0.4]
+        scene_colour = [0.2, 0.6, 0.9]
+    "#).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+    assert_matches!(scene.place(vect![3, 0, 0], 255u8), Err(RunError::InvalidPlacement { .. }));
+    assert_matches!(scene.place(vect![0, 0, 0], 7u8), Err(RunError::InvalidPlacement { .. }));
+    assert_matches!(scene.connect(&[vect![0, 0, 0], vect![0, 2, 0]]), Err(RunError::InvalidMember { index: 1, .. }));
+
+    // shrinking the grid drops whatever no longer fits, leaving a scene which still renders
+    scene.set_grid_size(vect![1, 1, 1]);
+    assert!(scene.render(Writer::new(vec![])).is_ok());
+}
+
+#[test]
+fn test_vector_settings() {
+    let lists = try_render(&Renderer::new(), r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = { a = [[0, 0, 0], [1, 0, 0]] }
+    "#).unwrap();
+    let tables = try_render(&Renderer::new(), r#"
+        grid_size = { x = 2, y = 1, z = 1 }
+        tiles = [[0, 0, 0], [1, 0, 0]]
+        equalities = { a = [{ x = 0, y = 0, z = 0 }, { x = 1, y = 0, z = 0 }] }
+    "#).unwrap();
+    assert_eq!(lists, tables);
+
+    let short = try_render(&Renderer::new(), r#"
+        grid_size = [2, 1, 1]
+        tiles = [[0, 0, 0]]
+        equalities = { a = [[0, 0, 0]], b = [[1, 0]] }
+    "#);
+    assert_eq!(short.unwrap_err().to_string(), "invalid value for equalities.b: invalid length 2, expected [x, y, z] or { x, y, z }");
+}
+
+#[test]
+fn test_map_cells() {
+    // with the `parallel` feature the cells are split between threads, but still come back in the order they went in
+    let cells = (0..10_000).map(|i| vect![i % 7, i % 5, i]).collect_vec();
+    let mapped = map_cells(&cells, |c| c.x + c.y * 10 + c.z * 100);
+    assert_eq!(mapped, cells.iter().map(|c| c.x + c.y * 10 + c.z * 100).collect_vec());
+}
+
+#[test]
+fn test_placement_is_deterministic() {
+    // enough cells to be split between threads, with connections and clipping going cell by cell after them
+    let scene = r#"
+        grid_size = [12, 6, 12]
+        generate = { kind = "noise", seed = 7, max_height = 5 }
+        clip_hidden_faces = true
+        tiles = [[0, 5, 0], [1, 5, 0]]
+        equalities = { beam = [[0, 5, 0], [1, 5, 0]] }
+    "#;
+    let first = render(&Renderer::new(), scene);
+    for _ in 0..3 {
+        assert_eq!(render(&Renderer::new(), scene), first);
+    }
+}
+
+/// Somewhere to write which can still be read from once the writer's been given away.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders every frame of `config`, in order.
+fn try_render_frames(config: &str) -> Result<Vec<String>, IsometricError> {
+    let mut reader = Reader::from_str(include_str!("../components.svg"));
+    reader.trim_text(true);
+    let shapes = parser::parse_shapes(&mut reader).unwrap();
+    let settings = Config::builder()
+        .add_source(config::File::from_str(config, FileFormat::Toml))
+        .build().unwrap();
+    let mut buffers = vec![];
+    Renderer::new().run_frames(shapes, settings, |index| {
+        assert_eq!(index, buffers.len());
+        let buffer = SharedBuffer::default();
+        buffers.push(buffer.clone());
+        Ok(Writer::new(buffer))
+    })?;
+    Ok(buffers.into_iter().map(|buffer| String::from_utf8(buffer.0.take()).unwrap()).collect())
+}
+
+#[test]
+fn test_frames() {
+    let scene = r#"
+        grid_size = [3, 2, 3]
+        tiles = [[0, 0, 0], [1, 0, 0]]
+    "#;
+    let frames = try_render_frames(&format!("{}{}", scene, r#"
+        [[frames]]
+        tiles = [[2, 0, 0], [0, 1, 0]]
+
+        [[frames]]
+        remove = [[1, 0, 0]]
+        light_vector = [-1, 1, 0]
+        scene_colour = [200, 120, 40]
+
+        [[frames]]
+        tiles = [[1, 0, 0]]
+    "#)).unwrap();
+    let renderer = Renderer::new();
+    // each frame is drawn just as a scene holding the same tiles would be, with the light and colour carrying on once they change
+    let lit = "light_vector = [-1, 1, 0]\nscene_colour = [200, 120, 40]\n";
+    assert_eq!(frames, [
+        render(&renderer, scene),
+        render(&renderer, "grid_size = [3, 2, 3]\ntiles = [[0, 0, 0], [1, 0, 0], [2, 0, 0], [0, 1, 0]]"),
+        render(&renderer, &format!("{}grid_size = [3, 2, 3]\ntiles = [[0, 0, 0], [2, 0, 0], [0, 1, 0]]", lit)),
+        render(&renderer, &format!("{}grid_size = [3, 2, 3]\ntiles = [[0, 0, 0], [1, 0, 0], [2, 0, 0], [0, 1, 0]]", lit)),
+    ]);
+    assert_eq!(try_render_frames(scene).unwrap(), [render(&renderer, scene)]);
+
+    let outside = try_render_frames(&format!("{}[[frames]]\n[[frames]]\nremove = [[3, 0, 0]]", scene));
+    assert_matches!(outside, Err(IsometricError::Run(RunError::InvalidFrame { index: 1, .. })));
+    let unknown = try_render_frames(&format!("{}[[frames]]\ntiles = [[2, 0, 0, 99]]", scene));
+    assert_matches!(unknown, Err(IsometricError::Run(RunError::InvalidFrame { index: 0, .. })));
+    let dark = try_render_frames(&format!("{}[[frames]]\nlight_vector = [0, 0, 0]", scene));
+    assert_matches!(dark, Err(IsometricError::Run(RunError::InvalidKey { key, .. })) if key == "frames[0].light_vector");
+}
@@ -0,0 +1,128 @@
+use std::ops::Mul;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::iter::split_params;
+use crate::matrix::Mat3;
+use crate::vector::Vec2;
+
+mod tests;
+
+lazy_static! {
+    static ref TRANSFORM_REGEX: Regex = Regex::new(r"(?P<name>[A-Za-z]+)\s*\((?P<args>[^)]*)\)").unwrap();
+}
+
+/// A 2D affine transform, laid out like SVG's `matrix(a, b, c, d, e, f)`.
+/// A point `(x, y)` is taken to `(a x + c y + e, b x + d y + f)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 }
+    }
+    pub fn translate(x: f64, y: f64) -> Transform {
+        Transform { e: x, f: y, ..Transform::identity() }
+    }
+    pub fn scale(x: f64, y: f64) -> Transform {
+        Transform { a: x, d: y, ..Transform::identity() }
+    }
+    /// A rotation by `angle` degrees about the origin, which is clockwise on screen.
+    pub fn rotate(angle: f64) -> Transform {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Transform { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+    pub fn skew_x(angle: f64) -> Transform {
+        Transform { c: angle.to_radians().tan(), ..Transform::identity() }
+    }
+    pub fn skew_y(angle: f64) -> Transform {
+        Transform { b: angle.to_radians().tan(), ..Transform::identity() }
+    }
+    pub fn apply(&self, p: Vec2<f64>) -> Vec2<f64> {
+        self.matrix().transform_point(p)
+    }
+    /// The transform undoing this one, unless it flattens everything onto a line or a point.
+    pub fn inverse(&self) -> Option<Transform> {
+        self.matrix().inverse().map(Transform::from)
+    }
+    /// The same transform as a matrix, acting on points `(x, y, 1)`.
+    pub fn matrix(&self) -> Mat3<f64> {
+        Mat3 { rows: [[self.a, self.c, self.e], [self.b, self.d, self.f], [0.0, 0.0, 1.0]] }
+    }
+}
+
+/// Takes the top two rows of an affine transform's matrix.
+impl From<Mat3<f64>> for Transform {
+    fn from(m: Mat3<f64>) -> Self {
+        let [[a, c, e], [b, d, f], _] = m.rows;
+        Transform { a, b, c, d, e, f }
+    }
+}
+
+/// `first * second` applies `second`, then `first`, the same order as they'd be written in a `transform` attribute.
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform::from(self.matrix() * rhs.matrix())
+    }
+}
+
+/// Reads an SVG `transform` attribute, which is a list of transforms applied right to left.
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut transform = Transform::identity();
+        let mut rest = s;
+        for captures in TRANSFORM_REGEX.captures_iter(s) {
+            let whole = captures.get(0).unwrap();
+            // only separators are allowed between the transforms
+            let gap = &s[s.len() - rest.len()..whole.start()];
+            if !gap.chars().all(|c| c.is_whitespace() || c == ',') {
+                return Err(format!("'{}' is not a transform", gap.trim()));
+            }
+            rest = &s[whole.end()..];
+
+            let name = &captures["name"];
+            let (args, skipped) = split_params(&captures["args"], false);
+            if let Some(token) = skipped.first() {
+                return Err(format!("'{}' in {}({}) is not a number", token, name, &captures["args"]));
+            }
+            let next = match (name, args.as_slice()) {
+                ("matrix", &[a, b, c, d, e, f]) => Transform { a, b, c, d, e, f },
+                ("translate", &[x]) => Transform::translate(x, 0.0),
+                ("translate", &[x, y]) => Transform::translate(x, y),
+                ("scale", &[s]) => Transform::scale(s, s),
+                ("scale", &[x, y]) => Transform::scale(x, y),
+                ("rotate", &[angle]) => Transform::rotate(angle),
+                ("rotate", &[angle, x, y]) => {
+                    Transform::translate(x, y) * Transform::rotate(angle) * Transform::translate(-x, -y)
+                }
+                ("skewX", &[angle]) => Transform::skew_x(angle),
+                ("skewY", &[angle]) => Transform::skew_y(angle),
+                _ => return Err(format!("{}({}) is not a transform", name, &captures["args"])),
+            };
+            transform = transform * next;
+        }
+        if !rest.chars().all(|c| c.is_whitespace() || c == ',') {
+            return Err(format!("'{}' is not a transform", rest.trim()));
+        }
+        Ok(transform)
+    }
+}
@@ -0,0 +1,51 @@
+#![cfg(test)]
+
+use crate::transform::Transform;
+use crate::vect;
+use crate::vector::Vec2;
+
+fn assert_close(a: Vec2<f64>, b: Vec2<f64>) {
+    assert!((a - b).magnitude() < 1e-9, "{} is not {}", a, b);
+}
+
+#[test]
+fn test_parse_single() {
+    let p = vect![1.0, 2.0];
+    assert_close("translate(10,20)".parse::<Transform>().unwrap().apply(p), vect![11.0, 22.0]);
+    assert_close("translate(10)".parse::<Transform>().unwrap().apply(p), vect![11.0, 2.0]);
+    assert_close("scale(2)".parse::<Transform>().unwrap().apply(p), vect![2.0, 4.0]);
+    assert_close("scale(2 -1)".parse::<Transform>().unwrap().apply(p), vect![2.0, -2.0]);
+    assert_close("rotate(90)".parse::<Transform>().unwrap().apply(p), vect![-2.0, 1.0]);
+    assert_close("rotate(180, 1, 1)".parse::<Transform>().unwrap().apply(p), vect![1.0, 0.0]);
+    assert_close("matrix(1 0 0 1 5 -5)".parse::<Transform>().unwrap().apply(p), vect![6.0, -3.0]);
+    assert_close("skewX(45)".parse::<Transform>().unwrap().apply(p), vect![3.0, 2.0]);
+}
+
+#[test]
+fn test_parse_order() {
+    // the rightmost transform is applied first
+    let p = vect![1.0, 0.0];
+    assert_close("translate(10 0) scale(2)".parse::<Transform>().unwrap().apply(p), vect![12.0, 0.0]);
+    assert_close("scale(2), translate(10 0)".parse::<Transform>().unwrap().apply(p), vect![22.0, 0.0]);
+    let parent: Transform = "translate(10 0)".parse().unwrap();
+    let child: Transform = "scale(2)".parse().unwrap();
+    assert_eq!(parent * child, "translate(10 0) scale(2)".parse().unwrap());
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert!("translate(1 2 3)".parse::<Transform>().is_err());
+    assert!("wobble(1)".parse::<Transform>().is_err());
+    assert!("scale(2) nonsense".parse::<Transform>().is_err());
+    assert_eq!("translate(1 x)".parse::<Transform>().unwrap_err(), "'x' in translate(1 x) is not a number");
+    assert_eq!("".parse::<Transform>(), Ok(Transform::identity()));
+}
+
+#[test]
+fn test_inverse() {
+    let transform: Transform = "translate(10 -5) rotate(30) scale(2 3)".parse().unwrap();
+    let p = vect![1.0, 2.0];
+    assert_close(transform.inverse().unwrap().apply(transform.apply(p)), p);
+    assert_eq!(Transform::from(transform.matrix()), transform);
+    assert_eq!("scale(0 1)".parse::<Transform>().unwrap().inverse(), None);
+}
@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+
+use crate::grid::{neighbours, Grid};
+use crate::shapes::ShapeId;
+use crate::vector::Vec3;
+
+mod tests;
+
+/// A single equality group, as a name alongside the cells it connects.
+pub type Connection = (String, Vec<Vec3<usize>>);
+
+#[derive(Debug, PartialEq)]
+pub enum ConnectionError {
+    /// The same cell appears in two different groups.
+    SharedMember { coordinate: Vec3<usize>, first: String, second: String },
+    /// A group connects cells holding different shapes, but is only ever drawn from one of them.
+    MixedShapes { group: String, coordinate: Vec3<usize>, id: ShapeId, expected: ShapeId },
+}
+
+impl Display for ConnectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionError::SharedMember { coordinate, first, second } => write!(
+                f, "equalities.{} and equalities.{} both contain {} (set merge_equalities = true to merge them)",
+                first, second, coordinate
+            ),
+            ConnectionError::MixedShapes { group, coordinate, id, expected } => write!(
+                f, "equalities.{} member {} uses shape {}, but other members of the group use shape {}",
+                group, coordinate, id, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionWarning {
+    /// A group member doesn't have a tile placed in it.
+    UnoccupiedMember { group: String, coordinate: Vec3<usize> },
+}
+
+impl Display for ConnectionWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionWarning::UnoccupiedMember { group, coordinate } => write!(
+                f, "equalities.{} member {} is not an occupied tile", group, coordinate
+            ),
+        }
+    }
+}
+
+/// Checks the `equalities` groups against the grid they'll be drawn from.
+/// Repeated members within a group are dropped, and groups are returned sorted by name.
+/// Groups sharing a member are an error, unless `merge` is set,
+/// in which case they're combined into one group named after all of them.
+pub fn validate_connections(grid: &Grid, connections: HashMap<String, Vec<Vec3<usize>>>, merge: bool) -> Result<Vec<Connection>, ConnectionError> {
+
+    let groups = connections.into_iter().sorted_by(|a, b| a.0.cmp(&b.0)).collect_vec();
+
+    // a tiny union-find over the group indices, only ever joined when merging
+    let mut parents = (0..groups.len()).collect_vec();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    let mut owners: HashMap<Vec3<usize>, usize> = HashMap::new();
+    for (i, (name, members)) in groups.iter().enumerate() {
+        for member in members {
+            match owners.get(member) {
+                Some(&j) if j == i => (),
+                Some(&j) if merge => {
+                    let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[usize::max(a, b)] = usize::min(a, b);
+                }
+                Some(&j) => {
+                    return Err(ConnectionError::SharedMember {
+                        coordinate: *member,
+                        first: groups[j].0.clone(),
+                        second: name.clone(),
+                    });
+                }
+                None => {
+                    owners.insert(*member, i);
+                }
+            }
+        }
+    }
+
+    let mut merged: Vec<(Vec<String>, Vec<Vec3<usize>>)> = vec![];
+    let mut positions: HashMap<usize, usize> = HashMap::new();
+    for (i, (name, members)) in groups.into_iter().enumerate() {
+        let r = root(&mut parents, i);
+        let position = *positions.entry(r).or_insert_with(|| {
+            merged.push((vec![], vec![]));
+            merged.len() - 1
+        });
+        let (names, all_members) = &mut merged[position];
+        names.push(name);
+        for member in members {
+            if !all_members.contains(&member) {
+                all_members.push(member);
+            }
+        }
+    }
+    let merged = merged.into_iter().map(|(names, members)| (names.join("+"), members)).collect_vec();
+
+    for (name, members) in &merged {
+        let mut expected: Option<(Vec3<usize>, &ShapeId)> = None;
+        for member in members {
+            let id = grid.get(*member);
+            if *id == ShapeId::EMPTY { continue; }
+            match expected {
+                None => expected = Some((*member, id)),
+                Some((_, e)) if e != id => {
+                    return Err(ConnectionError::MixedShapes {
+                        group: name.clone(),
+                        coordinate: *member,
+                        id: id.clone(),
+                        expected: e.clone(),
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Lists every group member which doesn't have a tile in it.
+/// These don't stop anything from rendering, but usually point to a typo.
+pub fn unoccupied_members(grid: &Grid, connections: &[Connection]) -> Vec<ConnectionWarning> {
+    connections.iter()
+        .flat_map(|(name, members)| members.iter()
+            .filter(|m| *grid.get(**m) == ShapeId::EMPTY)
+            .map(|m| ConnectionWarning::UnoccupiedMember { group: name.clone(), coordinate: *m })
+        )
+        .collect()
+}
+
+/// Finds every run of two or more face-adjacent cells holding the same shape, for `auto_merge`.
+/// Cells already in one of `connections` are left out, so explicit groups always win.
+/// Each run is named after its first cell, like `auto-0-2-1`.
+pub fn contiguous_regions(grid: &Grid, connections: &[Connection]) -> Vec<Connection> {
+
+    let mut visited: HashSet<Vec3<usize>> = connections.iter().flat_map(|(_, members)| members.iter().cloned()).collect();
+    let mut regions = vec![];
+
+    for (start, id) in grid.iter_occupied().sorted_by_key(|(c, _)| (c.x, c.y, c.z)) {
+        if !visited.insert(start) { continue; }
+
+        let mut region = vec![];
+        let mut queue = VecDeque::from([start]);
+        while let Some(c) = queue.pop_front() {
+            region.push(c);
+            for n in neighbours(c) {
+                if grid.get(n) == id && visited.insert(n) {
+                    queue.push_back(n);
+                }
+            }
+        }
+        if region.len() > 1 {
+            regions.push((format!("auto-{}-{}-{}", start.x, start.y, start.z), region));
+        }
+    }
+    regions
+}
@@ -0,0 +1,94 @@
+#![cfg(test)]
+
+use std::collections::HashMap;
+
+use crate::validation::{ConnectionError, ConnectionWarning, contiguous_regions, unoccupied_members, validate_connections};
+use crate::grid::Grid;
+use crate::vect;
+use crate::vector::Vec3;
+
+fn gen_grid() -> Grid {
+    let mut grid = Grid::new(vect![3, 3, 3]);
+    grid.set(vect![0, 0, 0], 255u8.into());
+    grid.set(vect![1, 0, 0], 255u8.into());
+    grid.set(vect![2, 0, 0], 255u8.into());
+    grid.set(vect![0, 1, 0], 170u8.into());
+    grid
+}
+
+fn gen_connections(groups: &[(&str, &[Vec3<usize>])]) -> HashMap<String, Vec<Vec3<usize>>> {
+    groups.iter().map(|(name, members)| (name.to_string(), members.to_vec())).collect()
+}
+
+#[test]
+fn test_duplicate_within_group() {
+    let connections = gen_connections(&[
+        ("a", &[vect![0, 0, 0], vect![1, 0, 0], vect![0, 0, 0]]),
+    ]);
+    let result = validate_connections(&gen_grid(), connections, false).unwrap();
+    assert_eq!(result, vec![("a".to_string(), vec![vect![0, 0, 0], vect![1, 0, 0]])]);
+}
+
+#[test]
+fn test_shared_member() {
+    let connections = gen_connections(&[
+        ("a", &[vect![0, 0, 0], vect![1, 0, 0]]),
+        ("b", &[vect![1, 0, 0], vect![2, 0, 0]]),
+    ]);
+    let result = validate_connections(&gen_grid(), connections, false);
+    assert_eq!(result, Err(ConnectionError::SharedMember {
+        coordinate: vect![1, 0, 0],
+        first: "a".to_string(),
+        second: "b".to_string(),
+    }));
+}
+
+#[test]
+fn test_shared_member_merged() {
+    let connections = gen_connections(&[
+        ("a", &[vect![0, 0, 0], vect![1, 0, 0]]),
+        ("b", &[vect![1, 0, 0], vect![2, 0, 0]]),
+        ("c", &[vect![2, 2, 2]]),
+    ]);
+    let result = validate_connections(&gen_grid(), connections, true).unwrap();
+    assert_eq!(result, vec![
+        ("a+b".to_string(), vec![vect![0, 0, 0], vect![1, 0, 0], vect![2, 0, 0]]),
+        ("c".to_string(), vec![vect![2, 2, 2]]),
+    ]);
+}
+
+#[test]
+fn test_mixed_shapes() {
+    let connections = gen_connections(&[
+        ("a", &[vect![0, 0, 0], vect![0, 1, 0]]),
+    ]);
+    let result = validate_connections(&gen_grid(), connections, false);
+    assert_eq!(result, Err(ConnectionError::MixedShapes {
+        group: "a".to_string(),
+        coordinate: vect![0, 1, 0],
+        id: 170u8.into(),
+        expected: 255u8.into(),
+    }));
+}
+
+#[test]
+fn test_unoccupied_member() {
+    let grid = gen_grid();
+    let connections = gen_connections(&[
+        ("a", &[vect![0, 0, 0], vect![2, 2, 2], vect![1, 0, 0]]),
+    ]);
+    let result = validate_connections(&grid, connections, false).unwrap();
+    assert_eq!(unoccupied_members(&grid, &result), vec![
+        ConnectionWarning::UnoccupiedMember { group: "a".to_string(), coordinate: vect![2, 2, 2] },
+    ]);
+}
+
+#[test]
+fn test_contiguous_regions() {
+    let grid = gen_grid();
+    assert_eq!(contiguous_regions(&grid, &[]), vec![
+        ("auto-0-0-0".to_string(), vec![vect![0, 0, 0], vect![1, 0, 0], vect![2, 0, 0]]),
+    ]);
+    let claimed = vec![("a".to_string(), vec![vect![1, 0, 0]])];
+    assert_eq!(contiguous_regions(&grid, &claimed), vec![]);
+}
@@ -1,6 +1,14 @@
+use std::fmt;
+use std::marker::PhantomData;
 use std::ops;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::matrix::Mat2;
 use crate::num;
 
+mod tests;
+
 #[macro_export]
 macro_rules! vect {
     ($x:expr, $y:expr) => {
@@ -21,7 +29,21 @@ macro_rules! vectp {
     };
 }
 
+// `scalar * vector`, which can only be written for each concrete scalar type
+macro_rules! left_scalar_mul {
+    ($vec:ident, $type:ident, $($field:ident),+) => {
+        impl ops::Mul<$vec<$type>> for $type {
+            type Output = $vec<$type>;
+
+            fn mul(self, rhs: $vec<$type>) -> Self::Output {
+                $vec { $($field: self * rhs.$field),+ }
+            }
+        }
+    };
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Vec2<T: Copy> {
     pub x: T,
     pub y: T
@@ -49,9 +71,52 @@ impl<T> Vec2<T> where T: Copy + ops::Sub<Output=T> + ops::Mul<Output=T> {
         self.x * other.y - self.y * other.x
     }
 }
-impl<T> Vec2<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> + num::Sin<Output=T> + num::Cos<Output=T> {
+impl<T> Vec2<T> where T: Copy + ops::Add<Output=T> + ops::Mul<Output=T> + ops::Neg<Output=T> + num::Sin<Output=T> + num::Cos<Output=T> {
     pub fn rot(self, angle: T) -> Vec2<T> {
-        vect![angle.cos() * self.x - angle.sin() * self.y, angle.sin() * self.x + angle.cos() * self.y]
+        Mat2::rotation(angle) * self
+    }
+}
+impl<T> Vec2<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> {
+    /// The point `t` of the way from `a` to `b`, carrying on past either end when `t` is outside 0 to 1.
+    pub fn lerp(a: Self, b: Self, t: T) -> Self {
+        a + (b - a) * t
+    }
+}
+impl<T> Vec2<T> where T: Copy + num::Min<Output=T> + num::Max<Output=T> {
+    /// The smaller of each pair of components, which is the corner of the bounding box of both nearest the origin.
+    pub fn min(self, other: Self) -> Self {
+        vect![self.x.min(other.x), self.y.min(other.y)]
+    }
+    /// The larger of each pair of components.
+    pub fn max(self, other: Self) -> Self {
+        vect![self.x.max(other.x), self.y.max(other.y)]
+    }
+}
+impl<T> Vec2<T> where T: Copy + ops::Neg<Output=T> {
+    /// The vector turned a quarter turn anticlockwise, the same way `rot` turns for a positive angle.
+    pub fn perp(self) -> Vec2<T> {
+        vect![-self.y, self.x]
+    }
+}
+impl<T> Vec2<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> + num::Atan2<Output=T> {
+    /// The angle to turn `self` by to point it along `other`, anticlockwise being positive, from -π to π.
+    pub fn angle_between(self, other: Self) -> T {
+        self.cross(other).atan2(self.dot(other))
+    }
+}
+impl<T> Vec2<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> + ops::Div<Output=T> {
+    /// The part of `self` along `other`.
+    pub fn project_onto(self, other: Self) -> Self {
+        other * (self.dot(other) / other.dot(other))
+    }
+    /// The part of `self` at right angles to `other`.
+    pub fn reject_from(self, other: Self) -> Self {
+        self - self.project_onto(other)
+    }
+    /// `self` bounced off a surface facing `normal`, which doesn't need to be normalised.
+    pub fn reflect(self, normal: Self) -> Self {
+        let along = self.project_onto(normal);
+        self - (along + along)
     }
 }
 impl<T> ops::Add for Vec2<T> where T: Copy + ops::Add<Output=T> {
@@ -144,13 +209,21 @@ impl<T> ops::Rem<T> for Vec2<T> where T: Copy + ops::Rem<Output=T> {
         vect![self.x % rhs, self.y % rhs]
     }
 }
+left_scalar_mul!(Vec2, f32, x, y);
+left_scalar_mul!(Vec2, f64, x, y);
 impl<T> From<(T, T)> for Vec2<T> where T: Copy {
     fn from(tup: (T, T)) -> Self {
         vect![tup.0, tup.1]
     }
 }
+impl<T> fmt::Display for Vec2<T> where T: Copy + fmt::Display {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Vec3<T: Copy> {
     pub x: T,
     pub y: T,
@@ -183,6 +256,37 @@ impl<T> Vec3<T> where T: Copy + ops::Sub<Output=T> + ops::Mul<Output=T> {
         ]
     }
 }
+impl<T> Vec3<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> {
+    /// The point `t` of the way from `a` to `b`, carrying on past either end when `t` is outside 0 to 1.
+    pub fn lerp(a: Self, b: Self, t: T) -> Self {
+        a + (b - a) * t
+    }
+}
+impl<T> Vec3<T> where T: Copy + num::Min<Output=T> + num::Max<Output=T> {
+    /// The smaller of each pair of components.
+    pub fn min(self, other: Self) -> Self {
+        vect![self.x.min(other.x), self.y.min(other.y), self.z.min(other.z)]
+    }
+    /// The larger of each pair of components.
+    pub fn max(self, other: Self) -> Self {
+        vect![self.x.max(other.x), self.y.max(other.y), self.z.max(other.z)]
+    }
+}
+impl<T> Vec3<T> where T: Copy + ops::Add<Output=T> + ops::Sub<Output=T> + ops::Mul<Output=T> + ops::Div<Output=T> {
+    /// The part of `self` along `other`.
+    pub fn project_onto(self, other: Self) -> Self {
+        other * (self.dot(other) / other.dot(other))
+    }
+    /// The part of `self` at right angles to `other`.
+    pub fn reject_from(self, other: Self) -> Self {
+        self - self.project_onto(other)
+    }
+    /// `self` bounced off a surface facing `normal`, which doesn't need to be normalised.
+    pub fn reflect(self, normal: Self) -> Self {
+        let along = self.project_onto(normal);
+        self - (along + along)
+    }
+}
 impl<T> ops::Add for Vec3<T> where T: Copy + ops::Add<Output=T> {
     type Output = Vec3<T>;
 
@@ -273,8 +377,81 @@ impl<T> ops::Rem<T> for Vec3<T> where T: Copy + ops::Rem<Output=T> {
         vect![self.x % rhs, self.y % rhs, self.z % rhs]
     }
 }
+left_scalar_mul!(Vec3, f32, x, y, z);
+left_scalar_mul!(Vec3, f64, x, y, z);
 impl<T> From<(T, T, T)> for Vec3<T> where T: Copy {
     fn from(tup: (T, T, T)) -> Self {
         vect![tup.0, tup.1, tup.2]
     }
 }
+impl<T> fmt::Display for Vec3<T> where T: Copy + fmt::Display {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {}, {})", self.x, self.y, self.z)
+    }
+}
+
+/// Read either as a list `[x, y]` or a table `{ x, y }`.
+impl<'de, T> Deserialize<'de> for Vec2<T> where T: Copy + Deserialize<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [x, y] = deserializer.deserialize_any(Components::new(&["x", "y"]))?;
+        Ok(vect![x, y])
+    }
+}
+/// Read either as a list `[x, y, z]` or a table `{ x, y, z }`.
+impl<'de, T> Deserialize<'de> for Vec3<T> where T: Copy + Deserialize<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [x, y, z] = deserializer.deserialize_any(Components::new(&["x", "y", "z"]))?;
+        Ok(vect![x, y, z])
+    }
+}
+
+/// Reads the `N` components of a vector, named `names`, from either a sequence or a map.
+struct Components<T, const N: usize> {
+    names: &'static [&'static str],
+    marker: PhantomData<T>,
+}
+
+impl<T, const N: usize> Components<T, N> {
+    fn new(names: &'static [&'static str; N]) -> Self {
+        Components { names, marker: PhantomData }
+    }
+}
+
+impl<'de, T, const N: usize> Visitor<'de> for Components<T, N> where T: Copy + Deserialize<'de> {
+    type Value = [T; N];
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{0}] or {{ {0} }}", self.names.join(", "))
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut components = [None; N];
+        for (i, component) in components.iter_mut().enumerate() {
+            *component = Some(seq.next_element()?.ok_or_else(|| de::Error::invalid_length(i, &self))?);
+        }
+        // anything left over is counted, so the error says how many there actually were
+        let mut len = N;
+        while seq.next_element::<de::IgnoredAny>()?.is_some() {
+            len += 1;
+        }
+        if len != N {
+            return Err(de::Error::invalid_length(len, &self));
+        }
+        Ok(components.map(Option::unwrap))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut components = [None; N];
+        while let Some(key) = map.next_key::<String>()? {
+            let Some(i) = self.names.iter().position(|name| *name == key) else {
+                return Err(de::Error::unknown_field(&key, self.names));
+            };
+            if components[i].is_some() {
+                return Err(de::Error::duplicate_field(self.names[i]));
+            }
+            components[i] = Some(map.next_value()?);
+        }
+        if let Some(i) = components.iter().position(Option::is_none) {
+            return Err(de::Error::missing_field(self.names[i]));
+        }
+        Ok(components.map(Option::unwrap))
+    }
+}
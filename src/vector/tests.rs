@@ -0,0 +1,117 @@
+#![cfg(test)]
+
+use config::{Config, FileFormat};
+
+use crate::vect;
+use crate::vector::{Vec2, Vec3};
+
+fn read<T: serde::de::DeserializeOwned>(toml: &str) -> Result<T, String> {
+    let settings = Config::builder().add_source(config::File::from_str(toml, FileFormat::Toml)).build().unwrap();
+    settings.get::<T>("v").map_err(|why| why.to_string())
+}
+
+#[test]
+fn test_deserialize() {
+    assert_eq!(read::<Vec3<usize>>("v = [1, 2, 3]"), Ok(vect![1, 2, 3]));
+    assert_eq!(read::<Vec3<usize>>("v = { z = 3, x = 1, y = 2 }"), Ok(vect![1, 2, 3]));
+    assert_eq!(read::<Vec2<f64>>("v = [0.5, 1.5]"), Ok(vect![0.5, 1.5]));
+    assert_eq!(read::<Vec2<f64>>("v = { x = 0.5, y = 1.5 }"), Ok(vect![0.5, 1.5]));
+}
+
+#[test]
+fn test_deserialize_errors() {
+    let short = read::<Vec3<usize>>("v = [1, 2]").unwrap_err();
+    assert!(short.contains("invalid length 2, expected [x, y, z] or { x, y, z }"), "{}", short);
+    let long = read::<Vec3<usize>>("v = [1, 2, 3, 4]").unwrap_err();
+    assert!(long.contains("invalid length 4"), "{}", long);
+    let missing = read::<Vec3<usize>>("v = { x = 1, y = 2 }").unwrap_err();
+    assert!(missing.contains("missing field `z`"), "{}", missing);
+    let unknown = read::<Vec2<usize>>("v = { x = 1, y = 2, w = 3 }").unwrap_err();
+    assert!(unknown.contains("unknown field `w`"), "{}", unknown);
+}
+
+#[test]
+fn test_perp() {
+    assert_eq!(vect![1, 0].perp(), vect![0, 1]);
+    assert_eq!(vect![0, 1].perp(), vect![-1, 0]);
+    assert_eq!(vect![3.0, -2.0].perp(), vect![2.0, 3.0]);
+    // a quarter turn is the same as rotating by π/2
+    let rotated = vect![3.0, -2.0].rot(std::f64::consts::FRAC_PI_2);
+    assert!((rotated - vect![2.0, 3.0]).magnitude() < 1e-12);
+}
+
+#[test]
+fn test_angle_between() {
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    let x: Vec2<f64> = vect![1.0, 0.0];
+    assert_eq!(x.angle_between(vect![0.0, 1.0]), FRAC_PI_2);
+    assert_eq!(x.angle_between(vect![0.0, -1.0]), -FRAC_PI_2);
+    assert_eq!(x.angle_between(vect![-1.0, 0.0]), PI);
+    assert_eq!(x.angle_between(x), 0.0);
+    // the lengths don't matter
+    assert!((vect![2.0, 2.0].angle_between(vect![0.0, 5.0]) - FRAC_PI_4).abs() < 1e-12);
+    assert!((vect![0.0, 5.0].angle_between(vect![2.0, 2.0]) + FRAC_PI_4).abs() < 1e-12);
+}
+
+#[test]
+fn test_project_and_reject() {
+    let v: Vec2<f64> = vect![3.0, 4.0];
+    assert_eq!(v.project_onto(vect![2.0, 0.0]), vect![3.0, 0.0]);
+    assert_eq!(v.reject_from(vect![2.0, 0.0]), vect![0.0, 4.0]);
+    // onto the diagonal, (3, 4) . (1, 1) / 2 = 3.5 of the way along
+    assert_eq!(v.project_onto(vect![1.0, 1.0]), vect![3.5, 3.5]);
+    assert_eq!(v.reject_from(vect![1.0, 1.0]), vect![-0.5, 0.5]);
+
+    let w: Vec3<f64> = vect![1.0, 2.0, 3.0];
+    assert_eq!(w.project_onto(vect![0.0, 0.0, 4.0]), vect![0.0, 0.0, 3.0]);
+    assert_eq!(w.reject_from(vect![0.0, 0.0, 4.0]), vect![1.0, 2.0, 0.0]);
+    // (1, 2, 3) . (1, 1, 0) / 2 = 1.5
+    assert_eq!(w.project_onto(vect![1.0, 1.0, 0.0]), vect![1.5, 1.5, 0.0]);
+}
+
+#[test]
+fn test_reflect() {
+    // off a floor, only the part going down flips
+    assert_eq!(vect![2.0, -1.0].reflect(vect![0.0, 3.0]), vect![2.0, 1.0]);
+    // off a 45 degree wall, x and y swap
+    assert_eq!(vect![1.0, 0.0].reflect(vect![-1.0, 1.0]), vect![0.0, 1.0]);
+    assert_eq!(vect![1.0, -2.0, 3.0].reflect(vect![0.0, 1.0, 0.0]), vect![1.0, 2.0, 3.0]);
+    // (1, 0, 0) . (1, 1, 1) / 3 = 1/3, so the reflection is (1, 0, 0) - 2/3 (1, 1, 1)
+    let reflected = vect![1.0, 0.0, 0.0].reflect(vect![1.0, 1.0, 1.0]);
+    assert!((reflected - vect![1.0 / 3.0, -2.0 / 3.0, -2.0 / 3.0]).magnitude() < 1e-12);
+}
+
+#[test]
+fn test_lerp() {
+    let (a, b): (Vec2<f64>, Vec2<f64>) = (vect![1.0, 2.0], vect![3.0, -2.0]);
+    assert_eq!(Vec2::lerp(a, b, 0.0), a);
+    assert_eq!(Vec2::lerp(a, b, 1.0), b);
+    assert_eq!(Vec2::lerp(a, b, 0.5), vect![2.0, 0.0]);
+    // past the end, carrying on the same way
+    assert_eq!(Vec2::lerp(a, b, 2.0), vect![5.0, -6.0]);
+
+    let (a, b): (Vec3<f64>, Vec3<f64>) = (vect![0.0, 1.0, 2.0], vect![4.0, 1.0, -2.0]);
+    assert_eq!(Vec3::lerp(a, b, 0.0), a);
+    assert_eq!(Vec3::lerp(a, b, 1.0), b);
+    assert_eq!(Vec3::lerp(a, b, 0.5), vect![2.0, 1.0, 0.0]);
+    assert_eq!(Vec3::lerp(a, b, 2.0), vect![8.0, 1.0, -6.0]);
+}
+
+#[test]
+fn test_scalar_on_the_left() {
+    let v: Vec2<f64> = vect![1.5, -2.0];
+    assert_eq!(2.0 * v, v * 2.0);
+    assert_eq!(2.0 * v, vect![3.0, -4.0]);
+    let w: Vec3<f32> = vect![1.0, 2.0, 3.0];
+    assert_eq!(0.5 * w, vect![0.5, 1.0, 1.5]);
+}
+
+#[test]
+fn test_min_max() {
+    let (a, b): (Vec2<f64>, Vec2<f64>) = (vect![1.0, 5.0], vect![3.0, -2.0]);
+    assert_eq!(a.min(b), vect![1.0, -2.0]);
+    assert_eq!(a.max(b), vect![3.0, 5.0]);
+    let (a, b): (Vec3<f64>, Vec3<f64>) = (vect![1.0, 5.0, 0.0], vect![3.0, -2.0, 0.0]);
+    assert_eq!(a.min(b), vect![1.0, -2.0, 0.0]);
+    assert_eq!(a.max(b), vect![3.0, 5.0, 0.0]);
+}
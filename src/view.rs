@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use crate::grid::Grid;
+use crate::vect;
+use crate::vector::Vec3;
+
+mod tests;
+
+/// Which corner of the grid the scene is viewed from, with `y` pointing up.
+/// Taking `x` as east and `z` as south, the components file draws the scene from the south-east,
+/// with positive `x` running to the right and positive `z` to the left.
+/// Other views rotate the grid about the vertical axis to bring their corner to the front.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum View {
+    NorthEast,
+    NorthWest,
+    #[default]
+    SouthEast,
+    SouthWest,
+}
+
+impl FromStr for View {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ne" => Ok(View::NorthEast),
+            "nw" => Ok(View::NorthWest),
+            "se" => Ok(View::SouthEast),
+            "sw" => Ok(View::SouthWest),
+            _ => Err(format!("'{}' is not a view, expected one of \"ne\", \"nw\", \"se\" or \"sw\"", s)),
+        }
+    }
+}
+
+impl View {
+    /// The size of a grid of `world_size` once rotated into this view.
+    pub fn view_size(&self, world_size: Vec3<usize>) -> Vec3<usize> {
+        match self {
+            View::SouthEast | View::NorthWest => world_size,
+            View::SouthWest | View::NorthEast => vect![world_size.z, world_size.y, world_size.x],
+        }
+    }
+    /// Where the cell at `cell` in a grid of `world_size` ends up when viewed from this corner.
+    pub fn to_view(&self, cell: Vec3<usize>, world_size: Vec3<usize>) -> Vec3<usize> {
+        let Vec3 { x, y, z } = cell;
+        match self {
+            View::SouthEast => cell,
+            View::SouthWest => vect![z, y, world_size.x - 1 - x],
+            View::NorthWest => vect![world_size.x - 1 - x, y, world_size.z - 1 - z],
+            View::NorthEast => vect![world_size.z - 1 - z, y, x],
+        }
+    }
+    /// The inverse of `to_view`.
+    pub fn to_world(&self, cell: Vec3<usize>, world_size: Vec3<usize>) -> Vec3<usize> {
+        let Vec3 { x, y, z } = cell;
+        match self {
+            View::SouthEast => cell,
+            View::SouthWest => vect![world_size.x - 1 - z, y, x],
+            View::NorthWest => vect![world_size.x - 1 - x, y, world_size.z - 1 - z],
+            View::NorthEast => vect![z, y, world_size.z - 1 - x],
+        }
+    }
+    /// Rotates a direction in the world, such as the light vector, into this view.
+    pub fn rotate(&self, v: Vec3<f64>) -> Vec3<f64> {
+        match self {
+            View::SouthEast => v,
+            View::SouthWest => vect![v.z, v.y, -v.x],
+            View::NorthWest => vect![-v.x, v.y, -v.z],
+            View::NorthEast => vect![-v.z, v.y, v.x],
+        }
+    }
+    /// Rotates a whole grid into this view, storing its cells the same way.
+    pub fn rotate_grid(&self, grid: &Grid) -> Grid {
+        let world_size = grid.size();
+        let mut rotated = if grid.is_sparse() { Grid::sparse(self.view_size(world_size)) } else { Grid::new(self.view_size(world_size)) };
+        for (cell, id) in grid.iter_occupied() {
+            rotated.set(self.to_view(cell, world_size), id.clone());
+        }
+        rotated
+    }
+}
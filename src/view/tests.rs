@@ -0,0 +1,56 @@
+#![cfg(test)]
+
+use crate::vect;
+use crate::vector::Vec3;
+use crate::view::View;
+
+const VIEWS: [View; 4] = [View::NorthEast, View::NorthWest, View::SouthEast, View::SouthWest];
+
+#[test]
+fn test_view_round_trip() {
+    let world_size = vect![2, 3, 4];
+    for view in VIEWS {
+        let size = view.view_size(world_size);
+        for x in 0..2 {
+            for y in 0..3 {
+                for z in 0..4 {
+                    let cell = vect![x, y, z];
+                    let v = view.to_view(cell, world_size);
+                    assert!(v.x < size.x && v.y < size.y && v.z < size.z);
+                    assert_eq!(view.to_world(v, world_size), cell);
+                }
+            }
+        }
+    }
+}
+#[test]
+fn test_view_rotation_consistent() {
+    // stepping along an axis in the grid moves the same way as rotating that axis as a direction
+    let world_size = vect![3, 3, 3];
+    let centre = vect![1, 1, 1];
+    for view in VIEWS {
+        let from = view.to_view(centre, world_size);
+        for (step, direction) in [(vect![2, 1, 1], vect![1.0, 0.0, 0.0]), (vect![1, 1, 2], vect![0.0, 0.0, 1.0])] {
+            let to = view.to_view(step, world_size);
+            let moved = vect![
+                to.x as f64 - from.x as f64,
+                to.y as f64 - from.y as f64,
+                to.z as f64 - from.z as f64
+            ];
+            assert_eq!(moved, view.rotate(direction));
+        }
+    }
+}
+#[test]
+fn test_view_opposite_corners() {
+    // the corner nearest the viewer is always drawn last
+    let world_size = vect![2, 1, 3];
+    let nearest = |view: View| (0..2)
+        .flat_map(|x| (0..3).map(move |z| vect![x, 0, z]))
+        .max_by_key(|c| { let v = view.to_view(*c, world_size); v.x + v.z })
+        .unwrap();
+    assert_eq!(nearest(View::SouthEast), vect![1, 0, 2]);
+    assert_eq!(nearest(View::SouthWest), vect![0, 0, 2]);
+    assert_eq!(nearest(View::NorthWest), vect![0, 0, 0]);
+    assert_eq!(nearest(View::NorthEast), vect![1, 0, 0]);
+}
@@ -0,0 +1,177 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the binary from the crate root with `args`, writing `input` to its stdin.
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_isometric"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("couldn't start the binary");
+    // arguments which are rejected straight away can have it exit before reading anything
+    match child.stdin.take().unwrap().write_all(input.as_bytes()) {
+        Err(why) if why.kind() == std::io::ErrorKind::BrokenPipe => (),
+        result => result.unwrap(),
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_config_from_stdin() {
+    let toml = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml")).unwrap();
+    let piped = run(&["--config", "-", "--output", "-"], &toml);
+    assert!(piped.status.success(), "{}", String::from_utf8_lossy(&piped.stderr));
+    assert!(piped.stdout.starts_with(b"<?xml"));
+
+    // reading the same config from its file gives the same picture
+    let from_file = run(&["--config", "config", "--output", "-"], "");
+    assert!(from_file.status.success());
+    assert_eq!(piped.stdout, from_file.stdout);
+}
+
+#[test]
+fn test_json_config_from_stdin() {
+    let json = r#"{ "grid_size": [2, 2, 2], "tiles": [[0, 0, 0], [1, 0, 1]], "equalities": {} }"#;
+    let output = run(&["--config", "-", "--output", "-"], json);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let svg = String::from_utf8(output.stdout).unwrap();
+    assert!(svg.starts_with("<?xml"));
+    assert!(svg.contains("<path"));
+}
+
+#[test]
+fn test_errors_go_to_stderr() {
+    let output = run(&["--config", "-", "--output", "-"], "size = [1, 1]\n");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn test_watch_needs_a_config_file() {
+    let output = run(&["--watch", "--config", "-"], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--watch"));
+}
+
+#[test]
+fn test_config_formats_agree() {
+    // the same scene in each format, with nested maps for the equalities and arrays of tables for the lights
+    let toml = run(&["--config", "tests/fixtures/scene.toml", "--output", "-"], "");
+    assert!(toml.status.success(), "{}", String::from_utf8_lossy(&toml.stderr));
+    assert!(toml.stdout.starts_with(b"<?xml"));
+    for fixture in ["tests/fixtures/scene.json", "tests/fixtures/scene.yaml"] {
+        let output = run(&["--config", fixture, "--output", "-"], "");
+        assert!(output.status.success(), "{}: {}", fixture, String::from_utf8_lossy(&output.stderr));
+        assert_eq!(output.stdout, toml.stdout, "{} doesn't match the TOML", fixture);
+    }
+}
+
+#[test]
+fn test_config_format_override() {
+    let toml = run(&["--config", "tests/fixtures/scene.toml", "--output", "-"], "");
+
+    // scene.conf is YAML, which nothing would know from its extension
+    let unknown = run(&["--config", "tests/fixtures/scene.conf", "--output", "-"], "");
+    assert!(!unknown.status.success());
+    let yaml = run(&["--config", "tests/fixtures/scene.conf", "--config-format", "yaml", "--output", "-"], "");
+    assert!(yaml.status.success(), "{}", String::from_utf8_lossy(&yaml.stderr));
+    assert_eq!(yaml.stdout, toml.stdout);
+
+    // the override goes for stdin as well, in place of guessing from the text
+    let yaml_text = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/scene.yaml")).unwrap();
+    let piped = run(&["--config", "-", "--config-format", "yaml", "--output", "-"], &yaml_text);
+    assert!(piped.status.success(), "{}", String::from_utf8_lossy(&piped.stderr));
+    assert_eq!(piped.stdout, toml.stdout);
+}
+
+#[test]
+fn test_config_format_errors() {
+    // reading a file as the wrong format says which format it was read as
+    let wrong = run(&["--config", "tests/fixtures/scene.json", "--config-format", "toml", "--output", "-"], "");
+    assert!(!wrong.status.success());
+    assert!(String::from_utf8_lossy(&wrong.stderr).contains("as TOML"));
+
+    let unknown = run(&["--config-format", "xml"], "");
+    assert!(!unknown.status.success());
+    let stderr = String::from_utf8_lossy(&unknown.stderr);
+    assert!(stderr.contains("unknown config format 'xml'"));
+    assert!(stderr.contains("yaml"));
+}
+
+#[test]
+fn test_wireframe_golden() {
+    // only what a solid render would show, and every face behind it as well
+    for (config, golden) in [
+        ("tests/fixtures/wireframe.toml", "tests/fixtures/wireframe.svg"),
+        ("tests/fixtures/wireframe_hidden.toml", "tests/fixtures/wireframe_hidden.svg"),
+    ] {
+        let output = run(&["--config", config, "--output", "-"], "");
+        assert!(output.status.success(), "{}: {}", config, String::from_utf8_lossy(&output.stderr));
+        let expected = std::fs::read_to_string(format!("{}/{}", env!("CARGO_MANIFEST_DIR"), golden)).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{} doesn't match {}", config, golden);
+    }
+}
+
+#[test]
+fn test_verbose() {
+    let quiet = run(&["--config", "tests/fixtures/scene.toml", "--output", "-"], "");
+    let verbose = run(&["--config", "tests/fixtures/scene.toml", "--output", "-", "--verbose"], "");
+    assert!(verbose.status.success(), "{}", String::from_utf8_lossy(&verbose.stderr));
+    // everything it reports goes to stderr, leaving the picture as it was
+    assert_eq!(verbose.stdout, quiet.stdout);
+    let stderr = String::from_utf8_lossy(&verbose.stderr);
+    for line in ["culling", "shapes placed:", "paths emitted:", "writing:"] {
+        assert!(stderr.contains(line), "no {} in {}", line, stderr);
+    }
+}
+
+#[test]
+fn test_output_dir() {
+    let dir = std::env::temp_dir().join(format!("isometric-objects-{}", std::process::id()));
+    let config = r#"
+grid_size = [4, 2, 4]
+tiles = [[0, 0, 0], [1, 0, 0], [0, 0, 1], [3, 1, 3]]
+[equalities]
+"the house" = [[0, 0, 0], [1, 0, 0]]
+"#;
+    let output = run(&["--config", "-", "--output-dir", dir.to_str().unwrap(), "--loose-tiles"], config);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let mut files = std::fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, ["the_house.svg", "tile-0-0-1.svg", "tile-3-1-3.svg"]);
+    // each tile on its own is cropped to the same size, wherever it was in the grid
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+    let header = |svg: String| svg.split("<g").next().unwrap().to_string();
+    assert_eq!(header(read("tile-0-0-1.svg")), header(read("tile-3-1-3.svg")));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let output = run(&["--config", "-", "--loose-tiles"], config);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_frames() {
+    let dir = std::env::temp_dir().join(format!("isometric-frames-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = format!("grid_size = [2, 1, 1]\ntiles = [[0, 0, 0]]\n{}", "[[frames]]\ntiles = [[1, 0, 0]]\n".repeat(10));
+    let output = dir.join("build.svg");
+    let result = run(&["--config", "-", "--output", output.to_str().unwrap()], &config);
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+    let mut files = std::fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+    let expected = (0..=10).map(|i| format!("build-{:02}.svg", i)).collect::<Vec<_>>();
+    assert_eq!(files, expected);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // there's only one stdout to write them all to
+    let result = run(&["--config", "-", "--output", "-"], &config);
+    assert!(!result.status.success());
+}